@@ -165,7 +165,7 @@ fn make_records(record: &Value, count: usize) -> Vec<Value> {
 }
 
 fn write(schema: &Schema, records: &[Value]) -> Vec<u8> {
-    let mut writer = Writer::new(&schema, Vec::new());
+    let mut writer = Writer::new(&schema, Vec::new()).unwrap();
     writer.extend_from_slice(records).unwrap();
     writer.into_inner()
 }
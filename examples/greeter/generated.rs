@@ -0,0 +1,244 @@
+// Generated by avro-rs from the `Greeter` protocol. Do not edit.
+
+/// JSON declaration of the `Greeter` protocol.
+pub const GREETER_PROTOCOL: &str = r#"{"doc":"Greets people.","messages":{"count":{"request":[],"response":"long"},"hello":{"doc":"Answer a greeting.","errors":["Rejection"],"request":[{"name":"greeting","type":"Greeting"},{"name":"type","type":"Mood"}],"response":"Greeting"},"wave":{"one-way":true,"request":[{"name":"times","type":"int"}],"response":"null"}},"namespace":"com.example","protocol":"Greeter","types":[{"name":"Mood","symbols":["HAPPY","GRUMPY"],"type":"enum"},{"name":"Id","size":4,"type":"fixed"},{"doc":"A greeting.","fields":[{"name":"id","type":"Id"},{"doc":"The words.","name":"message","type":"string"},{"name":"mood","type":["null","Mood"]},{"name":"tags","type":{"items":"string","type":"array"}}],"name":"Greeting","type":"record"},{"fields":[{"name":"reason","type":"string"}],"name":"Rejection","type":"error"}]}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mood {
+    Happy,
+    Grumpy,
+}
+
+impl ::avro_rs::rpc::AvroType for Mood {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        match self {
+            Mood::Happy => ::avro_rs::types::Value::Enum(0, "HAPPY".into()),
+            Mood::Grumpy => ::avro_rs::types::Value::Enum(1, "GRUMPY".into()),
+        }
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        let symbol: ::std::sync::Arc<str> = match value {
+            ::avro_rs::types::Value::Enum(_, symbol) => symbol,
+            ::avro_rs::types::Value::String(symbol) => symbol.into(),
+            other => return Err(::avro_rs::DecodeError::new(format!("Enum expected, got {:?}", other)).into()),
+        };
+        match &*symbol {
+            "HAPPY" => Ok(Mood::Happy),
+            "GRUMPY" => Ok(Mood::Grumpy),
+            _ => Err(::avro_rs::DecodeError::new(format!("unknown symbol {} of enum Mood", symbol)).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Id(pub Vec<u8>);
+
+impl ::avro_rs::rpc::AvroType for Id {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        ::avro_rs::types::Value::Fixed(4, self.0)
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        match value {
+            ::avro_rs::types::Value::Fixed(4, bytes) => Ok(Id(bytes)),
+            other => Err(::avro_rs::DecodeError::new(format!("Fixed(4) expected, got {:?}", other)).into()),
+        }
+    }
+}
+
+/// A greeting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Greeting {
+    pub id: Id,
+    /// The words.
+    pub message: String,
+    pub mood: Option<Mood>,
+    pub tags: Vec<String>,
+}
+
+impl ::avro_rs::rpc::AvroType for Greeting {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        ::avro_rs::types::Value::Record(vec![
+            ("id".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.id)),
+            ("message".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.message)),
+            ("mood".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.mood)),
+            ("tags".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.tags)),
+        ])
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        let mut fields = ::avro_rs::rpc::record_fields(value)?;
+        Ok(Greeting {
+            id: ::avro_rs::rpc::take_field(&mut fields, "id")?,
+            message: ::avro_rs::rpc::take_field(&mut fields, "message")?,
+            mood: ::avro_rs::rpc::take_field(&mut fields, "mood")?,
+            tags: ::avro_rs::rpc::take_field(&mut fields, "tags")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub reason: String,
+}
+
+impl ::avro_rs::rpc::AvroType for Rejection {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        ::avro_rs::types::Value::Record(vec![
+            ("reason".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.reason)),
+        ])
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        let mut fields = ::avro_rs::rpc::record_fields(value)?;
+        Ok(Rejection {
+            reason: ::avro_rs::rpc::take_field(&mut fields, "reason")?,
+        })
+    }
+}
+
+impl From<Rejection> for ::avro_rs::rpc::RemoteError {
+    fn from(error: Rejection) -> Self {
+        ::avro_rs::rpc::RemoteError::declared("com.example.Rejection", ::avro_rs::rpc::AvroType::to_avro_value(error))
+    }
+}
+
+/// Request of the `count` message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountRequest {}
+
+impl ::avro_rs::rpc::AvroType for CountRequest {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        ::avro_rs::types::Value::Record(vec![])
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        ::avro_rs::rpc::record_fields(value)?;
+        Ok(CountRequest {})
+    }
+}
+
+/// Request of the `hello` message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HelloRequest {
+    pub greeting: Greeting,
+    pub type_: Mood,
+}
+
+impl ::avro_rs::rpc::AvroType for HelloRequest {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        ::avro_rs::types::Value::Record(vec![
+            ("greeting".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.greeting)),
+            ("type".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.type_)),
+        ])
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        let mut fields = ::avro_rs::rpc::record_fields(value)?;
+        Ok(HelloRequest {
+            greeting: ::avro_rs::rpc::take_field(&mut fields, "greeting")?,
+            type_: ::avro_rs::rpc::take_field(&mut fields, "type")?,
+        })
+    }
+}
+
+/// Request of the `wave` message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveRequest {
+    pub times: i32,
+}
+
+impl ::avro_rs::rpc::AvroType for WaveRequest {
+    fn to_avro_value(self) -> ::avro_rs::types::Value {
+        ::avro_rs::types::Value::Record(vec![
+            ("times".to_owned(), ::avro_rs::rpc::AvroType::to_avro_value(self.times)),
+        ])
+    }
+
+    fn from_avro_value(value: ::avro_rs::types::Value) -> Result<Self, ::avro_rs::Error> {
+        let mut fields = ::avro_rs::rpc::record_fields(value)?;
+        Ok(WaveRequest {
+            times: ::avro_rs::rpc::take_field(&mut fields, "times")?,
+        })
+    }
+}
+
+/// Client of the `Greeter` protocol.
+pub trait GreeterClient {
+    fn count(&mut self) -> Result<i64, ::avro_rs::Error>;
+
+    /// Answer a greeting.
+    fn hello(&mut self, greeting: Greeting, type_: Mood) -> Result<Greeting, ::avro_rs::Error>;
+
+    fn wave(&mut self, times: i32) -> Result<(), ::avro_rs::Error>;
+}
+
+impl<T: ::avro_rs::rpc::Transceiver> GreeterClient for ::avro_rs::rpc::Requestor<T> {
+    fn count(&mut self) -> Result<i64, ::avro_rs::Error> {
+        let request = CountRequest {};
+        let response = self.request("count", ::avro_rs::rpc::AvroType::to_avro_value(request))?;
+        ::avro_rs::rpc::AvroType::from_avro_value(response)
+    }
+
+    fn hello(&mut self, greeting: Greeting, type_: Mood) -> Result<Greeting, ::avro_rs::Error> {
+        let request = HelloRequest { greeting, type_ };
+        let response = self.request("hello", ::avro_rs::rpc::AvroType::to_avro_value(request))?;
+        ::avro_rs::rpc::AvroType::from_avro_value(response)
+    }
+
+    fn wave(&mut self, times: i32) -> Result<(), ::avro_rs::Error> {
+        let request = WaveRequest { times };
+        let response = self.request("wave", ::avro_rs::rpc::AvroType::to_avro_value(request))?;
+        ::avro_rs::rpc::AvroType::from_avro_value(response)
+    }
+}
+
+/// Server of the `Greeter` protocol.
+///
+/// Errors converted from the declared errors of a message are sent back as such.
+pub trait GreeterServer: Send + Sync + 'static {
+    fn count(&self) -> Result<i64, ::avro_rs::Error>;
+
+    /// Answer a greeting.
+    fn hello(&self, greeting: Greeting, type_: Mood) -> Result<Greeting, ::avro_rs::Error>;
+
+    fn wave(&self, times: i32) -> Result<(), ::avro_rs::Error>;
+}
+
+/// Create a `Requestor` speaking the `Greeter` protocol through `transceiver`.
+pub fn greeter_requestor<T: ::avro_rs::rpc::Transceiver>(
+    transceiver: T,
+) -> Result<::avro_rs::rpc::Requestor<T>, ::avro_rs::Error> {
+    let protocol = ::avro_rs::rpc::Protocol::parse_str(GREETER_PROTOCOL)?;
+    Ok(::avro_rs::rpc::Requestor::new(protocol, transceiver))
+}
+
+/// Create a `Responder` dispatching the requests of the `Greeter` protocol to `server`.
+pub fn greeter_responder<S: GreeterServer>(server: S) -> Result<::avro_rs::rpc::Responder, ::avro_rs::Error> {
+    let server = ::std::sync::Arc::new(server);
+    let protocol = ::avro_rs::rpc::Protocol::parse_str(GREETER_PROTOCOL)?;
+    let mut responder = ::avro_rs::rpc::Responder::new(protocol);
+    {
+        let server = server.clone();
+        responder.add_handler("count", move |request| {
+            let _request: CountRequest = ::avro_rs::rpc::AvroType::from_avro_value(request)?;
+            server.count().map(::avro_rs::rpc::AvroType::to_avro_value)
+        })?;
+    }
+    {
+        let server = server.clone();
+        responder.add_handler("hello", move |request| {
+            let request: HelloRequest = ::avro_rs::rpc::AvroType::from_avro_value(request)?;
+            server.hello(request.greeting, request.type_).map(::avro_rs::rpc::AvroType::to_avro_value)
+        })?;
+    }
+    {
+        let server = server.clone();
+        responder.add_handler("wave", move |request| {
+            let request: WaveRequest = ::avro_rs::rpc::AvroType::from_avro_value(request)?;
+            server.wave(request.times).map(::avro_rs::rpc::AvroType::to_avro_value)
+        })?;
+    }
+    Ok(responder)
+}
@@ -0,0 +1,62 @@
+extern crate avro_rs;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use avro_rs::rpc::{LocalTransceiver, RemoteError};
+use avro_rs::Error;
+
+// Generated with `avro_rs::rpc::generate` from `greeter.avpr`.
+mod generated;
+
+use generated::*;
+
+#[derive(Default)]
+struct Server {
+    waves: AtomicUsize,
+}
+
+impl GreeterServer for Server {
+    fn count(&self) -> Result<i64, Error> {
+        Ok(self.waves.load(Ordering::SeqCst) as i64)
+    }
+
+    fn hello(&self, greeting: Greeting, type_: Mood) -> Result<Greeting, Error> {
+        if type_ == Mood::Grumpy {
+            return Err(RemoteError::from(Rejection {
+                reason: "not today".to_owned(),
+            }).into())
+        }
+        if greeting.message.is_empty() {
+            return Err(Error::custom("nothing to answer"))
+        }
+        Ok(Greeting {
+            message: format!("{} to you too", greeting.message),
+            mood: Some(type_),
+            ..greeting
+        })
+    }
+
+    fn wave(&self, times: i32) -> Result<(), Error> {
+        self.waves.fetch_add(times as usize, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let responder = greeter_responder(Server::default())?;
+    let mut client = greeter_requestor(LocalTransceiver::new(&responder))?;
+
+    let greeting = Greeting {
+        id: Id(vec![0, 0, 0, 1]),
+        message: "Hello".to_owned(),
+        mood: None,
+        tags: vec!["example".to_owned()],
+    };
+    println!("{:?}", client.hello(greeting.clone(), Mood::Happy)?);
+    println!("{}", client.hello(greeting, Mood::Grumpy).unwrap_err());
+
+    client.wave(3)?;
+    println!("{} waves", client.count()?);
+
+    Ok(())
+}
@@ -2,10 +2,8 @@ extern crate avro_rs;
 
 #[macro_use]
 extern crate serde_derive;
-extern crate failure;
 
-use avro_rs::to_value;
-use failure::Error;
+use avro_rs::{to_value, Error};
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Test {
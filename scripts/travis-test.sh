@@ -2,6 +2,9 @@
 set -ev
 make release
 make test
+make test-default
+make build-wasm
+make build-no-std
 if [ "$TRAVIS_RUST_VERSION" = "nightly" ]; then
     make benchmark
 fi
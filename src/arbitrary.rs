@@ -0,0 +1,219 @@
+//! [`proptest`](https://docs.rs/proptest) strategies generating valid schemas, and values
+//! conforming to them, to property-test code handling Avro data.
+use std::collections::HashSet;
+
+use proptest::collection::{hash_map, vec};
+use proptest::num::{f32, f64};
+use proptest::prelude::*;
+use proptest::strategy::Union;
+
+use schema::{Name, RecordField, RecordFieldOrder, Schema, SchemaKind, UnionSchema};
+use types::Value;
+
+/// A strategy generating valid schemas, of any type nested up to a few levels deep.
+///
+/// Named types are given distinct names (`n0`, `n1`, ...), so that the schemas also round-trip
+/// through their JSON form.
+pub fn arb_schema() -> BoxedStrategy<Schema> {
+    let leaf = prop_oneof![
+        Just(Schema::Null),
+        Just(Schema::Boolean),
+        Just(Schema::Int),
+        Just(Schema::Long),
+        Just(Schema::Float),
+        Just(Schema::Double),
+        Just(Schema::Bytes),
+        Just(Schema::String),
+        (0usize..8).prop_map(|size| Schema::Fixed {
+            name: Name::new("fixed"),
+            size,
+        }),
+        (1usize..4).prop_map(|n| Schema::Enum {
+            name: Name::new("enum"),
+            doc: None,
+            symbols: (0..n).map(|i| format!("S{}", i)).collect(),
+            default: None,
+        }),
+    ];
+
+    leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|items| Schema::Array(Box::new(items))),
+            inner.clone().prop_map(|values| Schema::Map(Box::new(values))),
+            vec(inner.clone(), 1..4).prop_map(union),
+            vec(inner, 0..4).prop_map(record),
+        ]
+    }).prop_map(|mut schema| {
+        rename(&mut schema, &mut 0);
+        schema
+    }).boxed()
+}
+
+/// A strategy generating values conforming to `schema`.
+///
+/// Floating-point values are finite, so that generated values compare equal to themselves.
+/// Values of logical types are generated in their underlying representation and converted, when
+/// valid.
+pub fn arb_value(schema: &Schema) -> BoxedStrategy<Value> {
+    match *schema {
+        Schema::Null => Just(Value::Null).boxed(),
+        Schema::Boolean => any::<bool>().prop_map(Value::Boolean).boxed(),
+        Schema::Int => any::<i32>().prop_map(Value::Int).boxed(),
+        Schema::Long => any::<i64>().prop_map(Value::Long).boxed(),
+        Schema::Float => (f32::POSITIVE | f32::NEGATIVE | f32::NORMAL | f32::SUBNORMAL | f32::ZERO)
+            .prop_map(Value::Float)
+            .boxed(),
+        Schema::Double => (f64::POSITIVE | f64::NEGATIVE | f64::NORMAL | f64::SUBNORMAL | f64::ZERO)
+            .prop_map(Value::Double)
+            .boxed(),
+        Schema::Bytes => vec(any::<u8>(), 0..8).prop_map(Value::Bytes).boxed(),
+        Schema::String => arb_string().prop_map(Value::String).boxed(),
+        Schema::Fixed { size, .. } => vec(any::<u8>(), size)
+            .prop_map(move |bytes| Value::Fixed(size, bytes))
+            .boxed(),
+        Schema::Enum { ref symbols, .. } => {
+            let symbols = symbols.clone();
+            (0..symbols.len())
+                .prop_map(move |i| Value::Enum(i as i32, symbols[i].as_str().into()))
+                .boxed()
+        },
+        Schema::Union(ref union) => {
+            Union::new(union.variants().iter().map(arb_value).collect::<Vec<_>>())
+                .prop_map(|value| Value::Union(Box::new(value)))
+                .boxed()
+        },
+        Schema::Array(ref items) => vec(arb_value(items), 0..4).prop_map(Value::Array).boxed(),
+        Schema::Map(ref values) => hash_map(arb_string(), arb_value(values), 0..4)
+            .prop_map(|items| Value::Map(items.into_iter().map(|(k, v)| (k.into(), v)).collect()))
+            .boxed(),
+        Schema::Logical(ref logical) => {
+            let logical = logical.clone();
+            arb_value(logical.schema())
+                .prop_map(move |value| {
+                    logical
+                        .logical_type()
+                        .to_logical(value.clone())
+                        .unwrap_or(value)
+                }).boxed()
+        },
+        Schema::Record { ref fields, .. } => {
+            let names = fields
+                .iter()
+                .map(|field| field.name.clone())
+                .collect::<Vec<_>>();
+            fields
+                .iter()
+                .map(|field| arb_value(&field.schema))
+                .collect::<Vec<_>>()
+                .prop_map(move |values| Value::Record(names.iter().cloned().zip(values).collect()))
+                .boxed()
+        },
+    }
+}
+
+/// A strategy generating a valid schema together with a value conforming to it.
+pub fn arb_schema_and_value() -> BoxedStrategy<(Schema, Value)> {
+    arb_schema()
+        .prop_flat_map(|schema| {
+            let value = arb_value(&schema);
+            (Just(schema), value)
+        }).boxed()
+}
+
+fn arb_string() -> BoxedStrategy<String> {
+    vec(any::<char>(), 0..8)
+        .prop_map(|chars| chars.into_iter().collect())
+        .boxed()
+}
+
+// Unions may neither contain unions directly, nor several schemas of the same kind.
+fn union(schemas: Vec<Schema>) -> Schema {
+    let mut kinds = HashSet::new();
+    let mut variants = schemas
+        .into_iter()
+        .filter(|schema| {
+            let kind = SchemaKind::from(schema);
+            kind != SchemaKind::Union && kinds.insert(kind)
+        }).collect::<Vec<_>>();
+    if variants.is_empty() {
+        variants.push(Schema::Null);
+    }
+    Schema::Union(UnionSchema::new(variants).unwrap())
+}
+
+fn record(schemas: Vec<Schema>) -> Schema {
+    let fields = schemas
+        .into_iter()
+        .enumerate()
+        .map(|(position, schema)| RecordField {
+            name: format!("f{}", position),
+            doc: None,
+            default: None,
+            schema,
+            order: RecordFieldOrder::Ascending,
+            position,
+        }).collect::<Vec<RecordField>>();
+    Schema::Record {
+        name: Name::new("record"),
+        doc: None,
+        lookup: fields
+            .iter()
+            .map(|field| (field.name.clone(), field.position))
+            .collect(),
+        fields,
+    }
+}
+
+fn rename(schema: &mut Schema, count: &mut usize) {
+    match *schema {
+        Schema::Record {
+            ref mut name,
+            ref mut fields,
+            ..
+        } => {
+            *name = Name::new(&format!("n{}", count));
+            *count += 1;
+            for field in fields {
+                rename(&mut field.schema, count);
+            }
+        },
+        Schema::Enum { ref mut name, .. } | Schema::Fixed { ref mut name, .. } => {
+            *name = Name::new(&format!("n{}", count));
+            *count += 1;
+        },
+        Schema::Array(ref mut inner) | Schema::Map(ref mut inner) => rename(inner, count),
+        Schema::Union(ref mut union) => {
+            let mut variants = union.variants().to_vec();
+            for variant in &mut variants {
+                rename(variant, count);
+            }
+            *union = UnionSchema::new(variants).unwrap();
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decode::decode;
+    use encode::encode;
+    use serde_json;
+
+    proptest! {
+        #[test]
+        fn test_round_trip((schema, value) in arb_schema_and_value()) {
+            prop_assert!(value.validate(&schema));
+
+            let mut encoded = Vec::new();
+            encode(&value, &schema, &mut encoded);
+            prop_assert_eq!(decode(&schema, &mut &encoded[..]).unwrap(), value);
+        }
+
+        #[test]
+        fn test_schema_json(schema in arb_schema()) {
+            let json = serde_json::to_string(&schema).unwrap();
+            prop_assert_eq!(Schema::parse_str(&json).unwrap(), schema);
+        }
+    }
+}
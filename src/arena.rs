@@ -0,0 +1,312 @@
+//! Logic for decoding Avro data into values allocated from a bump arena.
+//!
+//! Only available with the `arena` feature enabled.
+use std::io::Read;
+use std::str::from_utf8;
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use decode::{decode_block_len, enum_index_out_of_bounds, invalid_utf8};
+use error::Error;
+
+use schema::Schema;
+use types::Value;
+use util::{safe_depth, safe_len, zag_i32, zag_i64, ByteReader, DecodeError, VarintRead};
+
+/// Represents any valid Avro value, with every string, array, map and record allocated from a
+/// bump arena.
+///
+/// Record field names are borrowed from the writer `Schema` and map entries are kept in the
+/// order they were decoded in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArenaValue<'a> {
+    /// A `null` Avro value.
+    Null,
+    /// A `boolean` Avro value.
+    Boolean(bool),
+    /// A `int` Avro value.
+    Int(i32),
+    /// A `long` Avro value.
+    Long(i64),
+    /// A `float` Avro value.
+    Float(f32),
+    /// A `double` Avro value.
+    Double(f64),
+    /// A `bytes` Avro value.
+    Bytes(&'a [u8]),
+    /// A `string` Avro value.
+    String(&'a str),
+    /// A `fixed` Avro value.
+    /// The size of the fixed value is represented as a `usize`.
+    Fixed(usize, &'a [u8]),
+    /// An `enum` Avro value.
+    Enum(i32, &'a str),
+    /// An `union` Avro value.
+    Union(&'a ArenaValue<'a>),
+    /// An `array` Avro value.
+    Array(&'a [ArenaValue<'a>]),
+    /// A `map` Avro value.
+    Map(&'a [(&'a str, ArenaValue<'a>)]),
+    /// A `record` Avro value.
+    Record(&'a [(&'a str, ArenaValue<'a>)]),
+}
+
+impl<'a> ArenaValue<'a> {
+    /// Copy an `ArenaValue` out of its arena into a `Value`.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            ArenaValue::Null => Value::Null,
+            ArenaValue::Boolean(b) => Value::Boolean(b),
+            ArenaValue::Int(i) => Value::Int(i),
+            ArenaValue::Long(i) => Value::Long(i),
+            ArenaValue::Float(x) => Value::Float(x),
+            ArenaValue::Double(x) => Value::Double(x),
+            ArenaValue::Bytes(bytes) => Value::Bytes(bytes.to_vec()),
+            ArenaValue::String(s) => Value::String(s.to_owned()),
+            ArenaValue::Fixed(size, bytes) => Value::Fixed(size, bytes.to_vec()),
+            ArenaValue::Enum(i, symbol) => Value::Enum(i, symbol.into()),
+            ArenaValue::Union(value) => Value::Union(Box::new(value.to_value())),
+            ArenaValue::Array(items) => Value::Array(items.iter().map(Self::to_value).collect()),
+            ArenaValue::Map(items) => Value::Map(
+                items
+                    .iter()
+                    .map(|&(key, ref value)| (key.into(), value.to_value()))
+                    .collect(),
+            ),
+            ArenaValue::Record(fields) => Value::Record(
+                fields
+                    .iter()
+                    .map(|&(name, ref value)| (name.to_owned(), value.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Decode an `ArenaValue` encoded in Avro format given its `Schema` and anything implementing
+/// `io::Read` to read from, allocating it from `arena`.
+///
+/// **NOTE** Schema resolution is not supported: the value is decoded as written, values of logical
+/// types included.
+pub fn from_avro_datum_in<'a, R: Read>(
+    writer_schema: &'a Schema,
+    reader: &mut R,
+    arena: &'a Bump,
+) -> Result<ArenaValue<'a>, Error> {
+    decode_in(writer_schema, &mut ByteReader(reader), arena)
+}
+
+fn decode_len<R: VarintRead>(reader: &mut R) -> Result<usize, Error> {
+    zag_i64(reader).and_then(|len| safe_len(len as usize))
+}
+
+fn decode_bytes_in<'a, R: VarintRead>(reader: &mut R, arena: &'a Bump) -> Result<&'a [u8], Error> {
+    let len = decode_len(reader)?;
+    let buf = arena.alloc_slice_fill_copy(len, 0u8);
+    reader.read_exact(buf)?;
+    Ok(buf)
+}
+
+fn decode_str_in<'a, R: VarintRead>(reader: &mut R, arena: &'a Bump) -> Result<&'a str, Error> {
+    let bytes = decode_bytes_in(reader, arena)?;
+    from_utf8(bytes).map_err(|e| invalid_utf8(bytes.to_vec(), e.valid_up_to()))
+}
+
+/// Decode an `ArenaValue` from avro format given its `Schema`.
+pub fn decode_in<'a, R: VarintRead>(
+    schema: &'a Schema,
+    reader: &mut R,
+    arena: &'a Bump,
+) -> Result<ArenaValue<'a>, Error> {
+    decode_nested_in(schema, reader, arena, 0)
+}
+
+// Decode an `ArenaValue` nested within `depth` arrays, maps and records.
+fn decode_nested_in<'a, R: VarintRead>(
+    schema: &'a Schema,
+    reader: &mut R,
+    arena: &'a Bump,
+    depth: usize,
+) -> Result<ArenaValue<'a>, Error> {
+    match *schema {
+        Schema::Null => Ok(ArenaValue::Null),
+        Schema::Boolean => {
+            let mut buf = [0u8; 1];
+            reader.read_exact(&mut buf[..])?;
+
+            match buf[0] {
+                0u8 => Ok(ArenaValue::Boolean(false)),
+                1u8 => Ok(ArenaValue::Boolean(true)),
+                _ => Err(DecodeError::new("not a bool").into()),
+            }
+        },
+        Schema::Int => zag_i32(reader).map(ArenaValue::Int),
+        Schema::Long => zag_i64(reader).map(ArenaValue::Long),
+        Schema::Float => {
+            let mut buf = [0u8; 4];
+            reader.read_exact(&mut buf[..])?;
+            Ok(ArenaValue::Float(f32::from_le_bytes(buf)))
+        },
+        Schema::Double => {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf[..])?;
+            Ok(ArenaValue::Double(f64::from_le_bytes(buf)))
+        },
+        Schema::Bytes => decode_bytes_in(reader, arena).map(ArenaValue::Bytes),
+        Schema::String => decode_str_in(reader, arena).map(ArenaValue::String),
+        Schema::Fixed { size, .. } => {
+            let buf = arena.alloc_slice_fill_copy(size, 0u8);
+            reader.read_exact(buf)?;
+            Ok(ArenaValue::Fixed(size, buf))
+        },
+        Schema::Logical(ref logical) => decode_nested_in(logical.schema(), reader, arena, depth),
+        Schema::Array(ref inner) => {
+            let depth = safe_depth(depth + 1)?;
+            let mut items = BumpVec::new_in(arena);
+
+            loop {
+                let len = decode_block_len(reader)?;
+                // arrays are 0-terminated, reading a length of 0 means the end of the array
+                if len == 0 {
+                    break
+                }
+
+                items.reserve(len);
+                for _ in 0..len {
+                    let index = items.len();
+                    items.push(
+                        decode_nested_in(inner, reader, arena, depth)
+                            .map_err(|e| e.within(|| format!("[{}]", index)))?,
+                    );
+                }
+            }
+
+            Ok(ArenaValue::Array(items.into_bump_slice()))
+        },
+        Schema::Map(ref inner) => {
+            let depth = safe_depth(depth + 1)?;
+            let mut items = BumpVec::new_in(arena);
+
+            loop {
+                let len = decode_block_len(reader)?;
+                // maps are 0-terminated, reading a length of 0 means the end of the map
+                if len == 0 {
+                    break
+                }
+
+                items.reserve(len);
+                for _ in 0..len {
+                    let key = decode_str_in(reader, arena)?;
+                    let value = decode_nested_in(inner, reader, arena, depth)
+                        .map_err(|e| e.within(|| format!("[{:?}]", key)))?;
+                    items.push((key, value));
+                }
+            }
+
+            Ok(ArenaValue::Map(items.into_bump_slice()))
+        },
+        Schema::Union(ref inner) => {
+            let index = zag_i64(reader)?;
+            match inner.variants().get(index as usize) {
+                Some(variant) => {
+                    let value = decode_nested_in(variant, reader, arena, depth)?;
+                    Ok(ArenaValue::Union(arena.alloc(value)))
+                },
+                None => Err(DecodeError::new("Union index out of bounds").into()),
+            }
+        },
+        Schema::Record { ref fields, .. } => {
+            let depth = safe_depth(depth + 1)?;
+            let mut items = BumpVec::with_capacity_in(fields.len(), arena);
+            for field in fields {
+                items.push((
+                    field.name.as_str(),
+                    decode_nested_in(&field.schema, reader, arena, depth)
+                        .map_err(|e| e.within(|| format!(".{}", field.name)))?,
+                ));
+            }
+            Ok(ArenaValue::Record(items.into_bump_slice()))
+        },
+        Schema::Enum { ref symbols, .. } => {
+            let index = zag_i32(reader)?;
+            match symbols.get(index as usize) {
+                Some(symbol) if index >= 0 => Ok(ArenaValue::Enum(index, symbol)),
+                _ => Err(enum_index_out_of_bounds(index, symbols)),
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datum::to_avro_datum;
+    use decode::decode;
+    use encode::encode_sized;
+
+    static SCHEMA: &'static str = r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": ["null", "string"]},
+                    {"name": "c", "type": {"type": "map", "values": "double"}},
+                    {"name": "d", "type": {"type": "array", "items": "bytes"}},
+                    {"name": "e", "type": {"type": "enum", "name": "suit", "symbols": ["spades", "hearts"]}},
+                    {"name": "f", "type": {"type": "fixed", "name": "md5", "size": 2}}
+                ]
+            }
+        "#;
+
+    #[test]
+    fn test_from_avro_datum_in() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Long(27)),
+            (
+                "b".to_owned(),
+                Value::Union(Box::new(Value::String("foo".to_owned()))),
+            ),
+            (
+                "c".to_owned(),
+                Value::Map(vec![("x".into(), Value::Double(1.5))].into_iter().collect()),
+            ),
+            (
+                "d".to_owned(),
+                Value::Array(vec![Value::Bytes(vec![1, 2]), Value::Bytes(vec![])]),
+            ),
+            ("e".to_owned(), Value::Enum(1, "hearts".into())),
+            ("f".to_owned(), Value::Fixed(2, vec![3, 4])),
+        ]);
+        let encoded = to_avro_datum(&schema, value.clone()).unwrap();
+
+        let arena = Bump::new();
+        let decoded = from_avro_datum_in(&schema, &mut &encoded[..], &arena).unwrap();
+        match decoded {
+            ArenaValue::Record(fields) => {
+                assert_eq!(fields[0], ("a", ArenaValue::Long(27)));
+                assert_eq!(
+                    fields[1],
+                    ("b", ArenaValue::Union(&ArenaValue::String("foo")))
+                );
+                assert_eq!(fields[4], ("e", ArenaValue::Enum(1, "hearts")));
+            },
+            other => panic!("unexpected value {:?}", other),
+        }
+        assert_eq!(decoded.to_value(), value);
+        assert_eq!(
+            decoded.to_value(),
+            decode(&schema, &mut &encoded[..]).unwrap()
+        );
+
+        // truncated input
+        assert!(from_avro_datum_in(&schema, &mut &encoded[..4], &arena).is_err());
+
+        // arrays and maps written in sized blocks
+        let mut encoded = Vec::new();
+        encode_sized(&value, &schema, &mut encoded);
+        let decoded = from_avro_datum_in(&schema, &mut &encoded[..], &arena).unwrap();
+        assert_eq!(decoded.to_value(), value);
+    }
+}
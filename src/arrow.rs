@@ -0,0 +1,876 @@
+//! Logic handling the conversion between Avro values and [Arrow](https://arrow.apache.org/)
+//! record batches.
+//!
+//! Avro types are mapped to Arrow ones as follows:
+//!
+//! | Avro                    | Arrow                         |
+//! |-------------------------|-------------------------------|
+//! | `null`                  | `Null`                        |
+//! | `boolean`               | `Boolean`                     |
+//! | `int`, `long`           | `Int32`, `Int64`              |
+//! | `float`, `double`       | `Float32`, `Float64`          |
+//! | `bytes`, `string`       | `Binary`, `Utf8`              |
+//! | `fixed`                 | `FixedSizeBinary`             |
+//! | `enum`                  | `Dictionary(Int32, Utf8)`     |
+//! | `array`                 | `List`                        |
+//! | `map`                   | `Map` with `Utf8` keys        |
+//! | `record`                | `Struct`                      |
+//! | `["null", T]`           | `T`, nullable                 |
+//!
+//! Other unions are not supported.
+//!
+//! The other way around, narrower Arrow integers are written as `int` or `long` values,
+//! `LargeBinary`, `LargeUtf8` and `LargeList` as `bytes`, `string` and `array` ones, and nullable
+//! fields as `["null", T]` unions. Dictionaries with `Int32` keys and `Utf8` values are written as
+//! `string` or `enum` values.
+//!
+//! Values of logical types are converted to Arrow in their underlying representation.
+use std::borrow::Cow;
+use std::io::Read;
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type,
+    UInt8Type,
+};
+use arrow_array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, DictionaryArray, FixedSizeBinaryArray,
+    Float32Array, Float64Array, Int32Array, Int64Array, ListArray, MapArray, NullArray,
+    RecordBatch, RecordBatchOptions, StringArray, StructArray,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{ArrowError, DataType, Field, FieldRef, Fields, SchemaRef};
+use serde_json::Value as JsonValue;
+
+use error::Error;
+
+use logical::{has_logical, to_logical, to_underlying};
+use reader::Reader;
+use schema::{Schema, UnionSchema};
+use types::Value;
+
+/// Map a record `Schema` to the Arrow schema of the record batches holding its values.
+pub fn to_arrow_schema(schema: &Schema) -> Result<arrow_schema::Schema, Error> {
+    match *schema {
+        Schema::Record { ref fields, .. } => Ok(arrow_schema::Schema::new(
+            fields
+                .iter()
+                .map(|field| arrow_field(&field.name, &field.schema))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        _ => Err(unsupported("only records can be converted to record batches")),
+    }
+}
+
+/// Convert record values of a `Schema` into an Arrow record batch, one row per value, also
+/// performing schema validation.
+pub fn to_record_batch(schema: &Schema, values: &[Value]) -> Result<RecordBatch, Error> {
+    let values = underlying(schema, values)?;
+    for value in values.iter() {
+        if !value.validate(schema) {
+            return Err(value.mismatch(schema))
+        }
+    }
+    record_batch(schema, Arc::new(to_arrow_schema(schema)?), &values)
+}
+
+// Convert values of `schema` into their underlying representation, if it has logical types.
+fn underlying<'v>(schema: &Schema, values: &'v [Value]) -> Result<Cow<'v, [Value]>, Error> {
+    if !has_logical(schema) {
+        return Ok(Cow::Borrowed(values))
+    }
+    values
+        .iter()
+        .map(|value| to_underlying(value.clone(), schema))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Cow::Owned)
+}
+
+fn record_batch(
+    schema: &Schema,
+    arrow_schema: SchemaRef,
+    values: &[Value],
+) -> Result<RecordBatch, Error> {
+    let columns = match build_array(schema, &values.iter().map(Some).collect::<Vec<_>>())? {
+        Column::Struct(_, columns) => columns,
+        _ => return Err(unsupported("only records can be converted to record batches")),
+    };
+    let options = RecordBatchOptions::new().with_row_count(Some(values.len()));
+    Ok(RecordBatch::try_new_with_options(
+        arrow_schema,
+        columns,
+        &options,
+    )?)
+}
+
+/// Derive the record `Schema` of the rows of Arrow record batches, given the name of the record.
+///
+/// Nested records and fixed types are named after the path of their field (e.g. `name_field`).
+pub fn from_arrow_schema(schema: &arrow_schema::Schema, name: &str) -> Result<Schema, Error> {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| avro_field(field, name))
+        .collect::<Result<Vec<_>, _>>()?;
+    Schema::parse(&json!({"type": "record", "name": name, "fields": fields}))
+}
+
+/// Convert the rows of an Arrow record batch into record values of a `Schema` (e.g. derived
+/// with [`from_arrow_schema`](fn.from_arrow_schema.html)), record fields being looked up by
+/// name among the columns.
+pub fn from_record_batch(schema: &Schema, batch: &RecordBatch) -> Result<Vec<Value>, Error> {
+    record_batch_rows(schema, batch).collect()
+}
+
+/// Convert the rows of an Arrow record batch into record values of a `Schema`, one at a time.
+pub(crate) fn record_batch_rows<'a>(
+    schema: &'a Schema,
+    batch: &RecordBatch,
+) -> impl Iterator<Item = Result<Value, Error>> + 'a {
+    let rows = StructArray::from(batch.clone());
+    (0..rows.len()).map(move |row| to_logical(value_at(&rows, row, schema)?, schema))
+}
+
+/// Interface for reading Avro formatted values as Arrow record batches.
+///
+/// Created with [`Reader::into_arrow`](struct.Reader.html#method.into_arrow), and to be used as
+/// an iterator.
+pub struct ArrowReader<'a, R> {
+    reader: Reader<'a, R>,
+    schema: Schema,
+    arrow_schema: SchemaRef,
+    batch_size: usize,
+    rows: Vec<Value>,
+}
+
+impl<'a, R: Read> ArrowReader<'a, R> {
+    pub(crate) fn new(reader: Reader<'a, R>, batch_size: usize) -> Result<Self, Error> {
+        // The values read are the ones of the reader schema, if any.
+        let schema = reader
+            .reader_schema()
+            .unwrap_or_else(|| reader.writer_schema())
+            .clone();
+        let arrow_schema = Arc::new(to_arrow_schema(&schema)?);
+        Ok(ArrowReader {
+            reader,
+            schema,
+            arrow_schema,
+            batch_size: batch_size.max(1),
+            rows: Vec::new(),
+        })
+    }
+
+    /// Get the Arrow schema of the record batches.
+    pub fn arrow_schema(&self) -> SchemaRef {
+        self.arrow_schema.clone()
+    }
+}
+
+impl<'a, R: Read> Iterator for ArrowReader<'a, R> {
+    type Item = Result<RecordBatch, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.clear();
+        while self.rows.len() < self.batch_size {
+            match self.reader.next() {
+                // Values of logical types are decoded in their logical representation.
+                Some(Ok(value)) => match to_underlying(value, &self.schema) {
+                    Ok(value) => self.rows.push(value),
+                    Err(e) => return Some(Err(e)),
+                },
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        if self.rows.is_empty() {
+            return None
+        }
+        Some(record_batch(
+            &self.schema,
+            self.arrow_schema.clone(),
+            &self.rows,
+        ))
+    }
+}
+
+fn missing_column(name: &str) -> Error {
+    ArrowError::InvalidArgumentError(format!("no column named {:?}", name)).into()
+}
+
+fn unsupported(reason: &str) -> Error {
+    ArrowError::SchemaError(reason.to_owned()).into()
+}
+
+// The variant of a `["null", T]` or `[T, "null"]` union.
+fn nullable_variant(union: &UnionSchema) -> Option<&Schema> {
+    match union.variants() {
+        [Schema::Null, variant] | [variant, Schema::Null] => Some(variant),
+        _ => None,
+    }
+}
+
+fn arrow_field(name: &str, schema: &Schema) -> Result<Field, Error> {
+    match *schema {
+        Schema::Union(ref union) => match nullable_variant(union) {
+            Some(variant) => Ok(Field::new(name, data_type(variant)?, true)),
+            None => Err(unsupported("only unions of null and another type are supported")),
+        },
+        Schema::Null => Ok(Field::new(name, DataType::Null, true)),
+        _ => Ok(Field::new(name, data_type(schema)?, false)),
+    }
+}
+
+fn data_type(schema: &Schema) -> Result<DataType, Error> {
+    Ok(match *schema {
+        Schema::Null => DataType::Null,
+        Schema::Boolean => DataType::Boolean,
+        Schema::Int => DataType::Int32,
+        Schema::Long => DataType::Int64,
+        Schema::Float => DataType::Float32,
+        Schema::Double => DataType::Float64,
+        Schema::Bytes => DataType::Binary,
+        Schema::String => DataType::Utf8,
+        Schema::Fixed { size, .. } => DataType::FixedSizeBinary(size as i32),
+        Schema::Enum { .. } => {
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+        },
+        Schema::Array(ref inner) => DataType::List(Arc::new(arrow_field("item", inner)?)),
+        Schema::Map(ref inner) => DataType::Map(map_entries(inner)?, false),
+        Schema::Record { ref fields, .. } => DataType::Struct(
+            fields
+                .iter()
+                .map(|field| arrow_field(&field.name, &field.schema))
+                .collect::<Result<Fields, _>>()?,
+        ),
+        Schema::Union(_) => {
+            return Err(unsupported("unions can only be nullable record fields or items"))
+        },
+        Schema::Logical(ref logical) => data_type(logical.schema())?,
+    })
+}
+
+fn map_entries(inner: &Schema) -> Result<FieldRef, Error> {
+    let entries = Fields::from(vec![
+        Field::new("key", DataType::Utf8, false),
+        arrow_field("value", inner)?,
+    ]);
+    Ok(Arc::new(Field::new("entries", DataType::Struct(entries), false)))
+}
+
+// An array built out of values, keeping the columns of records apart for record batches.
+enum Column {
+    Array(ArrayRef),
+    Struct(Fields, Vec<ArrayRef>),
+}
+
+impl Column {
+    // `nulls` only applies to structs, other arrays being built with theirs.
+    fn into_array(self, len: usize, nulls: Option<NullBuffer>) -> Result<ArrayRef, Error> {
+        match self {
+            Column::Array(array) => Ok(array),
+            Column::Struct(fields, columns) => Ok(Arc::new(if fields.is_empty() {
+                StructArray::new_empty_fields(len, nulls)
+            } else {
+                StructArray::try_new(fields, columns, nulls)?
+            })),
+        }
+    }
+}
+
+fn mismatch() -> Error {
+    ArrowError::InvalidArgumentError("value does not match schema".to_owned()).into()
+}
+
+// Build the array of `values` (`None` being null) of a schema, unions included.
+fn build_field(schema: &Schema, values: &[Option<&Value>]) -> Result<ArrayRef, Error> {
+    match *schema {
+        Schema::Union(ref union) => {
+            let variant = nullable_variant(union).ok_or_else(mismatch)?;
+            let values = values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Union(inner)) | Some(Value::UnionBranch(_, inner))
+                        if **inner == Value::Null =>
+                    {
+                        Ok(None)
+                    },
+                    Some(Value::Union(inner)) | Some(Value::UnionBranch(_, inner)) => {
+                        Ok(Some(&**inner))
+                    },
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<Vec<_>, _>>()?;
+            build_field(variant, &values)
+        },
+        _ => build_array(schema, values)?.into_array(values.len(), nulls(values)),
+    }
+}
+
+fn nulls(values: &[Option<&Value>]) -> Option<NullBuffer> {
+    if values.iter().any(Option::is_none) {
+        Some(NullBuffer::from(
+            values.iter().map(Option::is_some).collect::<Vec<_>>(),
+        ))
+    } else {
+        None
+    }
+}
+
+fn build_array(schema: &Schema, values: &[Option<&Value>]) -> Result<Column, Error> {
+    macro_rules! primitives {
+        ($array:ident, $variant:ident) => {
+            Arc::new(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Some(Value::$variant(x)) => Ok(Some(*x)),
+                        None => Ok(None),
+                        _ => Err(mismatch()),
+                    }).collect::<Result<$array, Error>>()?,
+            )
+        };
+    }
+
+    let array: ArrayRef = match *schema {
+        Schema::Null => Arc::new(NullArray::new(values.len())),
+        Schema::Boolean => primitives!(BooleanArray, Boolean),
+        Schema::Int => primitives!(Int32Array, Int),
+        Schema::Long => primitives!(Int64Array, Long),
+        Schema::Float => primitives!(Float32Array, Float),
+        Schema::Double => primitives!(Float64Array, Double),
+        Schema::Bytes => Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Bytes(bytes)) => Ok(Some(&bytes[..])),
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<BinaryArray, Error>>()?,
+        ),
+        Schema::String => Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::String(s)) => Ok(Some(&s[..])),
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<StringArray, Error>>()?,
+        ),
+        Schema::Fixed { size, .. } => {
+            let bytes = values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Fixed(_, bytes)) => Ok(Some(&bytes[..])),
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<Vec<_>, Error>>()?;
+            Arc::new(FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                bytes.into_iter(),
+                size as i32,
+            )?)
+        },
+        Schema::Enum { ref symbols, .. } => {
+            let keys = values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Enum(index, _)) => Ok(Some(*index)),
+                    Some(Value::String(s)) => symbols
+                        .iter()
+                        .position(|symbol| symbol == s)
+                        .map(|index| Some(index as i32))
+                        .ok_or_else(mismatch),
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<Int32Array, Error>>()?;
+            let symbols = Arc::new(StringArray::from_iter_values(symbols.iter()));
+            Arc::new(DictionaryArray::<Int32Type>::try_new(keys, symbols)?)
+        },
+        Schema::Array(ref inner) => {
+            let lists = values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Array(items)) => Ok(&items[..]),
+                    None => Ok(&[][..]),
+                    _ => Err(mismatch()),
+                }).collect::<Result<Vec<_>, Error>>()?;
+            let items = lists
+                .iter()
+                .flat_map(|items| items.iter().map(Some))
+                .collect::<Vec<_>>();
+            Arc::new(ListArray::try_new(
+                Arc::new(arrow_field("item", inner)?),
+                OffsetBuffer::from_lengths(lists.iter().map(|items| items.len())),
+                build_field(inner, &items)?,
+                nulls(values),
+            )?)
+        },
+        Schema::Map(ref inner) => {
+            let maps = values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Map(items)) => Ok(Some(items)),
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<Vec<_>, Error>>()?;
+            let entries = maps
+                .iter()
+                .flat_map(|items| items.iter().flat_map(|items| items.iter()))
+                .collect::<Vec<_>>();
+            let keys = StringArray::from_iter_values(entries.iter().map(|entry| &**entry.0));
+            let entry_values = entries.iter().map(|entry| Some(entry.1)).collect::<Vec<_>>();
+            let field = map_entries(inner)?;
+            let fields = match *field.data_type() {
+                DataType::Struct(ref fields) => fields.clone(),
+                _ => unreachable!(),
+            };
+            Arc::new(MapArray::try_new(
+                field,
+                OffsetBuffer::from_lengths(
+                    maps.iter().map(|items| items.map_or(0, |items| items.len())),
+                ),
+                StructArray::try_new(
+                    fields,
+                    vec![Arc::new(keys), build_field(inner, &entry_values)?],
+                    None,
+                )?,
+                nulls(values),
+                false,
+            )?)
+        },
+        Schema::Record { ref fields, .. } => {
+            let records = values
+                .iter()
+                .map(|value| match value {
+                    Some(Value::Record(items)) if items.len() == fields.len() => {
+                        Ok(Some(items))
+                    },
+                    None => Ok(None),
+                    _ => Err(mismatch()),
+                }).collect::<Result<Vec<_>, Error>>()?;
+            let columns = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let values = records
+                        .iter()
+                        .map(|items| items.map(|items| &items[i].1))
+                        .collect::<Vec<_>>();
+                    build_field(&field.schema, &values)
+                }).collect::<Result<Vec<_>, _>>()?;
+            let fields = fields
+                .iter()
+                .map(|field| arrow_field(&field.name, &field.schema))
+                .collect::<Result<Fields, _>>()?;
+            return Ok(Column::Struct(fields, columns))
+        },
+        Schema::Union(_) => return Err(mismatch()),
+        Schema::Logical(ref logical) => return build_array(logical.schema(), values),
+    };
+    Ok(Column::Array(array))
+}
+
+fn avro_field(field: &Field, path: &str) -> Result<JsonValue, Error> {
+    let path = format!("{}_{}", path, field.name());
+    let schema = avro_type(field.data_type(), &path)?;
+    Ok(if field.is_nullable() && *field.data_type() != DataType::Null {
+        json!({"name": field.name(), "type": ["null", schema], "default": null})
+    } else {
+        json!({"name": field.name(), "type": schema})
+    })
+}
+
+fn avro_type(data_type: &DataType, path: &str) -> Result<JsonValue, Error> {
+    Ok(match *data_type {
+        DataType::Null => json!("null"),
+        DataType::Boolean => json!("boolean"),
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::UInt8
+        | DataType::UInt16 => json!("int"),
+        DataType::Int64 | DataType::UInt32 => json!("long"),
+        DataType::Float32 => json!("float"),
+        DataType::Float64 => json!("double"),
+        DataType::Binary | DataType::LargeBinary => json!("bytes"),
+        DataType::Utf8 | DataType::LargeUtf8 => json!("string"),
+        DataType::Dictionary(ref keys, ref values)
+            if **keys == DataType::Int32 && **values == DataType::Utf8 =>
+        {
+            json!("string")
+        },
+        DataType::FixedSizeBinary(size) => json!({"type": "fixed", "name": path, "size": size}),
+        DataType::List(ref item) | DataType::LargeList(ref item) => {
+            json!({"type": "array", "items": avro_item(item, path)?})
+        },
+        DataType::Map(ref entries, _) => match *entries.data_type() {
+            DataType::Struct(ref fields)
+                if fields.len() == 2 && *fields[0].data_type() == DataType::Utf8 =>
+            {
+                json!({"type": "map", "values": avro_item(&fields[1], path)?})
+            },
+            _ => return Err(unsupported("only maps with Utf8 keys are supported")),
+        },
+        DataType::Struct(ref fields) => json!({
+            "type": "record",
+            "name": path,
+            "fields": fields
+                .iter()
+                .map(|field| avro_field(field, path))
+                .collect::<Result<Vec<_>, _>>()?,
+        }),
+        ref other => return Err(unsupported(&format!("unsupported Arrow type {}", other))),
+    })
+}
+
+// Items of lists and values of maps, which are not named.
+fn avro_item(field: &Field, path: &str) -> Result<JsonValue, Error> {
+    let schema = avro_type(field.data_type(), path)?;
+    Ok(if field.is_nullable() && *field.data_type() != DataType::Null {
+        json!(["null", schema])
+    } else {
+        schema
+    })
+}
+
+fn cannot_write(array: &dyn Array, schema: &Schema) -> Error {
+    ArrowError::InvalidArgumentError(format!(
+        "cannot write Arrow {} as Avro {:?}",
+        array.data_type(),
+        ::schema::SchemaKind::from(schema)
+    )).into()
+}
+
+// Convert the value at `row` of an Arrow array into a value of `schema`.
+fn value_at(array: &dyn Array, row: usize, schema: &Schema) -> Result<Value, Error> {
+    if let Schema::Logical(ref logical) = *schema {
+        return value_at(array, row, logical.schema())
+    }
+    if let Schema::Union(ref union) = *schema {
+        if array.is_null(row) && union.variants().contains(&Schema::Null) {
+            return Ok(Value::Union(Box::new(Value::Null)))
+        }
+        let variant = nullable_variant(union).ok_or_else(|| cannot_write(array, schema))?;
+        return value_at(array, row, variant).map(|value| Value::Union(Box::new(value)))
+    }
+    if array.is_null(row) && *schema != Schema::Null {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "cannot write null as Avro {:?}",
+            ::schema::SchemaKind::from(schema)
+        )).into())
+    }
+
+    macro_rules! primitive {
+        ($type:ty) => {
+            array.as_primitive::<$type>().value(row)
+        };
+    }
+
+    Ok(match (schema, array.data_type()) {
+        (Schema::Null, _) => Value::Null,
+        (Schema::Boolean, DataType::Boolean) => Value::Boolean(array.as_boolean().value(row)),
+        (Schema::Int, DataType::Int8) => Value::Int(i32::from(primitive!(Int8Type))),
+        (Schema::Int, DataType::Int16) => Value::Int(i32::from(primitive!(Int16Type))),
+        (Schema::Int, DataType::Int32) => Value::Int(primitive!(Int32Type)),
+        (Schema::Int, DataType::UInt8) => Value::Int(i32::from(primitive!(UInt8Type))),
+        (Schema::Int, DataType::UInt16) => Value::Int(i32::from(primitive!(UInt16Type))),
+        (Schema::Long, DataType::Int8) => Value::Long(i64::from(primitive!(Int8Type))),
+        (Schema::Long, DataType::Int16) => Value::Long(i64::from(primitive!(Int16Type))),
+        (Schema::Long, DataType::Int32) => Value::Long(i64::from(primitive!(Int32Type))),
+        (Schema::Long, DataType::Int64) => Value::Long(primitive!(Int64Type)),
+        (Schema::Long, DataType::UInt8) => Value::Long(i64::from(primitive!(UInt8Type))),
+        (Schema::Long, DataType::UInt16) => Value::Long(i64::from(primitive!(UInt16Type))),
+        (Schema::Long, DataType::UInt32) => Value::Long(i64::from(primitive!(UInt32Type))),
+        (Schema::Float, DataType::Float32) => Value::Float(primitive!(Float32Type)),
+        (Schema::Double, DataType::Float32) => Value::Double(f64::from(primitive!(Float32Type))),
+        (Schema::Double, DataType::Float64) => Value::Double(primitive!(Float64Type)),
+        (Schema::Bytes, DataType::Binary) => {
+            Value::Bytes(array.as_binary::<i32>().value(row).to_vec())
+        },
+        (Schema::Bytes, DataType::LargeBinary) => {
+            Value::Bytes(array.as_binary::<i64>().value(row).to_vec())
+        },
+        (Schema::Bytes, DataType::FixedSizeBinary(_)) => {
+            Value::Bytes(array.as_fixed_size_binary().value(row).to_vec())
+        },
+        (&Schema::Fixed { size, .. }, &DataType::FixedSizeBinary(n)) if n as usize == size => {
+            Value::Fixed(size, array.as_fixed_size_binary().value(row).to_vec())
+        },
+        (Schema::String, _) | (Schema::Enum { .. }, _) => {
+            let s = string_at(array, row).ok_or_else(|| cannot_write(array, schema))?;
+            match *schema {
+                Schema::Enum { ref symbols, .. } => {
+                    let index = symbols.iter().position(|symbol| symbol == s).ok_or_else(|| {
+                        ArrowError::InvalidArgumentError(format!("{:?} is not a symbol", s))
+                    })?;
+                    Value::Enum(index as i32, s.into())
+                },
+                _ => Value::String(s.to_owned()),
+            }
+        },
+        (Schema::Array(inner), DataType::List(_)) => {
+            items_at(&*array.as_list::<i32>().value(row), inner)?
+        },
+        (Schema::Array(inner), DataType::LargeList(_)) => {
+            items_at(&*array.as_list::<i64>().value(row), inner)?
+        },
+        (Schema::Map(inner), DataType::Map(_, _)) => {
+            let entries = array.as_map().value(row);
+            match entries.column(0).as_string_opt::<i32>() {
+                Some(keys) => (0..entries.len())
+                    .map(|i| Ok((keys.value(i).into(), value_at(&**entries.column(1), i, inner)?)))
+                    .collect::<Result<_, Error>>()
+                    .map(Value::Map)?,
+                None => return Err(cannot_write(array, schema)),
+            }
+        },
+        (Schema::Record { fields, .. }, DataType::Struct(_)) => {
+            let record = array.as_struct();
+            fields
+                .iter()
+                .map(|field| {
+                    let column = record
+                        .column_by_name(&field.name)
+                        .ok_or_else(|| missing_column(&field.name))?;
+                    Ok((field.name.clone(), value_at(&**column, row, &field.schema)?))
+                }).collect::<Result<_, Error>>()
+                .map(Value::Record)?
+        },
+        _ => return Err(cannot_write(array, schema)),
+    })
+}
+
+fn string_at(array: &dyn Array, row: usize) -> Option<&str> {
+    match *array.data_type() {
+        DataType::Utf8 => Some(array.as_string::<i32>().value(row)),
+        DataType::LargeUtf8 => Some(array.as_string::<i64>().value(row)),
+        DataType::Dictionary(_, _) => {
+            let dictionary = array.as_dictionary_opt::<Int32Type>()?;
+            let key = dictionary.keys().value(row) as usize;
+            dictionary
+                .values()
+                .as_string_opt::<i32>()
+                .map(|values| values.value(key))
+        },
+        _ => None,
+    }
+}
+
+fn items_at(items: &dyn Array, inner: &Schema) -> Result<Value, Error> {
+    (0..items.len())
+        .map(|i| value_at(items, i, inner))
+        .collect::<Result<_, _>>()
+        .map(Value::Array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use types::{Record, ToAvro};
+    use writer::Writer;
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "b", "type": ["null", "string"]},
+                {"name": "c", "type": ["null", {"type": "array", "items": "int"}]},
+                {"name": "d", "type": {"type": "map", "values": "double"}},
+                {
+                    "name": "e",
+                    "type": {"type": "enum", "name": "suit", "symbols": ["heart", "spade"]}
+                },
+                {
+                    "name": "f",
+                    "type": {
+                        "type": "record",
+                        "name": "inner",
+                        "fields": [
+                            {"name": "g", "type": {"type": "fixed", "name": "id", "size": 2}}
+                        ]
+                    }
+                }
+            ]
+        }
+    "#;
+
+    fn record(schema: &Schema, a: i64) -> Value {
+        let mut record = Record::new(schema).unwrap();
+        record.put("a", a);
+        record.put(
+            "b",
+            Value::Union(Box::new(if a % 2 == 0 {
+                Value::Null
+            } else {
+                Value::String(format!("foo{}", a))
+            })),
+        );
+        record.put(
+            "c",
+            Value::Union(Box::new(if a == 1 {
+                Value::Null
+            } else {
+                Value::Array((0..a as i32).map(Value::Int).collect())
+            })),
+        );
+        let mut map = HashMap::new();
+        map.insert("x".into(), Value::Double(a as f64));
+        record.put("d", Value::Map(map));
+        let suit = (a % 2) as usize;
+        record.put("e", Value::Enum(suit as i32, ["heart", "spade"][suit].into()));
+        record.put(
+            "f",
+            Value::Record(vec![("g".to_owned(), Value::Fixed(2, vec![a as u8; 2]))]),
+        );
+        record.avro()
+    }
+
+    #[test]
+    fn test_to_arrow_schema() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let arrow_schema = to_arrow_schema(&schema).unwrap();
+        let types = arrow_schema
+            .fields()
+            .iter()
+            .map(|field| (field.name().as_str(), field.is_nullable()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                ("a", false),
+                ("b", true),
+                ("c", true),
+                ("d", false),
+                ("e", false),
+                ("f", false),
+            ]
+        );
+        assert_eq!(arrow_schema.field(1).data_type(), &DataType::Utf8);
+
+        assert!(to_arrow_schema(&Schema::Long).is_err());
+        let union = Schema::parse_str(
+            r#"{"type": "record", "name": "t", "fields": [{"name": "a", "type": ["int", "long"]}]}"#,
+        ).unwrap();
+        assert!(to_arrow_schema(&union).is_err());
+    }
+
+    #[test]
+    fn test_to_record_batch() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let values = (0..3).map(|a| record(&schema, a)).collect::<Vec<_>>();
+        let batch = to_record_batch(&schema, &values).unwrap();
+
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().values(), &[0, 1, 2]);
+        let b = batch.column(1).as_string::<i32>();
+        assert!(b.is_null(0));
+        assert_eq!(b.value(1), "foo1");
+        let c = batch.column(2).as_list::<i32>();
+        assert!(c.is_null(1));
+        assert_eq!(c.value(2).len(), 2);
+        assert_eq!(batch.column(3).as_map().value(1).len(), 1);
+        let e = batch.column(4).as_dictionary::<Int32Type>();
+        assert_eq!(e.keys().values(), &[0, 1, 0]);
+        let f = batch.column(5).as_struct();
+        assert_eq!(f.column(0).as_fixed_size_binary().value(2), &[2, 2]);
+
+        match to_record_batch(&schema, &[Value::Long(1)]).unwrap_err() {
+            Error::SchemaMismatch { .. } => (),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_arrow_reader() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        writer
+            .extend((0..5).map(|a| record(&schema, a)))
+            .unwrap();
+        let input = writer.into_inner();
+
+        let batches = Reader::new(&input[..])
+            .unwrap()
+            .into_arrow(2)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            batches.iter().map(RecordBatch::num_rows).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        assert_eq!(
+            batches[2].column(0).as_primitive::<Int64Type>().values(),
+            &[4]
+        );
+    }
+
+    #[test]
+    fn test_from_record_batch() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let values = (0..3).map(|a| record(&schema, a)).collect::<Vec<_>>();
+        let batch = to_record_batch(&schema, &values).unwrap();
+        assert_eq!(from_record_batch(&schema, &batch).unwrap(), values);
+
+        // with a derived schema, enums being written as strings
+        let derived = from_arrow_schema(&batch.schema(), "test").unwrap();
+        let mut writer = Writer::new(&derived, Vec::new()).unwrap();
+        writer.append_record_batch(&batch).unwrap();
+        writer.flush().unwrap();
+        let input = writer.into_inner();
+        let values = Reader::new(&input[..])
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values.len(), 3);
+        match values[1] {
+            Value::Record(ref fields) => {
+                assert_eq!(fields[4], ("e".to_owned(), Value::String("spade".to_owned())));
+                assert_eq!(fields[1].1, Value::Union(Box::new(Value::String("foo1".to_owned()))));
+            },
+            ref other => panic!("unexpected value {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_record_batch_conversions() {
+        let arrow_schema = Arc::new(arrow_schema::Schema::new(vec![
+            Field::new("a", DataType::Int16, false),
+            Field::new("b", DataType::UInt32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            arrow_schema.clone(),
+            vec![
+                Arc::new(arrow_array::Int16Array::from(vec![1, -2])),
+                Arc::new(arrow_array::UInt32Array::from(vec![Some(3), None])),
+            ],
+        ).unwrap();
+        let schema = from_arrow_schema(&arrow_schema, "test").unwrap();
+        assert_eq!(
+            from_record_batch(&schema, &batch).unwrap(),
+            vec![
+                Value::Record(vec![
+                    ("a".to_owned(), Value::Int(1)),
+                    ("b".to_owned(), Value::Union(Box::new(Value::Long(3)))),
+                ]),
+                Value::Record(vec![
+                    ("a".to_owned(), Value::Int(-2)),
+                    ("b".to_owned(), Value::Union(Box::new(Value::Null))),
+                ]),
+            ]
+        );
+
+        // a null value in a column not nullable in the Avro schema
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "b", "type": "long"}]}"#,
+        ).unwrap();
+        assert!(from_record_batch(&schema, &batch).is_err());
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "c", "type": "long"}]}"#,
+        ).unwrap();
+        assert!(from_record_batch(&schema, &batch).is_err());
+    }
+}
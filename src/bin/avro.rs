@@ -0,0 +1,615 @@
+//! Command-line tool to inspect and convert Avro object container files, in the spirit of
+//! `avro-tools`.
+//!
+//! Only built with the `cli` feature: `cargo run --features cli -- <command> ...`
+extern crate avro_rs;
+extern crate serde_json;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process;
+use std::str::FromStr;
+
+use avro_rs::types::Value;
+use avro_rs::{
+    diff_schemas, diff_values, migrate, repair, transcode, Codec, Error, JsonReader, JsonWriter,
+    Reader, Schema, ValueChange, Writer,
+};
+use serde_json::Value as JsonValue;
+
+const USAGE: &str = "\
+usage: avro <command> [options] <file>
+
+Reads from the standard input when <file> is `-`. Exits with status 2 on errors.
+
+commands:
+    cat [--limit <n>] <file>        print the records of a container file
+    getschema <file>                print the writer schema of a container file
+    getmeta <file>                  print the metadata of a container file
+    tojson <file>                   print the records of a container file, using the JSON encoding
+    fromjson --schema <schema> [--codec <codec>] <file>
+                                    write a container file of JSON-encoded records to the
+                                    standard output
+    diff [--data] [--key <fields>] <old> <new>
+                                    print a JSON report of the differences between the schemas
+                                    of two container files and, with --data, between their
+                                    records (matched by the comma-separated key <fields>, or by
+                                    position); exits with status 1 if they differ
+    fingerprint <file>              print the Parsing Canonical Form and the CRC-64-AVRO
+                                    (little-endian), MD5 and SHA-256 fingerprints of a schema
+                                    file, or of the writer schema of a container file
+    repair <file>                   write the records salvaged from a corrupt container file to
+                                    the standard output, reporting the lost blocks on the
+                                    standard error; exits with status 1 if any were lost
+    recodec [--codec <codec>] <file>
+                                    write a container file to the standard output compressed
+                                    with <codec> (null by default), without decoding its records
+    migrate --schema <schema> <file>
+                                    write the records of a container file, resolved against a
+                                    new compatible schema, to a container file written with it
+                                    to the standard output";
+
+// Options taking no value.
+const FLAGS: &[&str] = &["--data"];
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+
+    let result = run(&args, &mut open, &mut output, &mut io::stderr()).and_then(|same| {
+        output.flush()?;
+        Ok(same)
+    });
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        // e.g. when piped into `head`
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::BrokenPipe => (),
+        Err(e) => {
+            eprintln!("avro: {}", e);
+            process::exit(2);
+        },
+    }
+}
+
+// Opens the file at a path (`-` for the standard input).
+type Open<'a> = dyn FnMut(&str) -> Result<Box<dyn Read>, Error> + 'a;
+
+// Run a command reading its files with `open`, writing its output to `output` and its reports
+// to `log`, returning `false` when `diff` finds differences or `repair` loses records.
+fn run<W: Write, L: Write>(
+    args: &[String],
+    open: &mut Open,
+    output: &mut W,
+    log: &mut L,
+) -> Result<bool, Error> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), Options::parse(args)?),
+        None => return Err(Error::custom(USAGE)),
+    };
+
+    match command {
+        "cat" => {
+            let limit = match args.value("--limit") {
+                Some(limit) => {
+                    usize::from_str(limit).map_err(|_| Error::custom("invalid --limit"))?
+                },
+                None => usize::max_value(),
+            };
+            for value in Reader::new(args.input(open)?)?.take(limit) {
+                writeln!(output, "{}", value?)?;
+            }
+        },
+        "getschema" => {
+            let reader = Reader::new(args.input(open)?)?;
+            serde_json::to_writer_pretty(&mut *output, reader.writer_schema())?;
+            writeln!(output)?;
+        },
+        "getmeta" => {
+            let reader = Reader::new(args.input(open)?)?;
+            let mut metadata = reader.metadata().iter().collect::<Vec<_>>();
+            metadata.sort();
+            for (key, value) in metadata {
+                writeln!(output, "{}\t{}", key, String::from_utf8_lossy(value))?;
+            }
+        },
+        "tojson" => {
+            let reader = Reader::new(args.input(open)?)?;
+            let schema = reader.writer_schema().clone();
+            let mut writer = JsonWriter::new(&schema, &mut *output);
+            for value in reader {
+                writer.append(value?)?;
+            }
+        },
+        "fromjson" => {
+            let schema = args.schema(open)?;
+            let mut writer = Writer::with_codec(&schema, &mut *output, args.codec()?)?;
+            for value in JsonReader::new(&schema, args.input(open)?) {
+                writer.append(value?)?;
+            }
+            writer.flush()?;
+        },
+        "fingerprint" => {
+            let mut bytes = Vec::new();
+            args.input(open)?.read_to_end(&mut bytes)?;
+            let schema = if bytes.starts_with(b"Obj\x01") {
+                Reader::new(&bytes[..])?.writer_schema().clone()
+            } else {
+                let json = String::from_utf8(bytes)
+                    .map_err(|_| Error::custom("schema file is not valid UTF-8"))?;
+                Schema::parse_str(&json)?
+            };
+            let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+            writeln!(output, "{}", schema.canonical_form())?;
+            writeln!(output, "CRC-64-AVRO\t{}", hex(&schema.rabin_fingerprint().to_le_bytes()))?;
+            writeln!(output, "MD5\t{}", hex(&schema.md5_fingerprint()))?;
+            writeln!(output, "SHA-256\t{}", hex(&schema.sha256_fingerprint()))?;
+        },
+        "diff" => return diff(&args, open, output),
+        "repair" => {
+            let report = repair(args.input(open)?, &mut *output)?;
+            for lost in &report.lost {
+                let records = lost.records.map_or("unknown".to_owned(), |n| n.to_string());
+                writeln!(
+                    log,
+                    "lost {} records ({} bytes) at offset {}: {}",
+                    records, lost.size, lost.offset, lost.error
+                )?;
+            }
+            writeln!(
+                log,
+                "salvaged {} records, lost {} records and {} bytes",
+                report.records,
+                report.lost_records(),
+                report.lost_bytes()
+            )?;
+            return Ok(report.lost.is_empty())
+        },
+        "migrate" => {
+            migrate(args.input(open)?, &mut *output, &args.schema(open)?)?;
+        },
+        "recodec" => {
+            transcode(args.input(open)?, &mut *output, args.codec()?)?;
+        },
+        _ => return Err(Error::custom(USAGE)),
+    }
+    Ok(true)
+}
+
+fn diff<W: Write>(args: &Options, open: &mut Open, output: &mut W) -> Result<bool, Error> {
+    let (old, new) = match args.inputs[..] {
+        [old, new] => (Reader::new(open(old)?)?, Reader::new(open(new)?)?),
+        _ => return Err(Error::custom(USAGE)),
+    };
+    let schema = diff_schemas(old.writer_schema(), new.writer_schema())
+        .into_iter()
+        .map(|change| {
+            let json = |text: Option<String>| {
+                text.map_or(JsonValue::Null, |text| serde_json::from_str(&text).unwrap())
+            };
+            serde_json::json!({
+                "path": change.path,
+                "old": json(change.old),
+                "new": json(change.new),
+            })
+        }).collect::<Vec<_>>();
+    let mut same = schema.is_empty();
+    let mut report = serde_json::json!({ "schema": schema });
+
+    let key = args.value("--key");
+    if args.flag("--data") || key.is_some() {
+        let data = diff_records(old, new, key)?;
+        same &= data["records"].as_array().is_some_and(Vec::is_empty);
+        report["data"] = data;
+    }
+    serde_json::to_writer_pretty(&mut *output, &report)?;
+    writeln!(output)?;
+    Ok(same)
+}
+
+// Compare the records of two files, matched by their `key` fields (or by position), holding the
+// old ones in memory.
+fn diff_records<R: Read>(
+    old: Reader<R>,
+    new: Reader<R>,
+    key: Option<&str>,
+) -> Result<JsonValue, Error> {
+    let key_of = |position: usize, value: &Value| -> Result<JsonValue, Error> {
+        let fields = match (key, value) {
+            (None, _) => return Ok(JsonValue::from(position)),
+            (Some(key), Value::Record(fields)) => key.split(',').map(move |name| {
+                fields
+                    .iter()
+                    .find(|field| field.0 == name)
+                    .map(|field| json_value(&field.1))
+                    .ok_or_else(|| Error::custom(format!("missing key field {:?}", name)))
+            }),
+            (Some(_), _) => return Err(Error::custom("--key needs records")),
+        };
+        let mut fields = fields.collect::<Result<Vec<_>, _>>()?;
+        Ok(match fields.len() {
+            1 => fields.remove(0),
+            _ => JsonValue::Array(fields),
+        })
+    };
+    let duplicate = |key: &JsonValue| Error::custom(format!("duplicate key {}", key));
+
+    let mut olds = Vec::new();
+    let mut positions = HashMap::new();
+    for (i, value) in old.enumerate() {
+        let value = value?;
+        let key = key_of(i, &value)?;
+        if positions.insert(key.to_string(), olds.len()).is_some() {
+            return Err(duplicate(&key))
+        }
+        olds.push((key, Some(value)));
+    }
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    let mut records = Vec::new();
+    let mut seen = HashSet::new();
+    for (i, value) in new.enumerate() {
+        let value = value?;
+        let key = key_of(i, &value)?;
+        if !seen.insert(key.to_string()) {
+            return Err(duplicate(&key))
+        }
+        let changes = match positions.get(&key.to_string()) {
+            Some(&position) => {
+                let old = olds[position].1.take().expect("keys are unique");
+                let changes = diff_values(&old, &value);
+                if !changes.is_empty() {
+                    changed += 1;
+                }
+                changes
+            },
+            None => {
+                added += 1;
+                vec![ValueChange {
+                    path: "$".to_owned(),
+                    old: None,
+                    new: Some(value),
+                }]
+            },
+        };
+        if !changes.is_empty() {
+            records.push(record_report(key, changes));
+        }
+    }
+    for (key, old) in olds {
+        if let Some(old) = old {
+            removed += 1;
+            let change = ValueChange {
+                path: "$".to_owned(),
+                old: Some(old),
+                new: None,
+            };
+            records.push(record_report(key, vec![change]));
+        }
+    }
+
+    Ok(serde_json::json!({
+        "added": added,
+        "removed": removed,
+        "changed": changed,
+        "records": records,
+    }))
+}
+
+fn record_report(key: JsonValue, changes: Vec<ValueChange>) -> JsonValue {
+    let changes = changes
+        .iter()
+        .map(|change| {
+            serde_json::json!({
+                "path": change.path,
+                "old": change.old.as_ref().map_or(JsonValue::Null, json_value),
+                "new": change.new.as_ref().map_or(JsonValue::Null, json_value),
+            })
+        }).collect::<Vec<_>>();
+    serde_json::json!({ "key": key, "changes": changes })
+}
+
+// A value as JSON, as rendered by its `Display` implementation.
+fn json_value(value: &Value) -> JsonValue {
+    let text = value.to_json_string();
+    serde_json::from_str(&text).unwrap_or(JsonValue::String(text))
+}
+
+fn open(path: &str) -> Result<Box<dyn Read>, Error> {
+    match path {
+        "-" => Ok(Box::new(io::stdin())),
+        path => Ok(Box::new(BufReader::new(File::open(path)?))),
+    }
+}
+
+// Options given as `--name value` (or `--flag`), followed by the paths of the inputs.
+struct Options<'a> {
+    values: Vec<(&'a str, &'a str)>,
+    flags: Vec<&'a str>,
+    inputs: Vec<&'a str>,
+}
+
+impl<'a> Options<'a> {
+    fn parse(args: &'a [String]) -> Result<Options<'a>, Error> {
+        let mut options = Options {
+            values: Vec::new(),
+            flags: Vec::new(),
+            inputs: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if FLAGS.contains(&arg.as_str()) {
+                options.flags.push(arg);
+            } else if arg.starts_with("--") {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Error::custom(format!("missing value for {}", arg)))?;
+                options.values.push((arg, value));
+            } else {
+                options.inputs.push(arg);
+            }
+        }
+        Ok(options)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(&name)
+    }
+
+    fn value(&self, name: &str) -> Option<&'a str> {
+        self.values
+            .iter()
+            .find(|&&(option, _)| option == name)
+            .map(|&(_, value)| value)
+    }
+
+    // The schema of the file of `--schema`.
+    fn schema(&self, open: &mut Open) -> Result<Schema, Error> {
+        let path = self.value("--schema").ok_or_else(|| Error::custom("missing --schema"))?;
+        let mut json = String::new();
+        open(path)?.read_to_string(&mut json)?;
+        Ok(Schema::parse_str(&json)?)
+    }
+
+    // The codec of `--codec`, `null` by default.
+    fn codec(&self) -> Result<Codec, Error> {
+        match self.value("--codec") {
+            Some(codec) => Codec::from_str(codec).map_err(|_| Error::custom("unknown --codec")),
+            None => Ok(Codec::Null),
+        }
+    }
+
+    fn input(&self, open: &mut Open) -> Result<Box<dyn Read>, Error> {
+        match self.inputs[..] {
+            [path] => open(path),
+            _ => Err(Error::custom(USAGE)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"{"type": "record", "name": "test", "fields": [
+        {"name": "a", "type": "long"},
+        {"name": "b", "type": "string"}
+    ]}"#;
+
+    fn record(a: i64, b: &str) -> Value {
+        Value::Record(vec![
+            ("a".to_owned(), Value::Long(a)),
+            ("b".to_owned(), Value::String(b.to_owned())),
+        ])
+    }
+
+    // A container file of `values` written with `schema`, a block per value.
+    fn container(schema: &str, values: Vec<Value>) -> Vec<u8> {
+        let schema = Schema::parse_str(schema).unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        for value in values {
+            writer.append(value).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn records(container: &[u8]) -> Vec<Value> {
+        Reader::new(container).unwrap().map(Result::unwrap).collect()
+    }
+
+    // Run a command over in-memory files, returning its result, output and log.
+    fn run_on(args: &[&str], files: &[(&str, Vec<u8>)]) -> (Result<bool, Error>, Vec<u8>, String) {
+        let args = args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>();
+        let mut open = |path: &str| -> Result<Box<dyn Read>, Error> {
+            match files.iter().find(|file| file.0 == path) {
+                Some(file) => Ok(Box::new(io::Cursor::new(file.1.clone()))),
+                None => Err(Error::custom(format!("no file {}", path))),
+            }
+        };
+        let (mut output, mut log) = (Vec::new(), Vec::new());
+        let result = run(&args, &mut open, &mut output, &mut log);
+        (result, output, String::from_utf8(log).unwrap())
+    }
+
+    // The output of a successful command, as text.
+    fn output(args: &[&str], files: &[(&str, Vec<u8>)]) -> String {
+        let (result, output, _) = run_on(args, files);
+        assert!(result.unwrap());
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_cat_and_tojson() {
+        let files = [("f.avro", container(SCHEMA, vec![record(1, "x"), record(2, "y")]))];
+        assert_eq!(
+            output(&["cat", "f.avro"], &files),
+            "{\"a\": 1, \"b\": \"x\"}\n{\"a\": 2, \"b\": \"y\"}\n"
+        );
+        assert_eq!(
+            output(&["cat", "--limit", "1", "f.avro"], &files),
+            "{\"a\": 1, \"b\": \"x\"}\n"
+        );
+        assert!(run_on(&["cat", "--limit", "x", "f.avro"], &files).0.is_err());
+        assert_eq!(
+            output(&["tojson", "f.avro"], &files),
+            "{\"a\":1,\"b\":\"x\"}\n{\"a\":2,\"b\":\"y\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_getschema_and_getmeta() {
+        let files = [("f.avro", container(SCHEMA, vec![record(1, "x")]))];
+        let schema = output(&["getschema", "f.avro"], &files);
+        assert!(schema.ends_with("}\n"));
+        assert_eq!(
+            Schema::parse_str(&schema).unwrap(),
+            Schema::parse_str(SCHEMA).unwrap()
+        );
+        assert_eq!(
+            output(&["getmeta", "f.avro"], &files),
+            format!(
+                "avro.codec\tnull\navro.schema\t{}\n",
+                serde_json::to_string(&Schema::parse_str(SCHEMA).unwrap()).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_fromjson_and_recodec() {
+        let files = [
+            ("s.avsc", SCHEMA.as_bytes().to_vec()),
+            ("f.json", b"{\"a\": 1, \"b\": \"x\"}\n{\"a\": 2, \"b\": \"y\"}\n".to_vec()),
+        ];
+        let (result, written, _) = run_on(&["fromjson", "--schema", "s.avsc", "f.json"], &files);
+        assert!(result.unwrap());
+        assert_eq!(records(&written), vec![record(1, "x"), record(2, "y")]);
+        assert!(run_on(&["fromjson", "f.json"], &files).0.is_err());
+        assert!(run_on(&["fromjson", "--schema", "s.avsc", "--codec", "x", "f.json"], &files)
+            .0
+            .is_err());
+
+        let files = [("f.avro", written)];
+        let (result, recodec, _) = run_on(&["recodec", "--codec", "null", "f.avro"], &files);
+        assert!(result.unwrap());
+        assert_eq!(records(&recodec), vec![record(1, "x"), record(2, "y")]);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // Computed by Java (see the tests of `Schema::rabin_fingerprint`).
+        let expected = concat!(
+            r#"{"name":"test","type":"record","fields":[{"name":"a","type":"long"},"#,
+            r#"{"name":"b","type":"string"}]}"#,
+            "\nCRC-64-AVRO\te8c6c20c615f2c47\n",
+            "MD5\t7bce8188f28e66480a45ffbdc3615b7d\n",
+            "SHA-256\tc4d97949770866dec733ae7afa3046757e901d0cfea32eb92a8faeadcc4de153\n",
+        );
+        let files = [
+            ("s.avsc", SCHEMA.as_bytes().to_vec()),
+            ("f.avro", container(SCHEMA, vec![])),
+            ("bad.avsc", vec![0xff]),
+        ];
+        assert_eq!(output(&["fingerprint", "s.avsc"], &files), expected);
+        assert_eq!(output(&["fingerprint", "f.avro"], &files), expected);
+        assert!(run_on(&["fingerprint", "bad.avsc"], &files).0.is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let new_schema = r#"{"type": "record", "name": "test", "fields": [
+            {"name": "a", "type": "long"},
+            {"name": "b", "type": "string", "default": ""}
+        ]}"#;
+        let files = [
+            ("old.avro", container(SCHEMA, vec![record(1, "x"), record(2, "y")])),
+            ("new.avro", container(new_schema, vec![record(2, "z"), record(3, "w")])),
+            ("same.avro", container(SCHEMA, vec![record(1, "x"), record(2, "y")])),
+        ];
+        let (result, report, _) = run_on(&["diff", "--key", "a", "old.avro", "new.avro"], &files);
+        assert!(!result.unwrap());
+        let report = serde_json::from_slice::<JsonValue>(&report).unwrap();
+        assert_eq!(report["schema"].as_array().unwrap().len(), 1);
+        assert_eq!(report["data"]["added"], 1);
+        assert_eq!(report["data"]["removed"], 1);
+        assert_eq!(report["data"]["changed"], 1);
+        assert_eq!(
+            report["data"]["records"][0],
+            serde_json::json!({
+                "key": 2,
+                "changes": [{"path": "$.b", "old": "y", "new": "z"}],
+            })
+        );
+
+        let report = output(&["diff", "--data", "old.avro", "same.avro"], &files);
+        assert_eq!(
+            serde_json::from_str::<JsonValue>(&report).unwrap(),
+            serde_json::json!({
+                "schema": [],
+                "data": {"added": 0, "removed": 0, "changed": 0, "records": []},
+            })
+        );
+        assert!(run_on(&["diff", "old.avro"], &files).0.is_err());
+        assert!(run_on(&["diff", "--key", "c", "old.avro", "new.avro"], &files).0.is_err());
+    }
+
+    #[test]
+    fn test_repair() {
+        let mut corrupted = container(SCHEMA, vec![record(1, "x"), record(2, "y")]);
+        // The sync marker of the last block.
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        let files = [
+            ("f.avro", container(SCHEMA, vec![record(1, "x")])),
+            ("corrupted.avro", corrupted),
+        ];
+
+        let (result, repaired, log) = run_on(&["repair", "f.avro"], &files);
+        assert!(result.unwrap());
+        assert_eq!(records(&repaired), vec![record(1, "x")]);
+        assert_eq!(log, "salvaged 1 records, lost 0 records and 0 bytes\n");
+
+        let (result, repaired, log) = run_on(&["repair", "corrupted.avro"], &files);
+        assert!(!result.unwrap());
+        assert_eq!(records(&repaired), vec![record(1, "x")]);
+        assert_eq!(
+            log,
+            "lost 1 records (21 bytes) at offset 171: Decoding error: block marker does not \
+             match header marker\nsalvaged 1 records, lost 1 records and 21 bytes\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let new_schema = r#"{"type": "record", "name": "test", "fields": [
+            {"name": "a", "type": "long"},
+            {"name": "c", "type": "int", "default": 0}
+        ]}"#;
+        let files = [
+            ("f.avro", container(SCHEMA, vec![record(1, "x")])),
+            ("s.avsc", new_schema.as_bytes().to_vec()),
+        ];
+        let (result, migrated, _) = run_on(&["migrate", "--schema", "s.avsc", "f.avro"], &files);
+        assert!(result.unwrap());
+        assert_eq!(
+            records(&migrated),
+            vec![Value::Record(vec![
+                ("a".to_owned(), Value::Long(1)),
+                ("c".to_owned(), Value::Int(0)),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_usage() {
+        let files = [("f.avro", container(SCHEMA, vec![]))];
+        for args in &[&[][..], &["cat"], &["cat", "f.avro", "f.avro"], &["unknown", "f.avro"]] {
+            assert_eq!(run_on(args, &files).0.unwrap_err().to_string(), USAGE);
+        }
+        assert!(run_on(&["cat", "g.avro"], &files).0.is_err());
+        assert!(run_on(&["cat", "--limit"], &files).0.is_err());
+    }
+}
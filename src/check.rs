@@ -0,0 +1,235 @@
+//! Logic checking that values survive a round trip through the Avro binary encoding, to catch
+//! encode/decode asymmetries early.
+#[cfg(feature = "serde_layer")]
+use serde::Serialize;
+
+use datum::{from_avro_datum, to_avro_datum};
+use error::Error;
+use schema::Schema;
+#[cfg(feature = "serde_layer")]
+use ser::to_value;
+use types::{ToAvro, Value};
+
+/// A difference between a value and its decoded form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    /// Where the values differ, e.g. `$.field[0]`.
+    pub path: String,
+    /// The value as given.
+    pub expected: Value,
+    /// The value as decoded.
+    pub found: Value,
+}
+
+/// The outcome of [`check_round_trip`](fn.check_round_trip.html).
+#[derive(Debug)]
+pub struct RoundTripReport {
+    /// The value as given.
+    pub value: Value,
+    /// The Avro binary encoding of the value.
+    pub encoded: Vec<u8>,
+    /// The value decoded back from `encoded`, or the error decoding it.
+    pub decoded: Result<Value, Error>,
+    /// The number of encoded bytes left over once decoded.
+    pub trailing_bytes: usize,
+    /// The differences between `value` and `decoded`, in the order they were found.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl RoundTripReport {
+    /// Whether the value was decoded back to itself, using exactly all of its encoded bytes.
+    pub fn is_ok(&self) -> bool {
+        self.decoded.is_ok() && self.trailing_bytes == 0 && self.mismatches.is_empty()
+    }
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait), decode it back and compare both.
+///
+/// An error is only returned when the value does not match `schema`: anything going wrong
+/// afterwards is described by the returned report.
+///
+/// Floating-point values are compared by bits, so that `NaN`s compare equal to themselves, and
+/// strings given for enums compare equal to the decoded symbols.
+pub fn check_round_trip<T: ToAvro>(schema: &Schema, value: T) -> Result<RoundTripReport, Error> {
+    let value = value.avro();
+    let encoded = to_avro_datum(schema, value.clone())?;
+
+    let mut reader = &encoded[..];
+    let decoded = from_avro_datum(schema, &mut reader, None);
+    let trailing_bytes = reader.len();
+
+    let mut mismatches = Vec::new();
+    if let Ok(ref decoded) = decoded {
+        compare("$", &value, decoded, &mut mismatches);
+    }
+    Ok(RoundTripReport {
+        value,
+        encoded,
+        decoded,
+        trailing_bytes,
+        mismatches,
+    })
+}
+
+/// Same as [`check_round_trip`](fn.check_round_trip.html), for any type implementing
+/// `Serialize`.
+#[cfg(feature = "serde_layer")]
+pub fn check_round_trip_ser<S: Serialize>(
+    schema: &Schema,
+    value: S,
+) -> Result<RoundTripReport, Error> {
+    check_round_trip(schema, to_value(value)?)
+}
+
+fn compare(path: &str, expected: &Value, found: &Value, mismatches: &mut Vec<Mismatch>) {
+    let equal = match (expected, found) {
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+        (Value::String(a), Value::Enum(_, b)) => *a == **b,
+        (Value::Long(a), Value::Int(b)) => *a == i64::from(*b),
+        (Value::Union(a), Value::Union(b)) | (Value::UnionBranch(_, a), Value::Union(b)) => {
+            return compare(path, a, b, mismatches)
+        },
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+                compare(&format!("{}[{}]", path, i), a, b, mismatches);
+            }
+            return
+        },
+        (Value::Map(a), Value::Map(b))
+            if a.len() == b.len() && a.keys().all(|key| b.contains_key(key)) =>
+        {
+            let mut keys = a.keys().collect::<Vec<_>>();
+            keys.sort();
+            for key in keys {
+                compare(&format!("{}[{:?}]", path, &**key), &a[key], &b[key], mismatches);
+            }
+            return
+        },
+        (Value::Record(a), Value::Record(b))
+            if a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.0 == b.0) =>
+        {
+            for ((name, a), (_, b)) in a.iter().zip(b.iter()) {
+                compare(&format!("{}.{}", path, name), a, b, mismatches);
+            }
+            return
+        },
+        _ => expected == found,
+    };
+    if !equal {
+        mismatches.push(Mismatch {
+            path: path.to_owned(),
+            expected: expected.clone(),
+            found: found.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+
+    #[test]
+    fn test_check_round_trip() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "double"},
+                    {"name": "b", "type": {"type": "enum", "name": "e", "symbols": ["x", "y"]}},
+                    {"name": "c", "type": {"type": "array", "items": ["null", "string"]}}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Double(f64::NAN)),
+            ("b".to_owned(), Value::String("y".to_owned())),
+            (
+                "c".to_owned(),
+                Value::Array(vec![
+                    Value::Union(Box::new(Value::Null)),
+                    Value::Union(Box::new(Value::String("foo".to_owned()))),
+                ]),
+            ),
+        ]);
+
+        let report = check_round_trip(&schema, value.clone()).unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.encoded, to_avro_datum(&schema, value).unwrap());
+
+        // Any value not matching the schema is rejected upfront.
+        assert!(check_round_trip(&schema, Value::Null).is_err());
+    }
+
+    #[cfg(feature = "serde_layer")]
+    #[test]
+    fn test_check_round_trip_ser() {
+        #[derive(Serialize)]
+        struct Test {
+            a: i64,
+            b: Option<String>,
+        }
+
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": ["null", "string"]}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let value = Test {
+            a: 27,
+            b: Some("foo".to_owned()),
+        };
+        assert!(check_round_trip_ser(&schema, &value).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = Value::Record(vec![
+            ("a".to_owned(), Value::Array(vec![Value::Int(1), Value::Int(2)])),
+            ("b".to_owned(), Value::Long(3)),
+        ]);
+        let found = Value::Record(vec![
+            ("a".to_owned(), Value::Array(vec![Value::Int(1), Value::Int(4)])),
+            ("b".to_owned(), Value::Long(5)),
+        ]);
+        let mut mismatches = Vec::new();
+        compare("$", &expected, &found, &mut mismatches);
+        assert_eq!(
+            mismatches,
+            vec![
+                Mismatch {
+                    path: "$.a[1]".to_owned(),
+                    expected: Value::Int(2),
+                    found: Value::Int(4),
+                },
+                Mismatch {
+                    path: "$.b".to_owned(),
+                    expected: Value::Long(3),
+                    found: Value::Long(5),
+                },
+            ]
+        );
+
+        // Arrays of different lengths are reported as a whole.
+        let mut mismatches = Vec::new();
+        compare(
+            "$",
+            &Value::Array(vec![Value::Null]),
+            &Value::Array(vec![]),
+            &mut mismatches,
+        );
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].path, "$");
+    }
+}
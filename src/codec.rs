@@ -1,8 +1,16 @@
 //! Logic for all supported compression codecs in Avro.
-use std::io::{Read, Write};
+use std::fmt;
+#[cfg(any(feature = "deflate_codec", feature = "snappy"))]
+use std::io::Write;
+use std::io::{self, Read};
 use std::str::FromStr;
 
-use failure::Error;
+use error::Error;
+#[cfg(any(feature = "deflate_miniz", feature = "deflate_zlib"))]
+use flate2::read::DeflateDecoder as Decoder;
+#[cfg(any(feature = "deflate_miniz", feature = "deflate_zlib"))]
+use flate2::{write::DeflateEncoder, Compression};
+#[cfg(all(feature = "deflate", not(any(feature = "deflate_miniz", feature = "deflate_zlib"))))]
 use libflate::deflate::{Decoder, Encoder};
 #[cfg(feature = "snappy")]
 use snap::{Reader, Writer};
@@ -15,6 +23,7 @@ use util::DecodeError;
 pub enum Codec {
     /// The `Null` codec simply passes through data uncompressed.
     Null,
+    #[cfg(feature = "deflate_codec")]
     /// The `Deflate` codec writes the data block using the deflate algorithm
     /// as specified in RFC 1951, and typically implemented using the zlib library.
     /// Note that this format (unlike the "zlib format" in RFC 1950) does not have a checksum.
@@ -26,80 +35,205 @@ pub enum Codec {
     Snappy,
 }
 
+// The codecs enabled in this build.
+const CODECS: &[Codec] = &[
+    Codec::Null,
+    #[cfg(feature = "deflate_codec")]
+    Codec::Deflate,
+    #[cfg(feature = "snappy")]
+    Codec::Snappy,
+];
+
 impl ToAvro for Codec {
     fn avro(self) -> Value {
-        Value::Bytes(
-            match self {
-                Codec::Null => "null",
-                Codec::Deflate => "deflate",
-                #[cfg(feature = "snappy")]
-                Codec::Snappy => "snappy",
-            }.to_owned()
-                .into_bytes(),
-        )
+        Value::Bytes(self.name().to_owned().into_bytes())
     }
 }
 
+/// Parse the name of a codec as found in the `avro.codec` metadata, e.g. `"deflate"`.
 impl FromStr for Codec {
     type Err = DecodeError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "null" => Ok(Codec::Null),
-            "deflate" => Ok(Codec::Deflate),
-            #[cfg(feature = "snappy")]
-            "snappy" => Ok(Codec::Snappy),
-            _ => Err(DecodeError::new("unrecognized codec")),
-        }
+        CODECS
+            .iter()
+            .find(|codec| codec.name() == s)
+            .cloned()
+            .ok_or_else(|| {
+                DecodeError::new(format!(
+                    "unrecognized codec {:?}, expected one of {}",
+                    s,
+                    CODECS
+                        .iter()
+                        .map(|codec| codec.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })
+    }
+}
+
+/// Display the name of the codec as found in the `avro.codec` metadata, e.g. `deflate`.
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
     }
 }
 
 impl Codec {
+    fn name(&self) -> &'static str {
+        match *self {
+            Codec::Null => "null",
+            #[cfg(feature = "deflate_codec")]
+            Codec::Deflate => "deflate",
+            #[cfg(feature = "snappy")]
+            Codec::Snappy => "snappy",
+        }
+    }
+
     /// Compress a stream of bytes in-place.
     pub fn compress(&self, stream: &mut Vec<u8>) -> Result<(), Error> {
+        if let Codec::Null = *self {
+            return Ok(())
+        }
+
+        let mut compressed = Vec::new();
+        self.compress_into(stream, &mut compressed)?;
+        *stream = compressed;
+
+        Ok(())
+    }
+
+    /// Compress a stream of bytes into `output`, which is cleared first but keeps its capacity.
+    pub fn compress_into(&self, stream: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        output.clear();
         match *self {
-            Codec::Null => (),
-            Codec::Deflate => {
-                let mut encoder = Encoder::new(Vec::new());
-                encoder.write_all(stream)?;
-                *stream = encoder.finish().into_result()?;
-            },
+            Codec::Null => output.extend_from_slice(stream),
+            #[cfg(feature = "deflate_codec")]
+            Codec::Deflate => deflate(stream, output)?,
             #[cfg(feature = "snappy")]
             Codec::Snappy => {
-                let mut writer = Writer::new(Vec::new());
+                let mut writer = Writer::new(&mut *output);
                 writer.write_all(stream)?;
-                *stream = writer.into_inner()?; // .into_inner() will also call .flush()
+                writer.flush()?;
             },
         };
 
+        trace_event!(
+            DEBUG,
+            codec = ?self,
+            size = stream.len(),
+            compressed_size = output.len(),
+            "compressed block"
+        );
         Ok(())
     }
 
     /// Decompress a stream of bytes in-place.
     pub fn decompress(&self, stream: &mut Vec<u8>) -> Result<(), Error> {
-        match *self {
-            Codec::Null => (),
-            Codec::Deflate => {
-                let mut decoded = Vec::new();
+        if let Codec::Null = *self {
+            return Ok(())
+        }
+
+        let mut decompressed = Vec::new();
+        self.decompress_into(stream, &mut decompressed)?;
+        *stream = decompressed;
+
+        Ok(())
+    }
+
+    /// Decompress a stream of bytes into `output`, which is cleared first but keeps its capacity.
+    pub fn decompress_into(&self, stream: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        output.clear();
+        self.decompressor(stream).read_to_end(output)?;
+
+        trace_event!(
+            DEBUG,
+            codec = ?self,
+            size = output.len(),
+            compressed_size = stream.len(),
+            "decompressed block"
+        );
+        Ok(())
+    }
+
+    /// Decompress the bytes of a block read from `reader` on the fly.
+    ///
+    /// With `zlib`, `deflate` blocks are expected to be zlib streams (RFC 1950), as some older
+    /// implementations wrote them, instead of raw deflate streams: their header is checked and
+    /// skipped, and their trailing checksum ignored.
+    pub(crate) fn block_decompressor<R: Read>(
+        &self,
+        mut reader: R,
+        zlib: bool,
+    ) -> Result<Decompressor<R>, Error> {
+        #[cfg(feature = "deflate_codec")]
+        {
+            if zlib && *self == Codec::Deflate {
+                let mut header = [0u8; 2];
+                reader.read_exact(&mut header)?;
+                // Deflate compression method, no preset dictionary, and a valid header checksum.
+                if header[0] & 0x0F != 8
+                    || header[1] & 0x20 != 0
+                    || (u16::from(header[0]) << 8 | u16::from(header[1])) % 31 != 0
                 {
-                    // either the compiler or I is dumb
-                    let mut decoder = Decoder::new(&stream[..]);
-                    decoder.read_to_end(&mut decoded)?;
+                    return Err(DecodeError::new("invalid zlib header in deflate block").into())
                 }
-                *stream = decoded;
-            },
+            }
+        }
+        #[cfg(not(feature = "deflate_codec"))]
+        let _ = (zlib, &mut reader);
+        Ok(self.decompressor(reader))
+    }
+
+    /// Decompress the bytes read from `reader` on the fly, instead of in a single step.
+    pub(crate) fn decompressor<R: Read>(&self, reader: R) -> Decompressor<R> {
+        match *self {
+            Codec::Null => Decompressor::Null(reader),
+            #[cfg(feature = "deflate_codec")]
+            Codec::Deflate => Decompressor::Deflate(Decoder::new(reader)),
             #[cfg(feature = "snappy")]
-            Codec::Snappy => {
-                let mut read = Vec::new();
-                {
-                    let mut reader = Reader::new(&stream[..]);
-                    reader.read_to_end(&mut read)?;
-                }
-                *stream = read;
-            },
-        };
+            Codec::Snappy => Decompressor::Snappy(Reader::new(reader)),
+        }
+    }
+}
 
-        Ok(())
+// Compress `stream` into `output` as a raw deflate stream.
+#[cfg(all(feature = "deflate", not(any(feature = "deflate_miniz", feature = "deflate_zlib"))))]
+fn deflate(stream: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    let mut encoder = Encoder::new(output);
+    encoder.write_all(stream)?;
+    encoder.finish().into_result()?;
+    Ok(())
+}
+
+// Compress `stream` into `output` as a raw deflate stream.
+#[cfg(any(feature = "deflate_miniz", feature = "deflate_zlib"))]
+fn deflate(stream: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    let mut encoder = DeflateEncoder::new(output, Compression::default());
+    encoder.write_all(stream)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Reader of the decompressed bytes of a block, see `Codec::decompressor`.
+pub(crate) enum Decompressor<R: Read> {
+    Null(R),
+    #[cfg(feature = "deflate_codec")]
+    Deflate(Decoder<R>),
+    #[cfg(feature = "snappy")]
+    Snappy(Reader<R>),
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Decompressor::Null(ref mut reader) => reader.read(buf),
+            #[cfg(feature = "deflate_codec")]
+            Decompressor::Deflate(ref mut decoder) => decoder.read(buf),
+            #[cfg(feature = "snappy")]
+            Decompressor::Snappy(ref mut reader) => reader.read(buf),
+        }
     }
 }
 
@@ -119,6 +253,7 @@ mod tests {
         assert_eq!(INPUT, stream.as_slice());
     }
 
+    #[cfg(feature = "deflate_codec")]
     #[test]
     fn deflate_compress_and_decompress() {
         let codec = Codec::Deflate;
@@ -130,6 +265,74 @@ mod tests {
         assert_eq!(INPUT, stream.as_slice());
     }
 
+    // `theanswertolifetheuniverseandeverythingis42`, three times, compressed by Python's zlib.
+    #[cfg(feature = "deflate_codec")]
+    static RAW_DEFLATE: &'static [u8] = &[
+        43, 201, 72, 77, 204, 43, 46, 79, 45, 42, 201, 207, 201, 76, 75, 45, 201, 72, 45, 205, 203,
+        44, 75, 45, 42, 6, 10, 167, 164, 2, 25, 149, 37, 25, 153, 121, 233, 153, 197, 38, 70, 37,
+        52, 81, 10, 0,
+    ];
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn deflate_interop() {
+        let expected = b"theanswertolifetheuniverseandeverythingis42".repeat(3);
+
+        let mut stream = RAW_DEFLATE.to_vec();
+        Codec::Deflate.decompress(&mut stream).unwrap();
+        assert_eq!(stream, expected);
+
+        // Blocks are raw deflate streams, without a zlib header.
+        let mut compressed = expected.clone();
+        Codec::Deflate.compress(&mut compressed).unwrap();
+        assert!(Codec::Deflate.block_decompressor(&compressed[..], true).is_err());
+
+        // The same stream, with a zlib header and checksum.
+        let mut zlib = vec![120, 218];
+        zlib.extend_from_slice(RAW_DEFLATE);
+        zlib.extend_from_slice(&[161, 135, 53, 65]);
+        let mut decompressed = Vec::new();
+        Codec::Deflate
+            .block_decompressor(&zlib[..], true)
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, expected);
+
+        assert!(Codec::Deflate.block_decompressor(RAW_DEFLATE, true).is_err());
+        assert!(Codec::Null.block_decompressor(RAW_DEFLATE, true).is_ok());
+    }
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn deflate_compress_into() {
+        let codec = Codec::Deflate;
+        let mut expected = INPUT.to_vec();
+        codec.compress(&mut expected).unwrap();
+
+        let mut output = vec![42; 4];
+        codec.compress_into(INPUT, &mut output).unwrap();
+        assert_eq!(expected, output);
+
+        // the output is cleared but keeps its allocation
+        let ptr = output.as_ptr();
+        codec.compress_into(INPUT, &mut output).unwrap();
+        assert_eq!(expected, output);
+        assert_eq!(ptr, output.as_ptr());
+    }
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn deflate_decompress_into() {
+        let codec = Codec::Deflate;
+        let mut stream = INPUT.to_vec();
+        codec.compress(&mut stream).unwrap();
+
+        let mut output = vec![42; 4];
+        codec.decompress_into(&stream, &mut output).unwrap();
+        assert_eq!(INPUT, output.as_slice());
+    }
+
     #[cfg(feature = "snappy")]
     #[test]
     fn snappy_compress_and_decompress() {
@@ -141,4 +344,35 @@ mod tests {
         codec.decompress(&mut stream).unwrap();
         assert_eq!(INPUT, stream.as_slice());
     }
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn deflate_decompressor() {
+        let codec = Codec::Deflate;
+        let mut stream = INPUT.to_vec();
+        codec.compress(&mut stream).unwrap();
+
+        let mut decompressed = Vec::new();
+        codec
+            .decompressor(&stream[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(INPUT, decompressed.as_slice());
+    }
+
+    #[test]
+    fn codec_from_str_and_display() {
+        for &codec in CODECS {
+            assert_eq!(codec.to_string().parse::<Codec>().unwrap(), codec);
+        }
+        #[cfg(feature = "deflate_codec")]
+        assert_eq!("deflate".parse::<Codec>().unwrap(), Codec::Deflate);
+        assert_eq!(Codec::Null.to_string(), "null");
+
+        let error = Error::from("zstandard".parse::<Codec>().unwrap_err());
+        #[cfg(feature = "deflate_codec")]
+        assert!(error.to_string().contains("one of null, deflate"), "{}", error);
+        #[cfg(not(feature = "deflate_codec"))]
+        assert!(error.to_string().contains("one of null"), "{}", error);
+    }
 }
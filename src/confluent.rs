@@ -0,0 +1,159 @@
+//! Logic handling the Confluent Schema Registry wire format.
+//!
+//! A Confluent-framed message is made of the magic byte `0x00`, followed by the 4-byte big-endian
+//! id of the writer schema in the registry and by the Avro binary encoding of the datum.
+//!
+//! More information about the wire format can be found in the
+//! [Confluent documentation](https://docs.confluent.io/current/schema-registry/docs/serializer-formatter.html#wire-format)
+use std::io::{Read, Write};
+
+use error::Error;
+
+use datum::{from_avro_datum, to_avro_datum};
+use schema::Schema;
+use store::SchemaStore;
+use types::{ToAvro, Value};
+use util::DecodeError;
+
+const CONFLUENT_MAGIC: u8 = 0x00;
+const CONFLUENT_HEADER_SIZE: usize = 5;
+
+/// Build the Confluent header for a writer schema with the given registry id.
+pub fn confluent_header(id: u32) -> [u8; CONFLUENT_HEADER_SIZE] {
+    let mut header = [0u8; CONFLUENT_HEADER_SIZE];
+    header[0] = CONFLUENT_MAGIC;
+    header[1..].copy_from_slice(&id.to_be_bytes());
+    header
+}
+
+/// Read a Confluent header, returning the registry id of the writer schema.
+pub fn read_confluent_header<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut header = [0u8; CONFLUENT_HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+
+    if header[0] != CONFLUENT_MAGIC {
+        return Err(DecodeError::new("wrong magic byte in confluent header").into())
+    }
+
+    let mut id = [0u8; 4];
+    id.copy_from_slice(&header[1..]);
+    Ok(u32::from_be_bytes(id))
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) using the Confluent wire format,
+/// also performing schema validation.
+///
+/// `id` is the id under which `schema` is known to the schema registry.
+pub fn to_confluent<T: ToAvro>(schema: &Schema, id: u32, value: T) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    write_confluent(schema, id, value, &mut buffer)?;
+    Ok(buffer)
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) using the Confluent wire format
+/// and write it to anything implementing `io::Write`, also performing schema validation.
+///
+/// `id` is the id under which `schema` is known to the schema registry.
+///
+/// Return the number of bytes written.
+pub fn write_confluent<T: ToAvro, W: Write>(
+    schema: &Schema,
+    id: u32,
+    value: T,
+    writer: &mut W,
+) -> Result<usize, Error> {
+    // Encode first, so that nothing gets written if the value does not match the schema.
+    let datum = to_avro_datum(schema, value)?;
+    let header = confluent_header(id);
+
+    writer.write_all(&header)?;
+    writer.write_all(&datum)?;
+
+    Ok(header.len() + datum.len())
+}
+
+/// Decode a `Value` encoded using the Confluent wire format from anything implementing
+/// `io::Read`.
+///
+/// The writer `Schema` is looked up in `store` by the schema id found in the header.
+///
+/// In case a reader `Schema` is provided, schema resolution will also be performed.
+pub fn from_confluent<R, S>(
+    reader: &mut R,
+    store: &S,
+    reader_schema: Option<&Schema>,
+) -> Result<Value, Error>
+where
+    R: Read,
+    S: SchemaStore + ?Sized,
+{
+    let id = read_confluent_header(reader)?;
+    let schema = store
+        .get_by_id(id)
+        .ok_or_else(|| DecodeError::new(format!("unknown schema id {}", id)))?;
+
+    from_avro_datum(&schema, reader, reader_schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use store::MemorySchemaStore;
+    use types::Record;
+
+    static SCHEMA: &'static str = r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long", "default": 42},
+                    {"name": "b", "type": "string"}
+                ]
+            }
+        "#;
+
+    #[test]
+    fn test_confluent_round_trip() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        let expected = record.clone().avro();
+
+        let encoded = to_confluent(&schema, 258, record).unwrap();
+        assert_eq!(&encoded[..5], &[0, 0, 0, 1, 2]);
+        assert_eq!(&encoded[5..], &[54, 6, 102, 111, 111]);
+
+        let mut store = MemorySchemaStore::new();
+        store.insert_with_id(258, schema);
+        let decoded = from_confluent(&mut &encoded[..], &store, None).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_confluent_errors() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        let encoded = to_confluent(&schema, 1, record).unwrap();
+
+        // unknown id
+        let mut store = MemorySchemaStore::new();
+        assert!(from_confluent(&mut &encoded[..], &store, None).is_err());
+
+        // wrong magic
+        store.insert_with_id(1, schema.clone());
+        let mut invalid = encoded.clone();
+        invalid[0] = 1;
+        assert!(from_confluent(&mut &invalid[..], &store, None).is_err());
+
+        // truncated header
+        assert!(from_confluent(&mut &encoded[..3], &store, None).is_err());
+
+        // invalid value
+        let mut buffer = Vec::new();
+        assert!(write_confluent(&schema, 1, Value::Long(27), &mut buffer).is_err());
+        assert!(buffer.is_empty());
+    }
+}
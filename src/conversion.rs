@@ -0,0 +1,409 @@
+//! Logic for converting domain types (e.g. money amounts or IP addresses) to and from their Avro
+//! representation, through `Value`s as well as through serde.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, Serializer};
+
+use error::Error;
+use types::{ToAvro, Value};
+
+// Name of the newtype structs through which the serializer and deserializer of this crate hand
+// the Avro representation of converted values (and of explicit union branches) over as it is.
+pub(crate) const CONVERTED: &str = "$avro_rs::Converted";
+
+thread_local! {
+    // Whether the serializer of this crate is asking for the Avro representation of a value.
+    static CAPTURING: Cell<bool> = const { Cell::new(false) };
+    // The Avro representation handed over.
+    static CAPTURED: RefCell<Option<Value>> = const { RefCell::new(None) };
+}
+
+/// Conversion between a domain type and its Avro representation (à la Java's `Conversion`),
+/// e.g. a `Money` type represented as a `long` count of cents.
+///
+/// Values are converted with [`Converted`](struct.Converted.html) where a `ToAvro`, a
+/// `Serialize` or a `Deserialize` type is expected, and with
+/// [`serialize_converted`](fn.serialize_converted.html) and
+/// [`deserialize_converted`](fn.deserialize_converted.html) as the `serialize_with` and
+/// `deserialize_with` of serde fields.
+pub trait Conversion: Sized {
+    /// Convert `self` into its Avro representation.
+    fn to_avro(&self) -> Value;
+
+    /// Convert the Avro representation of a value back into `Self`.
+    fn from_avro(value: Value) -> Result<Self, Error>;
+}
+
+/// A value converted with its [`Conversion`](trait.Conversion.html) when turned into a `Value`,
+/// serialized or deserialized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Converted<T>(pub T);
+
+impl<T: Conversion> ToAvro for Converted<T> {
+    fn avro(self) -> Value {
+        self.0.to_avro()
+    }
+}
+
+impl<T: Conversion> Serialize for Converted<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_converted(&self.0, serializer)
+    }
+}
+
+impl<'de, T: Conversion> Deserialize<'de> for Converted<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_converted(deserializer).map(Converted)
+    }
+}
+
+/// A value serialized as the branch at the given index (counting from 0) of a union, i.e. as a
+/// [`Value::UnionBranch`](enum.Value.html#variant.UnionBranch), e.g. `Branch(1, "HEARTS")` for
+/// a `["string", "Suit"]` union of a string and an enum.
+///
+/// Other serializers get the plain value. Decoded values do not carry the index of their branch:
+/// they are deserialized as plain `T`s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Branch<T>(pub usize, pub T);
+
+impl<T: Serialize> Serialize for Branch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(CONVERTED, &BranchPayload(self.0, &self.1))
+    }
+}
+
+/// Serialize a value as its Avro representation, e.g. with
+/// `#[serde(serialize_with = "avro_rs::serialize_converted")]`.
+///
+/// The serializer of this crate gets the exact `Value`; other serializers get it as plain serde
+/// data (e.g. `fixed` values as bytes and records as maps).
+pub fn serialize_converted<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Conversion,
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(CONVERTED, &Payload(Cell::new(Some(value.to_avro()))))
+}
+
+/// Deserialize a value from its Avro representation, e.g. with
+/// `#[serde(deserialize_with = "avro_rs::deserialize_converted")]`.
+///
+/// The deserializer of this crate hands the exact `Value` over; the data of other deserializers
+/// is turned into the closest `Value` (e.g. integers into `Value::Long`).
+pub fn deserialize_converted<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Conversion,
+    D: Deserializer<'de>,
+{
+    let value = deserializer.deserialize_newtype_struct(CONVERTED, ValueVisitor)?;
+    T::from_avro(value).map_err(de::Error::custom)
+}
+
+/// Run `serialize` on a `Converted` newtype struct, returning the Avro representation it hands
+/// over.
+pub(crate) fn capture<E, F>(serialize: F) -> Result<Value, E>
+where
+    F: FnOnce() -> Result<Value, E>,
+{
+    CAPTURING.with(|capturing| capturing.set(true));
+    let result = serialize();
+    CAPTURING.with(|capturing| capturing.set(false));
+    let captured = CAPTURED.with(|captured| captured.borrow_mut().take());
+    result.map(|value| captured.unwrap_or(value))
+}
+
+/// Hand `value` over to the `deserialize_converted` about to visit it.
+pub(crate) fn hand_over(value: Value) {
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(value));
+}
+
+struct Payload(Cell<Option<Value>>);
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = self.0.take().unwrap_or(Value::Null);
+        if CAPTURING.with(|capturing| capturing.replace(false)) {
+            CAPTURED.with(|captured| *captured.borrow_mut() = Some(value));
+            return serializer.serialize_unit()
+        }
+        Plain(&value).serialize(serializer)
+    }
+}
+
+struct BranchPayload<'a, T: 'a>(usize, &'a T);
+
+impl<'a, T: Serialize> Serialize for BranchPayload<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if CAPTURING.with(|capturing| capturing.replace(false)) {
+            let value = ::ser::to_value(self.1).map_err(ser::Error::custom)?;
+            let branch = Value::UnionBranch(self.0, Box::new(value));
+            CAPTURED.with(|captured| *captured.borrow_mut() = Some(branch));
+            return serializer.serialize_unit()
+        }
+        self.1.serialize(serializer)
+    }
+}
+
+// A `Value` as plain serde data.
+struct Plain<'a>(&'a Value);
+
+impl<'a> Serialize for Plain<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self.0 {
+            Value::Null => serializer.serialize_unit(),
+            Value::Boolean(b) => serializer.serialize_bool(b),
+            Value::Int(i) => serializer.serialize_i32(i),
+            Value::Long(i) => serializer.serialize_i64(i),
+            Value::Float(x) => serializer.serialize_f32(x),
+            Value::Double(x) => serializer.serialize_f64(x),
+            Value::Bytes(ref bytes) | Value::Fixed(_, ref bytes) => {
+                serializer.serialize_bytes(bytes)
+            },
+            Value::String(ref s) => serializer.serialize_str(s),
+            Value::Enum(_, ref symbol) => serializer.serialize_str(symbol),
+            Value::Union(ref inner) | Value::UnionBranch(_, ref inner) => {
+                Plain(inner).serialize(serializer)
+            },
+            Value::Array(ref items) => serializer.collect_seq(items.iter().map(Plain)),
+            Value::Map(ref items) => {
+                serializer.collect_map(items.iter().map(|(key, item)| (&**key, Plain(item))))
+            },
+            Value::Record(ref fields) => serializer.collect_map(
+                fields.iter().map(|(name, field)| (name, Plain(field))),
+            ),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an Avro value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(b))
+    }
+
+    fn visit_i64<E>(self, i: i64) -> Result<Value, E> {
+        Ok(Value::Long(i))
+    }
+
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<Value, E> {
+        if u > i64::MAX as u64 {
+            return Err(E::custom("u64 is too large"))
+        }
+        Ok(Value::Long(u as i64))
+    }
+
+    fn visit_f64<E>(self, x: f64) -> Result<Value, E> {
+        Ok(Value::Double(x))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_owned()))
+    }
+
+    fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(bytes.to_owned()))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    // The deserializer of this crate visits a unit once it handed the value over.
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        let handed_over = CAPTURED.with(|captured| captured.borrow_mut().take());
+        Ok(handed_over.unwrap_or(Value::Null))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(AnyValue(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut items = HashMap::new();
+        while let Some((key, AnyValue(item))) = map.next_entry::<String, _>()? {
+            items.insert(key.into(), item);
+        }
+        Ok(Value::Map(items))
+    }
+}
+
+struct AnyValue(Value);
+
+impl<'de> Deserialize<'de> for AnyValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor).map(AnyValue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datum::{from_avro_datum, to_avro_datum};
+    use de::from_value;
+    use schema::Schema;
+    use ser::to_value;
+    use serde_json;
+    use types::Record;
+    use util::DecodeError;
+
+    // An amount of money, as a count of cents.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Money(i64);
+
+    impl Conversion for Money {
+        fn to_avro(&self) -> Value {
+            Value::Long(self.0)
+        }
+
+        fn from_avro(value: Value) -> Result<Self, Error> {
+            match value {
+                Value::Long(cents) => Ok(Money(cents)),
+                other => Err(DecodeError::new(format!("Long expected, got {:?}", other)).into()),
+            }
+        }
+    }
+
+    // An IPv4 address, as a fixed of 4 bytes.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Ip([u8; 4]);
+
+    impl Conversion for Ip {
+        fn to_avro(&self) -> Value {
+            Value::Fixed(4, self.0.to_vec())
+        }
+
+        fn from_avro(value: Value) -> Result<Self, Error> {
+            match value {
+                Value::Fixed(4, bytes) => Ok(Ip([bytes[0], bytes[1], bytes[2], bytes[3]])),
+                other => Err(DecodeError::new(format!("Fixed expected, got {:?}", other)).into()),
+            }
+        }
+    }
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "order",
+            "fields": [
+                {"name": "price", "type": "long"},
+                {"name": "ip", "type": ["null", {"type": "fixed", "name": "ip", "size": 4}]}
+            ]
+        }
+    "#;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Order {
+        #[serde(
+            serialize_with = "serialize_converted",
+            deserialize_with = "deserialize_converted"
+        )]
+        price: Money,
+        ip: Option<Converted<Ip>>,
+    }
+
+    #[test]
+    fn test_conversion_values() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("price", Converted(Money(1250)));
+        record.put("ip", Some(Converted(Ip([10, 0, 0, 1]))));
+
+        let encoded = to_avro_datum(&schema, record).unwrap();
+        let fields = match from_avro_datum(&schema, &mut &encoded[..], None).unwrap() {
+            Value::Record(fields) => fields,
+            other => panic!("unexpected value {:?}", other),
+        };
+        assert_eq!(Money::from_avro(fields[0].1.clone()).unwrap(), Money(1250));
+        match fields[1].1 {
+            Value::Union(ref ip) => {
+                assert_eq!(Ip::from_avro((**ip).clone()).unwrap(), Ip([10, 0, 0, 1]))
+            },
+            ref other => panic!("unexpected value {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_conversion_serde() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let order = Order {
+            price: Money(1250),
+            ip: Some(Converted(Ip([10, 0, 0, 1]))),
+        };
+
+        // The `fixed` representation survives serialization as it is.
+        let value = to_value(&order).unwrap();
+        assert!(value.validate(&schema));
+        let encoded = to_avro_datum(&schema, value).unwrap();
+        let decoded = from_avro_datum(&schema, &mut &encoded[..], None).unwrap();
+        assert_eq!(from_value::<Order>(&decoded).unwrap(), order);
+
+        // Other formats get plain serde data.
+        let json = serde_json::to_string(&order).unwrap();
+        assert_eq!(json, r#"{"price":1250,"ip":[10,0,0,1]}"#);
+        let err = serde_json::from_str::<Order>(&json).unwrap_err();
+        assert!(err.to_string().contains("Fixed expected"));
+        let order = serde_json::from_str::<Order>(r#"{"price":1250,"ip":null}"#).unwrap();
+        assert_eq!(order.price, Money(1250));
+    }
+
+    #[test]
+    fn test_branch_serde() {
+        #[derive(Serialize)]
+        struct Counts {
+            a: Branch<i64>,
+            b: Option<Branch<i64>>,
+        }
+
+        let schema = Schema::parse_str(
+            r#"{
+                "type": "record",
+                "name": "counts",
+                "fields": [
+                    {"name": "a", "type": ["int", "long"]},
+                    {"name": "b", "type": ["null", "int", "long"]}
+                ]
+            }"#,
+        ).unwrap();
+        let counts = Counts {
+            a: Branch(0, 7),
+            b: Some(Branch(1, 8)),
+        };
+        let value = to_value(&counts).unwrap();
+        assert_eq!(
+            value,
+            Value::Record(vec![
+                ("a".to_owned(), Value::UnionBranch(0, Box::new(Value::Long(7)))),
+                ("b".to_owned(), Value::UnionBranch(1, Box::new(Value::Long(8)))),
+            ])
+        );
+        // Both are encoded as `int`s rather than as the `long`s of their kind.
+        assert_eq!(to_avro_datum(&schema, value).unwrap(), vec![0, 14, 2, 16]);
+
+        assert_eq!(serde_json::to_string(&counts).unwrap(), r#"{"a":7,"b":8}"#);
+    }
+}
@@ -0,0 +1,436 @@
+//! Logic handling single datums, read and written given a schema without the framing of container
+//! files, and abstracting how they are so that specific readers and writers of user types can
+//! stand in for the generic `Value`-based ones.
+use std::io::Read;
+#[cfg(feature = "serde_layer")]
+use std::marker::PhantomData;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[cfg(feature = "serde_layer")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde_layer")]
+use serde::ser::Serialize;
+
+#[cfg(feature = "serde_layer")]
+use de::from_value;
+use decode::{decode, skip};
+use encode::{encode, encode_ref, encode_sized, encode_sorted, encode_with};
+use error::Error;
+use logical::{to_logical, to_underlying, to_underlying_ref};
+use schema::Schema;
+#[cfg(feature = "serde_layer")]
+use ser::to_value;
+use types::{ToAvro, Value};
+use util::{ByteReader, CountingReader};
+
+/// Writer of single datums of type `T` given the schema they are written with.
+///
+/// Implementations can be plugged into
+/// [`Writer::append_with`](struct.Writer.html#method.append_with) and
+/// [`MessageWriter::write_with`](struct.MessageWriter.html#method.write_with), e.g. to encode a
+/// user type straight from its fields instead of going through a `Value`.
+pub trait DatumWriter<T: ?Sized> {
+    /// Encode `datum` as a datum of `schema` at the end of `buffer`.
+    ///
+    /// On error, what was appended to `buffer` is discarded by the callers of this crate.
+    fn write(&mut self, schema: &Schema, datum: &T, buffer: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+/// Reader of single datums given the schema they were written with.
+///
+/// Implementations can be plugged into [`Reader::read_with`](struct.Reader.html#method.read_with)
+/// and [`MessageReader::read_with`](struct.MessageReader.html#method.read_with), e.g. to decode a
+/// user type straight from the bytes instead of going through a `Value`.
+pub trait DatumReader {
+    /// The type of the datums read.
+    type Datum;
+
+    /// Decode the next datum of `writer_schema` from `reader`, without reading past it.
+    fn read<R: Read>(&mut self, writer_schema: &Schema, reader: &mut R)
+        -> Result<Self::Datum, Error>;
+}
+
+/// A `DatumWriter` of `Value`s, also performing schema validation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GenericDatumWriter;
+
+impl DatumWriter<Value> for GenericDatumWriter {
+    fn write(&mut self, schema: &Schema, datum: &Value, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        write_value_ref(schema, datum, false, false, buffer)
+    }
+}
+
+/// A `DatumReader` of `Value`s, also performing schema resolution if a reader `Schema` is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GenericDatumReader<'a> {
+    reader_schema: Option<&'a Schema>,
+}
+
+impl<'a> GenericDatumReader<'a> {
+    /// Creates a `GenericDatumReader` with no reader `Schema`.
+    pub fn new() -> GenericDatumReader<'a> {
+        GenericDatumReader {
+            reader_schema: None,
+        }
+    }
+
+    /// Creates a `GenericDatumReader` resolving the datums read against `schema`.
+    pub fn with_schema(schema: &'a Schema) -> GenericDatumReader<'a> {
+        GenericDatumReader {
+            reader_schema: Some(schema),
+        }
+    }
+
+    /// Get a reference to the optional reader `Schema`.
+    pub fn reader_schema(&self) -> Option<&'a Schema> {
+        self.reader_schema
+    }
+}
+
+impl<'a> DatumReader for GenericDatumReader<'a> {
+    type Datum = Value;
+
+    fn read<R: Read>(&mut self, writer_schema: &Schema, reader: &mut R) -> Result<Value, Error> {
+        let value = decode(writer_schema, &mut ByteReader(reader))?;
+        resolve_datum(value, writer_schema, self.reader_schema)
+    }
+}
+
+/// A `DatumWriter` of anything implementing the `Serialize` trait, also performing schema
+/// validation.
+#[cfg(feature = "serde_layer")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerdeDatumWriter;
+
+#[cfg(feature = "serde_layer")]
+impl<T: Serialize + ?Sized> DatumWriter<T> for SerdeDatumWriter {
+    fn write(&mut self, schema: &Schema, datum: &T, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        write_value_ref(schema, &to_value(datum)?, false, false, buffer)
+    }
+}
+
+/// A `DatumReader` of anything implementing the `DeserializeOwned` trait, also performing schema
+/// resolution if a reader `Schema` is set.
+#[cfg(feature = "serde_layer")]
+pub struct SerdeDatumReader<'a, T> {
+    inner: GenericDatumReader<'a>,
+    datum: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "serde_layer")]
+impl<'a, T: DeserializeOwned> SerdeDatumReader<'a, T> {
+    /// Creates a `SerdeDatumReader` with no reader `Schema`.
+    pub fn new() -> SerdeDatumReader<'a, T> {
+        SerdeDatumReader {
+            inner: GenericDatumReader::new(),
+            datum: PhantomData,
+        }
+    }
+
+    /// Creates a `SerdeDatumReader` resolving the datums read against `schema`.
+    pub fn with_schema(schema: &'a Schema) -> SerdeDatumReader<'a, T> {
+        SerdeDatumReader {
+            inner: GenericDatumReader::with_schema(schema),
+            datum: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "serde_layer")]
+impl<'a, T: DeserializeOwned> Default for SerdeDatumReader<'a, T> {
+    fn default() -> Self {
+        SerdeDatumReader::new()
+    }
+}
+
+#[cfg(feature = "serde_layer")]
+impl<'a, T: DeserializeOwned> DatumReader for SerdeDatumReader<'a, T> {
+    type Datum = T;
+
+    fn read<R: Read>(&mut self, writer_schema: &Schema, reader: &mut R) -> Result<T, Error> {
+        let value = self.inner.read(writer_schema, reader)?;
+        Ok(from_value(&value)?)
+    }
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) into Avro format, also performing
+/// schema validation.
+///
+/// This is an internal function which gets the bytes buffer where to write as parameter instead of
+/// creating a new one like `to_avro_datum`.
+pub fn write_avro_datum<T: ToAvro>(
+    schema: &Schema,
+    value: T,
+    buffer: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let avro = to_underlying(value.avro(), schema)?;
+    if !avro.validate(schema) {
+        return Err(avro.mismatch(schema))
+    }
+    encode(&avro, schema, buffer);
+    Ok(())
+}
+
+pub(crate) fn write_value_ref(
+    schema: &Schema,
+    value: &Value,
+    sized_blocks: bool,
+    sorted_maps: bool,
+    buffer: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let value = &*to_underlying_ref(value, schema)?;
+    if !value.validate(schema) {
+        return Err(value.mismatch(schema))
+    }
+    if sorted_maps {
+        encode_with(value, schema, sized_blocks, true, buffer);
+    } else if sized_blocks {
+        encode_sized(value, schema, buffer);
+    } else {
+        encode_ref(value, schema, buffer);
+    }
+    Ok(())
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) into Avro format, also
+/// performing schema validation.
+///
+/// **NOTE** This function has a quite small niche of usage and does NOT generate headers and sync
+/// markers; use [`Writer`](struct.Writer.html) to be fully Avro-compatible if you don't know what
+/// you are doing, instead.
+pub fn to_avro_datum<T: ToAvro>(schema: &Schema, value: T) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    write_avro_datum(schema, value, &mut buffer)?;
+    Ok(buffer)
+}
+
+/// Same as [`to_avro_datum`](fn.to_avro_datum.html), writing the entries of maps in the order of
+/// their keys so that equal values always yield the same bytes, e.g. to hash them.
+pub fn to_avro_datum_sorted<T: ToAvro>(schema: &Schema, value: T) -> Result<Vec<u8>, Error> {
+    let avro = to_underlying(value.avro(), schema)?;
+    if !avro.validate(schema) {
+        return Err(avro.mismatch(schema))
+    }
+    let mut buffer = Vec::new();
+    encode_sorted(&avro, schema, &mut buffer);
+    Ok(buffer)
+}
+
+/// Decode a `Value` encoded in Avro format given its `Schema` and anything implementing `io::Read`
+/// to read from.
+///
+/// In case a reader `Schema` is provided, schema resolution will also be performed.
+///
+/// **NOTE** This function has a quite small niche of usage and does NOT take care of reading the
+/// header and consecutive data blocks; use [`Reader`](struct.Reader.html) if you don't know what
+/// you are doing, instead.
+pub fn from_avro_datum<R: Read>(
+    writer_schema: &Schema,
+    reader: &mut R,
+    reader_schema: Option<&Schema>,
+) -> Result<Value, Error> {
+    let mut reader = CountingReader::new(reader);
+    let value = decode(writer_schema, &mut ByteReader(&mut reader))
+        .map_err(|e| e.at(reader.count(), None))?;
+    resolve_datum(value, writer_schema, reader_schema)
+}
+
+/// Advance past a `Value` encoded in Avro format given its `Schema` and anything implementing
+/// `io::Read` to read from, without decoding it.
+///
+/// This is useful to jump over datums one is not interested in (e.g. when projecting or counting
+/// records) for a fraction of the cost of [`from_avro_datum`](fn.from_avro_datum.html).
+pub fn skip_value<R: Read>(writer_schema: &Schema, reader: &mut R) -> Result<(), Error> {
+    let mut reader = CountingReader::new(reader);
+    skip(writer_schema, &mut ByteReader(&mut reader)).map_err(|e| e.at(reader.count(), None))
+}
+
+/// Decode a `Value` encoded in Avro format given its `Schema`, from the start of `bytes`.
+///
+/// Return the value together with the number of bytes it was encoded with, so that datums
+/// concatenated in a buffer can be decoded one after the other.
+pub fn decode_from_slice(writer_schema: &Schema, bytes: &[u8]) -> Result<(Value, usize), Error> {
+    let (value, size) = decode_slice(writer_schema, bytes)?;
+    Ok((to_logical(value, writer_schema)?, size))
+}
+
+// Same as `decode_from_slice`, leaving values in their underlying representation.
+fn decode_slice(writer_schema: &Schema, bytes: &[u8]) -> Result<(Value, usize), Error> {
+    let mut reader = bytes;
+    match decode(writer_schema, &mut reader) {
+        Ok(value) => Ok((value, bytes.len() - reader.len())),
+        Err(e) => Err(e.at((bytes.len() - reader.len()) as u64, None)),
+    }
+}
+
+/// Decode a `Value` encoded in Avro format given its `Schema`, from exactly all of `bytes`.
+///
+/// In case a reader `Schema` is provided, schema resolution will also be performed.
+///
+/// Unlike [`from_avro_datum`](fn.from_avro_datum.html), bytes left over once the datum is decoded
+/// are an `Error::TrailingBytes`: decoding with the wrong writer schema (e.g. an older one) often
+/// succeeds otherwise, yielding garbage.
+pub fn from_avro_datum_strict(
+    writer_schema: &Schema,
+    bytes: &[u8],
+    reader_schema: Option<&Schema>,
+) -> Result<Value, Error> {
+    let (value, size) = decode_slice(writer_schema, bytes)?;
+    if size < bytes.len() {
+        return Err(Error::TrailingBytes {
+            size,
+            remaining: bytes.len() - size,
+        })
+    }
+    resolve_datum(value, writer_schema, reader_schema)
+}
+
+// Resolve a decoded value, and convert it into the logical representation of the final schema.
+pub(crate) fn resolve_datum(
+    value: Value,
+    writer_schema: &Schema,
+    reader_schema: Option<&Schema>,
+) -> Result<Value, Error> {
+    match reader_schema {
+        Some(schema) => to_logical(value.resolve(schema)?, schema),
+        None => to_logical(value, writer_schema),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+    use single_object::{MessageReader, MessageWriter};
+    use store::MemorySchemaStore;
+    use util::{zag_i64, zig_i64};
+    use writer::Writer;
+    use Codec;
+
+    // A specific writer and reader of points, encoded as records of two longs.
+    struct PointCodec;
+
+    impl DatumWriter<(i64, i64)> for PointCodec {
+        fn write(
+            &mut self,
+            _: &Schema,
+            datum: &(i64, i64),
+            buffer: &mut Vec<u8>,
+        ) -> Result<(), Error> {
+            zig_i64(datum.0, buffer);
+            zig_i64(datum.1, buffer);
+            Ok(())
+        }
+    }
+
+    impl DatumReader for PointCodec {
+        type Datum = (i64, i64);
+
+        fn read<R: Read>(&mut self, _: &Schema, reader: &mut R) -> Result<(i64, i64), Error> {
+            let mut reader = ByteReader(reader);
+            let x = zag_i64(&mut reader)?;
+            let y = zag_i64(&mut reader)?;
+            Ok((x, y))
+        }
+    }
+
+    fn schema() -> Schema {
+        Schema::parse_str(
+            r#"{"type": "record", "name": "point", "fields": [
+                {"name": "x", "type": "long"},
+                {"name": "y", "type": "long"}
+            ]}"#,
+        ).unwrap()
+    }
+
+    fn point(x: i64, y: i64) -> Value {
+        Value::Record(vec![("x".to_owned(), Value::Long(x)), ("y".to_owned(), Value::Long(y))])
+    }
+
+    #[test]
+    fn test_writer_and_reader_with() {
+        let schema = schema();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        writer.append_with(&mut PointCodec, &(1, -2)).unwrap();
+        writer.append_with(&mut GenericDatumWriter, &point(3, 4)).unwrap();
+        assert!(writer.append_with(&mut GenericDatumWriter, &Value::Long(5)).is_err());
+        writer.flush().unwrap();
+        let encoded = writer.into_inner();
+
+        let values = Reader::new(&encoded[..]).unwrap().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(values, vec![point(1, -2), point(3, 4)]);
+
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        assert_eq!(reader.read_with(&mut PointCodec).unwrap(), Some((1, -2)));
+        let mut generic = GenericDatumReader::new();
+        assert_eq!(reader.read_with(&mut generic).unwrap(), Some(point(3, 4)));
+        assert_eq!(reader.read_with(&mut PointCodec).unwrap(), None);
+
+        // Streamed blocks.
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        reader.set_max_block_size(1);
+        assert_eq!(reader.read_with(&mut PointCodec).unwrap(), Some((1, -2)));
+        assert_eq!(reader.read_with(&mut PointCodec).unwrap(), Some((3, 4)));
+        assert_eq!(reader.read_with(&mut PointCodec).unwrap(), None);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_writer_with_statistics() {
+        let schema = schema();
+        let output = ::std::io::Cursor::new(Vec::new());
+        let mut writer = Writer::with_statistics(&schema, output, Codec::Null).unwrap();
+        writer.append_with(&mut PointCodec, &(1, -2)).unwrap();
+        writer.append_with(&mut PointCodec, &(-3, 4)).unwrap();
+        let statistics = writer.statistics().unwrap();
+        assert_eq!(statistics.records, 2);
+        assert_eq!(statistics.fields["x"].min, Some(Value::Long(-3)));
+        assert_eq!(statistics.fields["y"].max, Some(Value::Long(4)));
+    }
+
+    #[test]
+    fn test_generic_datum_reader_resolution() {
+        let schema = schema();
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "point", "fields": [{"name": "y", "type": "long"}]}"#,
+        ).unwrap();
+        let mut buffer = Vec::new();
+        GenericDatumWriter.write(&schema, &point(1, 2), &mut buffer).unwrap();
+        let mut datum_reader = GenericDatumReader::with_schema(&reader_schema);
+        assert_eq!(
+            datum_reader.read(&schema, &mut &buffer[..]).unwrap(),
+            Value::Record(vec![("y".to_owned(), Value::Long(2))])
+        );
+    }
+
+    #[test]
+    fn test_message_writer_and_reader_with() {
+        let schema = schema();
+        let mut store = MemorySchemaStore::new();
+        store.insert(schema.clone());
+        let mut encoded = Vec::new();
+        MessageWriter::new(&schema).write_with(&mut PointCodec, &(5, 6), &mut encoded).unwrap();
+        let mut message_reader = MessageReader::new(&store);
+        assert_eq!(message_reader.read_with(&mut PointCodec, &mut &encoded[..]).unwrap(), (5, 6));
+        assert_eq!(message_reader.read(&mut &encoded[..]).unwrap(), point(5, 6));
+    }
+
+    #[cfg(feature = "serde_layer")]
+    #[test]
+    fn test_serde_datum_writer_and_reader() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        let schema = schema();
+        let mut buffer = Vec::new();
+        SerdeDatumWriter.write(&schema, &Point { x: 7, y: 8 }, &mut buffer).unwrap();
+        assert!(SerdeDatumWriter.write(&schema, &7i64, &mut Vec::new()).is_err());
+        let mut datum_reader = SerdeDatumReader::<Point>::new();
+        assert_eq!(datum_reader.read(&schema, &mut &buffer[..]).unwrap(), Point { x: 7, y: 8 });
+        assert_eq!(PointCodec.read(&schema, &mut &buffer[..]).unwrap(), (7, 8));
+    }
+}
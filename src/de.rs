@@ -4,9 +4,12 @@ use std::collections::HashMap;
 use std::error::{self, Error as StdError};
 use std::fmt;
 use std::slice::Iter;
+use std::sync::Arc;
 
+use serde::de::value::BorrowedStrDeserializer;
 use serde::de::{self, Deserialize, DeserializeSeed, Error as SerdeError, Visitor};
 
+use conversion::{self, CONVERTED};
 use types::Value;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -43,8 +46,8 @@ struct SeqDeserializer<'de> {
 }
 
 struct MapDeserializer<'de> {
-    input_keys: Keys<'de, String, Value>,
-    input_values: Values<'de, String, Value>,
+    input_keys: Keys<'de, Arc<str>, Value>,
+    input_values: Values<'de, Arc<str>, Value>,
 }
 
 struct StructDeserializer<'de> {
@@ -53,7 +56,12 @@ struct StructDeserializer<'de> {
 }
 
 impl<'de> Deserializer<'de> {
-    pub fn new(input: &'de Value) -> Self {
+    pub fn new(mut input: &'de Value) -> Self {
+        // Unions are transparent: their value is the one of their branch, which then resolves
+        // against either a non-union type or an `Option`.
+        while let Value::Union(ref inner) | Value::UnionBranch(_, ref inner) = *input {
+            input = inner;
+        }
         Deserializer { input }
     }
 }
@@ -67,7 +75,7 @@ impl<'de> SeqDeserializer<'de> {
 }
 
 impl<'de> MapDeserializer<'de> {
-    pub fn new(input: &'de HashMap<String, Value>) -> Self {
+    pub fn new(input: &'de HashMap<Arc<str>, Value>) -> Self {
         MapDeserializer {
             input_keys: input.keys(), // input.keys().map(|k| Value::String(k.clone())).collect::<Vec<_>>().iter(),
             input_values: input.values(),
@@ -100,7 +108,15 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             Value::Long(i) => visitor.visit_i64(i),
             Value::Float(x) => visitor.visit_f32(x),
             Value::Double(x) => visitor.visit_f64(x),
-            _ => Err(Error::custom("incorrect value")),
+            Value::Bytes(ref bytes) | Value::Fixed(_, ref bytes) => {
+                visitor.visit_borrowed_bytes(bytes)
+            },
+            Value::String(ref s) => visitor.visit_borrowed_str(s),
+            Value::Enum(_, ref symbol) => visitor.visit_borrowed_str(symbol),
+            Value::Union(_) | Value::UnionBranch(..) => unreachable!(),
+            Value::Array(ref items) => visitor.visit_seq(SeqDeserializer::new(items)),
+            Value::Map(ref items) => visitor.visit_map(MapDeserializer::new(items)),
+            Value::Record(ref fields) => visitor.visit_map(StructDeserializer::new(fields)),
         }
     }
 
@@ -172,9 +188,8 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         match *self.input {
-            Value::Union(ref inner) if inner.as_ref() == &Value::Null => visitor.visit_none(),
-            Value::Union(ref inner) => visitor.visit_some(&mut Deserializer::new(inner)),
-            _ => Err(Error::custom("not a union")),
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
         }
     }
 
@@ -201,12 +216,16 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if name == CONVERTED {
+            conversion::hand_over(self.input.clone());
+            return visitor.visit_unit()
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -216,6 +235,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     {
         match *self.input {
             Value::Array(ref items) => visitor.visit_seq(SeqDeserializer::new(items)),
+            // e.g. into a `Vec<u8>`
+            Value::Bytes(ref bytes) | Value::Fixed(_, ref bytes) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(bytes.iter().cloned()))
+            },
+            Value::String(ref s) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(s.bytes()))
+            },
             _ => Err(Error::custom("not an array")),
         }
     }
@@ -268,13 +294,17 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self,
         _: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         match *self.input {
-            // &Value::Enum(i) => ,  TODO
+            Value::Enum(_, ref symbol) => {
+                let symbol: BorrowedStrDeserializer<Error> = BorrowedStrDeserializer::new(symbol);
+                visitor.visit_enum(symbol)
+            },
+            Value::String(ref s) => visitor.visit_enum(BorrowedStrDeserializer::new(s)),
             _ => Err(Error::custom("not an enum")),
         }
     }
@@ -318,7 +348,7 @@ impl<'de> de::MapAccess<'de> for MapDeserializer<'de> {
         match self.input_keys.next() {
             Some(ref key) => seed
                 .deserialize(StringDeserializer {
-                    input: (*key).clone(),
+                    input: key.to_string(),
                 }).map(Some),
             None => Ok(None),
         }
@@ -388,9 +418,242 @@ impl<'de> de::Deserializer<'de> for StringDeserializer {
 
 /// Interpret a `Value` as an instance of type `D`.
 ///
+/// The `Value` can also be transcoded into other formats (e.g. JSON, with
+/// [`serde_transcode`](https://docs.rs/serde-transcode)), through `Deserializer::deserialize_any`:
+/// unions then hold the value of their branch, enums their symbol, and records and maps are
+/// transcoded into maps.
+///
 /// This conversion can fail if the structure of the `Value` does not match the
 /// structure expected by `D`.
 pub fn from_value<'de, D: Deserialize<'de>>(value: &'de Value) -> Result<D, Error> {
     let mut de = Deserializer::new(value);
     D::deserialize(&mut de)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schema::Schema;
+    use ser::Serializer;
+    use serde_json;
+    use serde_transcode::transcode;
+    #[cfg(feature = "rand")]
+    use {Reader, Writer};
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {"name": "a", "type": ["null", "int"]},
+                {"name": "b", "type": {"type": "map", "values": ["string", "long"]}},
+                {
+                    "name": "c",
+                    "type": {
+                        "type": "record",
+                        "name": "inner",
+                        "fields": [
+                            {"name": "d", "type": {"type": "array", "items": "double"}},
+                            {
+                                "name": "e",
+                                "type": {"type": "enum", "name": "e", "symbols": ["x", "y"]}
+                            }
+                        ]
+                    }
+                }
+            ]
+        }
+    "#;
+
+    // With `unsigned_long_as_fixed`, the non-negative JSON numbers are serialized as bytes.
+    #[cfg(not(feature = "unsigned_long_as_fixed"))]
+    #[test]
+    fn test_transcode_json_to_avro() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let json = r#"{"a": 1, "b": {"k": "v", "l": 2}, "c": {"d": [1.5, 2], "e": "y"}}"#;
+
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let value = transcode(&mut deserializer, &mut Serializer::default()).unwrap();
+        let value = value.resolve(&schema).unwrap();
+
+        let mut b = HashMap::new();
+        b.insert("k".into(), Value::String("v".to_owned()));
+        b.insert("l".into(), Value::Long(2));
+        assert_eq!(
+            value,
+            Value::Record(vec![
+                ("a".to_owned(), Value::Int(1)),
+                ("b".to_owned(), Value::Map(b)),
+                (
+                    "c".to_owned(),
+                    Value::Record(vec![
+                        (
+                            "d".to_owned(),
+                            Value::Array(vec![Value::Double(1.5), Value::Double(2.0)]),
+                        ),
+                        ("e".to_owned(), Value::Enum(1, "y".into())),
+                    ]),
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_transcode_avro_to_json() {
+        let mut b = HashMap::new();
+        b.insert("k".into(), Value::Union(Box::new(Value::String("v".to_owned()))));
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Union(Box::new(Value::Null))),
+            ("b".to_owned(), Value::Map(b)),
+            (
+                "c".to_owned(),
+                Value::Record(vec![
+                    ("d".to_owned(), Value::Array(vec![Value::Double(1.5)])),
+                    ("e".to_owned(), Value::Enum(1, "y".into())),
+                    ("f".to_owned(), Value::Bytes(vec![1, 2])),
+                ]),
+            ),
+        ]);
+
+        let mut json = Vec::new();
+        transcode(
+            &mut Deserializer::new(&value),
+            &mut serde_json::Serializer::new(&mut json),
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"a":null,"b":{"k":"v"},"c":{"d":[1.5],"e":"y","f":[1,2]}}"#
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Suit {
+        Heart,
+        Spade,
+    }
+
+    #[test]
+    fn test_from_value_enum() {
+        assert_eq!(
+            from_value::<Suit>(&Value::Enum(1, "Spade".into())).unwrap(),
+            Suit::Spade
+        );
+        assert!(from_value::<Suit>(&Value::Enum(0, "Club".into())).is_err());
+    }
+
+    #[cfg(feature = "rand")]
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Promoted {
+        a: i64,
+        b: f32,
+        c: f64,
+        d: Vec<u8>,
+        e: String,
+        f: Option<f64>,
+        g: Option<i64>,
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_from_value_promotions() {
+        let writer_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "int"},
+                    {"name": "b", "type": "long"},
+                    {"name": "c", "type": "float"},
+                    {"name": "d", "type": "string"},
+                    {"name": "e", "type": "bytes"},
+                    {"name": "f", "type": "int"},
+                    {"name": "g", "type": ["null", "int"]}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": "float"},
+                    {"name": "c", "type": "double"},
+                    {"name": "d", "type": "bytes"},
+                    {"name": "e", "type": "string"},
+                    {"name": "f", "type": ["null", "double"]},
+                    {"name": "g", "type": ["null", "long"]}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Int(1)),
+            ("b".to_owned(), Value::Long(2)),
+            ("c".to_owned(), Value::Float(0.5)),
+            ("d".to_owned(), Value::String("foo".to_owned())),
+            ("e".to_owned(), Value::Bytes(b"bar".to_vec())),
+            ("f".to_owned(), Value::Int(3)),
+            ("g".to_owned(), Value::Union(Box::new(Value::Null))),
+        ]);
+        let expected = Promoted {
+            a: 1,
+            b: 2.0,
+            c: 0.5,
+            d: b"foo".to_vec(),
+            e: "bar".to_owned(),
+            f: Some(3.0),
+            g: None,
+        };
+
+        // Straight from the writer value, and from the resolved one.
+        assert_eq!(from_value::<Promoted>(&value).unwrap(), expected);
+        let resolved = value.clone().resolve(&reader_schema).unwrap();
+        assert_eq!(from_value::<Promoted>(&resolved).unwrap(), expected);
+
+        let mut writer = Writer::new(&writer_schema, Vec::new()).unwrap();
+        writer.append(value).unwrap();
+        writer.flush().unwrap();
+        let input = writer.into_inner();
+        for value in Reader::with_schema(&reader_schema, &input[..]).unwrap() {
+            assert_eq!(from_value::<Promoted>(&value.unwrap()).unwrap(), expected);
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Branches {
+        a: String,
+        b: Vec<i64>,
+        c: Option<i32>,
+    }
+
+    #[test]
+    fn test_from_value_union_branches() {
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Union(Box::new(Value::String("foo".to_owned())))),
+            (
+                "b".to_owned(),
+                Value::Union(Box::new(Value::Array(vec![Value::Long(1)]))),
+            ),
+            ("c".to_owned(), Value::Int(2)),
+        ]);
+        assert_eq!(
+            from_value::<Branches>(&value).unwrap(),
+            Branches {
+                a: "foo".to_owned(),
+                b: vec![1],
+                c: Some(2),
+            }
+        );
+
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Union(Box::new(Value::Null))),
+            ("b".to_owned(), Value::Array(vec![])),
+            ("c".to_owned(), Value::Null),
+        ]);
+        assert!(from_value::<Branches>(&value).is_err());
+    }
+}
@@ -1,30 +1,149 @@
-use std::collections::HashMap;
-use std::io::Read;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::mem;
 use std::mem::transmute;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::str::{from_utf8, from_utf8_unchecked};
+use std::sync::Arc;
 
-use failure::Error;
+use error::Error;
 
 use schema::Schema;
 use types::Value;
-use util::{safe_len, zag_i32, zag_i64, DecodeError};
+use util::{safe_depth, safe_len, zag_i32, zag_i64, DecodeError, VarintRead};
 
 #[inline]
-fn decode_long<R: Read>(reader: &mut R) -> Result<Value, Error> {
+fn decode_long<R: VarintRead>(reader: &mut R) -> Result<Value, Error> {
     zag_i64(reader).map(Value::Long)
 }
 
 #[inline]
-fn decode_int<R: Read>(reader: &mut R) -> Result<Value, Error> {
+fn decode_int<R: VarintRead>(reader: &mut R) -> Result<Value, Error> {
     zag_i32(reader).map(Value::Int)
 }
 
 #[inline]
-fn decode_len<R: Read>(reader: &mut R) -> Result<usize, Error> {
+fn decode_len<R: VarintRead>(reader: &mut R) -> Result<usize, Error> {
     zag_i64(reader).and_then(|len| safe_len(len as usize))
 }
 
+/// Decode the item count of the next block of an array or a map. Blocks written with a negative
+/// count are followed by their size in bytes, of no use when decoding their items.
+pub(crate) fn decode_block_len<R: VarintRead>(reader: &mut R) -> Result<usize, Error> {
+    let len = zag_i64(reader)?;
+    if len < 0 {
+        if zag_i64(reader)? < 0 {
+            return Err(DecodeError::new("negative block size").into())
+        }
+        return safe_len(len.unsigned_abs() as usize)
+    }
+    safe_len(len as usize)
+}
+
+// Beyond this many strings (e.g. for maps keyed by ids), strings are not interned anymore.
+const MAX_INTERNED_STRINGS: usize = 4096;
+
+/// Pool of the map keys and enum symbols shared between decoded values.
+#[derive(Debug, Default, Clone)]
+pub struct StringPool {
+    strings: HashSet<Arc<str>>,
+    buf: Vec<u8>,
+    // Whether strings are known to be valid UTF-8, and so are not validated.
+    pub(crate) trusted_utf8: bool,
+}
+
+impl StringPool {
+    pub fn new() -> StringPool {
+        StringPool::default()
+    }
+
+    /// Return the shared copy of `s`.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        intern_in(&mut self.strings, s)
+    }
+
+    pub(crate) fn decode<R: VarintRead>(&mut self, reader: &mut R) -> Result<Arc<str>, Error> {
+        let len = decode_len(reader)?;
+        self.buf.clear();
+        self.buf.resize(len, 0);
+        reader.read_exact(&mut self.buf)?;
+
+        let s = str_from_utf8(&self.buf, self.trusted_utf8)?;
+        Ok(intern_in(&mut self.strings, s))
+    }
+
+    fn string_from_utf8(&self, buf: Vec<u8>) -> Result<String, Error> {
+        if self.trusted_utf8 {
+            // Safe as long as the caller vouched for the data.
+            return Ok(unsafe { String::from_utf8_unchecked(buf) })
+        }
+        String::from_utf8(buf).map_err(|e| {
+            let valid_up_to = e.utf8_error().valid_up_to();
+            invalid_utf8(e.into_bytes(), valid_up_to)
+        })
+    }
+}
+
+fn str_from_utf8(buf: &[u8], trusted: bool) -> Result<&str, Error> {
+    if trusted {
+        // Safe as long as the caller vouched for the data.
+        return Ok(unsafe { from_utf8_unchecked(buf) })
+    }
+    from_utf8(buf).map_err(|e| invalid_utf8(buf.to_vec(), e.valid_up_to()))
+}
+
+/// The error for a string which is not valid UTF-8, at the root of the value being decoded.
+pub(crate) fn invalid_utf8(bytes: Vec<u8>, valid_up_to: usize) -> Error {
+    Error::InvalidUtf8 {
+        path: "$".to_owned(),
+        valid_up_to,
+        bytes,
+    }
+}
+
+/// The error for an enum symbol index not within `symbols`.
+pub(crate) fn enum_index_out_of_bounds(index: i32, symbols: &[String]) -> Error {
+    DecodeError::new(format!(
+        "enum symbol index {} out of bounds, expected one of {:?}",
+        index, symbols
+    )).into()
+}
+
+fn intern_in(strings: &mut HashSet<Arc<str>>, s: &str) -> Arc<str> {
+    if let Some(interned) = strings.get(s) {
+        return interned.clone()
+    }
+
+    let interned = Arc::<str>::from(s);
+    if strings.len() < MAX_INTERNED_STRINGS {
+        strings.insert(interned.clone());
+    }
+    interned
+}
+
 /// Decode a `Value` from avro format given its `Schema`.
-pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Value, Error> {
+pub fn decode<R: VarintRead>(schema: &Schema, reader: &mut R) -> Result<Value, Error> {
+    decode_interned(schema, reader, &mut StringPool::new())
+}
+
+/// Decode a `Value` from avro format given its `Schema`, interning map keys and enum symbols in
+/// `pool`.
+pub fn decode_interned<R: VarintRead>(
+    schema: &Schema,
+    reader: &mut R,
+    pool: &mut StringPool,
+) -> Result<Value, Error> {
+    decode_nested(schema, reader, pool, 0)
+}
+
+// Decode a `Value` nested within `depth` arrays, maps and records.
+fn decode_nested<R: VarintRead>(
+    schema: &Schema,
+    reader: &mut R,
+    pool: &mut StringPool,
+    depth: usize,
+) -> Result<Value, Error> {
     match *schema {
         Schema::Null => Ok(Value::Null),
         Schema::Boolean => {
@@ -66,20 +185,20 @@ pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Value, Error>
             }
             reader.read_exact(&mut buf)?;
 
-            String::from_utf8(buf)
-                .map(Value::String)
-                .map_err(|_| DecodeError::new("not a valid utf-8 string").into())
+            pool.string_from_utf8(buf).map(Value::String)
         },
         Schema::Fixed { size, .. } => {
             let mut buf = vec![0u8; size as usize];
             reader.read_exact(&mut buf)?;
             Ok(Value::Fixed(size, buf))
         },
+        Schema::Logical(ref logical) => decode_nested(logical.schema(), reader, pool, depth),
         Schema::Array(ref inner) => {
+            let depth = safe_depth(depth + 1)?;
             let mut items = Vec::new();
 
             loop {
-                let len = decode_len(reader)?;
+                let len = decode_block_len(reader)?;
                 // arrays are 0-terminated, 0i64 is also encoded as 0 in Avro
                 // reading a length of 0 means the end of the array
                 if len == 0 {
@@ -88,17 +207,22 @@ pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Value, Error>
 
                 items.reserve(len as usize);
                 for _ in 0..len {
-                    items.push(decode(inner, reader)?);
+                    let index = items.len();
+                    items.push(
+                        decode_nested(inner, reader, pool, depth)
+                            .map_err(|e| e.within(|| format!("[{}]", index)))?,
+                    );
                 }
             }
 
             Ok(Value::Array(items))
         },
         Schema::Map(ref inner) => {
+            let depth = safe_depth(depth + 1)?;
             let mut items = HashMap::new();
 
             loop {
-                let len = decode_len(reader)?;
+                let len = decode_block_len(reader)?;
                 // maps are 0-terminated, 0i64 is also encoded as 0 in Avro
                 // reading a length of 0 means the end of the map
                 if len == 0 {
@@ -107,12 +231,10 @@ pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Value, Error>
 
                 items.reserve(len as usize);
                 for _ in 0..len {
-                    if let Value::String(key) = decode(&Schema::String, reader)? {
-                        let value = decode(inner, reader)?;
-                        items.insert(key, value);
-                    } else {
-                        return Err(DecodeError::new("map key is not a string").into())
-                    }
+                    let key = pool.decode(reader)?;
+                    let value = decode_nested(inner, reader, pool, depth)
+                        .map_err(|e| e.within(|| format!("[{:?}]", &*key)))?;
+                    items.insert(key, value);
                 }
             }
 
@@ -122,16 +244,23 @@ pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Value, Error>
             let index = zag_i64(reader)?;
             let variants = inner.variants();
             match variants.get(index as usize) {
-                Some(variant) => decode(variant, reader).map(|x| Value::Union(Box::new(x))),
+                Some(variant) => {
+                    decode_nested(variant, reader, pool, depth).map(|x| Value::Union(Box::new(x)))
+                },
                 None => Err(DecodeError::new("Union index out of bounds").into()),
             }
         },
         Schema::Record { ref fields, .. } => {
+            let depth = safe_depth(depth + 1)?;
             // Benchmarks indicate ~10% improvement using this method.
             let mut items = Vec::new();
             for field in fields {
                 // This clone is also expensive. See if we can do away with it...
-                items.push((field.name.clone(), decode(&field.schema, reader)?));
+                items.push((
+                    field.name.clone(),
+                    decode_nested(&field.schema, reader, pool, depth)
+                        .map_err(|e| e.within(|| format!(".{}", field.name)))?,
+                ));
             }
             Ok(Value::Record(items))
             // fields
@@ -141,16 +270,480 @@ pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Value, Error>
             // .map(|items| Value::Record(items))
         },
         Schema::Enum { ref symbols, .. } => {
-            if let Value::Int(index) = decode_int(reader)? {
-                if index >= 0 && (index as usize) <= symbols.len() {
-                    let symbol = symbols[index as usize].clone();
-                    Ok(Value::Enum(index, symbol))
-                } else {
-                    Err(DecodeError::new("enum symbol index out of bounds").into())
+            let index = zag_i32(reader)?;
+            match symbols.get(index as usize) {
+                Some(symbol) if index >= 0 => Ok(Value::Enum(index, pool.intern(symbol))),
+                _ => Err(enum_index_out_of_bounds(index, symbols)),
+            }
+        },
+    }
+}
+
+/// Decode a `Value` from avro format given its `Schema` into `value`, reusing the strings, bytes,
+/// arrays, maps and records already allocated there.
+///
+/// On error, `value` is left partially decoded.
+#[cfg(feature = "std")]
+pub fn decode_into<R: VarintRead>(
+    schema: &Schema,
+    reader: &mut R,
+    pool: &mut StringPool,
+    value: &mut Value,
+) -> Result<(), Error> {
+    decode_nested_into(schema, reader, pool, 0, value)
+}
+
+#[cfg(feature = "std")]
+fn decode_nested_into<R: VarintRead>(
+    schema: &Schema,
+    reader: &mut R,
+    pool: &mut StringPool,
+    depth: usize,
+    value: &mut Value,
+) -> Result<(), Error> {
+    if let Schema::Logical(ref logical) = *schema {
+        return decode_nested_into(logical.schema(), reader, pool, depth, value)
+    }
+    match (schema, value) {
+        (Schema::Bytes, Value::Bytes(buf)) => {
+            let len = decode_len(reader)?;
+            buf.clear();
+            buf.resize(len, 0);
+            reader.read_exact(buf)?;
+            Ok(())
+        },
+        (Schema::String, Value::String(string)) => {
+            let len = decode_len(reader)?;
+            let mut buf = mem::take(string).into_bytes();
+            buf.clear();
+            buf.resize(len, 0);
+            reader.read_exact(&mut buf)?;
+
+            *string = pool.string_from_utf8(buf)?;
+            Ok(())
+        },
+        (&Schema::Fixed { size, .. }, Value::Fixed(len, buf)) => {
+            *len = size;
+            buf.clear();
+            buf.resize(size, 0);
+            reader.read_exact(buf)?;
+            Ok(())
+        },
+        (Schema::Array(inner), Value::Array(items)) => {
+            let depth = safe_depth(depth + 1)?;
+            let mut count = 0;
+
+            loop {
+                let len = decode_block_len(reader)?;
+                // reading a length of 0 means the end of the array
+                if len == 0 {
+                    break
+                }
+
+                for _ in 0..len {
+                    let decoded = if count < items.len() {
+                        decode_nested_into(inner, reader, pool, depth, &mut items[count])
+                    } else {
+                        decode_nested(inner, reader, pool, depth).map(|item| items.push(item))
+                    };
+                    decoded.map_err(|e| e.within(|| format!("[{}]", count)))?;
+                    count += 1;
+                }
+            }
+
+            items.truncate(count);
+            Ok(())
+        },
+        (Schema::Map(inner), Value::Map(items)) => {
+            let depth = safe_depth(depth + 1)?;
+            items.clear();
+
+            loop {
+                let len = decode_block_len(reader)?;
+                // reading a length of 0 means the end of the map
+                if len == 0 {
+                    break
+                }
+
+                items.reserve(len);
+                for _ in 0..len {
+                    let key = pool.decode(reader)?;
+                    let value = decode_nested(inner, reader, pool, depth)
+                        .map_err(|e| e.within(|| format!("[{:?}]", &*key)))?;
+                    items.insert(key, value);
                 }
-            } else {
-                Err(DecodeError::new("enum symbol not found").into())
             }
+            Ok(())
         },
+        (Schema::Union(inner), Value::Union(boxed)) => {
+            let index = zag_i64(reader)?;
+            match inner.variants().get(index as usize) {
+                Some(variant) => decode_nested_into(variant, reader, pool, depth, boxed),
+                None => Err(DecodeError::new("Union index out of bounds").into()),
+            }
+        },
+        (Schema::Record { fields, .. }, Value::Record(items))
+            if items.len() == fields.len() =>
+        {
+            let depth = safe_depth(depth + 1)?;
+            for (field, item) in fields.iter().zip(items.iter_mut()) {
+                if item.0 != field.name {
+                    item.0.clone_from(&field.name);
+                }
+                decode_nested_into(&field.schema, reader, pool, depth, &mut item.1)
+                    .map_err(|e| e.within(|| format!(".{}", field.name)))?;
+            }
+            Ok(())
+        },
+        // Nothing to reuse: decode a new value.
+        (schema, value) => {
+            *value = decode_nested(schema, reader, pool, depth)?;
+            Ok(())
+        },
+    }
+}
+
+/// Advance `reader` past one datum encoded with `schema`, without decoding it into a `Value`.
+///
+/// Strings, bytes and fixed values are skipped over using their lengths, and array/map blocks
+/// written with their byte size (negative item count) are skipped in a single step.
+pub fn skip<R: VarintRead>(schema: &Schema, reader: &mut R) -> Result<(), Error> {
+    match *schema {
+        Schema::Null => Ok(()),
+        Schema::Boolean => reader.skip_bytes(1),
+        Schema::Int | Schema::Long | Schema::Enum { .. } => reader.read_variable().map(|_| ()),
+        Schema::Float => reader.skip_bytes(4),
+        Schema::Double => reader.skip_bytes(8),
+        Schema::Bytes | Schema::String => {
+            let len = zag_i64(reader)?;
+            if len < 0 {
+                return Err(DecodeError::new("negative length").into())
+            }
+            reader.skip_bytes(len as u64)
+        },
+        Schema::Fixed { size, .. } => reader.skip_bytes(size as u64),
+        Schema::Logical(ref logical) => skip(logical.schema(), reader),
+        Schema::Array(ref inner) => skip_blocks(reader, |reader| skip(inner, reader)),
+        Schema::Map(ref inner) => skip_blocks(reader, |reader| {
+            skip(&Schema::String, reader)?;
+            skip(inner, reader)
+        }),
+        Schema::Union(ref inner) => {
+            let index = zag_i64(reader)?;
+            match inner.variants().get(index as usize) {
+                Some(variant) => skip(variant, reader),
+                None => Err(DecodeError::new("Union index out of bounds").into()),
+            }
+        },
+        Schema::Record { ref fields, .. } => {
+            for field in fields {
+                skip(&field.schema, reader)?;
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Skip the blocks of an array or a map, calling `skip_item` for every item of the blocks which
+/// do not carry their size in bytes.
+///
+/// Their counts are bounded like when decoding, so that items taking no bytes (e.g. `null`s)
+/// cannot keep the skipping going for ever.
+fn skip_blocks<R, F>(reader: &mut R, mut skip_item: F) -> Result<(), Error>
+where
+    R: VarintRead,
+    F: FnMut(&mut R) -> Result<(), Error>,
+{
+    loop {
+        let len = zag_i64(reader)?;
+        if len == 0 {
+            return Ok(())
+        }
+
+        if len < 0 {
+            let size = zag_i64(reader)?;
+            if size < 0 {
+                return Err(DecodeError::new("negative block size").into())
+            }
+            reader.skip_bytes(size as u64)?;
+        } else {
+            for _ in 0..safe_len(len as usize)? {
+                skip_item(reader)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encode::encode;
+    use util::{zig_i64, ByteReader};
+
+    #[test]
+    fn test_skip() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": "string"},
+                    {"name": "c", "type": {"type": "array", "items": ["null", "double"]}},
+                    {"name": "d", "type": {"type": "map", "values": "boolean"}},
+                    {"name": "e", "type": {"type": "fixed", "name": "md5", "size": 16}}
+                ]
+            }
+        "#,
+        ).unwrap();
+
+        let mut map = HashMap::new();
+        map.insert("x".into(), Value::Boolean(true));
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Long(-27)),
+            ("b".to_owned(), Value::String("foo".to_owned())),
+            (
+                "c".to_owned(),
+                Value::Array(vec![
+                    Value::Union(Box::new(Value::Null)),
+                    Value::Union(Box::new(Value::Double(1.5))),
+                ]),
+            ),
+            ("d".to_owned(), Value::Map(map)),
+            ("e".to_owned(), Value::Fixed(16, vec![7; 16])),
+        ]);
+
+        let mut encoded = Vec::new();
+        encode(&value, &schema, &mut encoded);
+        encoded.push(42);
+
+        let mut slice = &encoded[..];
+        skip(&schema, &mut slice).unwrap();
+        assert_eq!(slice, &[42]);
+
+        let mut stream = &encoded[..];
+        skip(&schema, &mut ByteReader(&mut stream)).unwrap();
+        assert_eq!(stream, &[42]);
+
+        let mut truncated = &encoded[..encoded.len() - 3];
+        assert!(skip(&schema, &mut truncated).is_err());
+    }
+
+    #[test]
+    fn test_skip_sized_blocks() {
+        let schema = Schema::Array(Box::new(Schema::String));
+
+        // A block of 2 items, written with a negative count followed by its size in bytes.
+        let mut encoded = Vec::new();
+        zig_i64(-2, &mut encoded);
+        zig_i64(8, &mut encoded);
+        for s in &["foo", "bar"] {
+            zig_i64(3, &mut encoded);
+            encoded.extend_from_slice(s.as_bytes());
+        }
+        encoded.push(0);
+        encoded.push(42);
+
+        let mut slice = &encoded[..];
+        skip(&schema, &mut slice).unwrap();
+        assert_eq!(slice, &[42]);
+    }
+
+    #[test]
+    fn test_skip_huge_block_count() {
+        let schema = Schema::Array(Box::new(Schema::Null));
+        let mut encoded = Vec::new();
+        zig_i64(i64::MAX / 2, &mut encoded);
+        encoded.push(0);
+        match skip(&schema, &mut &encoded[..]).unwrap_err() {
+            Error::Allocation { requested, .. } => assert_eq!(requested, (i64::MAX / 2) as usize),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(decode(&schema, &mut &encoded[..]).is_err());
+    }
+
+    #[test]
+    fn test_decode_sized_blocks() {
+        use encode::encode_sized;
+
+        let schema = Schema::Map(Box::new(Schema::Array(Box::new(Schema::String))));
+        let mut items = HashMap::new();
+        items.insert(
+            Arc::from("a"),
+            Value::Array(vec![Value::String("foo".to_owned()); 2]),
+        );
+        items.insert(Arc::from("b"), Value::Array(vec![]));
+        let value = Value::Map(items);
+
+        let mut encoded = Vec::new();
+        encode_sized(&value, &schema, &mut encoded);
+        assert_eq!(decode(&schema, &mut &encoded[..]).unwrap(), value);
+        let mut decoded = Value::Map(HashMap::new());
+        decode_into(&schema, &mut &encoded[..], &mut StringPool::new(), &mut decoded).unwrap();
+        assert_eq!(decoded, value);
+
+        // Sized blocks can be mixed with other blocks.
+        let schema = Schema::Array(Box::new(Schema::Long));
+        let mut encoded = Vec::new();
+        zig_i64(-2, &mut encoded);
+        zig_i64(2, &mut encoded);
+        encoded.extend_from_slice(&[2, 4, 2, 6, 0]);
+        assert_eq!(
+            decode(&schema, &mut &encoded[..]).unwrap(),
+            Value::Array(vec![Value::Long(1), Value::Long(2), Value::Long(3)])
+        );
+
+        let mut encoded = Vec::new();
+        zig_i64(-1, &mut encoded);
+        zig_i64(-1, &mut encoded);
+        assert!(decode(&schema, &mut &encoded[..]).is_err());
+    }
+
+    #[test]
+    fn test_decode_into() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": {"type": "array", "items": "string"}},
+                    {"name": "b", "type": ["null", "bytes"]},
+                    {"name": "c", "type": {"type": "map", "values": "long"}}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let record = |a: &[&str], b: Value, c: &[(&str, i64)]| {
+            Value::Record(vec![
+                (
+                    "a".to_owned(),
+                    Value::Array(a.iter().map(|&s| Value::String(s.to_owned())).collect()),
+                ),
+                ("b".to_owned(), Value::Union(Box::new(b))),
+                (
+                    "c".to_owned(),
+                    Value::Map(c.iter().map(|&(k, v)| (k.into(), Value::Long(v))).collect()),
+                ),
+            ])
+        };
+        let values = vec![
+            record(&["foo", "bar"], Value::Bytes(vec![1, 2]), &[("x", 1)]),
+            record(&["baz"], Value::Null, &[("y", 2), ("z", 3)]),
+            record(&["a", "b", "c"], Value::Bytes(vec![3]), &[]),
+        ];
+
+        let mut decoded = Value::Null;
+        let mut pool = StringPool::new();
+        for value in &values {
+            let mut encoded = Vec::new();
+            encode(value, &schema, &mut encoded);
+            decode_into(&schema, &mut &encoded[..], &mut pool, &mut decoded).unwrap();
+            assert_eq!(&decoded, value);
+        }
+    }
+
+    #[test]
+    fn test_trusted_utf8() {
+        let mut encoded = Vec::new();
+        zig_i64(3, &mut encoded);
+        encoded.extend_from_slice(b"f\xffo");
+
+        let mut pool = StringPool::new();
+        assert!(decode_interned(&Schema::String, &mut &encoded[..], &mut pool).is_err());
+
+        let mut encoded = Vec::new();
+        encode(&Value::String("foo".to_owned()), &Schema::String, &mut encoded);
+        pool.trusted_utf8 = true;
+        assert_eq!(
+            decode_interned(&Schema::String, &mut &encoded[..], &mut pool).unwrap(),
+            Value::String("foo".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {
+                        "name": "b",
+                        "type": {"type": "map", "values": {"type": "array", "items": "string"}}
+                    }
+                ]
+            }
+        "#,
+        ).unwrap();
+        let mut encoded = Vec::new();
+        zig_i64(27, &mut encoded);
+        // A map with the single key "k", holding an array of 2 strings, the second one invalid.
+        encoded.extend_from_slice(&[2, 2, b'k', 4, 2, b'x']);
+        zig_i64(3, &mut encoded);
+        encoded.extend_from_slice(b"f\xffo");
+        encoded.extend_from_slice(&[0, 0]);
+
+        let check = |error: Error| {
+            assert_eq!(
+                error.to_string(),
+                r#"Decoding error: invalid utf-8 string at $.b["k"][1], byte 1 of 3: 66 [ff] 6f"#
+            );
+            assert_eq!(error.invalid_utf8_bytes(), Some(&b"f\xffo"[..]));
+        };
+        check(decode(&schema, &mut &encoded[..]).unwrap_err());
+
+        let truncated = decode(&schema, &mut &encoded[..encoded.len() - 6]).unwrap_err();
+        assert!(truncated.invalid_utf8_bytes().is_none());
+
+        let mut decoded = Value::Record(vec![
+            ("a".to_owned(), Value::Long(0)),
+            ("b".to_owned(), Value::Map(HashMap::new())),
+        ]);
+        let error = decode_into(&schema, &mut &encoded[..], &mut StringPool::new(), &mut decoded);
+        check(error.unwrap_err());
+    }
+
+    #[test]
+    fn test_decode_enum() {
+        let schema = Schema::parse_str(
+            r#"{"type": "enum", "name": "suit", "symbols": ["spades", "hearts"]}"#,
+        ).unwrap();
+        assert_eq!(
+            decode(&schema, &mut &[2u8][..]).unwrap(),
+            Value::Enum(1, "hearts".into())
+        );
+        for encoded in &[[4u8], [1u8]] {
+            match decode(&schema, &mut &encoded[..]).unwrap_err() {
+                Error::Decode { reason } => assert!(
+                    reason.ends_with(r#"out of bounds, expected one of ["spades", "hearts"]"#),
+                    "{}",
+                    reason
+                ),
+                other => panic!("unexpected error {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_depth_limit() {
+        let nested = |depth| {
+            (0..depth).fold(Schema::Null, |schema, _| Schema::Array(Box::new(schema)))
+        };
+        // One item in every array, followed by the end of every array.
+        let encoded = |depth| {
+            let mut encoded = vec![2u8; depth];
+            encoded.extend(vec![0u8; depth]);
+            encoded
+        };
+
+        assert!(decode(&nested(128), &mut &encoded(128)[..]).is_ok());
+        match decode(&nested(129), &mut &encoded(129)[..]).unwrap_err() {
+            Error::Depth { limit } => assert_eq!(limit, 128),
+            other => panic!("unexpected error {:?}", other),
+        }
     }
 }
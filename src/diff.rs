@@ -0,0 +1,389 @@
+//! Logic comparing two schemas, or two values, and listing their differences, e.g. to review the
+//! changes between two versions of a schema or of a dataset.
+use std::collections::HashMap;
+
+use serde_json::{self, Value as JsonValue};
+
+use json::branch_name;
+use schema::{RecordField, Schema};
+use types::Value;
+
+/// A difference between two schemas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaChange {
+    /// Where the schemas differ, e.g. `$.field.items` or `$.field.symbols`.
+    pub path: String,
+    /// The old schema (or property, e.g. the default of a field) as JSON, `None` if added.
+    pub old: Option<String>,
+    /// The new schema (or property) as JSON, `None` if removed.
+    pub new: Option<String>,
+}
+
+/// A difference between two values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueChange {
+    /// Where the values differ, e.g. `$.field[0]`.
+    pub path: String,
+    /// The old value, `None` if added.
+    pub old: Option<Value>,
+    /// The new value, `None` if removed.
+    pub new: Option<Value>,
+}
+
+/// List the differences between the `old` and the `new` version of a schema, in the order of
+/// the old one.
+///
+/// Record fields are matched by name, union branches by type name (written `$.field[long]`),
+/// and the properties of named types are compared separately (added or removed enum symbols are
+/// listed one by one as `symbols` changes).
+pub fn diff_schemas(old: &Schema, new: &Schema) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    diff_schema("$", old, new, &mut changes);
+    changes
+}
+
+/// List the differences between the `old` and the `new` version of a value.
+///
+/// Record fields are matched by name and map values by key, unions are transparent, `Int`s and
+/// `Long`s of the same number compare equal, as do enum symbols with the same name and
+/// floating-point values with the same bits.
+pub fn diff_values(old: &Value, new: &Value) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    diff_value("$", old, new, &mut changes);
+    changes
+}
+
+fn json(schema: &Schema) -> Option<String> {
+    serde_json::to_string(schema).ok()
+}
+
+fn change(path: String, old: Option<String>, new: Option<String>) -> SchemaChange {
+    SchemaChange { path, old, new }
+}
+
+fn diff_schema(path: &str, old: &Schema, new: &Schema, changes: &mut Vec<SchemaChange>) {
+    if old == new {
+        return
+    }
+    match (old, new) {
+        (Schema::Array(old), Schema::Array(new)) => {
+            diff_schema(&format!("{}.items", path), old, new, changes)
+        },
+        (Schema::Map(old), Schema::Map(new)) => {
+            diff_schema(&format!("{}.values", path), old, new, changes)
+        },
+        (Schema::Union(old), Schema::Union(new)) => {
+            let branch = |schema| branch_name(schema, None);
+            for variant in old.variants() {
+                let name = branch(variant);
+                let path = format!("{}[{}]", path, name);
+                match new.variants().iter().find(|other| branch(other) == name) {
+                    Some(other) => diff_schema(&path, variant, other, changes),
+                    None => changes.push(change(path, json(variant), None)),
+                }
+            }
+            for variant in new.variants() {
+                let name = branch(variant);
+                if !old.variants().iter().any(|other| branch(other) == name) {
+                    changes.push(change(format!("{}[{}]", path, name), None, json(variant)));
+                }
+            }
+        },
+        (
+            Schema::Record {
+                name: old_name,
+                fields: old_fields,
+                ..
+            },
+            Schema::Record {
+                name: new_name,
+                fields: new_fields,
+                ..
+            },
+        ) => {
+            diff_name(path, &old_name.fullname(None), &new_name.fullname(None), changes);
+            diff_fields(path, old_fields, new_fields, changes);
+        },
+        (
+            Schema::Enum {
+                name: old_name,
+                symbols: old_symbols,
+                default: old_default,
+                ..
+            },
+            Schema::Enum {
+                name: new_name,
+                symbols: new_symbols,
+                default: new_default,
+                ..
+            },
+        ) => {
+            diff_name(path, &old_name.fullname(None), &new_name.fullname(None), changes);
+            let symbol = |s: &String| Some(format!("{:?}", s));
+            let symbols = format!("{}.symbols", path);
+            for s in old_symbols.iter().filter(|s| !new_symbols.contains(s)) {
+                changes.push(change(symbols.clone(), symbol(s), None));
+            }
+            for s in new_symbols.iter().filter(|s| !old_symbols.contains(s)) {
+                changes.push(change(symbols.clone(), None, symbol(s)));
+            }
+            if old_default != new_default {
+                changes.push(change(
+                    format!("{}.default", path),
+                    old_default.as_ref().and_then(symbol),
+                    new_default.as_ref().and_then(symbol),
+                ));
+            }
+        },
+        (
+            Schema::Fixed {
+                name: old_name,
+                size: old_size,
+            },
+            Schema::Fixed {
+                name: new_name,
+                size: new_size,
+            },
+        ) => {
+            diff_name(path, &old_name.fullname(None), &new_name.fullname(None), changes);
+            if old_size != new_size {
+                changes.push(change(
+                    format!("{}.size", path),
+                    Some(old_size.to_string()),
+                    Some(new_size.to_string()),
+                ));
+            }
+        },
+        (Schema::Logical(old), Schema::Logical(new)) if old.name() == new.name() => {
+            diff_schema(path, old.schema(), new.schema(), changes)
+        },
+        _ => changes.push(change(path.to_owned(), json(old), json(new))),
+    }
+}
+
+fn diff_name(path: &str, old: &str, new: &str, changes: &mut Vec<SchemaChange>) {
+    if old != new {
+        changes.push(change(
+            format!("{}.name", path),
+            Some(format!("{:?}", old)),
+            Some(format!("{:?}", new)),
+        ));
+    }
+}
+
+fn diff_fields(
+    path: &str,
+    old: &[RecordField],
+    new: &[RecordField],
+    changes: &mut Vec<SchemaChange>,
+) {
+    for field in old {
+        let path = format!("{}.{}", path, field.name);
+        let other = match new.iter().find(|other| other.name == field.name) {
+            Some(other) => other,
+            None => {
+                changes.push(change(path, json(&field.schema), None));
+                continue
+            },
+        };
+        diff_schema(&path, &field.schema, &other.schema, changes);
+        if field.default != other.default {
+            changes.push(change(
+                format!("{}.default", path),
+                field.default.as_ref().map(JsonValue::to_string),
+                other.default.as_ref().map(JsonValue::to_string),
+            ));
+        }
+    }
+    for field in new {
+        if !old.iter().any(|other| other.name == field.name) {
+            changes.push(change(
+                format!("{}.{}", path, field.name),
+                None,
+                json(&field.schema),
+            ));
+        }
+    }
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, changes: &mut Vec<ValueChange>) {
+    let equal = match (old, new) {
+        (Value::Union(old), _) | (Value::UnionBranch(_, old), _) => {
+            return diff_value(path, old, new, changes)
+        },
+        (_, Value::Union(new)) | (_, Value::UnionBranch(_, new)) => {
+            return diff_value(path, old, new, changes)
+        },
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+        (Value::Int(a), Value::Long(b)) | (Value::Long(b), Value::Int(a)) => i64::from(*a) == *b,
+        (Value::Enum(_, a), Value::Enum(_, b)) => a == b,
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (a, b)) in old.iter().zip(new.iter()).enumerate() {
+                diff_value(&format!("{}[{}]", path, i), a, b, changes);
+            }
+            for (i, a) in old.iter().enumerate().skip(new.len()) {
+                changes.push(removed(format!("{}[{}]", path, i), a));
+            }
+            for (i, b) in new.iter().enumerate().skip(old.len()) {
+                changes.push(added(format!("{}[{}]", path, i), b));
+            }
+            return
+        },
+        (Value::Map(old), Value::Map(new)) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = format!("{}[{:?}]", path, &**key);
+                match (old.get(key), new.get(key)) {
+                    (Some(a), Some(b)) => diff_value(&path, a, b, changes),
+                    (Some(a), None) => changes.push(removed(path, a)),
+                    (None, Some(b)) => changes.push(added(path, b)),
+                    (None, None) => unreachable!(),
+                }
+            }
+            return
+        },
+        (Value::Record(old), Value::Record(new)) => {
+            let new_fields = new
+                .iter()
+                .map(|(name, value)| (name.as_str(), value))
+                .collect::<HashMap<_, _>>();
+            for (name, a) in old {
+                let path = format!("{}.{}", path, name);
+                match new_fields.get(name.as_str()) {
+                    Some(b) => diff_value(&path, a, b, changes),
+                    None => changes.push(removed(path, a)),
+                }
+            }
+            for (name, b) in new {
+                if !old.iter().any(|(other, _)| other == name) {
+                    changes.push(added(format!("{}.{}", path, name), b));
+                }
+            }
+            return
+        },
+        _ => old == new,
+    };
+    if !equal {
+        changes.push(ValueChange {
+            path: path.to_owned(),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        });
+    }
+}
+
+fn added(path: String, value: &Value) -> ValueChange {
+    ValueChange {
+        path,
+        old: None,
+        new: Some(value.clone()),
+    }
+}
+
+fn removed(path: String, value: &Value) -> ValueChange {
+    ValueChange {
+        path,
+        old: Some(value.clone()),
+        new: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static OLD: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {"name": "a", "type": "long", "default": 0},
+                {"name": "b", "type": ["null", "string"]},
+                {"name": "c", "type": {"type": "enum", "name": "suit", "symbols": ["H", "S"]}},
+                {"name": "d", "type": "int"}
+            ]
+        }
+    "#;
+
+    static NEW: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {"name": "a", "type": "long", "default": 1},
+                {"name": "b", "type": ["null", "string", "long"]},
+                {"name": "c", "type": {"type": "enum", "name": "suit", "symbols": ["H", "C"]}},
+                {"name": "e", "type": {"type": "array", "items": "int"}}
+            ]
+        }
+    "#;
+
+    fn change(path: &str, old: Option<&str>, new: Option<&str>) -> SchemaChange {
+        SchemaChange {
+            path: path.to_owned(),
+            old: old.map(str::to_owned),
+            new: new.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_diff_schemas() {
+        let old = Schema::parse_str(OLD).unwrap();
+        let new = Schema::parse_str(NEW).unwrap();
+        assert_eq!(diff_schemas(&old, &old), vec![]);
+        assert_eq!(
+            diff_schemas(&old, &new),
+            vec![
+                change("$.a.default", Some("0"), Some("1")),
+                change("$.b[long]", None, Some(r#""long""#)),
+                change("$.c.symbols", Some(r#""S""#), None),
+                change("$.c.symbols", None, Some(r#""C""#)),
+                change("$.d", Some(r#""int""#), None),
+                change("$.e", None, Some(r#"{"type":"array","items":"int"}"#)),
+            ]
+        );
+        assert_eq!(
+            diff_schemas(&Schema::Int, &Schema::Long),
+            vec![change("$", Some(r#""int""#), Some(r#""long""#))]
+        );
+    }
+
+    #[test]
+    fn test_diff_values() {
+        let record = |a: Value, b: Vec<Value>| {
+            Value::Record(vec![("a".to_owned(), a), ("b".to_owned(), Value::Array(b))])
+        };
+        let old = record(Value::Int(1), vec![Value::Null, Value::Double(1.0)]);
+        assert_eq!(diff_values(&old, &old), vec![]);
+        let promoted = record(
+            Value::Union(Box::new(Value::Long(1))),
+            vec![Value::Null, Value::Double(1.0)],
+        );
+        assert_eq!(diff_values(&old, &promoted), vec![]);
+
+        let new = record(Value::Int(2), vec![Value::Null, Value::Double(1.5), Value::Null]);
+        assert_eq!(
+            diff_values(&old, &new),
+            vec![
+                ValueChange {
+                    path: "$.a".to_owned(),
+                    old: Some(Value::Int(1)),
+                    new: Some(Value::Int(2)),
+                },
+                ValueChange {
+                    path: "$.b[1]".to_owned(),
+                    old: Some(Value::Double(1.0)),
+                    new: Some(Value::Double(1.5)),
+                },
+                ValueChange {
+                    path: "$.b[2]".to_owned(),
+                    old: None,
+                    new: Some(Value::Null),
+                },
+            ]
+        );
+    }
+}
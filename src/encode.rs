@@ -1,8 +1,12 @@
+use std::io::{self, Write};
 use std::mem::transmute;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 use schema::Schema;
 use types::Value;
 use util::{zig_i32, zig_i64};
+use varint::{encode_variable, zigzag, MAX_VARINT_LEN};
 
 /// Encode a `Value` into avro format.
 ///
@@ -27,12 +31,62 @@ fn encode_int(i: i32, buffer: &mut Vec<u8>) {
     zig_i32(i, buffer)
 }
 
+// Encode the items of an array, with tight loops for runs of numeric values.
+fn encode_items(items: &[Value], schema: &Schema, buffer: &mut Vec<u8>) {
+    match *schema {
+        Schema::Int => {
+            buffer.reserve(items.len());
+            for item in items {
+                match *item {
+                    Value::Int(i) => encode_int(i, buffer),
+                    ref item => encode_ref(item, schema, buffer),
+                }
+            }
+        },
+        Schema::Long => {
+            buffer.reserve(items.len());
+            for item in items {
+                match *item {
+                    Value::Long(i) => encode_long(i, buffer),
+                    ref item => encode_ref(item, schema, buffer),
+                }
+            }
+        },
+        Schema::Float => {
+            buffer.reserve(items.len() * 4);
+            for item in items {
+                match *item {
+                    Value::Float(x) => buffer.extend_from_slice(&x.to_le_bytes()),
+                    ref item => encode_ref(item, schema, buffer),
+                }
+            }
+        },
+        Schema::Double => {
+            buffer.reserve(items.len() * 8);
+            for item in items {
+                match *item {
+                    Value::Double(x) => buffer.extend_from_slice(&x.to_le_bytes()),
+                    ref item => encode_ref(item, schema, buffer),
+                }
+            }
+        },
+        _ => {
+            for item in items {
+                encode_ref(item, schema, buffer);
+            }
+        },
+    }
+}
+
 /// Encode a `Value` into avro format.
 ///
 /// **NOTE** This will not perform schema validation. The value is assumed to
 /// be valid with regards to the schema. Schema are needed only to guide the
 /// encoding for complex type values.
 pub fn encode_ref(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
+    if let Schema::Logical(ref logical) = *schema {
+        return encode_ref(value, logical.schema(), buffer)
+    }
     match value {
         Value::Null => (),
         Value::Boolean(b) => buffer.push(if *b { 1u8 } else { 0u8 }),
@@ -54,12 +108,12 @@ pub fn encode_ref(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
         },
         Value::Fixed(_, bytes) => buffer.extend(bytes),
         Value::Enum(i, _) => encode_int(*i, buffer),
-        Value::Union(item) => {
+        Value::Union(_) | Value::UnionBranch(..) => {
             if let Schema::Union(ref inner) = *schema {
                 // Find the schema that is matched here. Due to validation, this should always
                 // return a value.
-                let (idx, inner_schema) = inner
-                    .find_schema(item)
+                let (idx, inner_schema, item) = inner
+                    .find_branch(value)
                     .expect("Invalid Union validation occurred");
                 encode_long(idx as i64, buffer);
                 encode_ref(&*item, inner_schema, buffer);
@@ -69,9 +123,7 @@ pub fn encode_ref(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
             if let Schema::Array(ref inner) = *schema {
                 if items.len() > 0 {
                     encode_long(items.len() as i64, buffer);
-                    for item in items.iter() {
-                        encode_ref(item, inner, buffer);
-                    }
+                    encode_items(items, inner, buffer);
                 }
                 buffer.push(0u8);
             }
@@ -81,7 +133,7 @@ pub fn encode_ref(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
                 if items.len() > 0 {
                     encode_long(items.len() as i64, buffer);
                     for (key, value) in items {
-                        encode_bytes(key, buffer);
+                        encode_bytes(key.as_bytes(), buffer);
                         encode_ref(value, inner, buffer);
                     }
                 }
@@ -102,16 +154,225 @@ pub fn encode_ref(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
     }
 }
 
-pub fn encode_to_vec(value: &Value, schema: &Schema) -> Vec<u8> {
-    let mut buffer = Vec::new();
-    encode(&value, schema, &mut buffer);
-    buffer
+/// Encode a `Value` into avro format, writing the blocks of arrays and maps with their size in
+/// bytes: a negative item count followed by the size, so that readers can skip them in one step.
+///
+/// **NOTE** This will not perform schema validation. The value is assumed to
+/// be valid with regards to the schema. Schema are needed only to guide the
+/// encoding for complex type values.
+pub fn encode_sized(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
+    encode_with(value, schema, true, false, buffer)
+}
+
+/// Encode a `Value` into avro format, writing the entries of maps in the order of their keys so
+/// that equal values always yield the same bytes.
+///
+/// **NOTE** This will not perform schema validation. The value is assumed to
+/// be valid with regards to the schema. Schema are needed only to guide the
+/// encoding for complex type values.
+pub fn encode_sorted(value: &Value, schema: &Schema, buffer: &mut Vec<u8>) {
+    encode_with(value, schema, false, true, buffer)
+}
+
+/// Encode a `Value` into avro format, writing the blocks of arrays and maps with their size in
+/// bytes if `sized`, and the entries of maps in the order of their keys if `sorted`.
+pub(crate) fn encode_with(
+    value: &Value,
+    schema: &Schema,
+    sized: bool,
+    sorted: bool,
+    buffer: &mut Vec<u8>,
+) {
+    match (value, schema) {
+        (_, Schema::Logical(logical)) => {
+            encode_with(value, logical.schema(), sized, sorted, buffer)
+        },
+        (Value::Union(_), Schema::Union(inner))
+        | (Value::UnionBranch(..), Schema::Union(inner)) => {
+            let (idx, inner_schema, item) = inner
+                .find_branch(value)
+                .expect("Invalid Union validation occurred");
+            encode_long(idx as i64, buffer);
+            encode_with(item, inner_schema, sized, sorted, buffer);
+        },
+        (Value::Array(items), Schema::Array(inner)) => {
+            if !items.is_empty() {
+                if !sized {
+                    encode_long(items.len() as i64, buffer);
+                }
+                let start = buffer.len();
+                match **inner {
+                    Schema::Array(_)
+                    | Schema::Map(_)
+                    | Schema::Union(_)
+                    | Schema::Record { .. }
+                    | Schema::Logical(_) => {
+                        for item in items {
+                            encode_with(item, inner, sized, sorted, buffer);
+                        }
+                    },
+                    _ => encode_items(items, inner, buffer),
+                }
+                if sized {
+                    insert_block_header(items.len(), start, buffer);
+                }
+            }
+            buffer.push(0u8);
+        },
+        (Value::Map(items), Schema::Map(inner)) => {
+            if !items.is_empty() {
+                if !sized {
+                    encode_long(items.len() as i64, buffer);
+                }
+                let start = buffer.len();
+                let mut encode_entry = |key: &str, value| {
+                    encode_bytes(key.as_bytes(), buffer);
+                    encode_with(value, inner, sized, sorted, buffer);
+                };
+                if sorted {
+                    let mut entries = items.iter().collect::<Vec<_>>();
+                    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                    for (key, value) in entries {
+                        encode_entry(key, value);
+                    }
+                } else {
+                    for (key, value) in items {
+                        encode_entry(key, value);
+                    }
+                }
+                if sized {
+                    insert_block_header(items.len(), start, buffer);
+                }
+            }
+            buffer.push(0u8);
+        },
+        (Value::Record(fields), Schema::Record { fields: schema_fields, .. }) => {
+            for (i, (_, value)) in fields.iter().enumerate() {
+                encode_with(value, &schema_fields[i].schema, sized, sorted, buffer);
+            }
+        },
+        _ => encode_ref(value, schema, buffer),
+    }
+}
+
+// Insert the header of a block of `count` items, encoded from `start` to the end of `buffer`.
+fn insert_block_header(count: usize, start: usize, buffer: &mut Vec<u8>) {
+    let (mut count_bytes, mut size_bytes) = ([0u8; MAX_VARINT_LEN], [0u8; MAX_VARINT_LEN]);
+    let count_len = encode_variable(zigzag(-(count as i64)), &mut count_bytes);
+    let size_len = encode_variable(zigzag((buffer.len() - start) as i64), &mut size_bytes);
+    let header = count_bytes[..count_len].iter().chain(&size_bytes[..size_len]);
+    buffer.splice(start..start, header.cloned());
+}
+
+// Size above which buffered bytes are written out, and bytes are written without being buffered.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Encode a `Value` into avro format, writing it to `writer` as it goes.
+///
+/// Only up to a few kilobytes are buffered at any time: large bytes, strings and fixed values
+/// are written directly, without being copied.
+///
+/// **NOTE** This will not perform schema validation. The value is assumed to
+/// be valid with regards to the schema. Schema are needed only to guide the
+/// encoding for complex type values.
+pub fn encode_to<W: Write>(value: &Value, schema: &Schema, writer: &mut W) -> io::Result<()> {
+    let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+    encode_chunked(value, schema, &mut buffer, writer)?;
+    writer.write_all(&buffer)
+}
+
+fn encode_chunked<W: Write>(
+    value: &Value,
+    schema: &Schema,
+    buffer: &mut Vec<u8>,
+    writer: &mut W,
+) -> io::Result<()> {
+    match (value, schema) {
+        (_, Schema::Logical(logical)) => {
+            return encode_chunked(value, logical.schema(), buffer, writer)
+        },
+        (Value::Bytes(bytes), _) => write_bytes(bytes, true, buffer, writer)?,
+        (Value::String(s), Schema::String) => write_bytes(s.as_bytes(), true, buffer, writer)?,
+        (Value::Fixed(_, bytes), _) => write_bytes(bytes, false, buffer, writer)?,
+        (Value::Union(_), Schema::Union(inner))
+        | (Value::UnionBranch(..), Schema::Union(inner)) => {
+            let (idx, inner_schema, item) = inner
+                .find_branch(value)
+                .expect("Invalid Union validation occurred");
+            encode_long(idx as i64, buffer);
+            encode_chunked(item, inner_schema, buffer, writer)?;
+        },
+        (Value::Array(items), Schema::Array(inner)) => {
+            if !items.is_empty() {
+                encode_long(items.len() as i64, buffer);
+                match **inner {
+                    Schema::Int | Schema::Long | Schema::Float | Schema::Double => {
+                        for chunk in items.chunks(CHUNK_SIZE / 8) {
+                            encode_items(chunk, inner, buffer);
+                            flush_full(buffer, writer)?;
+                        }
+                    },
+                    _ => {
+                        for item in items {
+                            encode_chunked(item, inner, buffer, writer)?;
+                        }
+                    },
+                }
+            }
+            buffer.push(0u8);
+        },
+        (Value::Map(items), Schema::Map(inner)) => {
+            if !items.is_empty() {
+                encode_long(items.len() as i64, buffer);
+                for (key, value) in items {
+                    write_bytes(key.as_bytes(), true, buffer, writer)?;
+                    encode_chunked(value, inner, buffer, writer)?;
+                }
+            }
+            buffer.push(0u8);
+        },
+        (Value::Record(fields), Schema::Record { fields: schema_fields, .. }) => {
+            for (i, (_, value)) in fields.iter().enumerate() {
+                encode_chunked(value, &schema_fields[i].schema, buffer, writer)?;
+            }
+        },
+        _ => encode_ref(value, schema, buffer),
+    }
+    flush_full(buffer, writer)
+}
+
+// Write (length-prefixed if `prefixed`) bytes, directly to `writer` if they are large.
+fn write_bytes<W: Write>(
+    bytes: &[u8],
+    prefixed: bool,
+    buffer: &mut Vec<u8>,
+    writer: &mut W,
+) -> io::Result<()> {
+    if prefixed {
+        encode_long(bytes.len() as i64, buffer);
+    }
+    if bytes.len() < CHUNK_SIZE {
+        buffer.extend_from_slice(bytes);
+        return Ok(())
+    }
+    writer.write_all(buffer)?;
+    buffer.clear();
+    writer.write_all(bytes)
+}
+
+fn flush_full<W: Write>(buffer: &mut Vec<u8>, writer: &mut W) -> io::Result<()> {
+    if buffer.len() >= CHUNK_SIZE {
+        writer.write_all(buffer)?;
+        buffer.clear();
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::collections::HashMap;
+    use std::sync::Arc;
 
     #[test]
     fn test_encode_empty_array() {
@@ -125,10 +386,33 @@ mod tests {
         assert_eq!(vec![0u8], buf);
     }
 
+    #[test]
+    fn test_encode_numeric_arrays() {
+        let arrays = vec![
+            (Schema::Int, vec![Value::Int(0), Value::Int(-1), Value::Int(1 << 20)]),
+            (Schema::Long, vec![Value::Long(27), Value::Long(i64::min_value())]),
+            (Schema::Float, vec![Value::Float(1.5), Value::Float(-0.25)]),
+            (Schema::Double, vec![Value::Double(3.0), Value::Double(1e300)]),
+        ];
+
+        for (schema, items) in arrays {
+            let mut expected = Vec::new();
+            encode(&Value::Long(items.len() as i64), &Schema::Long, &mut expected);
+            for item in &items {
+                encode(item, &schema, &mut expected);
+            }
+            expected.push(0u8);
+
+            let mut buf = Vec::new();
+            encode(&Value::Array(items), &Schema::Array(Box::new(schema)), &mut buf);
+            assert_eq!(expected, buf);
+        }
+    }
+
     #[test]
     fn test_encode_empty_map() {
         let mut buf = Vec::new();
-        let empty: HashMap<String, Value> = HashMap::new();
+        let empty: HashMap<Arc<str>, Value> = HashMap::new();
         encode(
             &Value::Map(empty),
             &Schema::Map(Box::new(Schema::Int)),
@@ -136,4 +420,97 @@ mod tests {
         );
         assert_eq!(vec![0u8], buf);
     }
+
+    #[test]
+    fn test_encode_sized() {
+        use decode::skip;
+        use util::zig_i64;
+
+        let schema = Schema::Array(Box::new(Schema::Map(Box::new(Schema::Long))));
+        let mut map = HashMap::new();
+        map.insert(Arc::from("a"), Value::Long(1));
+        let value = Value::Array(vec![Value::Map(map), Value::Map(HashMap::new())]);
+
+        let mut buf = Vec::new();
+        encode_sized(&value, &schema, &mut buf);
+        let mut expected = Vec::new();
+        // A block of 2 maps, 7 bytes long: a block of 1 entry, 3 bytes long, then an empty map.
+        zig_i64(-2, &mut expected);
+        zig_i64(7, &mut expected);
+        zig_i64(-1, &mut expected);
+        zig_i64(3, &mut expected);
+        expected.extend_from_slice(&[2, b'a', 2, 0, 0, 0]);
+        assert_eq!(buf, expected);
+
+        buf.push(42);
+        let mut slice = &buf[..];
+        skip(&schema, &mut slice).unwrap();
+        assert_eq!(slice, &[42]);
+
+        // Empty collections are written the same way.
+        let mut buf = Vec::new();
+        encode_sized(&Value::Array(vec![]), &schema, &mut buf);
+        assert_eq!(buf, vec![0u8]);
+    }
+
+    #[test]
+    fn test_encode_to() {
+        use schema::Name;
+        use std::iter::repeat;
+
+        // Records bytes written in several calls.
+        struct Writes(Vec<Vec<u8>>);
+
+        impl Write for Writes {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push(buf.to_vec());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": ["null", "bytes"]},
+                    {"name": "b", "type": {"type": "array", "items": "long"}},
+                    {"name": "c", "type": {"type": "map", "values": "string"}},
+                    {"name": "d", "type": {"type": "fixed", "name": "f", "size": 2}}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let blob = repeat(7u8).take(3 * CHUNK_SIZE).collect::<Vec<_>>();
+        let mut map = HashMap::new();
+        map.insert(Arc::from("key"), Value::String("value".to_owned()));
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Union(Box::new(Value::Bytes(blob.clone())))),
+            ("b".to_owned(), Value::Array((0..CHUNK_SIZE as i64).map(Value::Long).collect())),
+            ("c".to_owned(), Value::Map(map)),
+            ("d".to_owned(), Value::Fixed(2, vec![1, 2])),
+        ]);
+
+        let mut expected = Vec::new();
+        encode(&value, &schema, &mut expected);
+        let mut writes = Writes(Vec::new());
+        encode_to(&value, &schema, &mut writes).unwrap();
+        assert_eq!(writes.0.concat(), expected);
+        // The blob is written as is, and nothing else is larger than the chunk size.
+        assert!(writes.0.iter().any(|write| *write == blob));
+        assert!(writes.0.iter().all(|write| *write == blob || write.len() < 2 * CHUNK_SIZE));
+
+        let mut buffer = Vec::new();
+        let fixed = Schema::Fixed {
+            name: Name::new("f"),
+            size: 2,
+        };
+        encode_to(&Value::Fixed(2, vec![1, 2]), &fixed, &mut buffer).unwrap();
+        assert_eq!(buffer, vec![1, 2]);
+    }
 }
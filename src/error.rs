@@ -0,0 +1,315 @@
+//! Logic for the error type returned by the public API of this crate.
+use std::fmt;
+use std::io;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[cfg(feature = "arrow")]
+use arrow_schema::ArrowError;
+#[cfg(feature = "serde_layer")]
+use de;
+#[cfg(feature = "rpc")]
+use rpc::RemoteError;
+#[cfg(feature = "serde_layer")]
+use ser;
+use serde_json;
+
+/// Describes any error happened while working with Avro data.
+///
+/// Callers can match on the kind of error instead of its description. More variants may be added
+/// in later versions.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A schema could not be parsed.
+    #[error("Failed to parse schema: {reason}")]
+    SchemaParse { reason: String },
+    /// A schema is nested deeper than the `limit` of its
+    /// [`ParseOptions`](schema/struct.ParseOptions.html).
+    #[error("Failed to parse schema: nesting deeper than {limit} levels")]
+    SchemaDepth { limit: usize },
+    /// A schema defines more named types than the `limit` of its
+    /// [`ParseOptions`](schema/struct.ParseOptions.html).
+    #[error("Failed to parse schema: more than {limit} named types")]
+    SchemaNamedTypes { limit: usize },
+    /// A named type is defined twice with the fullname `name`: at the `first` and `second`
+    /// locations (e.g. `$.fields[0].type`) of the schemas being parsed.
+    #[error("Failed to parse schema: {name} is defined at both {first} and {second}")]
+    SchemaRedefinition {
+        name: String,
+        first: String,
+        second: String,
+    },
+    /// A value does not match its schema: at `path` (e.g. `$.field[0]`), a value of kind `found`
+    /// was given where the schema expects `expected`.
+    #[error("Value does not match schema at {path}: expected {expected}, found {found}")]
+    SchemaMismatch {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    /// A value of the logical type `name` is invalid, or could not be converted.
+    #[error("Invalid value of logical type {name}: {reason}")]
+    LogicalType { name: String, reason: String },
+    /// Avro data could not be resolved against a reader schema.
+    #[error("Decoding error: {reason}")]
+    SchemaResolution { reason: String },
+    /// Decoding would have allocated `requested` bytes, more than the `limit` configured with
+    /// [`max_allocation_bytes`](fn.max_allocation_bytes.html).
+    #[error("Allocation error: Unable to allocate {requested} bytes (Maximum allowed: {limit})")]
+    Allocation { requested: usize, limit: usize },
+    /// Decoding would have nested arrays, maps and records deeper than the `limit` configured
+    /// with [`max_decoding_depth`](fn.max_decoding_depth.html).
+    #[error("Decoding error: nesting deeper than {limit} levels")]
+    Depth { limit: usize },
+    /// Avro data could not be decoded.
+    #[error("Decoding error: {reason}")]
+    Decode { reason: String },
+    /// A string decoded at `path` (e.g. `$.field[0]`) is not valid UTF-8 past its first
+    /// `valid_up_to` bytes. The raw `bytes` of the string are kept, so that they can be recovered.
+    #[error(
+        "Decoding error: invalid utf-8 string at {path}, byte {valid_up_to} of {}: {}",
+        .bytes.len(),
+        hexdump(.bytes, *.valid_up_to)
+    )]
+    InvalidUtf8 {
+        path: String,
+        valid_up_to: usize,
+        bytes: Vec<u8>,
+    },
+    /// A datum was decoded from the first `size` bytes of its input, leaving `remaining` bytes
+    /// over, e.g. as it was written with another schema.
+    #[error("Decoding error: {remaining} bytes left over after a datum of {size} bytes")]
+    TrailingBytes { size: usize, remaining: usize },
+    /// Decoding failed `offset` bytes from the start of the input, within the `block`-th data block
+    /// (counting from 0) when reading an object container file.
+    ///
+    /// Within compressed blocks, `offset` is the one of the block, as positions within the
+    /// decompressed data do not map to the input.
+    ///
+    /// Match on [`inner`](#method.inner) to tell the kind of the `error` itself, wrapped or not.
+    #[error("{error} (at byte {offset}{})", block_suffix(.block))]
+    AtOffset {
+        offset: u64,
+        block: Option<u64>,
+        error: Box<Error>,
+    },
+    /// A value could not be serialized with serde.
+    #[cfg(feature = "serde_layer")]
+    #[error(transparent)]
+    Ser(#[from] ser::Error),
+    /// A value could not be deserialized with serde.
+    #[cfg(feature = "serde_layer")]
+    #[error(transparent)]
+    De(#[from] de::Error),
+    /// Avro data could not be converted to Arrow data.
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+    /// The schema registry returned an error.
+    #[cfg(feature = "http")]
+    #[error("Schema registry error: {reason}")]
+    Registry { reason: String },
+    /// A remote procedure call failed.
+    #[cfg(feature = "rpc")]
+    #[error("RPC error: {reason}")]
+    Rpc { reason: String },
+    /// The remote end of a call returned an error.
+    #[cfg(feature = "rpc")]
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+    /// An I/O error happened while reading or writing.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// JSON could not be parsed or written.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// Text could not be formatted.
+    #[error(transparent)]
+    Fmt(#[from] fmt::Error),
+    /// Any other error, described by a string (e.g. returned by an RPC handler).
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl Error {
+    /// Create an `Error::Custom` given a description of the error.
+    pub fn custom<S>(msg: S) -> Error
+    where
+        S: Into<String>,
+    {
+        Error::Custom(msg.into())
+    }
+
+    /// The error itself, without the position of an `Error::AtOffset`, so that its kind can be
+    /// matched on whether it happened decoding a file or not:
+    ///
+    /// ```
+    /// # use avro_rs::{from_avro_datum, Error, Schema};
+    /// let schema = Schema::parse_str(r#""boolean""#).unwrap();
+    /// let error = from_avro_datum(&schema, &mut &[2u8][..], None).unwrap_err();
+    /// match *error.inner() {
+    ///     Error::Decode { ref reason } => assert!(reason.contains("bool")),
+    ///     ref other => panic!("unexpected error {}", other),
+    /// }
+    /// ```
+    pub fn inner(&self) -> &Error {
+        match *self {
+            Error::AtOffset { ref error, .. } => error,
+            _ => self,
+        }
+    }
+
+    /// Like [`inner`](#method.inner), taking ownership of the error.
+    pub fn into_inner(self) -> Error {
+        match self {
+            Error::AtOffset { error, .. } => *error,
+            other => other,
+        }
+    }
+
+    /// The raw bytes of the string which could not be decoded, if this is an
+    /// `Error::InvalidUtf8`, possibly attached to a position.
+    pub fn invalid_utf8_bytes(&self) -> Option<&[u8]> {
+        match *self.inner() {
+            Error::InvalidUtf8 { ref bytes, .. } => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Prepend `segment` (e.g. `.field` or `[0]`) to the path of an `Error::InvalidUtf8`, while
+    /// unwinding from the value it was decoded in.
+    pub(crate) fn within<F: FnOnce() -> String>(self, segment: F) -> Error {
+        match self {
+            Error::InvalidUtf8 {
+                path,
+                valid_up_to,
+                bytes,
+            } => Error::InvalidUtf8 {
+                path: format!("${}{}", segment(), &path[1..]),
+                valid_up_to,
+                bytes,
+            },
+            other => other,
+        }
+    }
+
+    /// Attach the position decoding failed at to the error, unless it already has one.
+    pub(crate) fn at(self, offset: u64, block: Option<u64>) -> Error {
+        match self {
+            Error::AtOffset { .. } => self,
+            error => Error::AtOffset {
+                offset,
+                block,
+                error: Box::new(error),
+            },
+        }
+    }
+}
+
+// So that `Error` can be the error of the serde formats of this crate.
+#[cfg(feature = "serde_layer")]
+impl ::serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Ser(::serde::ser::Error::custom(msg))
+    }
+}
+
+#[cfg(feature = "serde_layer")]
+impl ::serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::De(::serde::de::Error::custom(msg))
+    }
+}
+
+// Bytes shown before and after the first invalid byte of a string.
+const HEXDUMP_CONTEXT: usize = 8;
+
+// Dump the bytes around `at` in hexadecimal, e.g. `... 66 6f [ff] 6f`.
+fn hexdump(bytes: &[u8], at: usize) -> String {
+    let start = at.saturating_sub(HEXDUMP_CONTEXT);
+    let end = bytes.len().min(at + HEXDUMP_CONTEXT + 1);
+    let mut dump = Vec::new();
+    if start > 0 {
+        dump.push("...".to_owned());
+    }
+    for (i, byte) in bytes.iter().enumerate().take(end).skip(start) {
+        if i == at {
+            dump.push(format!("[{:02x}]", byte));
+        } else {
+            dump.push(format!("{:02x}", byte));
+        }
+    }
+    if end < bytes.len() {
+        dump.push("...".to_owned());
+    }
+    dump.join(" ")
+}
+
+fn block_suffix(block: &Option<u64>) -> String {
+    match *block {
+        Some(block) => format!(", block {}", block),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::DecodeError;
+
+    #[test]
+    fn test_error_display_and_source() {
+        let error: Error = DecodeError::new("not a bool").into();
+        assert_eq!(error.to_string(), "Decoding error: not a bool");
+        match error {
+            Error::Decode { ref reason } => assert_eq!(reason, "not a bool"),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+
+        let error = Error::Allocation {
+            requested: 1024,
+            limit: 512,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Allocation error: Unable to allocate 1024 bytes (Maximum allowed: 512)"
+        );
+
+        let error: Error = io::Error::new(io::ErrorKind::UnexpectedEof, "eof").into();
+        assert_eq!(error.to_string(), "eof");
+        match error {
+            Error::Io(ref e) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+
+        assert_eq!(Error::custom("boom").to_string(), "boom");
+
+        let error = Error::custom("boom").at(3, None).at(4, Some(1));
+        assert_eq!(error.to_string(), "boom (at byte 3)");
+        let error = Error::custom("boom").at(20, Some(1));
+        assert_eq!(error.to_string(), "boom (at byte 20, block 1)");
+        // The wrapped error is not also its source, as its message is already shown.
+        assert!(::std::error::Error::source(&error).is_none());
+        match *error.inner() {
+            Error::Custom(ref reason) => assert_eq!(reason, "boom"),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+        match error.into_inner() {
+            Error::Custom(reason) => assert_eq!(reason, "boom"),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(Error::custom("boom").inner().to_string(), "boom");
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(hexdump(b"\xff", 0), "[ff]");
+        let bytes = (0..32).collect::<Vec<u8>>();
+        assert_eq!(
+            hexdump(&bytes, 10),
+            "... 02 03 04 05 06 07 08 09 [0a] 0b 0c 0d 0e 0f 10 11 12 ..."
+        );
+        assert_eq!(hexdump(&bytes[..12], 2), "00 01 [02] 03 04 05 06 07 08 09 0a ...");
+    }
+}
@@ -0,0 +1,303 @@
+//! Logic for decoding a datum as a stream of fine-grained events, without building a `Value`
+//! tree, so that huge datums can be filtered or searched with constant memory.
+use std::io::Read;
+
+use decode::{decode, decode_block_len};
+use error::Error;
+use logical::to_logical;
+use schema::{Name, RecordField, Schema};
+use types::Value;
+use util::{safe_depth, zag_i32, ByteReader, CountingReader, DecodeError};
+
+/// What an [`EventReader`](struct.EventReader.html) decoded next.
+///
+/// The events of records, arrays and maps nest: every start event is eventually followed by the
+/// matching end event.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    /// The start of a record, with its name.
+    RecordStart(&'a Name),
+    /// A field of the current record, whose value follows.
+    Field(&'a str),
+    /// The end of the current record.
+    RecordEnd,
+    /// The start of an array.
+    ArrayStart,
+    /// An item of the current array, whose value follows.
+    Item,
+    /// The end of the current array.
+    ArrayEnd,
+    /// The start of a map.
+    MapStart,
+    /// A key of the current map, whose value follows.
+    Key(String),
+    /// The end of the current map.
+    MapEnd,
+    /// The index of the branch of a union, whose value follows.
+    Union(usize),
+    /// A value of any other type than records, arrays, maps and unions.
+    Value(Value),
+}
+
+enum Frame<'a> {
+    Record(&'a [RecordField], usize),
+    Array(&'a Schema, usize),
+    Map(&'a Schema, usize),
+}
+
+/// Pull-style decoder of one datum encoded in Avro format, emitting its events one at a time
+/// from anything implementing `io::Read`.
+///
+/// Only the values of primitive, enum and fixed types are ever held in memory; the values
+/// following `Field`, `Item`, `Key` or `Union` events can also be skipped without decoding them
+/// with [`skip_value`](#method.skip_value).
+pub struct EventReader<'a, R> {
+    reader: ByteReader<CountingReader<R>>,
+    // Schema of the value to decode next, if any.
+    next: Option<&'a Schema>,
+    stack: Vec<Frame<'a>>,
+}
+
+impl<'a, R: Read> EventReader<'a, R> {
+    /// Create an `EventReader` decoding a datum of `writer_schema` from `reader`.
+    pub fn new(writer_schema: &'a Schema, reader: R) -> EventReader<'a, R> {
+        EventReader {
+            reader: ByteReader(CountingReader::new(reader)),
+            next: Some(writer_schema),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Decode the next event, or `None` once the whole datum has been decoded.
+    pub fn next_event(&mut self) -> Result<Option<Event<'a>>, Error> {
+        match self.step() {
+            Ok(event) => Ok(event),
+            Err(e) => {
+                // Nothing sensible can be decoded after an error.
+                self.next = None;
+                self.stack.clear();
+                Err(e.at(self.reader.0.count(), None))
+            },
+        }
+    }
+
+    /// Skip the value announced by the last `Field`, `Item`, `Key` or `Union` event (or the
+    /// whole datum, before any event), without decoding it.
+    pub fn skip_value(&mut self) -> Result<(), Error> {
+        let schema = match self.next.take() {
+            Some(schema) => schema,
+            None => return Err(DecodeError::new("no value to skip").into()),
+        };
+        ::decode::skip(schema, &mut self.reader).map_err(|e| e.at(self.reader.0.count(), None))
+    }
+
+    /// Number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.reader.0.count()
+    }
+
+    /// Get back the underlying reader, positioned after the bytes decoded so far.
+    pub fn into_inner(self) -> R {
+        self.reader.0.into_inner()
+    }
+
+    fn step(&mut self) -> Result<Option<Event<'a>>, Error> {
+        if let Some(schema) = self.next.take() {
+            return self.start(schema).map(Some)
+        }
+        let event = match self.stack.last_mut() {
+            None => return Ok(None),
+            Some(Frame::Record(fields, next)) => match fields.get(*next) {
+                Some(field) => {
+                    *next += 1;
+                    self.next = Some(&field.schema);
+                    return Ok(Some(Event::Field(&field.name)))
+                },
+                None => Event::RecordEnd,
+            },
+            Some(Frame::Array(items, left)) => {
+                if *left == 0 {
+                    *left = decode_block_len(&mut self.reader)?;
+                }
+                if *left > 0 {
+                    *left -= 1;
+                    self.next = Some(items);
+                    return Ok(Some(Event::Item))
+                }
+                Event::ArrayEnd
+            },
+            Some(Frame::Map(values, left)) => {
+                if *left == 0 {
+                    *left = decode_block_len(&mut self.reader)?;
+                }
+                if *left > 0 {
+                    *left -= 1;
+                    self.next = Some(values);
+                    return match decode(&Schema::String, &mut self.reader)? {
+                        Value::String(key) => Ok(Some(Event::Key(key))),
+                        _ => unreachable!(),
+                    }
+                }
+                Event::MapEnd
+            },
+        };
+        self.stack.pop();
+        Ok(Some(event))
+    }
+
+    // Decode the start of a value of `schema`, or the whole value if it does not nest.
+    fn start(&mut self, schema: &'a Schema) -> Result<Event<'a>, Error> {
+        let (frame, event) = match *schema {
+            Schema::Record {
+                ref name,
+                ref fields,
+                ..
+            } => (Frame::Record(fields, 0), Event::RecordStart(name)),
+            Schema::Array(ref items) => (Frame::Array(items, 0), Event::ArrayStart),
+            Schema::Map(ref values) => (Frame::Map(values, 0), Event::MapStart),
+            Schema::Union(ref union) => {
+                let index = zag_i32(&mut self.reader)?;
+                return match union.variants().get(index as usize) {
+                    Some(variant) if index >= 0 => {
+                        self.next = Some(variant);
+                        Ok(Event::Union(index as usize))
+                    },
+                    _ => Err(DecodeError::new("union index out of bounds").into()),
+                }
+            },
+            // Values of logical types nesting others get the events of their underlying type.
+            Schema::Logical(ref logical) => match *logical.schema() {
+                Schema::Record { .. } | Schema::Array(_) | Schema::Map(_) | Schema::Union(_) => {
+                    return self.start(logical.schema())
+                },
+                _ => return self.value(schema),
+            },
+            _ => return self.value(schema),
+        };
+        safe_depth(self.stack.len() + 1)?;
+        self.stack.push(frame);
+        Ok(event)
+    }
+
+    fn value(&mut self, schema: &Schema) -> Result<Event<'a>, Error> {
+        let value = decode(schema, &mut self.reader)?;
+        to_logical(value, schema).map(Event::Value)
+    }
+}
+
+impl<'a, R: Read> Iterator for EventReader<'a, R> {
+    type Item = Result<Event<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datum::to_avro_datum;
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "namespace": "ns",
+            "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "b", "type": {"type": "array", "items": ["null", "string"]}},
+                {"name": "c", "type": {"type": "map", "values": "int"}}
+            ]
+        }
+    "#;
+
+    fn encoded(schema: &Schema) -> Vec<u8> {
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Long(27)),
+            (
+                "b".to_owned(),
+                Value::Array(vec![
+                    Value::Union(Box::new(Value::Null)),
+                    Value::Union(Box::new(Value::String("foo".to_owned()))),
+                ]),
+            ),
+            (
+                "c".to_owned(),
+                Value::Map(vec![("x".into(), Value::Int(1))].into_iter().collect()),
+            ),
+        ]);
+        to_avro_datum(schema, value).unwrap()
+    }
+
+    #[test]
+    fn test_events() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoded = encoded(&schema);
+        let events = EventReader::new(&schema, &encoded[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let name = match schema {
+            Schema::Record { ref name, .. } => name,
+            _ => unreachable!(),
+        };
+        assert_eq!(name.fullname(None), "ns.test");
+        assert_eq!(
+            events,
+            vec![
+                Event::RecordStart(name),
+                Event::Field("a"),
+                Event::Value(Value::Long(27)),
+                Event::Field("b"),
+                Event::ArrayStart,
+                Event::Item,
+                Event::Union(0),
+                Event::Value(Value::Null),
+                Event::Item,
+                Event::Union(1),
+                Event::Value(Value::String("foo".to_owned())),
+                Event::ArrayEnd,
+                Event::Field("c"),
+                Event::MapStart,
+                Event::Key("x".to_owned()),
+                Event::Value(Value::Int(1)),
+                Event::MapEnd,
+                Event::RecordEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_skip_value() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoded = encoded(&schema);
+        let mut reader = EventReader::new(&schema, &encoded[..]);
+        let mut events = Vec::new();
+        while let Some(event) = reader.next_event().unwrap() {
+            if event == Event::Field("b") {
+                reader.skip_value().unwrap();
+            }
+            events.push(event);
+        }
+        assert_eq!(events.len(), 10);
+        assert_eq!(events[3..5], [Event::Field("b"), Event::Field("c")]);
+        assert_eq!(reader.bytes_read(), encoded.len() as u64);
+        assert!(reader.skip_value().is_err());
+    }
+
+    #[test]
+    fn test_events_errors() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoded = encoded(&schema);
+        let mut reader = EventReader::new(&schema, &encoded[..encoded.len() - 2]);
+        let error = reader.by_ref().find(|event| event.is_err()).unwrap().unwrap_err();
+        match error {
+            Error::AtOffset { .. } => {},
+            other => panic!("unexpected error {:?}", other),
+        }
+        // Nothing is decoded after an error.
+        assert!(reader.next().is_none());
+
+        let schema = Schema::parse_str(r#"["null", "long"]"#).unwrap();
+        assert!(EventReader::new(&schema, &[4u8][..]).next().unwrap().is_err());
+    }
+}
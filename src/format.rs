@@ -0,0 +1,517 @@
+//! Logic exposing the Avro binary encoding as a plain serde format, for frameworks embedding Avro
+//! next to other serde formats without going through `Writer` and `Reader`.
+use serde::de::{Deserializer as SerdeDeserializer, Visitor};
+use serde::ser::{
+    Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer as SerdeSerializer,
+};
+
+use datum::{from_avro_datum_strict, to_avro_datum};
+use de::Deserializer as ValueDeserializer;
+use error::Error;
+use schema::Schema;
+use ser::{MapSerializer, SeqSerializer, Serializer as ValueSerializer, StructSerializer};
+use types::Value;
+
+/// A serde `Serializer` for the Avro binary encoding of datums of a schema.
+///
+/// Serializing a value yields its encoding, validated against the schema as
+/// [`Writer::append_ser`](struct.Writer.html#method.append_ser) does:
+///
+/// ```
+/// # extern crate avro_rs;
+/// # extern crate serde;
+/// # #[cfg(feature = "serde_layer")] {
+/// use avro_rs::{Schema, Serializer};
+/// use serde::Serialize;
+///
+/// let schema = Schema::parse_str(r#"{"type": "array", "items": "long"}"#).unwrap();
+/// let encoded = vec![1i64, 2, 3].serialize(&mut Serializer::new(&schema)).unwrap();
+/// assert_eq!(encoded, [6, 2, 4, 6, 0]);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Serializer<'s> {
+    schema: &'s Schema,
+}
+
+impl<'s> Serializer<'s> {
+    /// Creates a `Serializer` of datums of `schema`.
+    pub fn new(schema: &'s Schema) -> Serializer<'s> {
+        Serializer { schema }
+    }
+
+    /// Get a reference to the `Schema` associated to a `Serializer`.
+    pub fn schema(&self) -> &'s Schema {
+        self.schema
+    }
+}
+
+/// The state of a `Serializer` serializing a sequence, map or struct.
+pub struct Compound<'s, S> {
+    schema: &'s Schema,
+    inner: S,
+}
+
+impl<'s, S> Compound<'s, S> {
+    fn new(schema: &'s Schema, inner: Result<S, ::ser::Error>) -> Result<Self, Error> {
+        Ok(Compound {
+            schema,
+            inner: inner?,
+        })
+    }
+}
+
+// Encode the value serialized by the serializer of `Value`s, if any.
+fn encode(schema: &Schema, value: Result<Value, ::ser::Error>) -> Result<Vec<u8>, Error> {
+    to_avro_datum(schema, value?)
+}
+
+impl<'s> SerdeSerializer for &mut Serializer<'s> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+    type SerializeSeq = Compound<'s, SeqSerializer>;
+    type SerializeTuple = Compound<'s, SeqSerializer>;
+    type SerializeTupleStruct = Compound<'s, SeqSerializer>;
+    type SerializeTupleVariant = Compound<'s, SeqSerializer>;
+    type SerializeMap = Compound<'s, MapSerializer>;
+    type SerializeStruct = Compound<'s, StructSerializer>;
+    type SerializeStructVariant = Compound<'s, StructSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_i8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_i16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_i32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_i64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_u8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_u16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_u32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_u64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_u128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_f32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_f64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_str(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_bytes(v))
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_none())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_some(value))
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_unit())
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_unit_struct(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>, Error> {
+        let mut serializer = ValueSerializer::default();
+        encode(self.schema, serializer.serialize_unit_variant(name, index, variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>, Error> {
+        encode(self.schema, ValueSerializer::default().serialize_newtype_struct(name, value))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>, Error> {
+        let mut serializer = ValueSerializer::default();
+        encode(self.schema, serializer.serialize_newtype_variant(name, index, variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Compound::new(self.schema, ValueSerializer::default().serialize_seq(len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        Compound::new(self.schema, ValueSerializer::default().serialize_tuple(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Compound::new(
+            self.schema,
+            ValueSerializer::default().serialize_tuple_struct(name, len),
+        )
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        let mut serializer = ValueSerializer::default();
+        Compound::new(
+            self.schema,
+            serializer.serialize_tuple_variant(name, index, variant, len),
+        )
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Compound::new(self.schema, ValueSerializer::default().serialize_map(len))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Compound::new(self.schema, ValueSerializer::default().serialize_struct(name, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        let mut serializer = ValueSerializer::default();
+        Compound::new(
+            self.schema,
+            serializer.serialize_struct_variant(name, index, variant, len),
+        )
+    }
+}
+
+impl<'s> SerializeSeq for Compound<'s, SeqSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        Ok(SerializeSeq::serialize_element(&mut self.inner, value)?)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, SerializeSeq::end(self.inner))
+    }
+}
+
+impl<'s> SerializeTuple for Compound<'s, SeqSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl<'s> SerializeTupleStruct for Compound<'s, SeqSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl<'s> SerializeTupleVariant for Compound<'s, SeqSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        Ok(SerializeTupleVariant::serialize_field(&mut self.inner, value)?)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, SerializeTupleVariant::end(self.inner))
+    }
+}
+
+impl<'s> SerializeMap for Compound<'s, MapSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        Ok(self.inner.serialize_key(key)?)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        Ok(self.inner.serialize_value(value)?)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, self.inner.end())
+    }
+}
+
+impl<'s> SerializeStruct for Compound<'s, StructSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        Ok(SerializeStruct::serialize_field(&mut self.inner, name, value)?)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, SerializeStruct::end(self.inner))
+    }
+}
+
+impl<'s> SerializeStructVariant for Compound<'s, StructSerializer> {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        Ok(SerializeStructVariant::serialize_field(&mut self.inner, name, value)?)
+    }
+
+    fn end(self) -> Result<Vec<u8>, Error> {
+        encode(self.schema, SerializeStructVariant::end(self.inner))
+    }
+}
+
+/// A serde `Deserializer` of datums in the Avro binary encoding.
+///
+/// The datum is decoded (and resolved against the reader schema, if any) as
+/// [`from_avro_datum`](fn.from_avro_datum.html) does, then deserialized as
+/// [`from_value`](fn.from_value.html) does:
+///
+/// ```
+/// # extern crate avro_rs;
+/// # extern crate serde;
+/// # #[cfg(feature = "serde_layer")] {
+/// use avro_rs::{Deserializer, Schema};
+/// use serde::Deserialize;
+///
+/// let schema = Schema::parse_str(r#"{"type": "array", "items": "long"}"#).unwrap();
+/// let deserializer = Deserializer::new(&schema, &[6, 2, 4, 6, 0], None).unwrap();
+/// assert_eq!(Vec::<i64>::deserialize(&deserializer).unwrap(), [1, 2, 3]);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Deserializer {
+    value: Value,
+}
+
+impl Deserializer {
+    /// Creates a `Deserializer` of the datum of `writer_schema` encoded in `bytes`, resolved
+    /// against `reader_schema` if any.
+    ///
+    /// Bytes left after the datum are an error, see
+    /// [`from_avro_datum_strict`](fn.from_avro_datum_strict.html).
+    pub fn new(
+        writer_schema: &Schema,
+        bytes: &[u8],
+        reader_schema: Option<&Schema>,
+    ) -> Result<Deserializer, Error> {
+        let value = from_avro_datum_strict(writer_schema, bytes, reader_schema)?;
+        Ok(Deserializer { value })
+    }
+
+    /// Get a reference to the decoded `Value`.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+// Forward the methods of `serde::Deserializer` to the deserializer of `Value`s.
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error> {
+            Ok(ValueDeserializer::new(&self.value).$method($($arg,)* visitor)?)
+        }
+    )*};
+}
+
+impl<'de> SerdeDeserializer<'de> for &'de Deserializer {
+    type Error = Error;
+
+    forward! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Test {
+        a: i64,
+        b: Option<String>,
+        c: HashMap<String, f64>,
+    }
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "b", "type": ["null", "string"]},
+                {"name": "c", "type": {"type": "map", "values": "double"}}
+            ]
+        }
+    "#;
+
+    #[test]
+    fn test_serializer() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let test = Test {
+            a: 27,
+            b: Some("foo".to_owned()),
+            c: vec![("x".to_owned(), 1.5)].into_iter().collect(),
+        };
+        let mut serializer = Serializer::new(&schema);
+        let encoded = test.serialize(&mut serializer).unwrap();
+        assert_eq!(
+            encoded,
+            to_avro_datum(&schema, ::ser::to_value(&test).unwrap()).unwrap()
+        );
+
+        // Values are validated against the schema.
+        match "foo".serialize(&mut serializer).unwrap_err() {
+            Error::SchemaMismatch { .. } => {},
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(27i64.serialize(&mut Serializer::new(&Schema::Long)).unwrap(), [54]);
+    }
+
+    #[test]
+    fn test_deserializer() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let test = Test {
+            a: 27,
+            b: None,
+            c: vec![("x".to_owned(), 1.5)].into_iter().collect(),
+        };
+        let encoded = test.serialize(&mut Serializer::new(&schema)).unwrap();
+        let deserializer = Deserializer::new(&schema, &encoded, None).unwrap();
+        assert_eq!(Test::deserialize(&deserializer).unwrap(), test);
+
+        // Through a reader schema.
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "a", "type": "double"}]}"#,
+        ).unwrap();
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Projected {
+            a: f64,
+        }
+        let deserializer = Deserializer::new(&schema, &encoded, Some(&reader_schema)).unwrap();
+        assert_eq!(Projected::deserialize(&deserializer).unwrap(), Projected { a: 27.0 });
+
+        match i32::deserialize(&deserializer).unwrap_err() {
+            Error::De(_) => {},
+            other => panic!("unexpected error {:?}", other),
+        }
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(Deserializer::new(&schema, &trailing, None).is_err());
+    }
+}
@@ -0,0 +1,687 @@
+//! Logic handling reading and writing the JSON encoding of Avro data.
+//!
+//! Unlike plain JSON, the JSON encoding tells the branch of union values apart by wrapping them in
+//! an object keyed by the name of their type (e.g. `{"string": "foo"}`, `null` being encoded as
+//! is), and encodes bytes and fixed values as strings whose characters are the code points
+//! `0x00`-`0xFF` matching each byte.
+//!
+//! More information about the JSON encoding can be found in the
+//! [Avro specification](https://avro.apache.org/docs/current/spec.html#json_encoding)
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use serde::ser::{Error as SerError, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_json::de::IoRead;
+use serde_json::{self, StreamDeserializer, Value as JsonValue};
+
+use error::Error;
+use logical::{to_logical, to_underlying_ref};
+
+use schema::{Schema, SchemaKind};
+#[cfg(feature = "serde_layer")]
+use ser::Serializer as AvroSerializer;
+use types::{ToAvro, Value};
+
+/// Encode a compatible value (implementing the `ToAvro` trait) using the Avro JSON encoding, also
+/// performing schema validation.
+pub fn to_avro_json<T: ToAvro>(schema: &Schema, value: T) -> Result<String, Error> {
+    let mut buffer = Vec::new();
+    write_avro_json(schema, value, &mut buffer)?;
+    // Only valid UTF-8 is written by serde_json.
+    Ok(String::from_utf8(buffer).unwrap())
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) using the Avro JSON encoding and
+/// write it to anything implementing `io::Write`, also performing schema validation.
+pub fn write_avro_json<T: ToAvro, W: Write>(
+    schema: &Schema,
+    value: T,
+    writer: W,
+) -> Result<(), Error> {
+    write_value_ref(schema, &value.avro(), writer)
+}
+
+fn write_value_ref<W: Write>(schema: &Schema, value: &Value, writer: W) -> Result<(), Error> {
+    let value = to_underlying_ref(value, schema)?;
+    if !value.validate(schema) {
+        return Err(value.mismatch(schema))
+    }
+    let datum = JsonDatum {
+        value: &value,
+        schema,
+        namespace: None,
+    };
+    serde_json::to_writer(writer, &datum)?;
+    Ok(())
+}
+
+/// Interface for writing values using the Avro JSON encoding, one value per line (as
+/// `avro-tools tojson` does).
+pub struct JsonWriter<'a, W> {
+    schema: &'a Schema,
+    #[cfg(feature = "serde_layer")]
+    serializer: AvroSerializer,
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<'a, W: Write> JsonWriter<'a, W> {
+    /// Creates a `JsonWriter` given a `Schema` and something implementing the `io::Write` trait to
+    /// write to.
+    pub fn new(schema: &'a Schema, writer: W) -> JsonWriter<'a, W> {
+        JsonWriter {
+            schema,
+            #[cfg(feature = "serde_layer")]
+            serializer: AvroSerializer::default(),
+            writer,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the `Schema` associated to a `JsonWriter`.
+    pub fn schema(&self) -> &'a Schema {
+        self.schema
+    }
+
+    /// Append a compatible value (implementing the `ToAvro` trait) to a `JsonWriter`, also
+    /// performing schema validation.
+    ///
+    /// Return the number of bytes written.
+    pub fn append<T: ToAvro>(&mut self, value: T) -> Result<usize, Error> {
+        self.append_value_ref(&value.avro())
+    }
+
+    /// Append a compatible value to a `JsonWriter`, also performing schema validation.
+    ///
+    /// Return the number of bytes written.
+    pub fn append_value_ref(&mut self, value: &Value) -> Result<usize, Error> {
+        self.buffer.clear();
+        write_value_ref(self.schema, value, &mut self.buffer)?;
+        self.buffer.push(b'\n');
+        self.writer.write_all(&self.buffer)?;
+        Ok(self.buffer.len())
+    }
+
+    /// Append anything implementing the `Serialize` trait to a `JsonWriter` for
+    /// [`serde`](https://docs.serde.rs/serde/index.html) compatibility, also performing schema
+    /// validation.
+    ///
+    /// Return the number of bytes written.
+    #[cfg(feature = "serde_layer")]
+    pub fn append_ser<S: Serialize>(&mut self, value: S) -> Result<usize, Error> {
+        let avro_value = value.serialize(&mut self.serializer)?;
+        self.append(avro_value)
+    }
+
+    /// Extend a `JsonWriter` with an `Iterator` of compatible values (implementing the `ToAvro`
+    /// trait), also performing schema validation.
+    ///
+    /// Return the number of bytes written.
+    pub fn extend<I, T: ToAvro>(&mut self, values: I) -> Result<usize, Error>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut num_bytes = 0;
+        for value in values {
+            num_bytes += self.append(value)?;
+        }
+        Ok(num_bytes)
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Return what the `JsonWriter` is writing to, consuming the `JsonWriter` itself.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Decode a `Value` from the Avro JSON encoding, given its `Schema`.
+///
+/// Missing record fields take their default value. Use [`from_value`](fn.from_value.html) to
+/// then get an instance of a type implementing `Deserialize`.
+pub fn from_avro_json(schema: &Schema, json: &str) -> Result<Value, Error> {
+    let json = serde_json::from_str(json)?;
+    from_avro_json_value(schema, &json)
+}
+
+/// Decode a `Value` from already parsed JSON using the Avro JSON encoding, given its `Schema`.
+///
+/// Missing record fields take their default value.
+pub fn from_avro_json_value(schema: &Schema, json: &JsonValue) -> Result<Value, Error> {
+    let value = JsonDecoder {
+        path: "$".to_owned(),
+        namespace: None,
+        tagged_unions: true,
+    }.decode(json, schema)?;
+    to_logical(value, schema)
+}
+
+/// Interface for reading values using the Avro JSON encoding, from a stream of JSON documents
+/// separated by whitespace (e.g. written by a [`JsonWriter`](struct.JsonWriter.html)).
+///
+/// To be used as an iterator, stopping at the first error.
+pub struct JsonReader<'a, R: Read> {
+    schema: &'a Schema,
+    stream: StreamDeserializer<'static, IoRead<R>, JsonValue>,
+    errored: bool,
+}
+
+impl<'a, R: Read> JsonReader<'a, R> {
+    /// Creates a `JsonReader` given a `Schema` and something implementing the `io::Read` trait to
+    /// read from.
+    pub fn new(schema: &'a Schema, reader: R) -> JsonReader<'a, R> {
+        JsonReader {
+            schema,
+            stream: serde_json::Deserializer::from_reader(reader).into_iter(),
+            errored: false,
+        }
+    }
+
+    /// Get a reference to the `Schema` associated to a `JsonReader`.
+    pub fn schema(&self) -> &'a Schema {
+        self.schema
+    }
+}
+
+impl<'a, R: Read> Iterator for JsonReader<'a, R> {
+    type Item = Result<Value, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // to prevent keep on reading after the first error occurs
+        if self.errored {
+            return None
+        }
+        let result = self.stream.next()?.map_err(Error::from).and_then(|json| {
+            from_avro_json_value(self.schema, &json)
+        });
+        self.errored = result.is_err();
+        Some(result)
+    }
+}
+
+// Builds `Value`s out of JSON, tracking the path of the JSON being decoded for errors.
+//
+// Record field defaults are decoded with `tagged_unions` unset: their union values are the ones
+// of the first branch, as is.
+struct JsonDecoder<'a> {
+    path: String,
+    namespace: Option<&'a str>,
+    tagged_unions: bool,
+}
+
+impl<'a> JsonDecoder<'a> {
+    fn nested(&self, path: String) -> JsonDecoder<'a> {
+        JsonDecoder {
+            path,
+            namespace: self.namespace,
+            tagged_unions: self.tagged_unions,
+        }
+    }
+
+    fn mismatch(&self, schema: &Schema, json: &JsonValue) -> Error {
+        let found = match *json {
+            JsonValue::Null => "JSON null",
+            JsonValue::Bool(_) => "JSON boolean",
+            JsonValue::Number(_) => "JSON number",
+            JsonValue::String(_) => "JSON string",
+            JsonValue::Array(_) => "JSON array",
+            JsonValue::Object(_) => "JSON object",
+        };
+        self.mismatch_with(format!("{:?}", SchemaKind::from(schema)), found.to_owned())
+    }
+
+    fn mismatch_with(&self, expected: String, found: String) -> Error {
+        Error::SchemaMismatch {
+            path: self.path.clone(),
+            expected,
+            found,
+        }
+    }
+
+    fn decode(&self, json: &JsonValue, schema: &'a Schema) -> Result<Value, Error> {
+        if let Schema::Logical(ref logical) = *schema {
+            return self.decode(json, logical.schema())
+        }
+        match (schema, json) {
+            (Schema::Null, JsonValue::Null) => Ok(Value::Null),
+            (Schema::Boolean, &JsonValue::Bool(b)) => Ok(Value::Boolean(b)),
+            (Schema::Int, JsonValue::Number(n)) => n
+                .as_i64()
+                .filter(|&i| i == i64::from(i as i32))
+                .map(|i| Value::Int(i as i32))
+                .ok_or_else(|| self.mismatch_with("Int".to_owned(), format!("number {}", n))),
+            (Schema::Long, JsonValue::Number(n)) => n
+                .as_i64()
+                .map(Value::Long)
+                .ok_or_else(|| self.mismatch_with("Long".to_owned(), format!("number {}", n))),
+            (Schema::Float, _) => self.decode_double(json, schema).map(|x| Value::Float(x as f32)),
+            (Schema::Double, _) => self.decode_double(json, schema).map(Value::Double),
+            (Schema::Bytes, JsonValue::String(s)) => self.decode_bytes(s).map(Value::Bytes),
+            (&Schema::Fixed { size, .. }, JsonValue::String(s)) => {
+                let bytes = self.decode_bytes(s)?;
+                if bytes.len() != size {
+                    return Err(self.mismatch_with(
+                        format!("Fixed of size {}", size),
+                        format!("{} bytes", bytes.len()),
+                    ))
+                }
+                Ok(Value::Fixed(size, bytes))
+            },
+            (Schema::String, JsonValue::String(s)) => Ok(Value::String(s.clone())),
+            (Schema::Enum { symbols, .. }, JsonValue::String(s)) => {
+                match symbols.iter().position(|symbol| symbol == s) {
+                    Some(index) => Ok(Value::Enum(index as i32, s.as_str().into())),
+                    None => Err(self.mismatch_with(
+                        format!("one of {:?}", symbols),
+                        format!("symbol {:?}", s),
+                    )),
+                }
+            },
+            (Schema::Array(inner), JsonValue::Array(items)) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    self.nested(format!("{}[{}]", self.path, i))
+                        .decode(item, inner)
+                }).collect::<Result<_, _>>()
+                .map(Value::Array),
+            (Schema::Map(inner), JsonValue::Object(items)) => items
+                .iter()
+                .map(|(key, value)| {
+                    let value = self
+                        .nested(format!("{}[{:?}]", self.path, key))
+                        .decode(value, inner)?;
+                    Ok((key.as_str().into(), value))
+                }).collect::<Result<HashMap<_, _>, Error>>()
+                .map(Value::Map),
+            (Schema::Union(union), _) => {
+                let variants = union.variants();
+                let value = if !self.tagged_unions {
+                    match variants.first() {
+                        Some(variant) => self.decode(json, variant)?,
+                        None => return Err(self.mismatch(schema, json)),
+                    }
+                } else if json.is_null() && variants.contains(&Schema::Null) {
+                    Value::Null
+                } else {
+                    let (branch, json) = match json.as_object() {
+                        Some(tagged) if tagged.len() == 1 => tagged.iter().next().unwrap(),
+                        _ => return Err(self.mismatch(schema, json)),
+                    };
+                    let variant = variants
+                        .iter()
+                        .find(|variant| branch_name(variant, self.namespace) == *branch)
+                        .ok_or_else(|| {
+                            self.mismatch_with(
+                                format!(
+                                    "one of {:?}",
+                                    variants
+                                        .iter()
+                                        .map(|variant| branch_name(variant, self.namespace))
+                                        .collect::<Vec<_>>()
+                                ),
+                                format!("branch {:?}", branch),
+                            )
+                        })?;
+                    self.decode(json, variant)?
+                };
+                Ok(Value::Union(Box::new(value)))
+            },
+            (Schema::Record { name, fields, .. }, JsonValue::Object(items)) => {
+                let namespace = name.namespace.as_ref().map(|s| s.as_ref()).or(self.namespace);
+                let mut record = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let mut decoder = JsonDecoder {
+                        path: format!("{}.{}", self.path, field.name),
+                        namespace,
+                        tagged_unions: self.tagged_unions,
+                    };
+                    let json = match (items.get(&field.name), field.default.as_ref()) {
+                        (Some(json), _) => json,
+                        (None, Some(default)) => {
+                            decoder.tagged_unions = false;
+                            default
+                        },
+                        (None, None) => {
+                            return Err(decoder.mismatch_with(
+                                format!("{:?}", SchemaKind::from(&field.schema)),
+                                "missing field".to_owned(),
+                            ))
+                        },
+                    };
+                    record.push((field.name.clone(), decoder.decode(json, &field.schema)?));
+                }
+                Ok(Value::Record(record))
+            },
+            _ => Err(self.mismatch(schema, json)),
+        }
+    }
+
+    fn decode_double(&self, json: &JsonValue, schema: &Schema) -> Result<f64, Error> {
+        match *json {
+            JsonValue::Number(ref n) => Ok(n.as_f64().unwrap_or_default()),
+            JsonValue::String(ref s) if s == "NaN" => Ok(f64::NAN),
+            JsonValue::String(ref s) if s == "Infinity" => Ok(f64::INFINITY),
+            JsonValue::String(ref s) if s == "-Infinity" => Ok(f64::NEG_INFINITY),
+            _ => Err(self.mismatch(schema, json)),
+        }
+    }
+
+    fn decode_bytes(&self, s: &str) -> Result<Vec<u8>, Error> {
+        s.chars()
+            .map(|c| {
+                if (c as u32) < 0x100 {
+                    Ok(c as u8)
+                } else {
+                    Err(self.mismatch_with(
+                        "Bytes".to_owned(),
+                        format!("character {:?} beyond U+00FF", c),
+                    ))
+                }
+            }).collect()
+    }
+}
+
+// A value validated against its schema, serialized using the JSON encoding. Named types are
+// looked up within `namespace`, the one of their enclosing type.
+struct JsonDatum<'a> {
+    value: &'a Value,
+    schema: &'a Schema,
+    namespace: Option<&'a str>,
+}
+
+impl<'a> JsonDatum<'a> {
+    fn nested(&self, value: &'a Value, schema: &'a Schema) -> JsonDatum<'a> {
+        JsonDatum {
+            value,
+            schema,
+            namespace: self.namespace,
+        }
+    }
+}
+
+impl<'a> Serialize for JsonDatum<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Schema::Logical(ref logical) = *self.schema {
+            return self.nested(self.value, logical.schema()).serialize(serializer)
+        }
+        match (self.value, self.schema) {
+            (Value::Null, _) => serializer.serialize_unit(),
+            (&Value::Boolean(b), _) => serializer.serialize_bool(b),
+            (&Value::Int(i), _) => serializer.serialize_i32(i),
+            (&Value::Long(i), _) => serializer.serialize_i64(i),
+            (&Value::Float(x), _) if !x.is_finite() => {
+                serialize_non_finite(f64::from(x), serializer)
+            },
+            (&Value::Float(x), _) => serializer.serialize_f32(x),
+            (&Value::Double(x), _) if !x.is_finite() => serialize_non_finite(x, serializer),
+            (&Value::Double(x), _) => serializer.serialize_f64(x),
+            (Value::Bytes(bytes), _) | (Value::Fixed(_, bytes), _) => {
+                serializer.serialize_str(&bytes_to_json(bytes))
+            },
+            (Value::String(s), _) => serializer.serialize_str(s),
+            (Value::Enum(_, symbol), _) => serializer.serialize_str(symbol),
+            (Value::Array(items), Schema::Array(inner)) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(&self.nested(item, inner))?;
+                }
+                seq.end()
+            },
+            (Value::Map(items), Schema::Map(inner)) => {
+                // Sorted, for the output not to depend on the order of the `HashMap`.
+                let mut items = items.iter().collect::<Vec<_>>();
+                items.sort_by(|a, b| a.0.cmp(b.0));
+
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+                for (key, value) in items {
+                    map.serialize_entry(&**key, &self.nested(value, inner))?;
+                }
+                map.end()
+            },
+            (Value::Union(inner), Schema::Union(_))
+            | (Value::UnionBranch(_, inner), Schema::Union(_))
+                if **inner == Value::Null =>
+            {
+                serializer.serialize_unit()
+            },
+            (Value::Union(_), Schema::Union(union))
+            | (Value::UnionBranch(..), Schema::Union(union)) => {
+                match union.find_branch(self.value) {
+                    Some((_, variant, value)) => {
+                        let mut map = serializer.serialize_map(Some(1))?;
+                        map.serialize_entry(
+                            &branch_name(variant, self.namespace),
+                            &self.nested(value, variant),
+                        )?;
+                        map.end()
+                    },
+                    None => Err(S::Error::custom("value does not match union")),
+                }
+            },
+            (Value::Record(items), Schema::Record { name, fields, .. }) => {
+                let record = JsonDatum {
+                    namespace: name.namespace.as_ref().map(|s| s.as_ref()).or(self.namespace),
+                    ..self.nested(self.value, self.schema)
+                };
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+                for (field, (name, value)) in fields.iter().zip(items) {
+                    map.serialize_entry(name, &record.nested(value, &field.schema))?;
+                }
+                map.end()
+            },
+            _ => Err(S::Error::custom("value does not match schema")),
+        }
+    }
+}
+
+// JSON has no representation of NaN or infinite numbers: encode them as the strings Java uses.
+fn serialize_non_finite<S: Serializer>(x: f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if x.is_nan() {
+        serializer.serialize_str("NaN")
+    } else if x > 0.0 {
+        serializer.serialize_str("Infinity")
+    } else {
+        serializer.serialize_str("-Infinity")
+    }
+}
+
+/// Encode bytes as a string of the code points `0x00`-`0xFF` matching each byte.
+pub(crate) fn bytes_to_json(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+/// Name of the type of a union branch, as used to tag union values.
+pub(crate) fn branch_name(schema: &Schema, namespace: Option<&str>) -> String {
+    match *schema {
+        Schema::Null => "null".to_owned(),
+        Schema::Boolean => "boolean".to_owned(),
+        Schema::Int => "int".to_owned(),
+        Schema::Long => "long".to_owned(),
+        Schema::Float => "float".to_owned(),
+        Schema::Double => "double".to_owned(),
+        Schema::Bytes => "bytes".to_owned(),
+        Schema::String => "string".to_owned(),
+        Schema::Array(_) => "array".to_owned(),
+        Schema::Map(_) => "map".to_owned(),
+        Schema::Union(_) => "union".to_owned(),
+        Schema::Record { ref name, .. }
+        | Schema::Enum { ref name, .. }
+        | Schema::Fixed { ref name, .. } => name.fullname(namespace),
+        Schema::Logical(ref logical) => branch_name(logical.schema(), namespace),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "namespace": "com.example",
+            "fields": [
+                {"name": "b", "type": "long"},
+                {
+                    "name": "a",
+                    "type": ["null", "string", {"type": "fixed", "name": "md5", "size": 2}]
+                },
+                {"name": "c", "type": {"type": "fixed", "name": "id", "size": 2}},
+                {"name": "d", "type": {"type": "map", "values": "double"}},
+                {"name": "e", "type": {"type": "array", "items": "bytes"}}
+            ]
+        }
+    "#;
+
+    fn record(a: Value) -> Value {
+        let mut map = HashMap::new();
+        map.insert("y".into(), Value::Double(f64::NAN));
+        map.insert("x".into(), Value::Double(1.5));
+        Value::Record(vec![
+            ("b".to_owned(), Value::Long(-3)),
+            ("a".to_owned(), Value::Union(Box::new(a))),
+            ("c".to_owned(), Value::Fixed(2, vec![0, 255])),
+            ("d".to_owned(), Value::Map(map)),
+            ("e".to_owned(), Value::Array(vec![Value::Bytes(b"\"\n".to_vec())])),
+        ])
+    }
+
+    #[test]
+    fn test_to_avro_json() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        assert_eq!(
+            to_avro_json(&schema, record(Value::Null)).unwrap(),
+            r#"{"b":-3,"a":null,"c":"\u0000ÿ","d":{"x":1.5,"y":"NaN"},"e":["\"\n"]}"#
+        );
+        assert_eq!(
+            to_avro_json(&schema, record(Value::String("foo".to_owned()))).unwrap(),
+            r#"{"b":-3,"a":{"string":"foo"},"c":"\u0000ÿ","d":{"x":1.5,"y":"NaN"},"e":["\"\n"]}"#
+        );
+        assert!(
+            to_avro_json(&schema, record(Value::Fixed(2, vec![1, 2])))
+                .unwrap()
+                .contains(r#""a":{"com.example.md5":"\u0001\u0002"}"#)
+        );
+        assert_eq!(to_avro_json(&Schema::Float, 0.1f32).unwrap(), "0.1");
+
+        match to_avro_json(&schema, Value::Long(1)).unwrap_err() {
+            Error::SchemaMismatch { path, .. } => assert_eq!(path, "$"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_writer() {
+        let schema = Schema::Union(
+            ::schema::UnionSchema::new(vec![Schema::Null, Schema::Int]).unwrap(),
+        );
+        let mut writer = JsonWriter::new(&schema, Vec::new());
+        let n = writer
+            .extend(vec![
+                Value::Union(Box::new(Value::Int(1))),
+                Value::Union(Box::new(Value::Null)),
+            ]).unwrap();
+        assert_eq!(n, 15);
+        assert!(writer.append(Value::Int(1)).is_err());
+        assert_eq!(writer.into_inner(), b"{\"int\":1}\nnull\n");
+    }
+
+    #[test]
+    fn test_from_avro_json() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        for a in vec![
+            Value::Null,
+            Value::String("foo".to_owned()),
+            Value::Fixed(2, vec![1, 2]),
+        ] {
+            let json = to_avro_json(&schema, record(a)).unwrap();
+            let value = from_avro_json(&schema, &json).unwrap();
+            // NaN is not equal to itself
+            assert_eq!(to_avro_json(&schema, value).unwrap(), json);
+        }
+
+        let mismatch = |json: &str| match from_avro_json(&schema, json).unwrap_err() {
+            Error::SchemaMismatch {
+                path,
+                expected,
+                found,
+            } => (path, expected, found),
+            other => panic!("unexpected error {:?}", other),
+        };
+        let json = r#"{"b": 1, "a": %, "c": "ab", "d": {}, "e": []}"#;
+        assert_eq!(
+            mismatch(&json.replace('%', r#""foo""#)),
+            (
+                "$.a".to_owned(),
+                "Union".to_owned(),
+                "JSON string".to_owned()
+            )
+        );
+        assert_eq!(
+            mismatch(&json.replace('%', r#"{"md5": "abc"}"#)),
+            (
+                "$.a".to_owned(),
+                r#"one of ["null", "string", "com.example.md5"]"#.to_owned(),
+                r#"branch "md5""#.to_owned()
+            )
+        );
+        assert_eq!(
+            mismatch(r#"{"b": 1, "a": null, "c": "ab", "d": {"x": true}, "e": []}"#).0,
+            r#"$.d["x"]"#
+        );
+        assert_eq!(
+            mismatch(r#"{"b": 1, "a": null, "c": "ab", "d": {}}"#).2,
+            "missing field"
+        );
+    }
+
+    #[test]
+    fn test_from_avro_json_defaults() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "int"},
+                    {"name": "b", "type": ["string", "null"], "default": "foo"},
+                    {"name": "c", "type": "bytes", "default": "\u00ff"}
+                ]
+            }
+        "#,
+        ).unwrap();
+        assert_eq!(
+            from_avro_json(&schema, r#"{"a": 3}"#).unwrap(),
+            Value::Record(vec![
+                ("a".to_owned(), Value::Int(3)),
+                (
+                    "b".to_owned(),
+                    Value::Union(Box::new(Value::String("foo".to_owned())))
+                ),
+                ("c".to_owned(), Value::Bytes(vec![255])),
+            ])
+        );
+        assert!(from_avro_json(&schema, r#"{"a": 3000000000}"#).is_err());
+    }
+
+    #[test]
+    fn test_json_reader() {
+        let schema = Schema::Long;
+        let values = JsonReader::new(&schema, &b"1\n2 3\n"[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, vec![Value::Long(1), Value::Long(2), Value::Long(3)]);
+
+        let mut reader = JsonReader::new(&schema, &b"1 \"x\" 3"[..]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
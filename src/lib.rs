@@ -34,12 +34,15 @@
 //! avro-rs = "x.y"
 //! ```
 //!
-//! Or in case you want to leverage the **Snappy** codec:
+//! Only the encode/decode core is built by default, unless more features are enabled: `deflate`
+//! and `snappy` for the codecs of the same name, `rand` for `Writer`s generating their own sync
+//! marker, `serde_layer` for serde support, `rpc` for the Avro RPC protocol and `http` for the
+//! schema registry clients. The examples below use:
 //!
 //! ```text
 //! [dependencies.avro-rs]
 //! version = "x.y"
-//! features = ["snappy"]
+//! features = ["deflate", "rand", "serde_layer"]
 //! ```
 //!
 //! To use the library,  just add at the top of the crate:
@@ -104,6 +107,7 @@
 //!
 //! ```
 //! # extern crate avro_rs;
+//! # #[cfg(feature = "rand")] {
 //! # use avro_rs::Schema;
 //! use avro_rs::types::Record;
 //! use avro_rs::Writer;
@@ -120,7 +124,7 @@
 //! # "#;
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
 //! // a writer needs a schema and something to write to
-//! let mut writer = Writer::new(&schema, Vec::new());
+//! let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 //!
 //! // the Record type models our Record schema
 //! let mut record = Record::new(writer.schema()).unwrap();
@@ -135,6 +139,7 @@
 //!
 //! // this is how to get back the resulting avro bytecode
 //! let encoded = writer.into_inner();
+//! # }
 //! ```
 //!
 //! The vast majority of the times, schemas tend to define a record as a top-level container
@@ -168,6 +173,7 @@
 //!     b: String,
 //! }
 //!
+//! # #[cfg(all(feature = "rand", feature = "serde_layer"))]
 //! # fn main() {
 //! # let raw_schema = r#"
 //! #     {
@@ -181,7 +187,7 @@
 //! # "#;
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
 //! // a writer needs a schema and something to write to
-//! let mut writer = Writer::new(&schema, Vec::new());
+//! let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 //!
 //! // the structure models our Record schema
 //! let test = Test {
@@ -198,6 +204,8 @@
 //! // this is how to get back the resulting avro bytecode
 //! let encoded = writer.into_inner();
 //! # }
+//! # #[cfg(not(all(feature = "rand", feature = "serde_layer")))]
+//! # fn main() {}
 //! ```
 //!
 //! The vast majority of the times, schemas tend to define a record as a top-level container
@@ -216,15 +224,20 @@
 //!
 //! * **Null**: leaves data uncompressed;
 //! * **Deflate**: writes the data block using the deflate algorithm as specified in RFC 1951, and
-//! typically implemented using the zlib library. Note that this format (unlike the "zlib format" in
-//! RFC 1950) does not have a checksum.
+//!   typically implemented using the zlib library. Note that this format (unlike the "zlib format"
+//!   in RFC 1950) does not have a checksum. You must enable the `deflate` feature to use this
+//!   codec (backed by `libflate`), or instead `deflate_miniz` to back it with `flate2` and
+//!   `miniz_oxide` (both pure Rust too), or `deflate_zlib` to back it with `flate2` and the C zlib
+//!   library, the fastest. When several are enabled, zlib is preferred over `miniz_oxide`, and
+//!   both over `libflate`.
 //! * **Snappy**: uses Google's [Snappy](http://google.github.io/snappy/) compression library. Each
-//! compressed block is followed by the 4-byte, big-endianCRC32 checksum of the uncompressed data in
-//! the block. You must enable the `snappy` feature to use this codec.
+//!   compressed block is followed by the 4-byte, big-endianCRC32 checksum of the uncompressed data
+//!   in the block. You must enable the `snappy` feature to use this codec.
 //!
 //! To specify a codec to use to compress data, just specify it while creating a `Writer`:
 //! ```
 //! # extern crate avro_rs;
+//! # #[cfg(all(feature = "deflate_codec", feature = "rand"))] {
 //! # use avro_rs::Schema;
 //! use avro_rs::Writer;
 //! use avro_rs::Codec;
@@ -240,7 +253,8 @@
 //! #     }
 //! # "#;
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
-//! let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate);
+//! let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+//! # }
 //! ```
 //!
 //! # Reading data
@@ -251,6 +265,7 @@
 //!
 //! ```
 //! # extern crate avro_rs;
+//! # #[cfg(feature = "rand")] {
 //! use avro_rs::Reader;
 //! # use avro_rs::Schema;
 //! # use avro_rs::types::Record;
@@ -267,7 +282,7 @@
 //! #     }
 //! # "#;
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
-//! # let mut writer = Writer::new(&schema, Vec::new());
+//! # let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 //! # let mut record = Record::new(writer.schema()).unwrap();
 //! # record.put("a", 27i64);
 //! # record.put("b", "foo");
@@ -276,12 +291,14 @@
 //! # let input = writer.into_inner();
 //! // reader creation can fail in case the input to read from is not Avro-compatible or malformed
 //! let reader = Reader::new(&input[..]).unwrap();
+//! # }
 //! ```
 //!
 //! In case, instead, we want to specify a different (but compatible) reader schema from the schema
 //! the data has been written with, we can just do as the following:
 //! ```
 //! # extern crate avro_rs;
+//! # #[cfg(feature = "rand")] {
 //! use avro_rs::Schema;
 //! use avro_rs::Reader;
 //! # use avro_rs::types::Record;
@@ -298,7 +315,7 @@
 //! #     }
 //! # "#;
 //! # let writer_schema = Schema::parse_str(writer_raw_schema).unwrap();
-//! # let mut writer = Writer::new(&writer_schema, Vec::new());
+//! # let mut writer = Writer::new(&writer_schema, Vec::new()).unwrap();
 //! # let mut record = Record::new(writer.schema()).unwrap();
 //! # record.put("a", 27i64);
 //! # record.put("b", "foo");
@@ -322,6 +339,7 @@
 //!
 //! // reader creation can fail in case the input to read from is not Avro-compatible or malformed
 //! let reader = Reader::with_schema(&reader_schema, &input[..]).unwrap();
+//! # }
 //! ```
 //!
 //! The library will also automatically perform schema resolution while reading the data.
@@ -343,6 +361,7 @@
 //!
 //! ```
 //! # extern crate avro_rs;
+//! # #[cfg(feature = "rand")] {
 //! # use avro_rs::Schema;
 //! # use avro_rs::types::Record;
 //! # use avro_rs::Writer;
@@ -360,7 +379,7 @@
 //! # "#;
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
-//! # let mut writer = Writer::new(&schema, Vec::new());
+//! # let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 //! # let mut record = Record::new(writer.schema()).unwrap();
 //! # record.put("a", 27i64);
 //! # record.put("b", "foo");
@@ -374,6 +393,7 @@
 //!     println!("{:?}", value.unwrap());
 //! }
 //!
+//! # }
 //! ```
 //!
 //! ## The serde way
@@ -389,6 +409,7 @@
 //! # use avro_rs::Schema;
 //! # use avro_rs::Writer;
 //! use avro_rs::Reader;
+//! # #[cfg(all(feature = "rand", feature = "serde_layer"))]
 //! use avro_rs::from_value;
 //!
 //! # #[derive(Serialize)]
@@ -398,6 +419,7 @@
 //!     b: String,
 //! }
 //!
+//! # #[cfg(all(feature = "rand", feature = "serde_layer"))]
 //! # fn main() {
 //! # let raw_schema = r#"
 //! #     {
@@ -410,7 +432,7 @@
 //! #     }
 //! # "#;
 //! # let schema = Schema::parse_str(raw_schema).unwrap();
-//! # let mut writer = Writer::new(&schema, Vec::new());
+//! # let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 //! # let test = Test {
 //! #     a: 27,
 //! #     b: "foo".to_owned(),
@@ -425,6 +447,8 @@
 //!     println!("{:?}", from_value::<Test>(&value.unwrap()));
 //! }
 //! # }
+//! # #[cfg(not(all(feature = "rand", feature = "serde_layer")))]
+//! # fn main() {}
 //! ```
 //!
 //! # Putting everything together
@@ -437,10 +461,9 @@
 //!
 //! #[macro_use]
 //! extern crate serde_derive;
-//! extern crate failure;
 //!
-//! use avro_rs::{Codec, Reader, Schema, Writer, from_value, types::Record};
-//! use failure::Error;
+//! # #[cfg(all(feature = "deflate_codec", feature = "rand", feature = "serde_layer"))]
+//! use avro_rs::{Codec, Error, Reader, Schema, Writer, from_value, types::Record};
 //!
 //! #[derive(Debug, Deserialize, Serialize)]
 //! struct Test {
@@ -448,6 +471,7 @@
 //!     b: String,
 //! }
 //!
+//! # #[cfg(all(feature = "deflate_codec", feature = "rand", feature = "serde_layer"))]
 //! fn main() -> Result<(), Error> {
 //!     let raw_schema = r#"
 //!         {
@@ -464,7 +488,7 @@
 //!
 //!     println!("{:?}", schema);
 //!
-//!     let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate);
+//!     let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
 //!
 //!     let mut record = Record::new(writer.schema()).unwrap();
 //!     record.put("a", 27i64);
@@ -489,54 +513,257 @@
 //!     }
 //!     Ok(())
 //! }
+//! # #[cfg(not(all(feature = "deflate_codec", feature = "rand", feature = "serde_layer")))]
+//! # fn main() {}
 //! ```
 
-extern crate failure;
+#![cfg_attr(not(feature = "std"), no_std)]
+// Without compression codecs, `Codec` has a single variant.
+#![cfg_attr(
+    not(any(feature = "deflate_codec", feature = "snappy")),
+    allow(irrefutable_let_patterns, unreachable_patterns)
+)]
+
+#[cfg(all(
+    feature = "deflate_codec",
+    not(any(feature = "deflate", feature = "deflate_miniz", feature = "deflate_zlib"))
+))]
+compile_error!("the deflate codec needs a backend: `deflate`, `deflate_miniz` or `deflate_zlib`");
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("without the `std` feature, the `alloc` feature is needed");
+
+// Paths of the code derived by thiserror start from `::core`, which the standard library does not
+// put at the root of the crate in this edition.
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(not(feature = "std"))]
 #[macro_use]
-extern crate failure_derive;
+extern crate alloc;
+
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_buffer;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "arena")]
+extern crate bumpalo;
+#[cfg(any(feature = "deflate_miniz", feature = "deflate_zlib"))]
+extern crate flate2;
+#[cfg(not(feature = "std"))]
+extern crate hashbrown;
+#[cfg(all(feature = "deflate", not(any(feature = "deflate_miniz", feature = "deflate_zlib"))))]
 extern crate libflate;
+extern crate md5;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "rand")]
 extern crate rand;
-#[macro_use]
+#[cfg(feature = "reqwest")]
+extern crate reqwest;
+#[cfg_attr(feature = "serde_layer", macro_use)]
 extern crate serde;
-
+#[macro_use]
 extern crate serde_json;
+extern crate sha2;
 #[cfg(feature = "snappy")]
 extern crate snap;
+#[macro_use]
+extern crate thiserror;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "http")]
+extern crate ureq;
 
 // test dependency
-#[cfg(test)]
+#[cfg(all(test, feature = "serde_layer"))]
 #[macro_use]
 extern crate serde_derive;
+#[cfg(test)]
+extern crate serde_transcode;
+#[cfg(all(test, feature = "reqwest"))]
+extern crate tokio;
 
+#[macro_use]
+mod trace;
+
+#[cfg(not(feature = "std"))]
+#[path = "no_std.rs"]
+mod std;
+
+#[cfg(feature = "proptest")]
+mod arbitrary;
+#[cfg(feature = "arena")]
+mod arena;
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "std")]
+mod check;
+#[cfg(feature = "std")]
 mod codec;
+#[cfg(feature = "std")]
+mod confluent;
+#[cfg(feature = "serde_layer")]
+mod conversion;
+mod datum;
+#[cfg(feature = "serde_layer")]
 mod de;
 mod decode;
+#[cfg(feature = "std")]
+mod diff;
 mod encode;
+mod error;
+#[cfg(feature = "std")]
+mod events;
+#[cfg(feature = "serde_layer")]
+mod format;
+#[cfg(feature = "std")]
+mod json;
+mod logical;
+#[cfg(feature = "std")]
+mod migrate;
+#[cfg(feature = "std")]
+mod push;
+#[cfg(feature = "std")]
 mod reader;
+#[cfg(feature = "serde_layer")]
+mod reflect;
+#[cfg(feature = "http")]
+mod registry;
+#[cfg(feature = "std")]
+mod repair;
+#[cfg(feature = "std")]
+mod resolver;
+#[cfg(feature = "serde_layer")]
 mod ser;
+#[cfg(feature = "std")]
+mod single_object;
+#[cfg(feature = "std")]
+mod sort;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(all(test, feature = "http"))]
+mod test_http;
+#[cfg(feature = "std")]
+mod transcode;
+#[cfg(feature = "std")]
+mod transform;
 mod util;
+pub mod varint;
+#[cfg(feature = "std")]
 mod writer;
 
+#[cfg(feature = "rpc")]
+pub mod rpc;
 pub mod schema;
 pub mod types;
 
+/// The I/O traits datums are read and written through: `std::io`, or without the `std` feature,
+/// minimal `Read` and `Write` traits implemented for `&[u8]` and `Vec<u8>`.
+pub use std::io;
+
+#[cfg(feature = "proptest")]
+pub use arbitrary::{arb_schema, arb_schema_and_value, arb_value};
+#[cfg(feature = "arena")]
+pub use arena::{from_avro_datum_in, ArenaValue};
+#[cfg(feature = "arena")]
+pub use bumpalo::Bump;
+#[cfg(feature = "arrow")]
+pub use arrow::{
+    from_arrow_schema, from_record_batch, to_arrow_schema, to_record_batch, ArrowReader,
+};
+#[cfg(feature = "std")]
+pub use check::{check_round_trip, Mismatch, RoundTripReport};
+#[cfg(feature = "serde_layer")]
+pub use check::check_round_trip_ser;
+#[cfg(feature = "std")]
 pub use codec::Codec;
+#[cfg(feature = "std")]
+pub use confluent::{from_confluent, to_confluent, write_confluent};
+#[cfg(feature = "serde_layer")]
+pub use conversion::{deserialize_converted, serialize_converted, Branch, Conversion, Converted};
+pub use datum::{
+    decode_from_slice, from_avro_datum, from_avro_datum_strict, skip_value, to_avro_datum,
+    to_avro_datum_sorted, DatumReader, DatumWriter, GenericDatumReader, GenericDatumWriter,
+};
+#[cfg(feature = "serde_layer")]
+pub use datum::{SerdeDatumReader, SerdeDatumWriter};
+#[cfg(feature = "serde_layer")]
 pub use de::from_value;
-pub use reader::{from_avro_datum, Reader};
-pub use schema::{ParseSchemaError, Schema};
+#[cfg(feature = "std")]
+pub use diff::{diff_schemas, diff_values, SchemaChange, ValueChange};
+pub use error::Error;
+#[cfg(feature = "std")]
+pub use events::{Event, EventReader};
+#[cfg(feature = "serde_layer")]
+pub use format::{Deserializer, Serializer};
+#[cfg(feature = "std")]
+pub use json::{
+    from_avro_json, from_avro_json_value, to_avro_json, write_avro_json, JsonReader, JsonWriter,
+};
+pub use logical::LogicalType;
+#[cfg(feature = "std")]
+pub use logical::register_logical_type;
+#[cfg(feature = "std")]
+pub use migrate::migrate;
+#[cfg(feature = "std")]
+pub use push::{PushDecoder, PushEvent};
+#[cfg(feature = "std")]
+pub use reader::{
+    compute_splits, inspect, read_all_values, summarize, BlockLayout, FileLayout, FileSummary,
+    ParallelReader, Reader,
+};
+#[cfg(feature = "serde_layer")]
+pub use reader::read_file;
+#[cfg(feature = "http")]
+pub use registry::{
+    AsyncHttpClient, AsyncSchemaRegistryClient, ConfluentSerializer, HttpRequest, HttpResponse,
+    RegistryError, RegistryFuture, SchemaRegistryClient, SubjectNameStrategy,
+};
+#[cfg(feature = "serde_layer")]
+pub use reflect::schema_for;
+#[cfg(feature = "std")]
+pub use repair::{repair, LostBlock, RepairReport};
+#[cfg(feature = "std")]
+pub use resolver::Resolver;
+pub use schema::{ParseMode, ParseOptions, ParseSchemaError, Schema};
+#[cfg(feature = "serde_layer")]
 pub use ser::to_value;
+#[cfg(feature = "std")]
+pub use single_object::{
+    from_single_object, to_single_object, write_single_object, MessageReader, MessageWriter,
+};
+#[cfg(feature = "std")]
+pub use sort::{compare_encoded, merge_sorted};
+#[cfg(feature = "std")]
+pub use stats::{FieldStatistics, FileStatistics, STATISTICS_KEY};
+#[cfg(feature = "std")]
+pub use store::{MemorySchemaStore, SchemaStore};
+#[cfg(feature = "std")]
+pub use transcode::transcode;
 pub use types::SchemaResolutionError;
-pub use util::{max_allocation_bytes, DecodeError};
-pub use writer::{to_avro_datum, ValidationError, Writer};
+pub use util::{
+    allocation_limit, decoding_depth_limit, max_allocation_bytes, max_decoding_depth, DecodeError,
+};
+#[cfg(feature = "rand")]
+pub use writer::{write_file, write_to_vec};
+#[cfg(feature = "std")]
+pub use writer::{write_bytes_from, write_bytes_staged, Writer};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "rand")]
     use reader::Reader;
     use schema::Schema;
+    #[cfg(feature = "rand")]
     use types::{Record, Value};
 
     //TODO: move where it fits better
+    #[cfg(feature = "rand")]
     #[test]
     fn test_enum_default() {
         let writer_raw_schema = r#"
@@ -570,7 +797,7 @@ mod tests {
         "#;
         let writer_schema = Schema::parse_str(writer_raw_schema).unwrap();
         let reader_schema = Schema::parse_str(reader_raw_schema).unwrap();
-        let mut writer = Writer::with_codec(&writer_schema, Vec::new(), Codec::Null);
+        let mut writer = Writer::with_codec(&writer_schema, Vec::new(), Codec::Null).unwrap();
         let mut record = Record::new(writer.schema()).unwrap();
         record.put("a", 27i64);
         record.put("b", "foo");
@@ -583,13 +810,14 @@ mod tests {
             Value::Record(vec![
                 ("a".to_string(), Value::Long(27)),
                 ("b".to_string(), Value::String("foo".to_string())),
-                ("c".to_string(), Value::Enum(1, "spades".to_string())),
+                ("c".to_string(), Value::Enum(1, "spades".into())),
             ])
         );
         assert!(reader.next().is_none());
     }
 
     //TODO: move where it fits better
+    #[cfg(feature = "rand")]
     #[test]
     fn test_enum_string_value() {
         let raw_schema = r#"
@@ -612,7 +840,7 @@ mod tests {
             }
         "#;
         let schema = Schema::parse_str(raw_schema).unwrap();
-        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Null);
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Null).unwrap();
         let mut record = Record::new(writer.schema()).unwrap();
         record.put("a", 27i64);
         record.put("b", "foo");
@@ -626,13 +854,14 @@ mod tests {
             Value::Record(vec![
                 ("a".to_string(), Value::Long(27)),
                 ("b".to_string(), Value::String("foo".to_string())),
-                ("c".to_string(), Value::Enum(2, "clubs".to_string())),
+                ("c".to_string(), Value::Enum(2, "clubs".into())),
             ])
         );
         assert!(reader.next().is_none());
     }
 
     //TODO: move where it fits better
+    #[cfg(feature = "rand")]
     #[test]
     fn test_enum_resolution() {
         let writer_raw_schema = r#"
@@ -675,7 +904,7 @@ mod tests {
         "#;
         let writer_schema = Schema::parse_str(writer_raw_schema).unwrap();
         let reader_schema = Schema::parse_str(reader_raw_schema).unwrap();
-        let mut writer = Writer::with_codec(&writer_schema, Vec::new(), Codec::Null);
+        let mut writer = Writer::with_codec(&writer_schema, Vec::new(), Codec::Null).unwrap();
         let mut record = Record::new(writer.schema()).unwrap();
         record.put("a", 27i64);
         record.put("b", "foo");
@@ -689,6 +918,7 @@ mod tests {
     }
 
     //TODO: move where it fits better
+    #[cfg(feature = "rand")]
     #[test]
     fn test_enum_no_reader_schema() {
         let writer_raw_schema = r#"
@@ -711,7 +941,7 @@ mod tests {
             }
         "#;
         let writer_schema = Schema::parse_str(writer_raw_schema).unwrap();
-        let mut writer = Writer::with_codec(&writer_schema, Vec::new(), Codec::Null);
+        let mut writer = Writer::with_codec(&writer_schema, Vec::new(), Codec::Null).unwrap();
         let mut record = Record::new(writer.schema()).unwrap();
         record.put("a", 27i64);
         record.put("b", "foo");
@@ -725,7 +955,7 @@ mod tests {
             Value::Record(vec![
                 ("a".to_string(), Value::Long(27)),
                 ("b".to_string(), Value::String("foo".to_string())),
-                ("c".to_string(), Value::Enum(2, "clubs".to_string())),
+                ("c".to_string(), Value::Enum(2, "clubs".into())),
             ])
         );
     }
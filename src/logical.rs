@@ -0,0 +1,375 @@
+//! Logic for user-defined logical types, refining how the values of an Avro type are validated
+//! and converted, e.g. a `geo-point` over a `fixed` of 16 bytes.
+use std::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::RwLock;
+
+use error::Error;
+use schema::{LogicalSchema, Schema};
+use types::Value;
+
+/// A logical type, annotating schemas with its name as `logicalType`.
+///
+/// Values are encoded and decoded in the representation of the annotated (underlying) schema:
+/// the conversions turn them into their logical representation once decoded, and back before
+/// they are encoded. All the hooks but `name` have defaults accepting anything as it is.
+pub trait LogicalType: Send + Sync {
+    /// The name of the logical type, e.g. `geo-point`.
+    fn name(&self) -> &str;
+
+    /// Check that the logical type may annotate `schema`.
+    ///
+    /// Schemas it may not annotate are parsed as their underlying type, as the specification
+    /// requires (or rejected in `ParseMode::Strict`).
+    fn check_schema(&self, _schema: &Schema) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Check a value in its underlying representation, before it is encoded or once decoded.
+    fn validate(&self, _value: &Value) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Convert a value from its logical representation into its underlying one.
+    fn to_underlying(&self, value: Value) -> Result<Value, String> {
+        Ok(value)
+    }
+
+    /// Convert a value from its underlying representation into its logical one.
+    fn to_logical(&self, value: Value) -> Result<Value, String> {
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "std")]
+static LOGICAL_TYPES: RwLock<Vec<Arc<dyn LogicalType>>> = RwLock::new(Vec::new());
+
+/// Register a logical type, so that the schemas parsed afterwards with its name as
+/// `logicalType` get it as a `Schema::Logical`, replacing any logical type of the same name.
+///
+/// Schemas annotated with a `logicalType` which is not registered are parsed as their
+/// underlying type.
+///
+/// **NOTE** Only available with the `std` feature: without it, no logical type is registered.
+#[cfg(feature = "std")]
+pub fn register_logical_type<L: LogicalType + 'static>(logical_type: L) {
+    let mut logical_types = LOGICAL_TYPES.write().unwrap_or_else(|e| e.into_inner());
+    logical_types.retain(|registered| registered.name() != logical_type.name());
+    logical_types.push(Arc::new(logical_type));
+}
+
+/// Look up a registered logical type by name.
+#[cfg(feature = "std")]
+pub(crate) fn logical_type(name: &str) -> Option<Arc<dyn LogicalType>> {
+    let logical_types = LOGICAL_TYPES.read().unwrap_or_else(|e| e.into_inner());
+    logical_types
+        .iter()
+        .find(|logical_type| logical_type.name() == name)
+        .cloned()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn logical_type(_: &str) -> Option<Arc<dyn LogicalType>> {
+    None
+}
+
+fn error(logical: &LogicalSchema, reason: String) -> Error {
+    Error::LogicalType {
+        name: logical.name().to_owned(),
+        reason,
+    }
+}
+
+/// Whether `schema` has logical types anywhere, whose values need converting.
+pub(crate) fn has_logical(schema: &Schema) -> bool {
+    match *schema {
+        Schema::Logical(_) => true,
+        Schema::Array(ref inner) | Schema::Map(ref inner) => has_logical(inner),
+        Schema::Union(ref union) => union.variants().iter().any(has_logical),
+        Schema::Record { ref fields, .. } => fields.iter().any(|field| has_logical(&field.schema)),
+        _ => false,
+    }
+}
+
+/// Convert a value of `schema` from its logical representation into its underlying one, to
+/// encode it.
+///
+/// Values not matching `schema` are left as they are, for the validation to report them.
+pub(crate) fn to_underlying(value: Value, schema: &Schema) -> Result<Value, Error> {
+    convert(value, schema, true, &|logical, value| {
+        let value = logical
+            .logical_type()
+            .to_underlying(value)
+            .map_err(|reason| error(logical, reason))?;
+        let value = to_underlying(value, logical.schema())?;
+        logical
+            .logical_type()
+            .validate(&value)
+            .map_err(|reason| error(logical, reason))?;
+        Ok(value)
+    })
+}
+
+/// Same as `to_underlying`, only cloning values which have logical types.
+pub(crate) fn to_underlying_ref<'v>(
+    value: &'v Value,
+    schema: &Schema,
+) -> Result<Cow<'v, Value>, Error> {
+    if !has_logical(schema) {
+        return Ok(Cow::Borrowed(value))
+    }
+    to_underlying(value.clone(), schema).map(Cow::Owned)
+}
+
+/// Convert a decoded value of `schema` from its underlying representation into its logical one.
+pub(crate) fn to_logical(value: Value, schema: &Schema) -> Result<Value, Error> {
+    convert(value, schema, false, &|logical, value| {
+        let value = to_logical(value, logical.schema())?;
+        logical
+            .logical_type()
+            .validate(&value)
+            .map_err(|reason| error(logical, reason))?;
+        logical
+            .logical_type()
+            .to_logical(value)
+            .map_err(|reason| error(logical, reason))
+    })
+}
+
+// Apply `f` to the values of logical types within a value of `schema`, in their logical
+// representation when `encoding`, else in their underlying one.
+fn convert<F>(value: Value, schema: &Schema, encoding: bool, f: &F) -> Result<Value, Error>
+where
+    F: Fn(&LogicalSchema, Value) -> Result<Value, Error>,
+{
+    if !has_logical(schema) {
+        return Ok(value)
+    }
+    match (value, schema) {
+        (value, Schema::Logical(logical)) => f(logical, value),
+        (Value::Array(items), Schema::Array(inner)) => items
+            .into_iter()
+            .map(|item| convert(item, inner, encoding, f))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        (Value::Map(items), Schema::Map(inner)) => items
+            .into_iter()
+            .map(|(key, item)| Ok((key, convert(item, inner, encoding, f)?)))
+            .collect::<Result<_, Error>>()
+            .map(Value::Map),
+        (Value::Record(fields), Schema::Record { fields: schema_fields, lookup, .. }) => fields
+            .into_iter()
+            .map(|(name, field)| {
+                let field = match lookup.get(&name) {
+                    Some(&position) => {
+                        convert(field, &schema_fields[position].schema, encoding, f)?
+                    },
+                    None => field,
+                };
+                Ok((name, field))
+            }).collect::<Result<_, Error>>()
+            .map(Value::Record),
+        (Value::UnionBranch(index, inner), Schema::Union(union)) => {
+            match union.variants().get(index) {
+                Some(variant) => Ok(Value::UnionBranch(
+                    index,
+                    Box::new(convert(*inner, variant, encoding, f)?),
+                )),
+                None => Ok(Value::UnionBranch(index, inner)),
+            }
+        },
+        (Value::Union(inner), Schema::Union(union)) => {
+            if !encoding {
+                return match union.find_schema(&inner) {
+                    Some((_, variant)) => Ok(Value::Union(Box::new(convert(
+                        *inner, variant, encoding, f,
+                    )?))),
+                    None => Ok(Value::Union(inner)),
+                }
+            }
+            // The kind of a value in its logical representation tells nothing about the branch
+            // it belongs to: the first one it converts to is.
+            for variant in union.variants() {
+                if let Ok(converted) = convert((*inner).clone(), variant, encoding, f) {
+                    if converted.validate(variant) {
+                        return Ok(Value::Union(Box::new(converted)))
+                    }
+                }
+            }
+            Ok(Value::Union(inner))
+        },
+        (value, _) => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "rand")]
+    use datum::{from_avro_datum, to_avro_datum};
+    #[cfg(feature = "rand")]
+    use reader::Reader;
+    use schema::{ParseMode, ParseOptions};
+    #[cfg(feature = "rand")]
+    use writer::Writer;
+
+    // Points stored as a `fixed` of two big-endian doubles, as arrays of doubles.
+    struct GeoPoint;
+
+    impl LogicalType for GeoPoint {
+        fn name(&self) -> &str {
+            "test-geo-point"
+        }
+
+        fn check_schema(&self, schema: &Schema) -> Result<(), String> {
+            match *schema {
+                Schema::Fixed { size: 16, .. } => Ok(()),
+                _ => Err("not a fixed of 16 bytes".to_owned()),
+            }
+        }
+
+        fn validate(&self, value: &Value) -> Result<(), String> {
+            let latitude = match *value {
+                Value::Fixed(16, ref bytes) => double_at(bytes, 0),
+                _ => return Err("not a fixed of 16 bytes".to_owned()),
+            };
+            if latitude.abs() > 90.0 {
+                return Err(format!("latitude {} out of range", latitude))
+            }
+            Ok(())
+        }
+
+        fn to_underlying(&self, value: Value) -> Result<Value, String> {
+            match value {
+                Value::Array(ref items) if items.len() == 2 => {
+                    let mut bytes = Vec::new();
+                    for item in items {
+                        match *item {
+                            Value::Double(d) => bytes.extend(&d.to_bits().to_be_bytes()),
+                            _ => return Err("not a double".to_owned()),
+                        }
+                    }
+                    Ok(Value::Fixed(16, bytes))
+                },
+                _ => Err("not a pair of doubles".to_owned()),
+            }
+        }
+
+        fn to_logical(&self, value: Value) -> Result<Value, String> {
+            match value {
+                Value::Fixed(16, bytes) => Ok(Value::Array(
+                    [0, 8]
+                        .iter()
+                        .map(|&i| Value::Double(double_at(&bytes, i)))
+                        .collect(),
+                )),
+                _ => Err("not a fixed of 16 bytes".to_owned()),
+            }
+        }
+    }
+
+    fn double_at(bytes: &[u8], i: usize) -> f64 {
+        let mut array = [0u8; 8];
+        array.copy_from_slice(&bytes[i..i + 8]);
+        f64::from_bits(u64::from_be_bytes(array))
+    }
+
+    #[cfg(feature = "rand")]
+    fn point(latitude: f64, longitude: f64) -> Value {
+        Value::Array(vec![Value::Double(latitude), Value::Double(longitude)])
+    }
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {
+                    "name": "a",
+                    "type": {
+                        "type": "fixed",
+                        "name": "point",
+                        "size": 16,
+                        "logicalType": "test-geo-point"
+                    }
+                },
+                {"name": "b", "type": {"type": "array", "items": ["null", "point"]}}
+            ]
+        }
+    "#;
+
+    #[test]
+    fn test_logical_schema() {
+        register_logical_type(GeoPoint);
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let point_schema = match schema {
+            Schema::Record { ref fields, .. } => fields[0].schema.clone(),
+            _ => unreachable!(),
+        };
+        match point_schema {
+            Schema::Logical(ref logical) => {
+                assert_eq!(logical.name(), "test-geo-point");
+                assert_eq!(logical.schema().fullname(), Some("point".to_owned()));
+            },
+            ref other => panic!("unexpected schema {:?}", other),
+        }
+        // References to the named type keep its logical type.
+        assert_eq!(schema.get_named("point"), Some(&point_schema));
+        let json = serde_json::to_value(&schema).unwrap();
+        assert_eq!(json["fields"][0]["type"]["logicalType"], "test-geo-point");
+        assert_eq!(Schema::parse(&json).unwrap(), schema);
+
+        // Unknown logical types, or logical types annotating the wrong schemas, are ignored
+        // unless parsing strictly.
+        let unknown = r#"{"type": "long", "logicalType": "test-unknown"}"#;
+        assert_eq!(Schema::parse_str(unknown).unwrap(), Schema::Long);
+        let invalid = r#"{"type": "long", "logicalType": "test-geo-point"}"#;
+        assert_eq!(Schema::parse_str(invalid).unwrap(), Schema::Long);
+        let strict = ParseOptions::new().mode(ParseMode::Strict);
+        assert!(Schema::parse_str_with(invalid, &strict).is_err());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_logical_values() {
+        register_logical_type(GeoPoint);
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let value = Value::Record(vec![
+            ("a".to_owned(), point(48.85, 2.35)),
+            (
+                "b".to_owned(),
+                Value::Array(vec![
+                    Value::Union(Box::new(Value::Null)),
+                    Value::Union(Box::new(point(-33.87, 151.21))),
+                ]),
+            ),
+        ]);
+
+        let encoded = to_avro_datum(&schema, value.clone()).unwrap();
+        assert_eq!(encoded.len(), 16 + 1 + 1 + 1 + 16 + 1);
+        assert_eq!(from_avro_datum(&schema, &mut &encoded[..], None).unwrap(), value);
+
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        writer.append_value_ref(&value).unwrap();
+        writer.flush().unwrap();
+        let written = writer.into_inner();
+        let values = Reader::new(&written[..]).unwrap().collect::<Result<Vec<_>, _>>();
+        assert_eq!(values.unwrap(), vec![value]);
+
+        // Values failing to convert or to validate are rejected.
+        let invalid = |a| {
+            Value::Record(vec![("a".to_owned(), a), ("b".to_owned(), Value::Array(vec![]))])
+        };
+        match to_avro_datum(&schema, invalid(point(91.0, 0.0))).unwrap_err() {
+            Error::LogicalType { name, reason } => {
+                assert_eq!(name, "test-geo-point");
+                assert_eq!(reason, "latitude 91 out of range");
+            },
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(to_avro_datum(&schema, invalid(Value::Null)).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Logic upgrading object container files to a new schema their records can be resolved against.
+use std::io::{Read, Write};
+
+use error::Error;
+use reader::Reader;
+use schema::Schema;
+use writer::Writer;
+
+/// Read the object container file of `input` with `schema` as reader schema and write its
+/// records, resolved against `schema`, to a new container file written with `schema` into
+/// `output`, returning the number of records written.
+///
+/// Resolution fills in the defaults of the fields added to records and promotes the values of
+/// promoted types (e.g. `int` to `long`), just like reading with
+/// [`Reader::with_schema`](struct.Reader.html#method.with_schema) does: the first record that
+/// cannot be resolved is an error. The codec and sync marker are kept, but the user metadata of
+/// the header is not.
+pub fn migrate<R: Read, W: Write>(input: R, output: W, schema: &Schema) -> Result<u64, Error> {
+    let reader = Reader::with_schema(schema, input)?;
+    let (codec, marker) = reader.codec_and_marker();
+    let mut writer = Writer::with_marker(schema, output, codec, marker)?;
+    let mut records = 0;
+    for value in reader {
+        writer.append_value_ref(&value?)?;
+        records += 1;
+    }
+    writer.flush()?;
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Value;
+    use Codec;
+
+    fn old_schema() -> Schema {
+        Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "a", "type": "int"},
+                {"name": "b", "type": "string"}
+            ]}"#,
+        ).unwrap()
+    }
+
+    fn write(records: &[(i32, &str)]) -> Vec<u8> {
+        let schema = old_schema();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        for &(a, b) in records {
+            writer
+                .append(Value::Record(vec![
+                    ("a".to_owned(), Value::Int(a)),
+                    ("b".to_owned(), Value::String(b.to_owned())),
+                ])).unwrap();
+        }
+        writer.flush().unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn test_migrate() {
+        let encoded = write(&[(1, "foo"), (2, "bar")]);
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "c", "type": "boolean", "default": true}
+            ]}"#,
+        ).unwrap();
+        let mut migrated = Vec::new();
+        assert_eq!(migrate(&encoded[..], &mut migrated, &schema).unwrap(), 2);
+
+        let reader = Reader::new(&migrated[..]).unwrap();
+        assert_eq!(reader.writer_schema(), &schema);
+        assert_eq!(reader.codec_and_marker(), (Codec::Null, [7u8; 16]));
+        let values = reader.map(Result::unwrap).collect::<Vec<_>>();
+        let record = |a| {
+            Value::Record(vec![
+                ("a".to_owned(), Value::Long(a)),
+                ("c".to_owned(), Value::Boolean(true)),
+            ])
+        };
+        assert_eq!(values, vec![record(1), record(2)]);
+    }
+
+    #[test]
+    fn test_migrate_incompatible() {
+        let encoded = write(&[(1, "foo")]);
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [{"name": "d", "type": "int"}]}"#,
+        ).unwrap();
+        assert!(migrate(&encoded[..], Vec::new(), &schema).is_err());
+    }
+}
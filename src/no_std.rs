@@ -0,0 +1,154 @@
+//! Stand-ins for the paths of `std` used by the encode/decode core without the standard library.
+//!
+//! Mounted as `std` at the root of the crate, they point to `core` and `alloc`, to the hash maps
+//! of `hashbrown` and to minimal I/O traits over byte slices, so that the core builds the same
+//! code either way.
+pub use alloc::{borrow, fmt, str};
+pub use core::{cell, hash, mem};
+
+pub mod collections {
+    pub use alloc::collections::*;
+    pub use hashbrown::{HashMap, HashSet};
+}
+
+pub mod sync {
+    pub use alloc::sync::Arc;
+    pub use core::sync::atomic;
+}
+
+pub mod prelude {
+    pub mod v1 {
+        pub use alloc::borrow::ToOwned;
+        pub use alloc::boxed::Box;
+        pub use alloc::string::{String, ToString};
+        pub use alloc::vec::Vec;
+    }
+}
+
+pub mod io {
+    //! The subset of `std::io` used to decode values from, and encode them into, memory.
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    /// The kind of an I/O `Error`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        /// The input ended before all the bytes expected were read.
+        UnexpectedEof,
+        /// An output could not take all the bytes to write.
+        WriteZero,
+    }
+
+    /// Describes an error happened while reading from or writing to memory.
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: &'static str,
+    }
+
+    impl Error {
+        /// Create an `Error` of `kind`, with a description of the error.
+        pub fn new(kind: ErrorKind, message: &'static str) -> Error {
+            Error { kind, message }
+        }
+
+        /// The kind of the error.
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl ::core::error::Error for Error {}
+
+    pub type Result<T> = ::core::result::Result<T, Error>;
+
+    /// Sources of bytes, e.g. `&[u8]`.
+    pub trait Read {
+        /// Read some bytes into `buf`, returning how many were read (0 at the end of the input).
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        /// Read exactly enough bytes to fill `buf`.
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf)? {
+                    0 => {
+                        let message = "failed to fill whole buffer";
+                        return Err(Error::new(ErrorKind::UnexpectedEof, message))
+                    },
+                    n => buf = &mut buf[n..],
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            buf[..n].copy_from_slice(&self[..n]);
+            *self = &self[n..];
+            Ok(n)
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+
+        fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+            (**self).read_exact(buf)
+        }
+    }
+
+    /// Sinks of bytes, e.g. `Vec<u8>`.
+    pub trait Write {
+        /// Write some of the bytes of `buf`, returning how many were written.
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        /// Flush the bytes written so far to where they are going.
+        fn flush(&mut self) -> Result<()>;
+
+        /// Write all the bytes of `buf`.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf)? {
+                    0 => {
+                        let message = "failed to write whole buffer";
+                        return Err(Error::new(ErrorKind::WriteZero, message))
+                    },
+                    n => buf = &buf[n..],
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Logic for decoding object container files pushed in chunks of bytes, without reading them
+//! from an `io::Read` (sans-IO), e.g. to integrate with custom network stacks or event loops.
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read};
+use std::mem;
+
+use decode::StringPool;
+use error::Error;
+use reader::{decode_with, read_header, Header};
+use resolver::Resolver;
+use schema::Schema;
+use types::Value;
+use util::{self, safe_len, DecodeError};
+use Codec;
+
+/// What a [`PushDecoder`](struct.PushDecoder.html) decoded from the bytes pushed to it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PushEvent {
+    /// The header of the file, with the writer schema and the metadata of the file.
+    Header {
+        schema: Schema,
+        metadata: HashMap<String, Vec<u8>>,
+    },
+    /// A data block (counting from 0) of `count` values, which follow as `Value` events.
+    Block { index: u64, count: usize },
+    /// A value of the last data block.
+    Value(Value),
+}
+
+/// Decoder of object container files fed with chunks of bytes as they arrive, emitting events
+/// once whole headers and data blocks are available.
+///
+/// Nothing is decoded from incomplete data blocks: at most one data block is buffered at a time.
+pub struct PushDecoder {
+    reader_schema: Option<Schema>,
+    // Bytes pushed so far that are not part of a decoded header or data block.
+    buffer: Vec<u8>,
+    // Position of the start of `buffer` in the input.
+    offset: u64,
+    header: Option<Header>,
+    resolver: Option<Resolver>,
+    blocks: u64,
+    pool: StringPool,
+    // Scratch buffer the compressed blocks get decompressed into.
+    decompressed: Vec<u8>,
+    // Error met after other events were decoded, returned once they have been.
+    error: Option<Error>,
+}
+
+impl PushDecoder {
+    /// Create a `PushDecoder`, decoding values with the schema of the file.
+    pub fn new() -> PushDecoder {
+        PushDecoder {
+            reader_schema: None,
+            buffer: Vec::new(),
+            offset: 0,
+            header: None,
+            resolver: None,
+            blocks: 0,
+            pool: StringPool::new(),
+            decompressed: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Create a `PushDecoder`, resolving the values of the file against `reader_schema`.
+    pub fn with_schema(reader_schema: &Schema) -> PushDecoder {
+        PushDecoder {
+            reader_schema: Some(reader_schema.clone()),
+            ..PushDecoder::new()
+        }
+    }
+
+    /// Get the writer schema of the file, once its header has been decoded.
+    pub fn writer_schema(&self) -> Option<&Schema> {
+        self.header.as_ref().map(|header| &header.schema)
+    }
+
+    /// Feed the next `chunk` of the file, returning the events decoded thanks to it (possibly
+    /// none if more bytes are needed).
+    ///
+    /// When decoding fails after some events, these are returned first and the error is returned
+    /// by the next call (or by [`finish`](#method.finish)).
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<PushEvent>, Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error)
+        }
+        self.buffer.extend_from_slice(chunk);
+
+        let buffer = mem::take(&mut self.buffer);
+        let mut events = Vec::new();
+        let mut start = 0;
+        let result = loop {
+            let step = match self.header {
+                None => self.decode_header(&buffer[start..], &mut events),
+                Some(_) => self.decode_block(&buffer[start..], &mut events),
+            };
+            match step {
+                Ok(Some(len)) => start += len,
+                Ok(None) => break Ok(()),
+                Err(e) => {
+                    let block = self.header.as_ref().map(|_| self.blocks);
+                    break Err(e.at(self.offset + start as u64, block))
+                },
+            }
+        };
+        self.buffer = buffer;
+        self.buffer.drain(..start);
+        self.offset += start as u64;
+
+        match result {
+            Ok(()) => Ok(events),
+            Err(e) if events.is_empty() => Err(e),
+            Err(e) => {
+                self.error = Some(e);
+                Ok(events)
+            },
+        }
+    }
+
+    /// Check that the input ended cleanly, after its header and with no partial data block.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error)
+        }
+        if self.header.is_none() || !self.buffer.is_empty() {
+            let block = self.header.as_ref().map(|_| self.blocks);
+            let error: Error = io::Error::from(ErrorKind::UnexpectedEof).into();
+            return Err(error.at(self.offset + self.buffer.len() as u64, block))
+        }
+        Ok(())
+    }
+
+    // Decode the header at the start of `input`, returning its size, or `None` if incomplete.
+    fn decode_header(
+        &mut self,
+        input: &[u8],
+        events: &mut Vec<PushEvent>,
+    ) -> Result<Option<usize>, Error> {
+        let (header, len) = match read_header(input) {
+            Ok(header) => header,
+            Err(ref e) if is_truncated(e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if let Some(ref reader_schema) = self.reader_schema {
+            if *reader_schema != header.schema {
+                self.resolver = Some(Resolver::new(&header.schema, reader_schema));
+            }
+        }
+        events.push(PushEvent::Header {
+            schema: header.schema.clone(),
+            metadata: header.metadata.clone(),
+        });
+        self.header = Some(header);
+        Ok(Some(len))
+    }
+
+    // Decode the data block at the start of `input`, returning its size, or `None` if
+    // incomplete.
+    fn decode_block(
+        &mut self,
+        input: &[u8],
+        events: &mut Vec<PushEvent>,
+    ) -> Result<Option<usize>, Error> {
+        let header = self.header.as_ref().expect("header decoded first");
+        let mut rest = input;
+        let (count, size) = match read_block_header(&mut rest) {
+            Ok(header) => header,
+            Err(ref e) if is_truncated(e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if rest.len() < size + header.marker.len() {
+            return Ok(None)
+        }
+        let (data, rest) = rest.split_at(size);
+        if rest[..header.marker.len()] != header.marker {
+            return Err(DecodeError::new("block marker does not match header marker").into())
+        }
+
+        let mut data = match header.codec {
+            Codec::Null => data,
+            codec => {
+                self.decompressed.clear();
+                codec
+                    .block_decompressor(data, false)?
+                    .read_to_end(&mut self.decompressed)?;
+                &self.decompressed[..]
+            },
+        };
+        let mut values = Vec::new();
+        for _ in 0..count {
+            let value = decode_with(
+                &header.schema,
+                self.resolver.as_ref(),
+                &mut data,
+                &mut self.pool,
+            )?;
+            values.push(PushEvent::Value(value));
+        }
+
+        events.push(PushEvent::Block {
+            index: self.blocks,
+            count,
+        });
+        events.append(&mut values);
+        self.blocks += 1;
+        Ok(Some(input.len() - rest.len() + header.marker.len()))
+    }
+}
+
+impl Default for PushDecoder {
+    fn default() -> PushDecoder {
+        PushDecoder::new()
+    }
+}
+
+// Read the value count and the size in bytes of a data block.
+pub(crate) fn read_block_header(input: &mut &[u8]) -> Result<(usize, usize), Error> {
+    let count = util::read_long(input)?;
+    let size = util::read_long(input)?;
+    if count < 0 || size < 0 {
+        return Err(DecodeError::new("negative block count or size").into())
+    }
+    Ok((count as usize, safe_len(size as usize)?))
+}
+
+// Whether decoding failed only because the input ended early.
+fn is_truncated(error: &Error) -> bool {
+    match *error.inner() {
+        Error::Io(ref e) => e.kind() == ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use writer::Writer;
+
+    static SCHEMA: &'static str = r#"
+        {
+            "type": "record",
+            "name": "test",
+            "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "b", "type": "string"}
+            ]
+        }
+    "#;
+
+    fn record(a: i64, b: &str) -> Value {
+        Value::Record(vec![
+            ("a".to_owned(), Value::Long(a)),
+            ("b".to_owned(), Value::String(b.to_owned())),
+        ])
+    }
+
+    fn write(codec: Codec) -> (Schema, Vec<u8>) {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), codec, [3u8; 16]).unwrap();
+        writer.append_value_ref(&record(1, "foo")).unwrap();
+        writer.append_value_ref(&record(2, "bar")).unwrap();
+        writer.flush().unwrap();
+        writer.append_value_ref(&record(3, "baz")).unwrap();
+        writer.flush().unwrap();
+        let encoded = writer.into_inner();
+        (schema, encoded)
+    }
+
+    #[test]
+    fn test_push_decoder() {
+        for &codec in &[Codec::Null, #[cfg(feature = "deflate_codec")] Codec::Deflate] {
+            let (schema, encoded) = write(codec);
+
+            for chunk_size in &[1, 7, encoded.len()] {
+                let mut decoder = PushDecoder::new();
+                let mut events = Vec::new();
+                for chunk in encoded.chunks(*chunk_size) {
+                    events.extend(decoder.push(chunk).unwrap());
+                }
+                decoder.finish().unwrap();
+
+                assert_eq!(decoder.writer_schema(), Some(&schema));
+                match events[0] {
+                    PushEvent::Header { schema: ref s, .. } => assert_eq!(*s, schema),
+                    ref other => panic!("unexpected event {:?}", other),
+                }
+                assert_eq!(
+                    events[1..].to_vec(),
+                    vec![
+                        PushEvent::Block { index: 0, count: 2 },
+                        PushEvent::Value(record(1, "foo")),
+                        PushEvent::Value(record(2, "bar")),
+                        PushEvent::Block { index: 1, count: 1 },
+                        PushEvent::Value(record(3, "baz")),
+                    ]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_decoder_with_schema() {
+        let (_, encoded) = write(Codec::Null);
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "b", "type": "string"}]}"#,
+        ).unwrap();
+        let mut decoder = PushDecoder::with_schema(&reader_schema);
+        let values = decoder
+            .push(&encoded)
+            .unwrap()
+            .into_iter()
+            .filter_map(|event| match event {
+                PushEvent::Value(value) => Some(value),
+                _ => None,
+            }).collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            ["foo", "bar", "baz"]
+                .iter()
+                .map(|b| Value::Record(vec![("b".to_owned(), Value::String((*b).to_owned()))]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_push_decoder_errors() {
+        let (_, encoded) = write(Codec::Null);
+
+        // A truncated input.
+        let mut decoder = PushDecoder::new();
+        assert_eq!(decoder.push(&encoded[..encoded.len() - 1]).unwrap().len(), 4);
+        match decoder.finish().unwrap_err() {
+            Error::AtOffset { block, error, .. } => {
+                assert_eq!(block, Some(1));
+                assert!(is_truncated(&error));
+            },
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(PushDecoder::new().finish().is_err());
+
+        // A corrupted marker, after the first block: its events come first.
+        let mut corrupted = encoded.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+        let mut decoder = PushDecoder::new();
+        assert_eq!(decoder.push(&corrupted).unwrap().len(), 4);
+        match decoder.push(&[]).unwrap_err() {
+            Error::AtOffset { block, .. } => assert_eq!(block, Some(1)),
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        assert!(PushDecoder::new().push(b"Obj\x02").is_err());
+    }
+}
@@ -1,44 +1,126 @@
 //! Logic handling reading from Avro format at user level.
-use std::io::{ErrorKind, Read};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::mem;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::str::{from_utf8, FromStr};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::vec;
 
-use failure::Error;
+#[cfg(feature = "arena")]
+use bumpalo::Bump;
+use error::Error;
+#[cfg(feature = "serde_layer")]
+use serde::de::DeserializeOwned;
 use serde_json::from_slice;
 
-use decode::decode;
+#[cfg(feature = "arena")]
+use arena::{decode_in, ArenaValue};
+#[cfg(feature = "arrow")]
+use arrow::ArrowReader;
+use codec::Decompressor;
+use datum::DatumReader;
+#[cfg(feature = "serde_layer")]
+use de::from_value;
+use decode::{decode, decode_interned, decode_into, StringPool};
+use logical::{has_logical, to_logical};
+use resolver::Resolver;
 use schema::ParseSchemaError;
 use schema::Schema;
+use stats::{parse_statistics, FileStatistics, STATISTICS_KEY};
+use transform::Transforms;
 use types::Value;
-use util::{self, DecodeError};
+use util::{self, safe_len, ByteReader, CountingReader, DecodeError, VarintRead};
 use Codec;
 
+// Source of the values of a block larger than the maximum block size, decoded one at a time
+// instead of being held in memory all at once.
+enum BlockStream {
+    // Values read straight from the input, with this many bytes of the block left.
+    Raw(u64),
+    // Values read from the decompressed block.
+    Compressed(BufReader<Decompressor<Cursor<Vec<u8>>>>),
+}
+
+impl fmt::Debug for BlockStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlockStream::Raw(left) => write!(f, "Raw({})", left),
+            BlockStream::Compressed(_) => f.write_str("Compressed"),
+        }
+    }
+}
+
+// A data block as stored in the input, to be decompressed and decoded by a `ParallelReader`.
+struct RawBlock {
+    index: u64,
+    offset: u64,
+    count: usize,
+    data: Vec<u8>,
+}
+
 // Internal Block reader.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct Block<R> {
-    reader: R,
+    reader: CountingReader<R>,
     // Internal buffering to reduce allocation.
     buf: Vec<u8>,
     buf_idx: usize,
+    // Scratch buffer the compressed blocks get decompressed into, swapped with `buf`.
+    decompressed: Vec<u8>,
+    // Map keys and enum symbols shared by all the values read.
+    pool: StringPool,
     // Number of elements expected to exist within this block.
     message_count: usize,
     marker: [u8; 16],
     codec: Codec,
+    // Whether `deflate` blocks are zlib streams, see `Reader::set_zlib_deflate`.
+    zlib_deflate: bool,
     writer_schema: Schema,
+    // Whether the values of `writer_schema` have logical types to convert.
+    logical: bool,
+    metadata: HashMap<String, Vec<u8>>,
+    // Number of data blocks read so far, and position of the data of the last one in the input.
+    blocks_read: u64,
+    block_offset: u64,
+    // Blocks larger than this many (decompressed) bytes are streamed, see `BlockStream`.
+    max_block_size: Option<usize>,
+    stream: Option<BlockStream>,
+    // No block starting at or after this position in the input is read, see `Reader::with_range`.
+    end: Option<u64>,
 }
 
 impl<R: Read> Block<R> {
     fn new(reader: R) -> Result<Block<R>, Error> {
         let mut block = Block {
-            reader,
+            reader: CountingReader::new(reader),
             codec: Codec::Null,
+            zlib_deflate: false,
             writer_schema: Schema::Null,
+            logical: false,
+            metadata: HashMap::new(),
             buf: vec![],
             buf_idx: 0,
+            decompressed: vec![],
+            pool: StringPool::new(),
             message_count: 0,
             marker: [0; 16],
+            blocks_read: 0,
+            block_offset: 0,
+            max_block_size: None,
+            stream: None,
+            end: None,
         };
 
-        block.read_header()?;
+        block
+            .read_header()
+            .map_err(|e| e.at(block.reader.count(), None))?;
         Ok(block)
     }
 
@@ -54,7 +136,7 @@ impl<R: Read> Block<R> {
             return Err(DecodeError::new("wrong magic in header").into())
         }
 
-        if let Value::Map(meta) = decode(&meta_schema, &mut self.reader)? {
+        if let Value::Map(meta) = decode(&meta_schema, &mut ByteReader(&mut self.reader))? {
             // TODO: surface original parse schema errors instead of coalescing them here
             let schema = meta
                 .get("avro.schema")
@@ -67,24 +149,33 @@ impl<R: Read> Block<R> {
                 })
                 .and_then(|json| Schema::parse(&json).ok());
             if let Some(schema) = schema {
+                self.logical = has_logical(&schema);
                 self.writer_schema = schema;
             } else {
                 return Err(ParseSchemaError::new("unable to parse schema").into())
             }
 
-            if let Some(codec) = meta
-                .get("avro.codec")
-                .and_then(|codec| {
-                    if let Value::Bytes(ref bytes) = *codec {
-                        from_utf8(bytes.as_ref()).ok()
-                    } else {
-                        None
-                    }
-                })
-                .and_then(|codec| Codec::from_str(codec).ok())
-            {
-                self.codec = codec;
-            }
+            // A missing codec means `null`. Other metadata entries are kept as they are, whether
+            // they hold UTF-8 or not.
+            self.codec = match meta.get("avro.codec") {
+                Some(Value::Bytes(bytes)) => from_utf8(bytes)
+                    .ok()
+                    .and_then(|codec| Codec::from_str(codec).ok())
+                    .ok_or_else(|| {
+                        DecodeError::new(format!(
+                            "unsupported codec: {}",
+                            String::from_utf8_lossy(bytes)
+                        ))
+                    })?,
+                _ => Codec::Null,
+            };
+
+            self.metadata = meta
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    Value::Bytes(bytes) => Some((key.to_string(), bytes)),
+                    _ => None,
+                }).collect();
         } else {
             return Err(DecodeError::new("no metadata in header").into())
         }
@@ -114,45 +205,366 @@ impl<R: Read> Block<R> {
     /// the block. The objects are stored in an internal buffer to the `Reader`.
     fn read_block_next(&mut self) -> Result<(), Error> {
         assert!(self.is_empty(), "Expected self to be empty!");
+        let block = self.blocks_read;
+        self.read_block()
+            .map_err(|e| e.at(self.reader.count(), Some(block)))
+    }
+
+    fn read_block(&mut self) -> Result<(), Error> {
+        if self.past_end() {
+            return Ok(())
+        }
         match util::read_long(&mut self.reader) {
             Ok(block_len) => {
                 self.message_count = block_len as usize;
                 let block_bytes = util::read_long(&mut self.reader)?;
-                self.fill_buf(block_bytes as usize)?;
-                let mut marker = [0u8; 16];
-                self.reader.read_exact(&mut marker)?;
+                self.block_offset = self.reader.count();
+                self.blocks_read += 1;
+                trace_span!(
+                    "read_block",
+                    index = self.blocks_read - 1,
+                    offset = self.block_offset,
+                    count = block_len,
+                    size = block_bytes
+                );
 
-                if marker != self.marker {
-                    return Err(DecodeError::new("block marker does not match header marker").into())
+                if self.codec == Codec::Null && self.exceeds_max_block_size(block_bytes as u64) {
+                    // The values (and the marker) are read from the input as they get decoded.
+                    self.stream = Some(BlockStream::Raw(block_bytes as u64));
+                    return self.finish_empty_stream()
                 }
 
-                // NOTE (JAB): This doesn't fit this Reader pattern very well.
-                // `self.buf` is a growable buffer that is reused as the reader is iterated.
-                // For non `Codec::Null` variants, `decompress` will allocate a new `Vec`
-                // and replace `buf` with the new one, instead of reusing the same buffer.
-                // We can address this by using some "limited read" type to decode directly
-                // into the buffer. But this is fine, for now.
-                self.codec.decompress(&mut self.buf)?;
+                self.fill_buf(block_bytes as usize)?;
+                self.read_marker()?;
+
+                // Both buffers are reused (and only ever grow) from block to block.
+                if self.codec != Codec::Null {
+                    match self.max_block_size {
+                        Some(max_block_size) => {
+                            self.decompressed.clear();
+                            self.codec
+                                .block_decompressor(&self.buf[..], self.zlib_deflate)?
+                                .take((max_block_size as u64).saturating_add(1))
+                                .read_to_end(&mut self.decompressed)?;
+                        },
+                        None => {
+                            self.decompressed.clear();
+                            self.codec
+                                .block_decompressor(&self.buf[..], self.zlib_deflate)?
+                                .read_to_end(&mut self.decompressed)?;
+                        },
+                    }
+                    trace_event!(
+                        DEBUG,
+                        codec = ?self.codec,
+                        size = self.decompressed.len(),
+                        compressed_size = self.buf.len(),
+                        "decompressed block"
+                    );
+                    if self.exceeds_max_block_size(self.decompressed.len() as u64) {
+                        // Decompress the block again, one value at a time.
+                        self.decompressed.clear();
+                        let compressed = Cursor::new(mem::take(&mut self.buf));
+                        let decompressor =
+                            self.codec.block_decompressor(compressed, self.zlib_deflate)?;
+                        self.stream = Some(BlockStream::Compressed(BufReader::new(decompressor)));
+                        return self.finish_empty_stream()
+                    }
+                    mem::swap(&mut self.buf, &mut self.decompressed);
+                }
 
                 return Ok(())
             },
-            Err(e) => if let ErrorKind::UnexpectedEof = e.downcast::<::std::io::Error>()?.kind() {
+            Err(Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof => {
                 // to not return any error in case we only finished to read cleanly from the stream
                 return Ok(())
             },
+            Err(Error::Io(_)) => (),
+            Err(e) => return Err(e),
         };
         Err(DecodeError::new("unable to read block").into())
     }
 
+    // Read the next data block without decompressing it, or `None` at the end of the input.
+    fn read_raw_block(&mut self) -> Result<Option<RawBlock>, Error> {
+        let index = self.blocks_read;
+        self.read_raw().map_err(|e| e.at(self.reader.count(), Some(index)))
+    }
+
+    fn read_raw(&mut self) -> Result<Option<RawBlock>, Error> {
+        if self.past_end() {
+            return Ok(None)
+        }
+        let count = match util::read_long(&mut self.reader) {
+            Ok(count) => count as usize,
+            Err(Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let size = util::read_long(&mut self.reader)?;
+        let offset = self.reader.count();
+        let mut data = vec![0u8; safe_len(size as usize)?];
+        self.reader.read_exact(&mut data)?;
+        self.read_marker()?;
+        trace_event!(
+            DEBUG,
+            index = self.blocks_read,
+            offset,
+            count,
+            size,
+            "read raw block"
+        );
+
+        self.blocks_read += 1;
+        Ok(Some(RawBlock {
+            index: self.blocks_read - 1,
+            offset,
+            count,
+            data,
+        }))
+    }
+
+    fn inspect_block(&mut self) -> Result<Option<BlockLayout>, Error> {
+        let index = self.blocks_read;
+        self.inspect_raw()
+            .map_err(|e| e.at(self.reader.count(), Some(index)))
+    }
+
+    fn inspect_raw(&mut self) -> Result<Option<BlockLayout>, Error> {
+        let offset = self.reader.count();
+        let count = match util::read_long(&mut self.reader) {
+            Ok(count) => count,
+            Err(Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let size = util::read_long(&mut self.reader)?;
+        if count < 0 || size < 0 {
+            return Err(DecodeError::new("negative block count or size").into())
+        }
+        self.fill_buf(safe_len(size as usize)?)?;
+        let mut marker = [0u8; 16];
+        self.reader.read_exact(&mut marker)?;
+
+        let uncompressed_size = match self.codec {
+            Codec::Null => Some(size as u64),
+            codec => codec
+                .block_decompressor(&self.buf[..], self.zlib_deflate)
+                .and_then(|mut decompressor| Ok(io::copy(&mut decompressor, &mut io::sink())?))
+                .ok(),
+        };
+        self.blocks_read += 1;
+        Ok(Some(BlockLayout {
+            offset,
+            count: count as u64,
+            size: size as u64,
+            uncompressed_size,
+            valid_marker: marker == self.marker,
+        }))
+    }
+
+    // Advance past a block, returning its record count.
+    fn skip_block(&mut self) -> Result<Option<u64>, Error> {
+        let index = self.blocks_read;
+        self.skip_raw()
+            .map_err(|e| e.at(self.reader.count(), Some(index)))
+    }
+
+    fn skip_raw(&mut self) -> Result<Option<u64>, Error> {
+        let count = match util::read_long(&mut self.reader) {
+            Ok(count) => count,
+            Err(Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let size = util::read_long(&mut self.reader)?;
+        if count < 0 || size < 0 {
+            return Err(DecodeError::new("negative block count or size").into())
+        }
+        if io::copy(&mut (&mut self.reader).take(size as u64), &mut io::sink())? != size as u64 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into())
+        }
+        self.read_marker()?;
+        self.blocks_read += 1;
+        Ok(Some(count as u64))
+    }
+
+    fn past_end(&self) -> bool {
+        self.end.is_some_and(|end| self.reader.count() >= end)
+    }
+
+    fn exceeds_max_block_size(&self, size: u64) -> bool {
+        self.max_block_size
+            .is_some_and(|max_block_size| size > max_block_size as u64)
+    }
+
+    fn read_marker(&mut self) -> Result<(), Error> {
+        let mut marker = [0u8; 16];
+        self.reader.read_exact(&mut marker)?;
+
+        if marker != self.marker {
+            return Err(DecodeError::new("block marker does not match header marker").into())
+        }
+        Ok(())
+    }
+
+    // Once all the values of a streamed block are read, check that the whole block was consumed.
+    fn finish_stream(&mut self) -> Result<(), Error> {
+        match self.stream.take() {
+            Some(BlockStream::Raw(0)) => self.read_marker(),
+            Some(BlockStream::Raw(_)) => {
+                Err(DecodeError::new("block size does not match its content").into())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn finish_empty_stream(&mut self) -> Result<(), Error> {
+        if self.is_empty() {
+            self.finish_stream()
+        } else {
+            Ok(())
+        }
+    }
+
+    // Decode the next value of the block being streamed.
+    fn read_streamed(&mut self, resolver: Option<&Resolver>) -> Result<Value, Error> {
+        let item = match self.stream {
+            Some(BlockStream::Raw(ref mut left)) => {
+                let mut block = (&mut self.reader).take(*left);
+                let item = decode_with(
+                    &self.writer_schema,
+                    resolver,
+                    &mut ByteReader(&mut block),
+                    &mut self.pool,
+                );
+                *left = block.limit();
+                item
+            },
+            Some(BlockStream::Compressed(ref mut decompressor)) => {
+                decode_with(&self.writer_schema, resolver, decompressor, &mut self.pool)
+            },
+            None => unreachable!(),
+        };
+        self.end_streamed(item)
+    }
+
+    // Decode the next datum of the block being streamed with `datum_reader`.
+    fn read_streamed_with<D: DatumReader>(
+        &mut self,
+        datum_reader: &mut D,
+    ) -> Result<D::Datum, Error> {
+        let item = match self.stream {
+            Some(BlockStream::Raw(ref mut left)) => {
+                let mut block = (&mut self.reader).take(*left);
+                let item = datum_reader.read(&self.writer_schema, &mut block);
+                *left = block.limit();
+                item
+            },
+            Some(BlockStream::Compressed(ref mut decompressor)) => {
+                datum_reader.read(&self.writer_schema, decompressor)
+            },
+            None => unreachable!(),
+        };
+        self.end_streamed(item)
+    }
+
+    // Account for the value just decoded from the block being streamed.
+    fn end_streamed<T>(&mut self, item: Result<T, Error>) -> Result<T, Error> {
+        let offset = match self.stream {
+            Some(BlockStream::Raw(_)) => self.reader.count(),
+            _ => self.block_offset,
+        };
+        let item = item.map_err(|e| e.at(offset, self.blocks_read.checked_sub(1)))?;
+
+        self.message_count -= 1;
+        if self.is_empty() {
+            self.finish_stream()
+                .map_err(|e| e.at(self.reader.count(), self.blocks_read.checked_sub(1)))?;
+        }
+        Ok(item)
+    }
+
+    // Attach to `error` the position in the input of the byte at `position` in the current block.
+    fn locate(&self, error: Error, position: usize) -> Error {
+        let offset = if self.codec == Codec::Null {
+            self.block_offset + position as u64
+        } else {
+            self.block_offset
+        };
+        error.at(offset, self.blocks_read.checked_sub(1))
+    }
+
     fn len(&self) -> usize {
         self.message_count
     }
 
+    fn shrink_to_fit(&mut self) {
+        self.buf.shrink_to_fit();
+        self.decompressed = Vec::new();
+    }
+
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
-    fn read_next(&mut self, read_schema: Option<&Schema>) -> Result<Option<Value>, Error> {
+    #[cfg(feature = "arena")]
+    fn for_each_in<F>(&mut self, arena: &mut Bump, f: &mut F) -> Result<usize, Error>
+    where
+        F: FnMut(ArenaValue) -> Result<(), Error>,
+    {
+        let mut count = 0;
+        loop {
+            if self.is_empty() {
+                self.read_block_next()?;
+                if self.is_empty() {
+                    return Ok(count)
+                }
+            }
+
+            if self.stream.is_some() {
+                return Err(DecodeError::new(
+                    "blocks larger than the maximum block size cannot be decoded into an arena",
+                ).into())
+            }
+
+            // Values decoded from the previous block are gone by now.
+            arena.reset();
+            let mut block_bytes = &self.buf[self.buf_idx..];
+            while self.message_count > 0 {
+                let item = decode_in(&self.writer_schema, &mut block_bytes, arena)
+                    .map_err(|e| self.locate(e, self.buf.len() - block_bytes.len()))?;
+                self.message_count -= 1;
+                count += 1;
+                f(item)?;
+            }
+            self.buf_idx = self.buf.len() - block_bytes.len();
+        }
+    }
+
+    fn read_next(&mut self, resolver: Option<&Resolver>) -> Result<Option<Value>, Error> {
+        if self.is_empty() {
+            self.read_block_next()?;
+            if self.is_empty() {
+                return Ok(None)
+            }
+        }
+
+        if self.stream.is_some() {
+            return self.read_streamed(resolver).map(Some)
+        }
+
+        let mut block_bytes = &self.buf[self.buf_idx..];
+        let b_original = block_bytes.len();
+        // Decoding straight from the block slice takes the fast path for variable-length integers.
+        let item = decode_with(&self.writer_schema, resolver, &mut block_bytes, &mut self.pool)
+            .map_err(|e| self.locate(e, self.buf_idx + b_original - block_bytes.len()))?;
+        self.buf_idx += b_original - block_bytes.len();
+        self.message_count -= 1;
+        Ok(Some(item))
+    }
+
+    fn read_with<D: DatumReader>(
+        &mut self,
+        datum_reader: &mut D,
+    ) -> Result<Option<D::Datum>, Error> {
         if self.is_empty() {
             self.read_block_next()?;
             if self.is_empty() {
@@ -160,13 +572,44 @@ impl<R: Read> Block<R> {
             }
         }
 
+        if self.stream.is_some() {
+            return self.read_streamed_with(datum_reader).map(Some)
+        }
+
         let mut block_bytes = &self.buf[self.buf_idx..];
         let b_original = block_bytes.len();
-        let item = from_avro_datum(&self.writer_schema, &mut block_bytes, read_schema)?;
+        let item = datum_reader
+            .read(&self.writer_schema, &mut block_bytes)
+            .map_err(|e| self.locate(e, self.buf_idx + b_original - block_bytes.len()))?;
         self.buf_idx += b_original - block_bytes.len();
         self.message_count -= 1;
         Ok(Some(item))
     }
+
+    fn read_into(
+        &mut self,
+        resolver: Option<&Resolver>,
+        value: &mut Value,
+    ) -> Result<bool, Error> {
+        // Resolved, converted and streamed values are decoded anew.
+        if resolver.is_some() || self.logical || self.stream.is_some() || self.is_empty() {
+            return match self.read_next(resolver)? {
+                Some(item) => {
+                    *value = item;
+                    Ok(true)
+                },
+                None => Ok(false),
+            }
+        }
+
+        let mut block_bytes = &self.buf[self.buf_idx..];
+        let b_original = block_bytes.len();
+        decode_into(&self.writer_schema, &mut block_bytes, &mut self.pool, value)
+            .map_err(|e| self.locate(e, self.buf_idx + b_original - block_bytes.len()))?;
+        self.buf_idx += b_original - block_bytes.len();
+        self.message_count -= 1;
+        Ok(true)
+    }
 }
 
 /// Main interface for reading Avro formatted values.
@@ -184,11 +627,17 @@ impl<R: Read> Block<R> {
 ///     };
 /// }
 /// ```
+///
+/// A `Reader` is `Send` when `R` is, so it can be moved to a worker thread. Without a reader
+/// schema (`Reader::new`), it borrows nothing but `R`: it can then be moved to
+/// `std::thread::spawn` or an async task as well when `R` is `'static`, e.g. a `Cursor<Vec<u8>>`.
 pub struct Reader<'a, R> {
     block: Block<R>,
     reader_schema: Option<&'a Schema>,
     errored: bool,
-    should_resolve_schema: bool,
+    // Only set if the reader and writer schemas disagree.
+    resolver: Option<Resolver>,
+    transforms: Transforms,
 }
 
 impl<'a, R: Read> Reader<'a, R> {
@@ -202,7 +651,8 @@ impl<'a, R: Read> Reader<'a, R> {
             block,
             reader_schema: None,
             errored: false,
-            should_resolve_schema: false,
+            resolver: None,
+            transforms: Transforms::default(),
         };
         Ok(reader)
     }
@@ -217,10 +667,13 @@ impl<'a, R: Read> Reader<'a, R> {
             block,
             reader_schema: Some(schema),
             errored: false,
-            should_resolve_schema: false,
+            resolver: None,
+            transforms: Transforms::default(),
         };
         // Check if the reader and writer schemas disagree.
-        reader.should_resolve_schema = reader.writer_schema() != schema;
+        if reader.writer_schema() != schema {
+            reader.resolver = Some(Resolver::new(reader.writer_schema(), schema));
+        }
         Ok(reader)
     }
 
@@ -229,20 +682,509 @@ impl<'a, R: Read> Reader<'a, R> {
         &self.block.writer_schema
     }
 
+    /// Get a reference to the metadata of the header, `avro.schema` and `avro.codec` included.
+    pub fn metadata(&self) -> &HashMap<String, Vec<u8>> {
+        &self.block.metadata
+    }
+
+    /// Get the statistics stored in the metadata of the header, if any, see
+    /// [`Writer::with_statistics`](struct.Writer.html#method.with_statistics).
+    pub fn statistics(&self) -> Result<Option<FileStatistics>, Error> {
+        match self.block.metadata.get(STATISTICS_KEY) {
+            Some(bytes) => parse_statistics(&self.block.writer_schema, bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
     /// Get a reference to the optional reader `Schema`.
     pub fn reader_schema(&self) -> Option<&Schema> {
         self.reader_schema
     }
 
+    /// Get the number of bytes read from the underlying reader so far, header included.
+    ///
+    /// Blocks are read whole (unless larger than the maximum block size), so once all the values
+    /// of a block are read, this is the position right after it.
+    pub fn position(&self) -> u64 {
+        self.block.reader.count()
+    }
+
+    /// Unwrap this `Reader`, returning the underlying reader, e.g. to keep reading data following
+    /// the Avro data in the same stream.
+    ///
+    /// **NOTE** The underlying reader is left at [`position`](#method.position): a new block is
+    /// only read once a value is requested past the end of the current one.
+    pub fn into_inner(self) -> R {
+        self.block.reader.into_inner()
+    }
+
+    /// Decode the remaining values one by one, allocating them from `arena` and passing them to
+    /// `f`.
+    ///
+    /// `arena` is reset every time a new block is read, so that its memory is reused from block
+    /// to block instead of being allocated for every value.
+    ///
+    /// Return the number of values decoded.
+    ///
+    /// **NOTE** Schema resolution is not supported: an error is returned if the reader `Schema`
+    /// differs from the writer one.
+    #[cfg(feature = "arena")]
+    pub fn for_each_in<F>(&mut self, arena: &mut Bump, mut f: F) -> Result<usize, Error>
+    where
+        F: FnMut(ArenaValue) -> Result<(), Error>,
+    {
+        if self.errored {
+            return Ok(0)
+        }
+        if self.resolver.is_some() {
+            return Err(DecodeError::new(
+                "schema resolution is not supported when decoding into an arena",
+            ).into())
+        }
+
+        let result = self.block.for_each_in(arena, &mut f);
+        self.errored = result.is_err();
+        result
+    }
+
+    /// Read the remaining values as Arrow record batches of `batch_size` rows (the last one
+    /// possibly fewer), see [`to_record_batch`](fn.to_record_batch.html).
+    ///
+    /// Fails if the (reader) `Schema` cannot be mapped to an Arrow schema.
+    #[cfg(feature = "arrow")]
+    pub fn into_arrow(self, batch_size: usize) -> Result<ArrowReader<'a, R>, Error> {
+        ArrowReader::new(self, batch_size)
+    }
+
+    /// Set the maximum size of the (decompressed) blocks to hold in memory.
+    ///
+    /// The values of larger blocks are decoded from the input, and decompressed, one at a time
+    /// instead, so that a single huge block cannot exhaust the memory. Block sizes are not limited
+    /// by default.
+    ///
+    /// **NOTE** The values of uncompressed blocks are then read straight from the input, which
+    /// should be buffered.
+    pub fn set_max_block_size(&mut self, max_block_size: usize) {
+        self.block.max_block_size = Some(max_block_size);
+    }
+
+    /// Read `deflate` blocks as zlib streams (RFC 1950, with a header and a checksum) rather than
+    /// the raw deflate streams (RFC 1951) required by the specification, as some older tools
+    /// wrote them.
+    pub fn set_zlib_deflate(&mut self, zlib: bool) {
+        self.block.zlib_deflate = zlib;
+    }
+
+    /// Do not check that strings, map keys included, are valid UTF-8 when decoding them into
+    /// `Value`s.
+    ///
+    /// UTF-8 validation is a measurable part of decoding string-heavy data. Strings are still
+    /// validated when decoding into an arena.
+    ///
+    /// # Safety
+    ///
+    /// The input must be trusted to only hold valid UTF-8 strings: an invalid one would be
+    /// decoded into a `str`, which is undefined behavior.
+    pub unsafe fn set_trusted_utf8(&mut self, trusted: bool) {
+        self.block.pool.trusted_utf8 = trusted;
+    }
+
+    /// Release the memory held by the internal block buffers beyond what the current block
+    /// needs.
+    ///
+    /// The buffers grow to fit the largest block read so far and are otherwise reused from block
+    /// to block.
+    pub fn shrink_to_fit(&mut self) {
+        self.block.shrink_to_fit()
+    }
+
+    /// Apply `transform` to the values of the field at the dotted `path` (e.g. `user.email`) of
+    /// the records read, in place before they are returned, e.g. to hash or null out personal
+    /// data without rewriting whole values.
+    ///
+    /// The components of `path` name nested record fields, going through unions and the items
+    /// of arrays and maps, of the reader `Schema` if any (or else of the writer `Schema`);
+    /// `transform` is given the values of the field as decoded, unions included. Fails if there
+    /// is no such field. Transformations apply in the order they are registered.
+    ///
+    /// **NOTE** Transformations apply to the values of the `Reader` as an iterator (or as a
+    /// [`ParallelReader`](struct.ParallelReader.html)) and of [`read_into`](#method.read_into),
+    /// but not to those of [`read_with`](#method.read_with) or `for_each_in`.
+    ///
+    /// ```no_run
+    /// # use avro_rs::{Reader, types::Value};
+    /// # let input: &[u8] = &[];
+    /// let mut reader = Reader::new(input).unwrap();
+    /// reader
+    ///     .transform_field("user.email", |email| *email = Value::Null)
+    ///     .unwrap();
+    /// ```
+    pub fn transform_field<F>(&mut self, path: &str, transform: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut Value) + Send + 'static,
+    {
+        let schema = self.reader_schema.unwrap_or(&self.block.writer_schema);
+        self.transforms.add(schema, path, Box::new(transform))
+    }
+
+    /// Decode the next value into `value`, reusing the strings, bytes, arrays, maps and records
+    /// already allocated there, and return whether there was a value left to read.
+    ///
+    /// This avoids allocating a new `Value` per record when reading values of a similar shape.
+    /// Values performing schema resolution are not reused.
+    ///
+    /// ```no_run
+    /// # use avro_rs::{Reader, types::Value};
+    /// # let input: &[u8] = &[];
+    /// let mut reader = Reader::new(input).unwrap();
+    /// let mut value = Value::Null;
+    /// while reader.read_into(&mut value).unwrap() {
+    ///     println!("{:?}", value);
+    /// }
+    /// ```
+    pub fn read_into(&mut self, value: &mut Value) -> Result<bool, Error> {
+        // to prevent keep on reading after the first error occurs
+        if self.errored {
+            return Ok(false)
+        }
+        let result = self.block.read_into(self.resolver.as_ref(), value);
+        self.errored = result.is_err();
+        if let Ok(true) = result {
+            self.transforms.apply(value);
+        }
+        result
+    }
+
+    /// Decode the next datum with `datum_reader`, e.g. into a user type without going through a
+    /// `Value`, see [`DatumReader`](trait.DatumReader.html). Return `None` once all the datums
+    /// have been read.
+    ///
+    /// The reader `Schema` of the `Reader`, if any, is not used: `datum_reader` is given the
+    /// writer `Schema` and is in charge of the resolution.
+    pub fn read_with<D: DatumReader>(
+        &mut self,
+        datum_reader: &mut D,
+    ) -> Result<Option<D::Datum>, Error> {
+        // to prevent keep on reading after the first error occurs
+        if self.errored {
+            return Ok(None)
+        }
+        let result = self.block.read_with(datum_reader);
+        self.errored = result.is_err();
+        result
+    }
+
     #[inline]
     fn read_next(&mut self) -> Result<Option<Value>, Error> {
-        let read_schema = if self.should_resolve_schema {
-            self.reader_schema
-        } else {
-            None
+        let mut value = self.block.read_next(self.resolver.as_ref())?;
+        if let Some(ref mut value) = value {
+            self.transforms.apply(value);
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn codec_and_marker(&self) -> (Codec, [u8; 16]) {
+        (self.block.codec, self.block.marker)
+    }
+
+    // Read the next data block whole, returning its record count with its decompressed data, or
+    // `None` at the end of the input. Only for readers no value was read from.
+    pub(crate) fn read_block_data(&mut self) -> Result<Option<(usize, Vec<u8>)>, Error> {
+        let raw = match self.block.read_raw_block()? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        if self.block.codec == Codec::Null {
+            return Ok(Some((raw.count, raw.data)))
+        }
+        let mut decompressed = Vec::new();
+        self.block
+            .codec
+            .block_decompressor(&raw.data[..], self.block.zlib_deflate)
+            .and_then(|mut decompressor| Ok(decompressor.read_to_end(&mut decompressed)?))
+            .map_err(|e| e.at(raw.offset, Some(raw.index)))?;
+        Ok(Some((raw.count, decompressed)))
+    }
+
+    /// Decompress and decode the remaining blocks on `threads` worker threads, while still
+    /// yielding the values in order.
+    ///
+    /// Blocks are read from the input on the calling thread, and up to twice as many blocks as
+    /// there are threads are decoded ahead of the values being consumed. This speeds up reading
+    /// large, compressed files on multi-core machines.
+    ///
+    /// **NOTE** The maximum block size of the `Reader` is ignored: every block is held in memory.
+    pub fn into_parallel(mut self, threads: usize) -> ParallelReader<R> {
+        // Values left in the current block are decoded on the calling thread.
+        let mut values = Vec::new();
+        let mut error = None;
+        while !self.errored && !self.block.is_empty() {
+            match self.read_next() {
+                Ok(Some(value)) => values.push(value),
+                Ok(None) => break,
+                Err(e) => {
+                    self.errored = true;
+                    error = Some(e);
+                },
+            }
+        }
+
+        let threads = threads.max(1);
+        let writer_schema = Arc::new(self.block.writer_schema.clone());
+        let resolver = self.resolver.take().map(Arc::new);
+        let codec = self.block.codec;
+        let zlib_deflate = self.block.zlib_deflate;
+        let trusted_utf8 = self.block.pool.trusted_utf8;
+        let (jobs, job_receiver) = channel::<RawBlock>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = channel();
+
+        let workers = (0..threads)
+            .map(|_| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                let writer_schema = writer_schema.clone();
+                let resolver = resolver.clone();
+                thread::spawn(move || {
+                    let mut pool = StringPool::new();
+                    pool.trusted_utf8 = trusted_utf8;
+                    loop {
+                        let raw = match jobs.lock().unwrap().recv() {
+                            Ok(raw) => raw,
+                            Err(_) => return,
+                        };
+                        let index = raw.index;
+                        // A panic, e.g. in a logical type, fails the block instead of leaving
+                        // the reader waiting for it forever.
+                        let decoded = panic::catch_unwind(AssertUnwindSafe(|| {
+                            decode_raw_block(
+                                raw,
+                                codec,
+                                zlib_deflate,
+                                &writer_schema,
+                                resolver.as_deref(),
+                                &mut pool,
+                            )
+                        })).unwrap_or_else(|_| {
+                            pool = StringPool::new();
+                            pool.trusted_utf8 = trusted_utf8;
+                            Err(DecodeError::new("a decoding thread panicked").into())
+                        });
+                        if results.send((index, decoded)).is_err() {
+                            return
+                        }
+                    }
+                })
+            }).collect();
+
+        let next_block = self.block.blocks_read;
+        let mut reader = ParallelReader {
+            block: self.block,
+            jobs: Some(jobs),
+            results,
+            workers,
+            pending: HashMap::new(),
+            max_in_flight: 2 * threads as u64,
+            next_block,
+            end_block: next_block,
+            values: values.into_iter(),
+            transforms: self.transforms,
+            finished: self.errored,
+            errored: false,
+        };
+        if let Some(e) = error {
+            reader.fail(e);
+        }
+        reader
+    }
+}
+
+impl<'a, R: Read + Seek> Reader<'a, R> {
+    /// Creates a `Reader` of the values of the data blocks starting within `range` of the
+    /// container file of `reader`, e.g. a range computed by
+    /// [`compute_splits`](fn.compute_splits.html). No reader `Schema` will be set.
+    ///
+    /// The header is read from the start of the file, then the reader resyncs at the first data
+    /// block starting at or after `range.start` (after the first sync marker ending there) and
+    /// stops after the first sync marker at or beyond `range.end`. Any ranges partitioning a
+    /// file thus read all of its values exactly once, however they are aligned.
+    ///
+    /// **NOTE** [`position`](#method.position) is the position in the file.
+    pub fn with_range(mut reader: R, range: Range<u64>) -> Result<Reader<'a, R>, Error> {
+        reader.seek(SeekFrom::Start(0))?;
+        let mut block = Block::new(reader)?;
+        let header_size = block.reader.count();
+        if range.start > header_size {
+            let from = range.start.saturating_sub(16).max(header_size);
+            block.reader.seek_to(from)?;
+            // `find_marker` reads ahead of the marker.
+            if let Some(read) = find_marker(&mut block.reader, &block.marker)? {
+                block.reader.seek_to(from + read)?;
+            }
+        }
+        block.end = Some(range.end);
+        Ok(Reader {
+            block,
+            reader_schema: None,
+            errored: false,
+            resolver: None,
+            transforms: Transforms::default(),
+        })
+    }
+}
+
+// Decompress a block and decode its values, performing schema resolution if needed.
+fn decode_raw_block(
+    raw: RawBlock,
+    codec: Codec,
+    zlib_deflate: bool,
+    writer_schema: &Schema,
+    resolver: Option<&Resolver>,
+    pool: &mut StringPool,
+) -> Result<Vec<Value>, Error> {
+    let RawBlock {
+        index,
+        offset,
+        count,
+        mut data,
+    } = raw;
+    if codec != Codec::Null {
+        let mut decompressed = Vec::new();
+        codec
+            .block_decompressor(&data[..], zlib_deflate)
+            .and_then(|mut decompressor| Ok(decompressor.read_to_end(&mut decompressed)?))
+            .map_err(|e| e.at(offset, Some(index)))?;
+        trace_event!(
+            DEBUG,
+            codec = ?codec,
+            size = decompressed.len(),
+            compressed_size = data.len(),
+            "decompressed block"
+        );
+        data = decompressed;
+    }
+
+    let mut values = Vec::with_capacity(count.min(data.len()));
+    let mut bytes = &data[..];
+    for _ in 0..count {
+        let value = decode_with(writer_schema, resolver, &mut bytes, pool).map_err(|e| {
+            let position = if codec == Codec::Null {
+                offset + (data.len() - bytes.len()) as u64
+            } else {
+                offset
+            };
+            e.at(position, Some(index))
+        })?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Interface for reading Avro formatted values, decoding blocks on several threads.
+///
+/// Created with [`Reader::into_parallel`](struct.Reader.html#method.into_parallel), and to be
+/// used as an iterator.
+pub struct ParallelReader<R> {
+    block: Block<R>,
+    jobs: Option<Sender<RawBlock>>,
+    results: Receiver<(u64, Result<Vec<Value>, Error>)>,
+    workers: Vec<JoinHandle<()>>,
+    // Outcome of the blocks decoded ahead of their turn, by block index.
+    pending: HashMap<u64, Result<Vec<Value>, Error>>,
+    max_in_flight: u64,
+    // Index of the next block to yield the values of, and of the next block to read.
+    next_block: u64,
+    end_block: u64,
+    values: vec::IntoIter<Value>,
+    transforms: Transforms,
+    finished: bool,
+    errored: bool,
+}
+
+impl<R: Read> ParallelReader<R> {
+    /// Get a reference to the writer `Schema`.
+    pub fn writer_schema(&self) -> &Schema {
+        &self.block.writer_schema
+    }
+
+    // Record `error` as the outcome of the next block, and stop reading.
+    fn fail(&mut self, error: Error) {
+        self.pending.insert(self.end_block, Err(error));
+        self.end_block += 1;
+        self.finished = true;
+    }
+
+    // Hand blocks to the workers, until enough of them are in flight.
+    fn read_ahead(&mut self) {
+        while !self.finished && self.end_block - self.next_block < self.max_in_flight {
+            match self.block.read_raw_block() {
+                Ok(Some(raw)) => {
+                    self.end_block += 1;
+                    if let Some(ref jobs) = self.jobs {
+                        // Workers only stop once `jobs` is dropped.
+                        jobs.send(raw).ok();
+                    }
+                },
+                Ok(None) => self.finished = true,
+                Err(e) => self.fail(e),
+            }
+        }
+    }
+
+    fn next_block_values(&mut self) -> Result<vec::IntoIter<Value>, Error> {
+        let outcome = loop {
+            if let Some(outcome) = self.pending.remove(&self.next_block) {
+                break outcome
+            }
+            match self.results.recv() {
+                Ok((index, outcome)) => {
+                    self.pending.insert(index, outcome);
+                },
+                Err(_) => break Err(DecodeError::new("a decoding thread panicked").into()),
+            }
         };
+        self.next_block += 1;
+        let mut values = outcome?;
+        values.iter_mut().for_each(|value| self.transforms.apply(value));
+        Ok(values.into_iter())
+    }
+}
+
+impl<R: Read> Iterator for ParallelReader<R> {
+    type Item = Result<Value, Error>;
 
-        self.block.read_next(read_schema)
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.values.next() {
+                return Some(Ok(value))
+            }
+            // to prevent keep on reading after the first error occurs
+            if self.errored {
+                return None
+            }
+
+            self.read_ahead();
+            if self.next_block == self.end_block {
+                return None
+            }
+            match self.next_block_values() {
+                Ok(values) => self.values = values,
+                Err(e) => {
+                    self.errored = true;
+                    return Some(Err(e))
+                },
+            }
+        }
+    }
+}
+
+impl<R> Drop for ParallelReader<R> {
+    fn drop(&mut self) {
+        // Workers stop once there are no more blocks to decode.
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
     }
 }
 
@@ -264,23 +1206,231 @@ impl<'a, R: Read> Iterator for Reader<'a, R> {
     }
 }
 
-/// Decode a `Value` encoded in Avro format given its `Schema` and anything implementing `io::Read`
-/// to read from.
+/// A summary of an Avro container file, see [`summarize`](fn.summarize.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileSummary {
+    /// Schema the values were written with.
+    pub schema: Schema,
+    /// Codec the blocks are compressed with.
+    pub codec: Codec,
+    /// Metadata of the header, `avro.schema` and `avro.codec` excluded.
+    pub metadata: HashMap<String, Vec<u8>>,
+    /// Sync marker of the header.
+    pub marker: [u8; 16],
+    /// Number of blocks.
+    pub block_count: u64,
+    /// Number of records, as counted by the blocks.
+    pub record_count: u64,
+}
+
+/// Summarize an Avro container file, reading its header and the record counts of its blocks,
+/// without decompressing nor decoding them.
 ///
-/// In case a reader `Schema` is provided, schema resolution will also be performed.
+/// This is much cheaper than reading the values, e.g. to classify many files.
+pub fn summarize<R: Read>(reader: R) -> Result<FileSummary, Error> {
+    let mut block = Block::new(reader)?;
+    let mut block_count = 0;
+    let mut record_count = 0;
+    while let Some(count) = block.skip_block()? {
+        block_count += 1;
+        record_count += count;
+    }
+    let metadata = block
+        .metadata
+        .iter()
+        .filter(|&(key, _)| key != "avro.schema" && key != "avro.codec")
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    Ok(FileSummary {
+        schema: block.writer_schema,
+        codec: block.codec,
+        metadata,
+        marker: block.marker,
+        block_count,
+        record_count,
+    })
+}
+
+/// The physical layout of an Avro container file, see [`inspect`](fn.inspect.html).
+#[derive(Debug)]
+pub struct FileLayout {
+    /// Size of the header, magic, metadata and sync marker included.
+    pub header_size: u64,
+    /// Codec the blocks are compressed with.
+    pub codec: Codec,
+    /// Sync marker of the header, expected after every block.
+    pub marker: [u8; 16],
+    /// Layout of the blocks, in order.
+    pub blocks: Vec<BlockLayout>,
+    /// Error which stopped the walk through the blocks, e.g. on a truncated file.
+    pub error: Option<Error>,
+}
+
+/// The physical layout of a block of an Avro container file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockLayout {
+    /// Position of the block in the file, from its record count.
+    pub offset: u64,
+    /// Number of records in the block.
+    pub count: u64,
+    /// Size of the (compressed) data of the block, counts and sync marker excluded.
+    pub size: u64,
+    /// Size of the data once decompressed, `None` if it could not be decompressed.
+    pub uncompressed_size: Option<u64>,
+    /// Whether the block is followed by the sync marker of the header.
+    pub valid_marker: bool,
+}
+
+/// Walk through the blocks of an Avro container file without decoding them, reporting its
+/// physical layout, e.g. to debug bloated or corrupted files.
 ///
-/// **NOTE** This function has a quite small niche of usage and does NOT take care of reading the
-/// header and consecutive data blocks; use [`Reader`](struct.Reader.html) if you don't know what
-/// you are doing, instead.
-pub fn from_avro_datum<R: Read>(
+/// Only an invalid header is returned as an error: the walk stops at the first block which
+/// cannot be read, with the error reported in [`FileLayout::error`](struct.FileLayout.html).
+/// Blocks not followed by the sync marker are reported as such, and the walk goes on.
+pub fn inspect<R: Read>(reader: R) -> Result<FileLayout, Error> {
+    let mut block = Block::new(reader)?;
+    let mut blocks = Vec::new();
+    let header_size = block.reader.count();
+    let error = loop {
+        match block.inspect_block() {
+            Ok(Some(layout)) => blocks.push(layout),
+            Ok(None) => break None,
+            Err(e) => break Some(e),
+        }
+    };
+    Ok(FileLayout {
+        header_size,
+        codec: block.codec,
+        marker: block.marker,
+        blocks,
+        error,
+    })
+}
+
+/// Compute the byte ranges of the container file of `reader`, `file_len` bytes long, to be read by
+/// independent workers, e.g. Spark or Hadoop style.
+///
+/// Every range but the first (which starts right after the header) starts right after a sync
+/// marker, i.e. at the start of a data block, and ranges are at least `split_size` bytes long
+/// (except the last one). They are found by sampling the file: only the bytes from every
+/// `split_size` bytes to the next sync marker are read, the data blocks are not walked through.
+/// The ranges cover the whole file but its header, and an empty file gets no range.
+pub fn compute_splits<R: Read + Seek>(
+    mut reader: R,
+    file_len: u64,
+    split_size: u64,
+) -> Result<Vec<Range<u64>>, Error> {
+    reader.seek(SeekFrom::Start(0))?;
+    let (marker, mut start) = {
+        let block = Block::new(BufReader::new(&mut reader))?;
+        (block.marker, block.reader.count())
+    };
+
+    let mut splits = Vec::new();
+    let split_size = split_size.max(1);
+    while start + split_size < file_len {
+        let probe = start + split_size;
+        reader.seek(SeekFrom::Start(probe))?;
+        let end = match find_marker(&mut reader, &marker)? {
+            Some(read) if probe + read < file_len => probe + read,
+            _ => break,
+        };
+        splits.push(start..end);
+        start = end;
+    }
+    if start < file_len {
+        splits.push(start..file_len);
+    }
+    Ok(splits)
+}
+
+// Read up to the end of the next occurrence of `marker`, returning the number of bytes read, or
+// `None` if it does not occur before the end of the input.
+pub(crate) fn find_marker<R: Read>(reader: &mut R, marker: &[u8; 16]) -> io::Result<Option<u64>> {
+    let mut buf = vec![0; 64 * 1024];
+    // Bytes of `buf` held over from the previous read, in case they start the marker.
+    let mut kept = 0;
+    let mut read = 0;
+    loop {
+        let n = match reader.read(&mut buf[kept..]) {
+            Ok(0) => return Ok(None),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let len = kept + n;
+        if let Some(i) = buf[..len].windows(marker.len()).position(|w| w == marker) {
+            return Ok(Some(read + (i + marker.len()) as u64))
+        }
+        let keep = len.min(marker.len() - 1);
+        buf.copy_within(len - keep..len, 0);
+        read += (len - keep) as u64;
+        kept = keep;
+    }
+}
+
+/// Read all the values of the Avro file at `path`.
+pub fn read_all_values<P: AsRef<Path>>(path: P) -> Result<Vec<Value>, Error> {
+    Reader::new(BufReader::new(File::open(path)?))?.collect()
+}
+
+/// Read all the values of the Avro file at `path`, deserialized into `T`s.
+#[cfg(feature = "serde_layer")]
+pub fn read_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<Vec<T>, Error> {
+    Reader::new(BufReader::new(File::open(path)?))?
+        .map(|value| Ok(from_value(&value?)?))
+        .collect()
+}
+
+/// Decode a `Value` encoded in Avro format given its writer `Schema` and anything implementing
+/// `io::Read` to read from, resolving it with `resolver` if provided.
+pub(crate) fn from_avro_datum_with<R: Read>(
     writer_schema: &Schema,
     reader: &mut R,
-    reader_schema: Option<&Schema>,
+    resolver: Option<&Resolver>,
 ) -> Result<Value, Error> {
-    let value = decode(writer_schema, reader)?;
-    match reader_schema {
-        Some(ref schema) => value.resolve(schema),
-        None => Ok(value),
+    let mut reader = CountingReader::new(reader);
+    decode_with(
+        writer_schema,
+        resolver,
+        &mut ByteReader(&mut reader),
+        &mut StringPool::new(),
+    ).map_err(|e| e.at(reader.count(), None))
+}
+
+// The header of an object container file.
+pub(crate) struct Header {
+    pub schema: Schema,
+    pub codec: Codec,
+    pub marker: [u8; 16],
+    pub metadata: HashMap<String, Vec<u8>>,
+}
+
+// Read the header at the start of `bytes`, returning it with its size in bytes.
+pub(crate) fn read_header(bytes: &[u8]) -> Result<(Header, usize), Error> {
+    let mut block = Block::new(bytes)?;
+    let header = Header {
+        schema: mem::replace(&mut block.writer_schema, Schema::Null),
+        codec: block.codec,
+        marker: block.marker,
+        metadata: mem::take(&mut block.metadata),
+    };
+    Ok((header, block.reader.count() as usize))
+}
+
+// Decode a value, straight into its resolved form if a `Resolver` is given.
+pub(crate) fn decode_with<R: VarintRead>(
+    writer_schema: &Schema,
+    resolver: Option<&Resolver>,
+    reader: &mut R,
+    pool: &mut StringPool,
+) -> Result<Value, Error> {
+    match resolver {
+        Some(resolver) => resolver.decode(reader, pool),
+        None => {
+            let value = decode_interned(writer_schema, reader, pool)?;
+            to_logical(value, writer_schema)
+        },
     }
 }
 
@@ -288,8 +1438,14 @@ pub fn from_avro_datum<R: Read>(
 mod tests {
     use super::*;
     use std::io::Cursor;
+    use std::collections::HashMap;
+    #[cfg(feature = "rand")]
+    use std::sync::Arc;
+    use datum::{decode_from_slice, from_avro_datum, from_avro_datum_strict};
+    use encode::encode;
     use types::{Record, ToAvro};
     use Reader;
+    use Writer;
 
     static SCHEMA: &'static str = r#"
             {
@@ -337,6 +1493,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_from_slice() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoded: &'static [u8] = &[54, 6, 102, 111, 111, 84, 6, 98, 97, 114];
+
+        let (first, consumed) = decode_from_slice(&schema, encoded).unwrap();
+        assert_eq!(consumed, 5);
+        let (second, consumed) = decode_from_slice(&schema, &encoded[consumed..]).unwrap();
+        assert_eq!(consumed, 5);
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        assert_eq!(first, record.avro());
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 42i64);
+        record.put("b", "bar");
+        assert_eq!(second, record.avro());
+
+        match decode_from_slice(&schema, &encoded[..3]).unwrap_err() {
+            Error::AtOffset { offset, .. } => assert!(offset <= 3),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_avro_datum_strict() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let encoded: &'static [u8] = &[54, 6, 102, 111, 111, 84, 6, 98, 97, 114];
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        assert_eq!(
+            from_avro_datum_strict(&schema, &encoded[..5], None).unwrap(),
+            record.avro()
+        );
+        match from_avro_datum_strict(&schema, encoded, None).unwrap_err() {
+            Error::TrailingBytes { size, remaining } => assert_eq!((size, remaining), (5, 5)),
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        // A long written with an older schema, decoded with the current one.
+        let old_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "a", "type": "long"}]}"#,
+        ).unwrap();
+        assert!(from_avro_datum_strict(&old_schema, &encoded[..5], None).is_err());
+        let reader_schema = Schema::parse_str(UNION_SCHEMA).unwrap();
+        assert_eq!(
+            from_avro_datum_strict(&Schema::Long, &[54], Some(&reader_schema)).unwrap(),
+            Value::Long(27)
+        );
+    }
+
+    #[test]
+    fn test_decode_error_offset() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut truncated: &'static [u8] = &[54, 6, 102];
+        match from_avro_datum(&schema, &mut truncated, None).unwrap_err() {
+            Error::AtOffset { offset, block, .. } => assert_eq!((offset, block), (3, None)),
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        // The first record holds an invalid utf-8 string, ending 5 bytes into the only block.
+        let mut corrupted = ENCODED.to_vec();
+        let data_start = corrupted.len() - 16 - 10;
+        corrupted[data_start + 2] = 0xFF;
+        let mut reader = Reader::new(&corrupted[..]).unwrap();
+        match reader.next().unwrap().unwrap_err() {
+            Error::AtOffset {
+                offset,
+                block,
+                error,
+            } => {
+                assert_eq!((offset, block), (data_start as u64 + 5, Some(0)));
+                match *error {
+                    Error::InvalidUtf8 { ref path, .. } => assert_eq!(path, "$.b"),
+                    ref other => panic!("unexpected error {:?}", other),
+                }
+            },
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
     #[test]
     fn test_null_union() {
         let schema = Schema::parse_str(UNION_SCHEMA).unwrap();
@@ -363,11 +1602,98 @@ mod tests {
 
         let expected = vec![record1.avro(), record2.avro()];
 
+        assert_eq!(reader.metadata()["avro.codec"], b"null");
+        assert!(reader.metadata().contains_key("avro.schema"));
         for (i, value) in reader.enumerate() {
             assert_eq!(value.unwrap(), expected[i]);
         }
     }
 
+    // The header of `ENCODED`, with other metadata, followed by its marker and blocks.
+    fn with_metadata(metadata: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut meta = HashMap::new();
+        meta.insert("avro.schema".into(), Value::Bytes(SCHEMA.as_bytes().to_vec()));
+        for &(key, value) in metadata {
+            meta.insert(key.into(), Value::Bytes(value.to_vec()));
+        }
+
+        let mut encoded = b"Obj\x01".to_vec();
+        encode(
+            &Value::Map(meta),
+            &Schema::Map(Box::new(Schema::Bytes)),
+            &mut encoded,
+        );
+        encoded.extend_from_slice(&ENCODED[147..]);
+        encoded
+    }
+
+    #[test]
+    fn test_reader_header_metadata() {
+        let expected = Reader::new(ENCODED)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        for metadata in &[
+            &[][..],
+            &[("avro.codec", &b"null"[..])][..],
+            &[("avro.codec", &b"null"[..]), ("custom", &[0xFF, 0x00, 0xC3][..])][..],
+        ] {
+            let encoded = with_metadata(metadata);
+            let reader = Reader::new(&encoded[..]).unwrap();
+            assert_eq!(reader.metadata().len(), metadata.len() + 1);
+            for &(key, value) in metadata.iter() {
+                assert_eq!(reader.metadata()[key], value);
+            }
+            assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), expected);
+        }
+
+        let encoded = with_metadata(&[("avro.codec", b"zstandard")]);
+        match Reader::new(&encoded[..]).map(|_| ()).unwrap_err() {
+            Error::AtOffset { error, .. } => match *error {
+                Error::Decode { ref reason } => assert_eq!(reason, "unsupported codec: zstandard"),
+                ref other => panic!("unexpected error {:?}", other),
+            },
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn test_reader_zlib_deflate() {
+        let expected = Reader::new(ENCODED)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // The block of `ENCODED`, compressed and wrapped in a zlib header and (unchecked) checksum.
+        let mut data = ENCODED[165..175].to_vec();
+        Codec::Deflate.compress(&mut data).unwrap();
+        let mut zlib = vec![120, 218];
+        zlib.extend_from_slice(&data);
+        zlib.extend_from_slice(&[0, 0, 0, 0]);
+
+        let mut encoded = with_metadata(&[("avro.codec", b"deflate")]);
+        encoded.truncate(encoded.len() - (ENCODED.len() - 163));
+        util::zig_i64(2, &mut encoded);
+        util::zig_i64(zlib.len() as i64, &mut encoded);
+        encoded.extend_from_slice(&zlib);
+        encoded.extend_from_slice(&ENCODED[147..163]);
+
+        assert!(Reader::new(&encoded[..]).unwrap().any(|value| value.is_err()));
+
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        reader.set_zlib_deflate(true);
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), expected);
+
+        let mut reader = Reader::new(&encoded[..]).unwrap().into_parallel(2);
+        assert!(reader.any(|value| value.is_err()));
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        reader.set_zlib_deflate(true);
+        let values = reader.into_parallel(2).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(values, expected);
+    }
+
     #[test]
     fn test_reader_invalid_header() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
@@ -393,6 +1719,225 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_reader_into_inner() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        for &(a, b) in &[(1i64, "foo"), (2, "bar")] {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", a);
+            record.put("b", b);
+            writer.append(record).unwrap();
+        }
+        writer.flush().unwrap();
+        let mut input = writer.into_inner();
+        let avro_len = input.len() as u64;
+        input.extend_from_slice(b"trailer");
+
+        let mut reader = Reader::new(&input[..]).unwrap();
+        assert!(reader.position() > 0 && reader.position() < avro_len);
+        assert_eq!(reader.by_ref().take(2).count(), 2);
+        assert_eq!(reader.position(), avro_len);
+        let mut rest = Vec::new();
+        reader.into_inner().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailer");
+    }
+
+    #[test]
+    fn test_summarize() {
+        let encoded = with_metadata(&[("avro.codec", &b"null"[..]), ("custom", &[0xFF][..])]);
+        let summary = summarize(&encoded[..]).unwrap();
+        assert_eq!(summary.schema, Schema::parse_str(SCHEMA).unwrap());
+        assert_eq!(summary.codec, Codec::Null);
+        assert_eq!(summary.metadata.len(), 1);
+        assert_eq!(summary.metadata["custom"], vec![0xFF]);
+        assert_eq!(&summary.marker, &encoded[encoded.len() - 16..]);
+        assert_eq!((summary.block_count, summary.record_count), (1, 2));
+
+        let mut corrupted = ENCODED.to_vec();
+        let len = corrupted.len();
+        corrupted[len - 1] ^= 0xFF;
+        assert!(summarize(&corrupted[..]).is_err());
+        assert!(summarize(&ENCODED[..len - 1]).is_err());
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_inspect() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+        for &(a, b) in &[(1i64, "foo"), (2, "bar"), (3, "baz")] {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", a);
+            record.put("b", b);
+            writer.append(record).unwrap();
+            if a != 2 {
+                writer.flush().unwrap();
+            }
+        }
+        let input = writer.into_inner();
+
+        let layout = inspect(&input[..]).unwrap();
+        assert_eq!(layout.codec, Codec::Deflate);
+        assert!(layout.error.is_none());
+        assert_eq!(
+            layout.blocks.iter().map(|block| block.count).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        let (first, second) = (&layout.blocks[0], &layout.blocks[1]);
+        assert_eq!(first.offset, layout.header_size);
+        // Counts of 1 and sizes below 64 are encoded on a byte each.
+        assert_eq!(second.offset, first.offset + 2 + first.size + 16);
+        assert_eq!(second.offset + 2 + second.size + 16, input.len() as u64);
+        assert_eq!(first.uncompressed_size, Some(5));
+        assert_eq!(second.uncompressed_size, Some(10));
+        assert!(first.valid_marker && second.valid_marker);
+        assert_eq!(&input[first.offset as usize - 16..first.offset as usize], &layout.marker);
+
+        // The walk goes on past invalid markers, and stops on truncated blocks.
+        let mut corrupted = input.clone();
+        corrupted[second.offset as usize - 1] ^= 0xFF;
+        let layout = inspect(&corrupted[..]).unwrap();
+        assert_eq!(layout.blocks.len(), 2);
+        assert!(!layout.blocks[0].valid_marker && layout.blocks[1].valid_marker);
+
+        let layout = inspect(&input[..input.len() - 1]).unwrap();
+        assert_eq!(layout.blocks.len(), 1);
+        match layout.error {
+            Some(Error::AtOffset { block, .. }) => assert_eq!(block, Some(1)),
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        assert!(inspect(&b"Obj"[..]).is_err());
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_compute_splits() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+        for a in 0..10i64 {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", a);
+            record.put("b", "foo");
+            writer.append(record).unwrap();
+            writer.flush().unwrap();
+        }
+        let input = writer.into_inner();
+        let len = input.len() as u64;
+        let layout = inspect(&input[..]).unwrap();
+        let offsets = layout.blocks.iter().map(|block| block.offset).collect::<Vec<_>>();
+
+        // A split per block.
+        let splits = compute_splits(Cursor::new(&input), len, 1).unwrap();
+        let starts = splits.iter().map(|split| split.start).collect::<Vec<_>>();
+        assert_eq!(starts, offsets);
+        assert_eq!(splits.last().unwrap().end, len);
+        assert!(splits.windows(2).all(|pair| pair[0].end == pair[1].start));
+
+        // Splits of several blocks.
+        let block_size = offsets[1] - offsets[0];
+        let splits = compute_splits(Cursor::new(&input), len, 2 * block_size + 1).unwrap();
+        assert_eq!(
+            splits,
+            vec![
+                offsets[0]..offsets[3],
+                offsets[3]..offsets[6],
+                offsets[6]..offsets[9],
+                offsets[9]..len,
+            ]
+        );
+
+        assert_eq!(
+            compute_splits(Cursor::new(&input), len, len).unwrap(),
+            vec![layout.header_size..len]
+        );
+        let header = &input[..layout.header_size as usize];
+        assert!(compute_splits(Cursor::new(header), header.len() as u64, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_reader_with_range() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+        for a in 0..10i64 {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", a);
+            record.put("b", "foo");
+            writer.append(record).unwrap();
+            if a % 3 != 1 {
+                writer.flush().unwrap();
+            }
+        }
+        let input = writer.into_inner();
+        let len = input.len() as u64;
+        let expected = Reader::new(&input[..]).unwrap().map(Result::unwrap).collect::<Vec<_>>();
+        let read = |range: Range<u64>| {
+            Reader::with_range(Cursor::new(&input), range)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(read(0..len), expected);
+        for &split_size in &[1, 20, 50, len] {
+            let splits = compute_splits(Cursor::new(&input), len, split_size).unwrap();
+            let values = splits.into_iter().flat_map(&read).collect::<Vec<_>>();
+            assert_eq!(values, expected);
+        }
+        // Arbitrary bounds.
+        for mid in 0..len {
+            let mut values = read(0..mid);
+            values.extend(read(mid..len));
+            assert_eq!(values, expected, "split at {}", mid);
+        }
+        assert!(read(len..len).is_empty());
+
+        let parallel = Reader::with_range(Cursor::new(&input), 0..len / 2)
+            .unwrap()
+            .into_parallel(2)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(parallel, read(0..len / 2));
+    }
+
+    #[test]
+    fn test_find_marker() {
+        let marker = [7u8; 16];
+        let mut input = vec![0u8; 100 * 1024];
+        input[64 * 1024 - 8..64 * 1024 + 8].copy_from_slice(&marker);
+        assert_eq!(
+            find_marker(&mut &input[..], &marker).unwrap(),
+            Some(64 * 1024 + 8)
+        );
+        assert_eq!(find_marker(&mut &input[..64 * 1024], &marker).unwrap(), None);
+    }
+
+    #[test]
+    fn test_reader_is_send() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        writer.append(record).unwrap();
+        writer.flush().unwrap();
+        let input = writer.into_inner();
+
+        let reader = Reader::new(&input[..]).unwrap();
+        // A `Reader` can be moved to a worker thread.
+        let count = ::std::thread::scope(|scope| scope.spawn(move || reader.count()).join());
+        assert_eq!(count.unwrap(), 1);
+
+        // Even one which is not scoped, when it owns its input.
+        let reader = Reader::new(::std::io::Cursor::new(input)).unwrap();
+        assert_eq!(::std::thread::spawn(move || reader.count()).join().unwrap(), 1);
+    }
+
     #[test]
     fn test_reader_empty_buffer() {
         let empty = Cursor::new(Vec::new());
@@ -411,4 +1956,274 @@ mod tests {
             assert!(value.is_err());
         }
     }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_reader_interns_strings() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": {"type": "map", "values": "long"}},
+                    {"name": "b", "type": {"type": "enum", "name": "e", "symbols": ["x", "y"]}}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        for i in 0..2i64 {
+            let mut map = HashMap::new();
+            map.insert("key", i);
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", map);
+            record.put("b", Value::Enum(1, "y".into()));
+            writer.append(record).unwrap();
+        }
+        writer.flush().unwrap();
+        let input = writer.into_inner();
+
+        let strings = |value: Value| match value {
+            Value::Record(mut fields) => match (fields.remove(0).1, fields.remove(0).1) {
+                (Value::Map(map), Value::Enum(_, symbol)) => {
+                    (map.keys().next().unwrap().clone(), symbol)
+                },
+                other => panic!("unexpected fields {:?}", other),
+            },
+            other => panic!("unexpected value {:?}", other),
+        };
+        let mut reader = Reader::new(&input[..]).unwrap();
+        let (key1, symbol1) = strings(reader.next().unwrap().unwrap());
+        let (key2, symbol2) = strings(reader.next().unwrap().unwrap());
+        assert_eq!(&*key1, "key");
+        assert!(Arc::ptr_eq(&key1, &key2));
+        assert_eq!(&*symbol1, "y");
+        assert!(Arc::ptr_eq(&symbol1, &symbol2));
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_reader_reuses_block_buffers() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        for _ in 0..10 {
+            writer.append(record.clone()).unwrap();
+            writer.flush().unwrap();
+        }
+        let input = writer.into_inner();
+
+        let mut reader = Reader::new(&input[..]).unwrap();
+        let mut buffers = Vec::new();
+        for _ in 0..10 {
+            assert_eq!(reader.next().unwrap().unwrap(), record.clone().avro());
+            let mut pointers = [reader.block.buf.as_ptr(), reader.block.decompressed.as_ptr()];
+            pointers.sort();
+            buffers.push(pointers);
+        }
+        assert!(reader.next().is_none());
+        // once grown to fit a block, the two buffers are swapped back and forth
+        assert!(buffers[2..].iter().all(|pointers| *pointers == buffers[2]));
+
+        reader.shrink_to_fit();
+        assert_eq!(reader.block.decompressed.capacity(), 0);
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_reader_streams_large_blocks() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        for &codec in &[Codec::Null, Codec::Deflate] {
+            let mut writer = Writer::with_codec(&schema, Vec::new(), codec).unwrap();
+            let records = (0..20i64)
+                .map(|a| {
+                    let mut record = Record::new(&schema).unwrap();
+                    record.put("a", a);
+                    record.put("b", "foo");
+                    record.avro()
+                }).collect::<Vec<_>>();
+            // a small block, and a block larger than the maximum block size
+            writer.append(records[0].clone()).unwrap();
+            writer.flush().unwrap();
+            writer.extend(records[1..].iter().cloned()).unwrap();
+            let input = writer.into_inner();
+
+            let mut reader = Reader::new(&input[..]).unwrap();
+            reader.set_max_block_size(16);
+            let decoded = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(decoded, records);
+            assert!(reader.block.buf.len() <= 16);
+        }
+
+        // a streamed block, truncated within its last value
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        writer.extend((0..4i64).map(|a| {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", a);
+            record.put("b", "foo");
+            record
+        })).unwrap();
+        let input = writer.into_inner();
+        let mut reader = Reader::new(&input[..input.len() - 18]).unwrap();
+        reader.set_max_block_size(16);
+        assert_eq!(reader.by_ref().take(3).filter(Result::is_ok).count(), 3);
+        match reader.next().unwrap().unwrap_err() {
+            Error::AtOffset { block, .. } => assert_eq!(block, Some(0)),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_reader_read_into() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        let records = (0..5i64)
+            .map(|a| {
+                let mut record = Record::new(&schema).unwrap();
+                record.put("a", a);
+                record.put("b", "foo".repeat(a as usize));
+                record.avro()
+            }).collect::<Vec<_>>();
+        writer.extend(records.iter().cloned()).unwrap();
+        writer.flush().unwrap();
+        let input = writer.into_inner();
+
+        let mut reader = Reader::new(&input[..]).unwrap();
+        let mut value = Value::Null;
+        for record in &records {
+            assert!(reader.read_into(&mut value).unwrap());
+            assert_eq!(&value, record);
+        }
+        assert!(!reader.read_into(&mut value).unwrap());
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_parallel_reader() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+        let records = (0..50i64)
+            .map(|a| {
+                let mut record = Record::new(&schema).unwrap();
+                record.put("a", a);
+                record.put("b", format!("foo{}", a));
+                record.avro()
+            }).collect::<Vec<_>>();
+        for (i, record) in records.iter().enumerate() {
+            writer.append(record.clone()).unwrap();
+            if i % 3 == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        writer.flush().unwrap();
+        let input = writer.into_inner();
+
+        // starting within a block
+        let mut reader = Reader::new(&input[..]).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), records[0]);
+        assert_eq!(reader.next().unwrap().unwrap(), records[1]);
+        let decoded = reader
+            .into_parallel(3)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, &records[2..]);
+
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "b", "type": "string"}]}"#,
+        ).unwrap();
+        let sequential = Reader::with_schema(&reader_schema, &input[..])
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let parallel = Reader::with_schema(&reader_schema, &input[..])
+            .unwrap()
+            .into_parallel(4)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(parallel, sequential);
+
+        // a truncated input yields the values of the complete blocks, then an error
+        let mut reader = Reader::new(&input[..input.len() - 20])
+            .unwrap()
+            .into_parallel(2);
+        let decoded = reader.by_ref().take_while(Result::is_ok).count();
+        assert!(decoded > 0 && decoded < records.len());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_parallel_reader_worker_panic() {
+        // Longs whose conversion panics on 13.
+        struct Unlucky;
+
+        impl ::logical::LogicalType for Unlucky {
+            fn name(&self) -> &str {
+                "test-unlucky"
+            }
+
+            fn to_logical(&self, value: Value) -> Result<Value, String> {
+                assert_ne!(value, Value::Long(13), "unlucky");
+                Ok(value)
+            }
+        }
+
+        ::logical::register_logical_type(Unlucky);
+        let schema = Schema::parse_str(r#"{"type": "long", "logicalType": "test-unlucky"}"#)
+            .unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        for i in 10..20i64 {
+            writer.append(i).unwrap();
+            writer.flush().unwrap();
+        }
+        let input = writer.into_inner();
+
+        let mut reader = Reader::new(&input[..]).unwrap().into_parallel(2);
+        let decoded = reader.by_ref().take_while(Result::is_ok).count();
+        assert_eq!(decoded, 3);
+        assert!(reader.next().is_none());
+    }
+
+    #[cfg(feature = "arena")]
+    #[test]
+    fn test_reader_for_each_in() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        for _ in 0..3 {
+            writer.append(record.clone()).unwrap();
+            writer.append(record.clone()).unwrap();
+            writer.flush().unwrap();
+        }
+        let input = writer.into_inner();
+
+        let mut reader = Reader::new(&input[..]).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), record.clone().avro());
+        let mut arena = Bump::new();
+        let mut longs = 0;
+        let count = reader
+            .for_each_in(&mut arena, |value| {
+                if let ArenaValue::Record(fields) = value {
+                    if let ArenaValue::Long(a) = fields[0].1 {
+                        longs += a;
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(longs, 5 * 27);
+        assert!(reader.next().is_none());
+
+        let other_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "b", "type": "string"}]}"#,
+        ).unwrap();
+        let mut reader = Reader::with_schema(&other_schema, &input[..]).unwrap();
+        assert!(reader.for_each_in(&mut arena, |_| Ok(())).is_err());
+    }
 }
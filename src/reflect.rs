@@ -0,0 +1,511 @@
+//! Logic inferring the schema of Rust types from their `Deserialize` implementation, for types
+//! which cannot derive one (e.g. foreign types).
+//!
+//! The type is deserialized from a tracing `Deserializer`, which records what it is asked for
+//! and hands out placeholder values.
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, Deserialize, DeserializeSeed, Error as SerdeError, Visitor};
+
+use de::Error;
+use error;
+use schema::{Name, RecordField, RecordFieldOrder, Schema, UnionSchema};
+
+/// Infer the schema of the values of type `T`, as expected by [`from_value`](fn.from_value.html)
+/// and produced by [`to_value`](fn.to_value.html).
+///
+/// Structs become records, `Option`s unions with `null`, sequences arrays, maps with string keys
+/// maps, and enums with unit variants only enums. `u32` and `u64` become `long`s.
+///
+/// Types whose `Deserialize` implementation relies on self-describing formats (e.g. untagged
+/// enums), recursive types, and tuples holding values of different types are not supported.
+/// Types validating their values (e.g. rejecting zeros) may reject the placeholder values.
+pub fn schema_for<T: Deserialize<'static>>() -> Result<Schema, error::Error> {
+    let mut state = State {
+        pass: 0,
+        max_variants: 0,
+        records: Vec::new(),
+    };
+    // The variants of every enum are visited in turn, one per pass.
+    loop {
+        let schema = {
+            let mut tracer = Tracer::new(&mut state);
+            T::deserialize(&mut tracer)?;
+            tracer.take()?
+        };
+        state.pass += 1;
+        if state.pass >= state.max_variants {
+            return Ok(schema)
+        }
+    }
+}
+
+struct State {
+    pass: usize,
+    max_variants: usize,
+    // Names of the records being traced, to detect recursive types.
+    records: Vec<&'static str>,
+}
+
+struct Tracer<'a> {
+    state: &'a mut State,
+    schema: Option<Schema>,
+}
+
+impl<'a> Tracer<'a> {
+    fn new(state: &'a mut State) -> Tracer<'a> {
+        Tracer {
+            state,
+            schema: None,
+        }
+    }
+
+    fn take(&mut self) -> Result<Schema, Error> {
+        self.schema
+            .take()
+            .ok_or_else(|| Error::custom("nothing was deserialized"))
+    }
+
+    // Trace the value deserialized by `seed`, returning it along with its schema.
+    fn trace<S>(&mut self, seed: S) -> Result<(S::Value, Schema), Error>
+    where
+        S: DeserializeSeed<'static>,
+    {
+        let mut tracer = Tracer::new(self.state);
+        let value = seed.deserialize(&mut tracer)?;
+        Ok((value, tracer.take()?))
+    }
+
+    fn traced<T>(&mut self, value: T, schema: Schema) -> Result<T, Error> {
+        self.schema = Some(schema);
+        Ok(value)
+    }
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $visit:ident, $value:expr, $schema:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'static>,
+        {
+            let value = visitor.$visit($value)?;
+            self.traced(value, $schema)
+        }
+    };
+}
+
+impl<'a, 'b> de::Deserializer<'static> for &'b mut Tracer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        Err(Error::custom("cannot infer the schema of a self-describing type"))
+    }
+
+    trace_primitive!(deserialize_bool, visit_bool, false, Schema::Boolean);
+    trace_primitive!(deserialize_i8, visit_i8, 0, Schema::Int);
+    trace_primitive!(deserialize_i16, visit_i16, 0, Schema::Int);
+    trace_primitive!(deserialize_i32, visit_i32, 0, Schema::Int);
+    trace_primitive!(deserialize_i64, visit_i64, 0, Schema::Long);
+    trace_primitive!(deserialize_u8, visit_u8, 0, Schema::Int);
+    trace_primitive!(deserialize_u16, visit_u16, 0, Schema::Int);
+    trace_primitive!(deserialize_u32, visit_u32, 0, Schema::Long);
+    trace_primitive!(deserialize_u64, visit_u64, 0, Schema::Long);
+    trace_primitive!(deserialize_f32, visit_f32, 0.0, Schema::Float);
+    trace_primitive!(deserialize_f64, visit_f64, 0.0, Schema::Double);
+    trace_primitive!(deserialize_char, visit_char, 'a', Schema::String);
+    trace_primitive!(deserialize_str, visit_borrowed_str, "", Schema::String);
+    trace_primitive!(deserialize_string, visit_borrowed_str, "", Schema::String);
+    trace_primitive!(deserialize_bytes, visit_borrowed_bytes, &[], Schema::Bytes);
+    trace_primitive!(deserialize_byte_buf, visit_borrowed_bytes, &[], Schema::Bytes);
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        let (value, schema) = self.trace(OptionSeed(visitor))?;
+        let mut variants = match schema {
+            Schema::Union(union) => union.variants().to_vec(),
+            schema => vec![schema],
+        };
+        if !variants.contains(&Schema::Null) {
+            variants.insert(0, Schema::Null);
+        }
+        let union = UnionSchema::new(variants).map_err(|e| Error::custom(e.to_string()))?;
+        self.traced(value, Schema::Union(union))
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        let value = visitor.visit_unit()?;
+        self.traced(value, Schema::Null)
+    }
+
+    fn deserialize_unit_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'static>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'static>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        self.deserialize_tuple(1, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        let mut items = SeqTracer {
+            tracer: Tracer::new(self.state),
+            len,
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_seq(&mut items)?;
+        let mut schemas = items.schemas.into_iter();
+        let schema = schemas
+            .next()
+            .ok_or_else(|| Error::custom("cannot infer the schema of empty sequences"))?;
+        if schemas.any(|other| other != schema) {
+            return Err(Error::custom("tuples must hold values of the same type"))
+        }
+        self.traced(value, Schema::Array(Box::new(schema)))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        let mut entries = MapTracer {
+            tracer: Tracer::new(self.state),
+            done: false,
+            schema: None,
+        };
+        let value = visitor.visit_map(&mut entries)?;
+        let schema = entries
+            .schema
+            .ok_or_else(|| Error::custom("cannot infer the schema of empty maps"))?;
+        self.traced(value, Schema::Map(Box::new(schema)))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        if self.state.records.contains(&name) {
+            return Err(Error::custom(format!(
+                "cannot infer the schema of recursive type {}",
+                name
+            )))
+        }
+        self.state.records.push(name);
+        let mut record = StructTracer {
+            tracer: Tracer::new(self.state),
+            fields: fields.iter(),
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_map(&mut record);
+        let schemas = record.schemas;
+        self.state.records.pop();
+
+        let fields = schemas
+            .into_iter()
+            .enumerate()
+            .map(|(position, (name, schema))| RecordField {
+                name: name.to_owned(),
+                doc: None,
+                default: match schema {
+                    Schema::Union(ref union) if union.variants()[0] == Schema::Null => {
+                        Some(::serde_json::Value::Null)
+                    },
+                    _ => None,
+                },
+                schema,
+                order: RecordFieldOrder::Ascending,
+                position,
+            }).collect::<Vec<_>>();
+        let lookup = fields
+            .iter()
+            .map(|field| (field.name.clone(), field.position))
+            .collect();
+        self.traced(
+            value?,
+            Schema::Record {
+                name: Name::new(name),
+                doc: None,
+                fields,
+                lookup,
+            },
+        )
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        let variant = variants
+            .get(self.state.pass)
+            .or_else(|| variants.first())
+            .ok_or_else(|| Error::custom(format!("enum {} has no variants", name)))?;
+        self.state.max_variants = self.state.max_variants.max(variants.len());
+        // Only unit variants are accepted.
+        let value = visitor.visit_enum(BorrowedStrDeserializer::<Error>::new(variant))?;
+        self.traced(
+            value,
+            Schema::Enum {
+                name: Name::new(name),
+                doc: None,
+                symbols: variants.iter().map(|&variant| variant.to_owned()).collect(),
+                default: None,
+            },
+        )
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'static>,
+    {
+        self.deserialize_unit(visitor)
+    }
+}
+
+// Deserializes the value of an `Option` as present.
+struct OptionSeed<V>(V);
+
+impl<V: Visitor<'static>> DeserializeSeed<'static> for OptionSeed<V> {
+    type Value = V::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: de::Deserializer<'static>,
+    {
+        self.0.visit_some(deserializer)
+    }
+}
+
+// Hands out `len` items, recording their schemas.
+struct SeqTracer<'a> {
+    tracer: Tracer<'a>,
+    len: usize,
+    schemas: Vec<Schema>,
+}
+
+impl<'a, 'b> de::SeqAccess<'static> for &'b mut SeqTracer<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'static>,
+    {
+        if self.schemas.len() == self.len {
+            return Ok(None)
+        }
+        let (value, schema) = self.tracer.trace(seed)?;
+        self.schemas.push(schema);
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.schemas.len())
+    }
+}
+
+// Hands out a single entry, recording the schema of its value.
+struct MapTracer<'a> {
+    tracer: Tracer<'a>,
+    done: bool,
+    schema: Option<Schema>,
+}
+
+impl<'a, 'b> de::MapAccess<'static> for &'b mut MapTracer<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'static>,
+    {
+        if self.done {
+            return Ok(None)
+        }
+        self.done = true;
+        match self.tracer.trace(seed)? {
+            (key, Schema::String) => Ok(Some(key)),
+            _ => Err(Error::custom("map keys must be strings")),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'static>,
+    {
+        let (value, schema) = self.tracer.trace(seed)?;
+        self.schema = Some(schema);
+        Ok(value)
+    }
+}
+
+// Hands out the fields of a struct, recording their schemas.
+struct StructTracer<'a> {
+    tracer: Tracer<'a>,
+    fields: ::std::slice::Iter<'static, &'static str>,
+    schemas: Vec<(&'static str, Schema)>,
+}
+
+impl<'a, 'b> de::MapAccess<'static> for &'b mut StructTracer<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'static>,
+    {
+        match self.fields.clone().next() {
+            Some(&field) => seed
+                .deserialize(BorrowedStrDeserializer::new(field))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'static>,
+    {
+        let field = self
+            .fields
+            .next()
+            .ok_or_else(|| Error::custom("no field left"))?;
+        let (value, schema) = self.tracer.trace(seed)?;
+        self.schemas.push((field, schema));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use de::from_value;
+    use ser::to_value;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    enum Suit {
+        Hearts,
+        Spades,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Point(i64, i64);
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Test {
+        a: i32,
+        b: Option<String>,
+        c: Vec<f64>,
+        d: HashMap<String, bool>,
+        e: Suit,
+        f: Point,
+        g: Vec<u8>,
+    }
+
+    #[test]
+    fn test_schema_for() {
+        let expected = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "Test",
+                "fields": [
+                    {"name": "a", "type": "int"},
+                    {"name": "b", "type": ["null", "string"], "default": null},
+                    {"name": "c", "type": {"type": "array", "items": "double"}},
+                    {"name": "d", "type": {"type": "map", "values": "boolean"}},
+                    {
+                        "name": "e",
+                        "type": {"type": "enum", "name": "Suit", "symbols": ["Hearts", "Spades"]}
+                    },
+                    {"name": "f", "type": {"type": "array", "items": "long"}},
+                    {"name": "g", "type": {"type": "array", "items": "int"}}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let schema = schema_for::<Test>().unwrap();
+        assert_eq!(schema, expected);
+
+        let mut d = HashMap::new();
+        d.insert("x".to_owned(), true);
+        let test = Test {
+            a: 1,
+            b: Some("foo".to_owned()),
+            c: vec![1.5],
+            d,
+            e: Suit::Spades,
+            f: Point(2, 3),
+            g: vec![4, 5],
+        };
+        let value = to_value(&test).unwrap();
+        assert!(value.validate(&schema));
+        assert_eq!(from_value::<Test>(&value).unwrap(), test);
+    }
+
+    #[test]
+    fn test_schema_for_unsupported() {
+        #[derive(Deserialize)]
+        struct Node {
+            _next: Option<Box<Node>>,
+        }
+
+        #[derive(Deserialize)]
+        enum Shape {
+            _Circle(f64),
+        }
+
+        assert!(schema_for::<Node>().is_err());
+        assert!(schema_for::<Shape>().is_err());
+        assert!(schema_for::<(i32, String)>().is_err());
+        assert!(schema_for::<::serde_json::Value>().is_err());
+    }
+}
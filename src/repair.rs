@@ -0,0 +1,209 @@
+//! Logic salvaging the records of corrupt object container files, skipping the data blocks that
+//! cannot be decoded.
+use std::io::{Read, Write};
+
+use decode::StringPool;
+use error::Error;
+use push::read_block_header;
+use reader::{decode_with, read_header, Header};
+use types::Value;
+use util::DecodeError;
+use writer::Writer;
+use Codec;
+
+/// A range of bytes of a container file that could not be decoded, see
+/// [`repair`](fn.repair.html).
+#[derive(Debug)]
+pub struct LostBlock {
+    /// Position of the first lost byte in the file.
+    pub offset: u64,
+    /// Number of bytes lost, up to the next data block found (or the end of the file).
+    pub size: u64,
+    /// Number of records the first lost data block claimed to hold, if known.
+    pub records: Option<u64>,
+    /// Why the data block could not be decoded.
+    pub error: Error,
+}
+
+/// The outcome of [`repair`](fn.repair.html).
+#[derive(Debug)]
+pub struct RepairReport {
+    /// Number of records salvaged.
+    pub records: u64,
+    /// The ranges of bytes lost, in the order of the file.
+    pub lost: Vec<LostBlock>,
+}
+
+impl RepairReport {
+    /// Number of records known to be lost (the records of data blocks whose count could not be
+    /// read are not counted).
+    pub fn lost_records(&self) -> u64 {
+        self.lost.iter().filter_map(|lost| lost.records).sum()
+    }
+
+    /// Number of bytes lost.
+    pub fn lost_bytes(&self) -> u64 {
+        self.lost.iter().map(|lost| lost.size).sum()
+    }
+}
+
+/// Read the object container file of `input` in tolerant mode and write the records salvaged
+/// from it to a new container file, with the same schema, codec and sync marker, into `output`.
+///
+/// Data blocks that cannot be decoded are skipped whole, decoding resuming after the next sync
+/// marker of the file. Only a corrupt header is an error, as nothing can be decoded without
+/// the schema. The input is read in memory, and its user metadata is not kept.
+pub fn repair<R: Read, W: Write>(mut input: R, output: W) -> Result<RepairReport, Error> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let (header, mut position) = read_header(&bytes)?;
+
+    let mut writer = Writer::with_marker(&header.schema, output, header.codec, header.marker)?;
+    let mut report = RepairReport {
+        records: 0,
+        lost: Vec::new(),
+    };
+    let mut pool = StringPool::new();
+    let mut decompressed = Vec::new();
+    while position < bytes.len() {
+        let input = &bytes[position..];
+        let (records, error) = match decode_block(&header, input, &mut pool, &mut decompressed) {
+            Ok((values, len)) => {
+                for value in &values {
+                    writer.append_value_ref(value)?;
+                }
+                report.records += values.len() as u64;
+                position += len;
+                continue
+            },
+            Err(lost) => lost,
+        };
+        // The next data block follows the next sync marker.
+        let size = input[1..]
+            .windows(header.marker.len())
+            .position(|window| *window == header.marker)
+            .map_or(input.len(), |i| 1 + i + header.marker.len());
+        report.lost.push(LostBlock {
+            offset: position as u64,
+            size: size as u64,
+            records,
+            error,
+        });
+        position += size;
+    }
+    writer.flush()?;
+    Ok(report)
+}
+
+// Decode the data block at the start of `input`, returning its values and its size, or the
+// number of values it claims to hold (if known) with the error decoding it.
+fn decode_block(
+    header: &Header,
+    input: &[u8],
+    pool: &mut StringPool,
+    decompressed: &mut Vec<u8>,
+) -> Result<(Vec<Value>, usize), (Option<u64>, Error)> {
+    let mut rest = input;
+    let (count, size) = read_block_header(&mut rest).map_err(|e| (None, e))?;
+    let lost = |e: Error| (Some(count as u64), e);
+    if rest.len() < size + header.marker.len() {
+        return Err(lost(DecodeError::new("truncated data block").into()))
+    }
+    let (data, rest) = rest.split_at(size);
+    if rest[..header.marker.len()] != header.marker {
+        return Err(lost(DecodeError::new("block marker does not match header marker").into()))
+    }
+
+    let mut data = match header.codec {
+        Codec::Null => data,
+        codec => {
+            decompressed.clear();
+            codec
+                .block_decompressor(data, false)
+                .and_then(|mut decompressor| Ok(decompressor.read_to_end(decompressed)?))
+                .map_err(lost)?;
+            &decompressed[..]
+        },
+    };
+    let values = (0..count)
+        .map(|_| decode_with(&header.schema, None, &mut data, pool))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(lost)?;
+    if !data.is_empty() {
+        return Err(lost(DecodeError::new("trailing bytes in data block").into()))
+    }
+    Ok((values, input.len() - rest.len() + header.marker.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+    use schema::Schema;
+
+    fn write(codec: Codec) -> (Vec<u8>, Vec<usize>) {
+        let schema = Schema::parse_str(r#"{"type": "array", "items": "string"}"#).unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), codec, [7u8; 16]).unwrap();
+        let mut sizes = Vec::new();
+        for i in 0..3 {
+            writer.append(Value::Array(vec![Value::String(i.to_string()); 3])).unwrap();
+            writer.append(Value::Array(vec![])).unwrap();
+            sizes.push(writer.flush().unwrap());
+        }
+        let encoded = writer.into_inner();
+        // The end of each data block.
+        let mut end = encoded.len() - sizes.iter().sum::<usize>();
+        let ends = sizes
+            .iter()
+            .map(|size| {
+                end += size;
+                end
+            }).collect();
+        (encoded, ends)
+    }
+
+    fn values(encoded: &[u8]) -> Vec<Value> {
+        Reader::new(encoded).unwrap().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_repair() {
+        for &codec in &[Codec::Null, #[cfg(feature = "deflate_codec")] Codec::Deflate] {
+            let (encoded, ends) = write(codec);
+
+            // Nothing lost.
+            let mut repaired = Vec::new();
+            let report = repair(&encoded[..], &mut repaired).unwrap();
+            assert_eq!((report.records, report.lost.len()), (6, 0));
+            assert_eq!(values(&repaired), values(&encoded));
+
+            // A corrupt block in the middle.
+            let mut corrupt = encoded.clone();
+            corrupt[ends[0] + 4] ^= 0xFF;
+            corrupt[ends[0] + 5] ^= 0xFF;
+            let mut repaired = Vec::new();
+            let report = repair(&corrupt[..], &mut repaired).unwrap();
+            assert_eq!(report.records, 4);
+            assert_eq!(report.lost.len(), 1);
+            assert_eq!(report.lost[0].offset, ends[0] as u64);
+            assert_eq!(report.lost_bytes(), (ends[1] - ends[0]) as u64);
+            let mut expected = values(&encoded);
+            expected.drain(2..4);
+            assert_eq!(values(&repaired), expected);
+
+            // A truncated last block.
+            let mut repaired = Vec::new();
+            let report = repair(&encoded[..encoded.len() - 3], &mut repaired).unwrap();
+            assert_eq!(report.records, 4);
+            assert_eq!(report.lost_records(), 2);
+            assert_eq!(report.lost_bytes(), (ends[2] - ends[1] - 3) as u64);
+        }
+    }
+
+    #[test]
+    fn test_repair_header() {
+        let (encoded, _) = write(Codec::Null);
+        assert!(repair(&encoded[..10], Vec::new()).is_err());
+        assert!(repair(&b"Obj\x02"[..], Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,1010 @@
+//! Logic for resolving values between a writer and a reader schema, using a plan computed once.
+use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+use decode::{decode_block_len, decode_interned, enum_index_out_of_bounds, skip, StringPool};
+use error::Error;
+use logical::{has_logical, to_logical};
+use schema::{RecordField, Schema, SchemaKind, UnionSchema};
+use types::{SchemaResolutionError, ToAvro, Value};
+use util::{safe_depth, zag_i32, zag_i64, DecodeError, VarintRead};
+
+/// Resolution of the values decoded with a writer `Schema` against a reader `Schema`.
+///
+/// The steps needed to resolve a value (which writer fields go where, which values get promoted,
+/// which defaults get filled in and how union branches and enum symbols get re-mapped) are
+/// computed once, upon creation of the `Resolver`, instead of walking both schemas for every
+/// value. The resolved values are the same as the ones given by
+/// [`Value::resolve`](types/enum.Value.html#method.resolve).
+///
+/// Values which cannot be resolved (e.g. a record missing a field without a default, or an enum
+/// symbol unknown to the reader) fail to resolve when met.
+///
+/// The plan is also compiled into a flat sequence of instructions, which readers interpret to
+/// decode values written with the writer `Schema` straight into resolved values.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    plan: Plan,
+    program: Vec<Op>,
+    // The reader schema, only if the decoded values have logical types to convert.
+    logical: Option<Schema>,
+}
+
+// How to resolve a value with a given writer schema.
+#[derive(Clone, Debug)]
+enum Plan {
+    // The value is kept as it is.
+    Identity,
+    Promote(SchemaKind),
+    // The reader symbol of each writer symbol, if any.
+    Enum(Vec<Option<(i32, Arc<str>)>>),
+    Array(Box<Plan>),
+    Map(Box<Plan>),
+    Record(RecordPlan),
+    // The plan of each branch of the writer union, if any of the reader schema matches it.
+    Union(UnionSchema, Vec<Plan>),
+    Error(String),
+}
+
+#[derive(Clone, Debug)]
+struct RecordPlan {
+    // The names of the reader fields.
+    names: Vec<String>,
+    // Position among the reader fields, and plan, of each writer field (`None` if the field is
+    // skipped).
+    writer_fields: Vec<Option<(usize, Plan)>>,
+    // The resolved defaults of the reader fields missing from the writer schema.
+    defaults: Vec<(usize, Value)>,
+}
+
+impl Resolver {
+    /// Compute the resolution of the values decoded with `writer_schema` against
+    /// `reader_schema`.
+    pub fn new(writer_schema: &Schema, reader_schema: &Schema) -> Resolver {
+        let plan = Plan::new(writer_schema, reader_schema);
+        let mut program = Vec::new();
+        compile(writer_schema, &plan, &mut program);
+        let logical = if has_logical(reader_schema) {
+            Some(reader_schema.clone())
+        } else {
+            None
+        };
+        Resolver {
+            plan,
+            program,
+            logical,
+        }
+    }
+
+    /// Resolve a value decoded with the writer `Schema`.
+    pub fn resolve(&self, value: Value) -> Result<Value, Error> {
+        self.plan.apply(value)
+    }
+
+    /// Decode a value encoded with the writer `Schema` and resolve it, in a single pass, into
+    /// the logical representation of the reader `Schema`.
+    pub(crate) fn decode<R: VarintRead>(
+        &self,
+        reader: &mut R,
+        pool: &mut StringPool,
+    ) -> Result<Value, Error> {
+        let value = Machine::default().run(&self.program, reader, pool)?;
+        match self.logical {
+            Some(ref reader_schema) => to_logical(value, reader_schema),
+            None => Ok(value),
+        }
+    }
+}
+
+impl Plan {
+    fn new(writer: &Schema, reader: &Schema) -> Plan {
+        match (writer, reader) {
+            // Values are resolved in their underlying representation.
+            (Schema::Logical(writer), _) => Plan::new(writer.schema(), reader),
+            (_, Schema::Logical(reader)) => Plan::new(writer, reader.schema()),
+            (Schema::Union(writer), Schema::Union(reader)) => Plan::Union(
+                writer.clone(),
+                writer
+                    .variants()
+                    .iter()
+                    .map(|branch| Plan::to_union(branch, reader))
+                    .collect(),
+            ),
+            (Schema::Union(writer), _) => Plan::Union(
+                writer.clone(),
+                writer
+                    .variants()
+                    .iter()
+                    .map(|branch| Plan::new(branch, reader))
+                    .collect(),
+            ),
+            (_, Schema::Union(reader)) => Plan::to_union(writer, reader),
+            (Schema::Record { fields, .. }, Schema::Record { fields: reader_fields, .. }) => {
+                Plan::record(fields, reader_fields)
+            },
+            (
+                Schema::Enum { symbols, .. },
+                Schema::Enum {
+                    symbols: reader_symbols,
+                    default,
+                    ..
+                },
+            ) => {
+                if symbols == reader_symbols {
+                    return Plan::Identity
+                }
+                // Unknown writer symbols resolve to the reader default, if any.
+                let position = |symbol: &str| {
+                    reader_symbols
+                        .iter()
+                        .position(|reader_symbol| reader_symbol == symbol)
+                        .map(|index| (index as i32, symbol.into()))
+                };
+                Plan::Enum(
+                    symbols
+                        .iter()
+                        .map(|symbol| {
+                            position(symbol).or_else(|| default.as_deref().and_then(position))
+                        }).collect(),
+                )
+            },
+            (Schema::Fixed { size, .. }, Schema::Fixed { size: reader_size, .. }) => {
+                if size == reader_size {
+                    Plan::Identity
+                } else {
+                    Plan::Error(format!(
+                        "Fixed size mismatch, {} expected, got {}",
+                        reader_size, size
+                    ))
+                }
+            },
+            (Schema::Array(items), Schema::Array(reader_items)) => {
+                match Plan::new(items, reader_items) {
+                    Plan::Identity => Plan::Identity,
+                    plan => Plan::Array(Box::new(plan)),
+                }
+            },
+            (Schema::Map(values), Schema::Map(reader_values)) => {
+                match Plan::new(values, reader_values) {
+                    Plan::Identity => Plan::Identity,
+                    plan => Plan::Map(Box::new(plan)),
+                }
+            },
+            _ => {
+                let (kind, reader_kind) = (SchemaKind::from(writer), SchemaKind::from(reader));
+                match (kind, reader_kind) {
+                    _ if kind == reader_kind => Plan::Identity,
+                    (SchemaKind::Int, SchemaKind::Long)
+                    | (SchemaKind::Int, SchemaKind::Float)
+                    | (SchemaKind::Int, SchemaKind::Double)
+                    | (SchemaKind::Long, SchemaKind::Float)
+                    | (SchemaKind::Long, SchemaKind::Double)
+                    | (SchemaKind::Float, SchemaKind::Double)
+                    | (SchemaKind::String, SchemaKind::Bytes)
+                    | (SchemaKind::Bytes, SchemaKind::String) => Plan::Promote(reader_kind),
+                    _ => Plan::Error(format!("{:?} expected, got {:?}", reader_kind, kind)),
+                }
+            },
+        }
+    }
+
+    // The plan of a writer schema against the first matching branch of a reader union: the one
+    // of the same kind, or else the first one it can be promoted to.
+    fn to_union(writer: &Schema, reader: &UnionSchema) -> Plan {
+        let kind = SchemaKind::from(writer);
+        let same_kind = reader
+            .variants()
+            .iter()
+            .position(|branch| SchemaKind::from(branch) == kind);
+        let branches = same_kind
+            .into_iter()
+            .chain(0..reader.variants().len())
+            .map(|index| (index, Plan::new(writer, &reader.variants()[index])));
+        for (index, plan) in branches {
+            if let Plan::Error(_) = plan {
+                continue
+            }
+            trace_event!(DEBUG, index, schema = ?writer, "chose union branch");
+            return plan
+        }
+        Plan::Error("Could not find matching type in union".to_owned())
+    }
+
+    fn record(fields: &[RecordField], reader_fields: &[RecordField]) -> Plan {
+        let reader_positions = reader_fields
+            .iter()
+            .enumerate()
+            .map(|(position, field)| (field.name.as_str(), position))
+            .collect::<HashMap<_, _>>();
+        let writer_fields = fields
+            .iter()
+            .map(|field| {
+                reader_positions.get(field.name.as_str()).map(|&position| {
+                    (position, Plan::new(&field.schema, &reader_fields[position].schema))
+                })
+            }).collect::<Vec<_>>();
+
+        let mut defaults = Vec::new();
+        for (position, reader_field) in reader_fields.iter().enumerate() {
+            if fields.iter().any(|field| field.name == reader_field.name) {
+                continue
+            }
+            let default = match reader_field.default {
+                Some(ref default) => default.clone().avro().resolve(&reader_field.schema),
+                None => {
+                    return Plan::Error(format!("missing field {} in record", reader_field.name))
+                },
+            };
+            match default {
+                Ok(default) => {
+                    trace_event!(
+                        DEBUG,
+                        field = %reader_field.name,
+                        default = ?default,
+                        "applied field default"
+                    );
+                    defaults.push((position, default))
+                },
+                Err(e) => return Plan::Error(e.to_string()),
+            }
+        }
+
+        let is_identity = defaults.is_empty()
+            && fields.len() == reader_fields.len()
+            && writer_fields.iter().enumerate().all(|(i, field)| match field {
+                Some((position, Plan::Identity)) => *position == i,
+                _ => false,
+            });
+        if is_identity {
+            return Plan::Identity
+        }
+
+        Plan::Record(RecordPlan {
+            names: reader_fields.iter().map(|field| field.name.clone()).collect(),
+            writer_fields,
+            defaults,
+        })
+    }
+
+    fn apply(&self, value: Value) -> Result<Value, Error> {
+        match (self, value) {
+            (Plan::Identity, value) => Ok(value),
+            (Plan::Promote(kind), value) => promote(value, *kind),
+            (Plan::Enum(symbols), Value::Enum(index, symbol)) => {
+                match symbols.get(index as usize) {
+                    Some(Some((index, symbol))) => Ok(Value::Enum(*index, symbol.clone())),
+                    _ => Err(mismatch(format!("Enum symbol {} is unknown to the reader", symbol))),
+                }
+            },
+            (Plan::Array(plan), Value::Array(items)) => Ok(Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| plan.apply(item))
+                    .collect::<Result<_, _>>()?,
+            )),
+            (Plan::Map(plan), Value::Map(items)) => Ok(Value::Map(
+                items
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, plan.apply(value)?)))
+                    .collect::<Result<_, Error>>()?,
+            )),
+            (Plan::Record(plan), Value::Record(fields)) => plan.apply(fields),
+            (Plan::Union(writer, branches), Value::Union(value)) => {
+                match writer.find_schema(&value) {
+                    Some((index, _)) => branches[index].apply(*value),
+                    None => Err(mismatch("Could not find matching type in union")),
+                }
+            },
+            (Plan::Error(reason), _) => Err(mismatch(reason.as_str())),
+            (plan, value) => Err(mismatch(format!("{:?} cannot resolve {:?}", plan, value))),
+        }
+    }
+}
+
+impl RecordPlan {
+    fn apply(&self, fields: Vec<(String, Value)>) -> Result<Value, Error> {
+        let mut values = vec![None; self.names.len()];
+        for ((_, value), field) in fields.into_iter().zip(&self.writer_fields) {
+            if let Some((position, plan)) = field {
+                values[*position] = Some(plan.apply(value)?);
+            }
+        }
+        for (position, default) in &self.defaults {
+            values[*position] = Some(default.clone());
+        }
+
+        Ok(Value::Record(
+            self.names
+                .iter()
+                .zip(values)
+                .map(|(name, value)| {
+                    let value = value.ok_or_else(|| mismatch(format!("missing field {}", name)))?;
+                    Ok((name.clone(), value))
+                }).collect::<Result<_, Error>>()?,
+        ))
+    }
+}
+
+// An instruction of a compiled `Plan`, operating on a stack of values and a stack of the
+// arrays, maps and records being decoded.
+#[derive(Clone, Debug)]
+enum Op {
+    // Decode a value whose writer and reader schemas match, and push it.
+    Decode(Schema),
+    Skip(Schema),
+    Promote(Schema, SchemaKind),
+    // The writer symbols, and the reader symbol of each of them, if any.
+    Enum(Vec<String>, Vec<Option<(i32, Arc<str>)>>),
+    ArrayStart,
+    // Start the next item, reading the next block if needed, or else push the array and jump to
+    // the given instruction.
+    ArrayNext(usize),
+    // Pop the item, and jump back to the `ArrayNext` instruction.
+    ArrayPush(usize),
+    MapStart,
+    MapNext(usize),
+    MapInsert(usize),
+    RecordStart(usize),
+    // Pop the value of the reader field at the given position.
+    SetField(usize),
+    Default(usize, Value),
+    RecordEnd(Vec<String>),
+    // Jump to the instructions of the writer branch.
+    Union(Vec<usize>),
+    Jump(usize),
+    Fail(String),
+}
+
+enum Frame {
+    Array(Vec<Value>, usize),
+    Map(HashMap<Arc<str>, Value>, usize, Option<Arc<str>>),
+    Record(Vec<Option<Value>>),
+}
+
+fn compile(writer: &Schema, plan: &Plan, program: &mut Vec<Op>) {
+    match (writer, plan) {
+        (Schema::Logical(logical), _) => compile(logical.schema(), plan, program),
+        (_, Plan::Identity) => program.push(Op::Decode(writer.clone())),
+        (_, Plan::Promote(kind)) => program.push(Op::Promote(writer.clone(), *kind)),
+        (Schema::Enum { symbols, .. }, Plan::Enum(reader_symbols)) => {
+            program.push(Op::Enum(symbols.clone(), reader_symbols.clone()))
+        },
+        (Schema::Array(items), Plan::Array(plan)) => {
+            program.push(Op::ArrayStart);
+            let next = program.len();
+            program.push(Op::ArrayNext(0));
+            compile(items, plan, program);
+            program.push(Op::ArrayPush(next));
+            program[next] = Op::ArrayNext(program.len());
+        },
+        (Schema::Map(values), Plan::Map(plan)) => {
+            program.push(Op::MapStart);
+            let next = program.len();
+            program.push(Op::MapNext(0));
+            compile(values, plan, program);
+            program.push(Op::MapInsert(next));
+            program[next] = Op::MapNext(program.len());
+        },
+        (Schema::Record { fields, .. }, Plan::Record(plan)) => {
+            program.push(Op::RecordStart(plan.names.len()));
+            for (field, writer_field) in fields.iter().zip(&plan.writer_fields) {
+                match writer_field {
+                    Some((position, plan)) => {
+                        compile(&field.schema, plan, program);
+                        program.push(Op::SetField(*position));
+                    },
+                    None => program.push(Op::Skip(field.schema.clone())),
+                }
+            }
+            for (position, default) in &plan.defaults {
+                program.push(Op::Default(*position, default.clone()));
+            }
+            program.push(Op::RecordEnd(plan.names.clone()));
+        },
+        (Schema::Union(union), Plan::Union(_, branches)) => {
+            let start = program.len();
+            program.push(Op::Union(Vec::new()));
+            let mut starts = Vec::with_capacity(branches.len());
+            let mut jumps = Vec::with_capacity(branches.len());
+            for (variant, plan) in union.variants().iter().zip(branches) {
+                starts.push(program.len());
+                compile(variant, plan, program);
+                jumps.push(program.len());
+                program.push(Op::Jump(0));
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Op::Jump(end);
+            }
+            program[start] = Op::Union(starts);
+        },
+        (_, Plan::Error(reason)) => program.push(Op::Fail(reason.clone())),
+        (_, plan) => program.push(Op::Fail(format!("{:?} cannot resolve {:?}", plan, writer))),
+    }
+}
+
+#[derive(Default)]
+struct Machine {
+    values: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+impl Machine {
+    fn run<R: VarintRead>(
+        mut self,
+        program: &[Op],
+        reader: &mut R,
+        pool: &mut StringPool,
+    ) -> Result<Value, Error> {
+        let mut pc = 0;
+        while pc < program.len() {
+            pc = match program[pc] {
+                Op::Decode(ref schema) => {
+                    self.values.push(decode_interned(schema, reader, pool)?);
+                    pc + 1
+                },
+                Op::Skip(ref schema) => {
+                    skip(schema, reader)?;
+                    pc + 1
+                },
+                Op::Promote(ref schema, kind) => {
+                    let value = decode_interned(schema, reader, pool)?;
+                    self.values.push(promote(value, kind)?);
+                    pc + 1
+                },
+                Op::Enum(ref writer_symbols, ref symbols) => {
+                    let index = zag_i32(reader)?;
+                    match symbols.get(index as usize) {
+                        Some(Some((index, symbol))) => {
+                            self.values.push(Value::Enum(*index, symbol.clone()))
+                        },
+                        Some(None) => {
+                            return Err(mismatch(format!(
+                                "Enum symbol {} is unknown to the reader",
+                                writer_symbols[index as usize]
+                            )))
+                        },
+                        _ => return Err(enum_index_out_of_bounds(index, writer_symbols)),
+                    }
+                    pc + 1
+                },
+                Op::ArrayStart => {
+                    self.push_frame(Frame::Array(Vec::new(), 0))?;
+                    pc + 1
+                },
+                Op::ArrayNext(end) => {
+                    let done = match self.frames.last_mut() {
+                        Some(Frame::Array(items, left)) => next_item(reader, left, |len| {
+                            items.reserve(len)
+                        })?,
+                        _ => unreachable!(),
+                    };
+                    if done {
+                        if let Some(Frame::Array(items, _)) = self.frames.pop() {
+                            self.values.push(Value::Array(items));
+                        }
+                        end
+                    } else {
+                        pc + 1
+                    }
+                },
+                Op::ArrayPush(next) => {
+                    let value = self.pop();
+                    if let Some(Frame::Array(items, _)) = self.frames.last_mut() {
+                        items.push(value);
+                    }
+                    next
+                },
+                Op::MapStart => {
+                    self.push_frame(Frame::Map(HashMap::new(), 0, None))?;
+                    pc + 1
+                },
+                Op::MapNext(end) => {
+                    let done = match self.frames.last_mut() {
+                        Some(Frame::Map(items, left, key)) => {
+                            let done = next_item(reader, left, |len| items.reserve(len))?;
+                            if !done {
+                                *key = Some(pool.decode(reader)?);
+                            }
+                            done
+                        },
+                        _ => unreachable!(),
+                    };
+                    if done {
+                        if let Some(Frame::Map(items, _, _)) = self.frames.pop() {
+                            self.values.push(Value::Map(items));
+                        }
+                        end
+                    } else {
+                        pc + 1
+                    }
+                },
+                Op::MapInsert(next) => {
+                    let value = self.pop();
+                    if let Some(Frame::Map(items, _, key)) = self.frames.last_mut() {
+                        if let Some(key) = key.take() {
+                            items.insert(key, value);
+                        }
+                    }
+                    next
+                },
+                Op::RecordStart(len) => {
+                    self.push_frame(Frame::Record(vec![None; len]))?;
+                    pc + 1
+                },
+                Op::SetField(position) => {
+                    let value = self.pop();
+                    if let Some(Frame::Record(values)) = self.frames.last_mut() {
+                        values[position] = Some(value);
+                    }
+                    pc + 1
+                },
+                Op::Default(position, ref default) => {
+                    if let Some(Frame::Record(values)) = self.frames.last_mut() {
+                        values[position] = Some(default.clone());
+                    }
+                    pc + 1
+                },
+                Op::RecordEnd(ref names) => {
+                    let values = match self.frames.pop() {
+                        Some(Frame::Record(values)) => values,
+                        _ => unreachable!(),
+                    };
+                    let fields = names
+                        .iter()
+                        .zip(values)
+                        .map(|(name, value)| {
+                            let value = value
+                                .ok_or_else(|| mismatch(format!("missing field {}", name)))?;
+                            Ok((name.clone(), value))
+                        }).collect::<Result<_, Error>>()?;
+                    self.values.push(Value::Record(fields));
+                    pc + 1
+                },
+                Op::Union(ref starts) => {
+                    let index = zag_i64(reader)?;
+                    match starts.get(index as usize) {
+                        Some(&start) => start,
+                        None => return Err(DecodeError::new("Union index out of bounds").into()),
+                    }
+                },
+                Op::Jump(end) => end,
+                Op::Fail(ref reason) => return Err(mismatch(reason.as_str())),
+            };
+        }
+        Ok(self.pop())
+    }
+
+    fn push_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        safe_depth(self.frames.len() + 1)?;
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.values.pop().unwrap_or(Value::Null)
+    }
+}
+
+// Account for the next item of an array or a map, `left` items being left in the current block.
+// Return whether there are no items left.
+fn next_item<R, F>(reader: &mut R, left: &mut usize, reserve: F) -> Result<bool, Error>
+where
+    R: VarintRead,
+    F: FnOnce(usize),
+{
+    if *left == 0 {
+        let len = decode_block_len(reader)?;
+        if len == 0 {
+            return Ok(true)
+        }
+        reserve(len);
+        *left = len;
+    }
+    *left -= 1;
+    Ok(false)
+}
+
+fn promote(value: Value, kind: SchemaKind) -> Result<Value, Error> {
+    match (value, kind) {
+        (Value::Int(n), SchemaKind::Long) => Ok(Value::Long(i64::from(n))),
+        (Value::Int(n), SchemaKind::Float) => Ok(Value::Float(n as f32)),
+        (Value::Int(n), SchemaKind::Double) => Ok(Value::Double(f64::from(n))),
+        (Value::Long(n), SchemaKind::Float) => Ok(Value::Float(n as f32)),
+        (Value::Long(n), SchemaKind::Double) => Ok(Value::Double(n as f64)),
+        (Value::Float(x), SchemaKind::Double) => Ok(Value::Double(f64::from(x))),
+        (Value::String(s), SchemaKind::Bytes) => Ok(Value::Bytes(s.into_bytes())),
+        (Value::Bytes(bytes), SchemaKind::String) => String::from_utf8(bytes)
+            .map(Value::String)
+            .map_err(|e| mismatch(e.to_string())),
+        (value, kind) => Err(mismatch(format!("{:?} expected, got {:?}", kind, value))),
+    }
+}
+
+fn mismatch<S: Into<String>>(reason: S) -> Error {
+    SchemaResolutionError::new(reason).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decode::decode;
+    use encode::{encode, encode_sized};
+
+    #[test]
+    fn test_resolver() {
+        let writer_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "int"},
+                    {"name": "b", "type": "string"},
+                    {"name": "c", "type": {"type": "array", "items": ["null", "int"]}},
+                    {
+                        "name": "d",
+                        "type": {"type": "enum", "name": "suit", "symbols": ["hearts", "clubs"]}
+                    }
+                ]
+            }
+        "#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {
+                        "name": "d",
+                        "type": {"type": "enum", "name": "suit", "symbols": ["clubs", "hearts"]}
+                    },
+                    {"name": "e", "type": "string", "default": "foo"},
+                    {"name": "c", "type": {"type": "array", "items": ["null", "double"]}},
+                    {"name": "a", "type": "long"}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+
+        for &(ref symbol, index) in &[("hearts", 1), ("clubs", 0)] {
+            let value = Value::Record(vec![
+                ("a".to_owned(), Value::Int(1)),
+                ("b".to_owned(), Value::String("bar".to_owned())),
+                (
+                    "c".to_owned(),
+                    Value::Array(vec![
+                        Value::Union(Box::new(Value::Null)),
+                        Value::Union(Box::new(Value::Int(2))),
+                    ]),
+                ),
+                (
+                    "d".to_owned(),
+                    Value::Enum(1 - index, (*symbol).into()),
+                ),
+            ]);
+            let resolved = resolver.resolve(value.clone()).unwrap();
+            assert_eq!(resolved, value.clone().resolve(&reader_schema).unwrap());
+
+            let mut encoded = Vec::new();
+            encode(&value, &writer_schema, &mut encoded);
+            let decoded = resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .unwrap();
+            assert_eq!(decoded, resolved);
+            assert_eq!(
+                resolved,
+                Value::Record(vec![
+                    ("d".to_owned(), Value::Enum(index, (*symbol).into())),
+                    ("e".to_owned(), Value::String("foo".to_owned())),
+                    (
+                        "c".to_owned(),
+                        Value::Array(vec![Value::Null, Value::Double(2.0)]),
+                    ),
+                    ("a".to_owned(), Value::Long(1)),
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolver_errors() {
+        // Unknown enum symbols, and branches without a match, fail when met.
+        let writer_schema = Schema::parse_str(
+            r#"["int", {"type": "enum", "name": "suit", "symbols": ["hearts", "clubs"]}]"#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "enum", "name": "suit", "symbols": ["hearts", "spades"]}"#,
+        ).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+        assert_eq!(
+            resolver
+                .resolve(Value::Union(Box::new(Value::Enum(0, "hearts".into()))))
+                .unwrap(),
+            Value::Enum(0, "hearts".into())
+        );
+        assert!(
+            resolver
+                .resolve(Value::Union(Box::new(Value::Enum(1, "clubs".into()))))
+                .is_err()
+        );
+        assert!(resolver.resolve(Value::Union(Box::new(Value::Int(1)))).is_err());
+        let decode_error = |encoded: &[u8]| {
+            let mut encoded = encoded;
+            resolver
+                .decode(&mut encoded, &mut StringPool::new())
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(decode_error(&[2, 2]).contains("clubs is unknown"));
+        assert!(
+            decode_error(&[2, 4])
+                .ends_with(r#"index 2 out of bounds, expected one of ["hearts", "clubs"]"#)
+        );
+
+        // A field without a default.
+        let writer_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "a", "type": "int"}]}"#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "b", "type": "int"}]}"#,
+        ).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+        assert!(
+            resolver
+                .resolve(Value::Record(vec![("a".to_owned(), Value::Int(1))]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_resolver_decode() {
+        let writer_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": {"type": "map", "values": ["null", "long", "string"]}},
+                    {"name": "b", "type": {"type": "array", "items": "float"}},
+                    {"name": "c", "type": "string"}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "b", "type": {"type": "array", "items": "double"}},
+                    {"name": "a", "type": {"type": "map", "values": ["string", "double"]}},
+                    {"name": "d", "type": "bytes", "default": "foo"}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+
+        let mut a = HashMap::new();
+        a.insert("x".into(), Value::Union(Box::new(Value::Long(1))));
+        a.insert("y".into(), Value::Union(Box::new(Value::String("z".to_owned()))));
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Map(a)),
+            ("b".to_owned(), Value::Array(vec![Value::Float(0.5), Value::Float(2.0)])),
+            ("c".to_owned(), Value::String("foo".to_owned())),
+        ]);
+        let mut encoded = Vec::new();
+        encode(&value, &writer_schema, &mut encoded);
+
+        let decoded = resolver
+            .decode(&mut &encoded[..], &mut StringPool::new())
+            .unwrap();
+        let expected = resolver
+            .resolve(decode(&writer_schema, &mut &encoded[..]).unwrap())
+            .unwrap();
+        assert_eq!(decoded, expected);
+        match decoded {
+            Value::Record(ref fields) => {
+                assert_eq!(fields[2], ("d".to_owned(), Value::Bytes(b"foo".to_vec())))
+            },
+            ref other => panic!("unexpected value {:?}", other),
+        }
+
+        // Arrays and maps written in sized blocks.
+        let mut encoded = Vec::new();
+        encode_sized(&value, &writer_schema, &mut encoded);
+        assert_eq!(
+            resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .unwrap(),
+            expected
+        );
+
+        // A `null` map value has no matching reader branch.
+        let mut a = HashMap::new();
+        a.insert("x".into(), Value::Union(Box::new(Value::Null)));
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Map(a)),
+            ("b".to_owned(), Value::Array(vec![])),
+            ("c".to_owned(), Value::String("foo".to_owned())),
+        ]);
+        let mut encoded = Vec::new();
+        encode(&value, &writer_schema, &mut encoded);
+        assert!(
+            resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_resolver_promotions() {
+        let cases = vec![
+            ("int", Value::Int(7), "long", Value::Long(7)),
+            ("int", Value::Int(7), "float", Value::Float(7.0)),
+            ("int", Value::Int(7), "double", Value::Double(7.0)),
+            ("long", Value::Long(7), "float", Value::Float(7.0)),
+            ("long", Value::Long(7), "double", Value::Double(7.0)),
+            ("float", Value::Float(0.5), "double", Value::Double(0.5)),
+            ("string", Value::String("foo".to_owned()), "bytes", Value::Bytes(b"foo".to_vec())),
+            ("bytes", Value::Bytes(b"foo".to_vec()), "string", Value::String("foo".to_owned())),
+        ];
+        for (writer, value, reader, expected) in cases {
+            let writer_schema = Schema::parse_str(&format!(r#""{}""#, writer)).unwrap();
+            let reader_schema = Schema::parse_str(&format!(r#""{}""#, reader)).unwrap();
+            let resolver = Resolver::new(&writer_schema, &reader_schema);
+
+            let mut encoded = Vec::new();
+            encode(&value, &writer_schema, &mut encoded);
+            let decoded = resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .unwrap();
+            assert_eq!(decoded, expected, "{} as {}", writer, reader);
+            assert_eq!(resolver.resolve(value.clone()).unwrap(), expected);
+            assert_eq!(value.resolve(&reader_schema).unwrap(), expected);
+        }
+
+        // No demotions.
+        for &(writer, reader) in &[("long", "int"), ("double", "float"), ("float", "long")] {
+            let writer_schema = Schema::parse_str(&format!(r#""{}""#, writer)).unwrap();
+            let reader_schema = Schema::parse_str(&format!(r#""{}""#, reader)).unwrap();
+            let resolver = Resolver::new(&writer_schema, &reader_schema);
+            let mut encoded = Vec::new();
+            encode(&Value::Long(7).resolve(&writer_schema).unwrap(), &writer_schema, &mut encoded);
+            assert!(
+                resolver
+                    .decode(&mut &encoded[..], &mut StringPool::new())
+                    .is_err(),
+                "{} as {}",
+                writer,
+                reader
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolver_enum_default() {
+        let writer_schema = Schema::parse_str(
+            r#"{"type": "enum", "name": "Suit", "symbols": ["spades", "hearts", "clubs"]}"#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"
+            {"type": "enum", "name": "Suit", "symbols": ["hearts", "spades"], "default": "hearts"}
+            "#,
+        ).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+
+        for (index, symbol, expected) in vec![
+            (0, "spades", Value::Enum(1, "spades".into())),
+            (2, "clubs", Value::Enum(0, "hearts".into())),
+        ] {
+            let value = Value::Enum(index, symbol.into());
+            assert_eq!(resolver.resolve(value.clone()).unwrap(), expected);
+
+            let mut encoded = Vec::new();
+            encode(&value, &writer_schema, &mut encoded);
+            let decoded = resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .unwrap();
+            assert_eq!(decoded, expected);
+        }
+    }
+
+    #[test]
+    fn test_resolver_union_branches() {
+        let record = |a: &str, b: &str| {
+            format!(
+                r#"{{"type": "record", "name": "r", "fields": [
+                    {{"name": "a", "type": "{}"}},
+                    {{"name": "b", "type": "{}", "default": "foo"}}
+                ]}}"#,
+                a, b
+            )
+        };
+        let writer_schema =
+            Schema::parse_str(&format!(r#"["null", {}]"#, record("int", "string"))).unwrap();
+        let reader_schema = Schema::parse_str(&record("long", "bytes")).unwrap();
+        let written = Value::Union(Box::new(Value::Record(vec![
+            ("a".to_owned(), Value::Int(1)),
+            ("b".to_owned(), Value::String("bar".to_owned())),
+        ])));
+        let expected = Value::Record(vec![
+            ("a".to_owned(), Value::Long(1)),
+            ("b".to_owned(), Value::Bytes(b"bar".to_vec())),
+        ]);
+
+        // A union writer and a non-union reader: the selected branch resolves recursively, other
+        // branches fail.
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+        let mut encoded = Vec::new();
+        encode(&written, &writer_schema, &mut encoded);
+        let decoded = resolver
+            .decode(&mut &encoded[..], &mut StringPool::new())
+            .unwrap();
+        assert_eq!(decoded, expected);
+        assert_eq!(resolver.resolve(written.clone()).unwrap(), expected);
+        assert_eq!(written.resolve(&reader_schema).unwrap(), expected);
+        let mut encoded = Vec::new();
+        encode(&Value::Union(Box::new(Value::Null)), &writer_schema, &mut encoded);
+        assert!(
+            resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .is_err()
+        );
+
+        // A non-union writer and a union reader.
+        let writer_schema = Schema::parse_str(&record("int", "string")).unwrap();
+        let reader_schema =
+            Schema::parse_str(&format!(r#"["null", {}]"#, record("double", "string"))).unwrap();
+        let written = Value::Record(vec![
+            ("a".to_owned(), Value::Int(1)),
+            ("b".to_owned(), Value::String("bar".to_owned())),
+        ]);
+        let expected = Value::Record(vec![
+            ("a".to_owned(), Value::Double(1.0)),
+            ("b".to_owned(), Value::String("bar".to_owned())),
+        ]);
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+        let mut encoded = Vec::new();
+        encode(&written, &writer_schema, &mut encoded);
+        let decoded = resolver
+            .decode(&mut &encoded[..], &mut StringPool::new())
+            .unwrap();
+        assert_eq!(decoded, expected);
+        assert_eq!(resolver.resolve(written.clone()).unwrap(), expected);
+        assert_eq!(written.resolve(&reader_schema).unwrap(), expected);
+
+        // No matching branch.
+        let reader_schema = Schema::parse_str(r#"["null", "string"]"#).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+        assert!(
+            resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_resolver_null_branch_position() {
+        // Unions of the same branches in different orders resolve branch by branch.
+        let writer_schema = Schema::parse_str(r#"["null", "string"]"#).unwrap();
+        let reader_schema = Schema::parse_str(r#"["string", "null"]"#).unwrap();
+        let resolver = Resolver::new(&writer_schema, &reader_schema);
+        for value in vec![Value::Null, Value::String("foo".to_owned())] {
+            let written = Value::Union(Box::new(value.clone()));
+            let mut encoded = Vec::new();
+            encode(&written, &writer_schema, &mut encoded);
+            let decoded = resolver
+                .decode(&mut &encoded[..], &mut StringPool::new())
+                .unwrap();
+            assert_eq!(decoded, value);
+            assert_eq!(written.resolve(&reader_schema).unwrap(), value);
+        }
+    }
+}
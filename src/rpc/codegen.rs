@@ -0,0 +1,811 @@
+//! Logic for generating Rust clients and servers of Avro protocols.
+//!
+//! [`generate`](fn.generate.html) emits the Rust source of:
+//! * a struct (or enum) for each named type of the protocol and for the request of each message;
+//! * a `<Protocol>Client` trait with a method per message, implemented by `Requestor`;
+//! * a `<Protocol>Server` trait to implement, served through the `Responder` returned by
+//!   `<protocol>_responder`.
+//!
+//! The generated source is meant to be written to a file by a build script and `include!`d in
+//! a crate depending on `avro-rs`.
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use error::Error;
+use serde_json;
+
+use schema::{Name, RecordField, Schema};
+use types::Value;
+use util::DecodeError;
+
+use super::protocol::schema_name;
+use super::{Message, Protocol};
+
+/// Conversion between Rust types and Avro `Value`s, as used by generated code.
+pub trait AvroType: Sized {
+    /// Convert `self` into a `Value`.
+    fn to_avro_value(self) -> Value;
+
+    /// Convert a `Value` into `Self`.
+    fn from_avro_value(value: Value) -> Result<Self, Error>;
+}
+
+macro_rules! avro_type {
+    ($type_:ty, $variant:ident, $name:expr) => {
+        impl AvroType for $type_ {
+            fn to_avro_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_avro_value(value: Value) -> Result<Self, Error> {
+                match value {
+                    Value::$variant(value) => Ok(value),
+                    other => Err(DecodeError::new(format!("{} expected, got {:?}", $name, other)).into()),
+                }
+            }
+        }
+    };
+}
+
+avro_type!(bool, Boolean, "Boolean");
+avro_type!(i32, Int, "Int");
+avro_type!(i64, Long, "Long");
+avro_type!(f32, Float, "Float");
+avro_type!(f64, Double, "Double");
+avro_type!(Vec<u8>, Bytes, "Bytes");
+avro_type!(String, String, "String");
+
+impl AvroType for () {
+    fn to_avro_value(self) -> Value {
+        Value::Null
+    }
+
+    fn from_avro_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Null => Ok(()),
+            other => Err(DecodeError::new(format!("Null expected, got {:?}", other)).into()),
+        }
+    }
+}
+
+impl AvroType for Value {
+    fn to_avro_value(self) -> Value {
+        self
+    }
+
+    fn from_avro_value(value: Value) -> Result<Self, Error> {
+        Ok(value)
+    }
+}
+
+impl<T: AvroType> AvroType for Option<T> {
+    fn to_avro_value(self) -> Value {
+        Value::Union(Box::new(self.map_or(Value::Null, T::to_avro_value)))
+    }
+
+    fn from_avro_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Union(value) => match *value {
+                Value::Null => Ok(None),
+                value => T::from_avro_value(value).map(Some),
+            },
+            other => Err(DecodeError::new(format!("Union expected, got {:?}", other)).into()),
+        }
+    }
+}
+
+impl<T: AvroType> AvroType for Vec<T> {
+    fn to_avro_value(self) -> Value {
+        Value::Array(self.into_iter().map(T::to_avro_value).collect())
+    }
+
+    fn from_avro_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Array(items) => items.into_iter().map(T::from_avro_value).collect(),
+            other => Err(DecodeError::new(format!("Array expected, got {:?}", other)).into()),
+        }
+    }
+}
+
+impl<T: AvroType> AvroType for HashMap<String, T> {
+    fn to_avro_value(self) -> Value {
+        Value::Map(
+            self.into_iter()
+                .map(|(key, value)| (key.into(), value.to_avro_value()))
+                .collect(),
+        )
+    }
+
+    fn from_avro_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Map(items) => items
+                .into_iter()
+                .map(|(key, value)| T::from_avro_value(value).map(|value| (key.to_string(), value)))
+                .collect(),
+            other => Err(DecodeError::new(format!("Map expected, got {:?}", other)).into()),
+        }
+    }
+}
+
+/// Return the fields of a record `Value`, by name, for generated code to take them out of.
+pub fn record_fields(value: Value) -> Result<HashMap<String, Value>, Error> {
+    match value {
+        Value::Record(fields) => Ok(fields.into_iter().collect()),
+        other => Err(DecodeError::new(format!("Record expected, got {:?}", other)).into()),
+    }
+}
+
+/// Take the field `name` out of the fields of a record and convert it.
+pub fn take_field<T: AvroType>(fields: &mut HashMap<String, Value>, name: &str) -> Result<T, Error> {
+    fields
+        .remove(name)
+        .ok_or_else(|| DecodeError::new(format!("missing field {} in record", name)).into())
+        .and_then(T::from_avro_value)
+}
+
+const VALUE: &str = "::avro_rs::types::Value";
+const AVRO_TYPE: &str = "::avro_rs::rpc::AvroType";
+const RESULT_ERROR: &str = "::avro_rs::Error";
+
+/// Generate the Rust source of the types, client and server of `protocol`.
+///
+/// **NOTE** Unions other than `["null", T]` (mapped to `Option<T>`) are kept as
+/// `avro_rs::types::Value`s.
+pub fn generate(protocol: &Protocol) -> Result<String, Error> {
+    let mut named = Vec::new();
+    for schema in protocol.types() {
+        collect_named(schema, &mut named);
+    }
+    let mut messages = protocol.messages().values().collect::<Vec<_>>();
+    messages.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut errors = Vec::new();
+    for message in &messages {
+        for schema in record_fields_of(&message.request) {
+            collect_named(&schema.schema, &mut named);
+        }
+        collect_named(&message.response, &mut named);
+        if let Schema::Union(ref union) = message.errors {
+            for schema in &union.variants()[1..] {
+                collect_named(schema, &mut named);
+                if let Some(name) = schema_name(schema) {
+                    errors.push(name.fullname(None));
+                }
+            }
+        }
+    }
+
+    let protocol_name = to_pascal_case(&protocol.name().name);
+    let protocol_const = format!("{}_PROTOCOL", to_snake_case(&protocol_name).to_uppercase());
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// Generated by avro-rs from the `{}` protocol. Do not edit.",
+        protocol.name().name
+    )?;
+    writeln!(out)?;
+    writeln!(out, "/// JSON declaration of the `{}` protocol.", protocol.name().name)?;
+    writeln!(
+        out,
+        "pub const {}: &str = {};",
+        protocol_const,
+        raw_string(&serde_json::to_string(protocol)?)
+    )?;
+
+    for schema in &named {
+        writeln!(out)?;
+        generate_named(schema, &errors, &mut out)?;
+    }
+
+    for message in &messages {
+        writeln!(out)?;
+        let name = format!("{}Request", to_pascal_case(&message.name));
+        let doc = format!("Request of the `{}` message.", message.name);
+        generate_record(&name, &Some(doc), record_fields_of(&message.request), &mut out)?;
+    }
+
+    writeln!(out)?;
+    generate_client(protocol, &protocol_name, &messages, &mut out)?;
+    writeln!(out)?;
+    generate_server(
+        protocol,
+        &protocol_name,
+        &protocol_const,
+        &messages,
+        &mut out,
+    )?;
+    Ok(out)
+}
+
+fn generate_named(schema: &Schema, errors: &[String], out: &mut String) -> Result<(), Error> {
+    match *schema {
+        Schema::Record {
+            ref name,
+            ref doc,
+            ref fields,
+            ..
+        } => {
+            generate_record(&to_pascal_case(&name.name), doc, fields, out)?;
+            if errors.contains(&name.fullname(None)) {
+                generate_error(name, out)?;
+            }
+        },
+        Schema::Enum {
+            ref name,
+            ref doc,
+            ref symbols,
+            ..
+        } => {
+            let type_name = to_pascal_case(&name.name);
+            write_doc(doc, "", out)?;
+            writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]")?;
+            writeln!(out, "pub enum {} {{", type_name)?;
+            for symbol in symbols {
+                writeln!(out, "    {},", to_pascal_case(symbol))?;
+            }
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+            writeln!(out, "impl {} for {} {{", AVRO_TYPE, type_name)?;
+            writeln!(out, "    fn to_avro_value(self) -> {} {{", VALUE)?;
+            writeln!(out, "        match self {{")?;
+            for (index, symbol) in symbols.iter().enumerate() {
+                writeln!(
+                    out,
+                    "            {}::{} => {}::Enum({}, {:?}.into()),",
+                    type_name,
+                    to_pascal_case(symbol),
+                    VALUE,
+                    index,
+                    symbol
+                )?;
+            }
+            writeln!(out, "        }}")?;
+            writeln!(out, "    }}")?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "    fn from_avro_value(value: {}) -> Result<Self, {}> {{",
+                VALUE, RESULT_ERROR
+            )?;
+            writeln!(
+                out,
+                "        let symbol: ::std::sync::Arc<str> = match value {{"
+            )?;
+            writeln!(out, "            {}::Enum(_, symbol) => symbol,", VALUE)?;
+            writeln!(out, "            {}::String(symbol) => symbol.into(),", VALUE)?;
+            writeln!(
+                out,
+                "            other => return Err(::avro_rs::DecodeError::new(format!(\"Enum expected, got {{:?}}\", other)).into()),"
+            )?;
+            writeln!(out, "        }};")?;
+            writeln!(out, "        match &*symbol {{")?;
+            for symbol in symbols {
+                writeln!(
+                    out,
+                    "            {:?} => Ok({}::{}),",
+                    symbol,
+                    type_name,
+                    to_pascal_case(symbol)
+                )?;
+            }
+            writeln!(
+                out,
+                "            _ => Err(::avro_rs::DecodeError::new(format!(\"unknown symbol {{}} of enum {}\", symbol)).into()),",
+                name.name
+            )?;
+            writeln!(out, "        }}")?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}")?;
+            if errors.contains(&name.fullname(None)) {
+                generate_error(name, out)?;
+            }
+        },
+        Schema::Fixed { ref name, size } => {
+            let type_name = to_pascal_case(&name.name);
+            writeln!(out, "#[derive(Debug, Clone, PartialEq)]")?;
+            writeln!(out, "pub struct {}(pub Vec<u8>);", type_name)?;
+            writeln!(out)?;
+            writeln!(out, "impl {} for {} {{", AVRO_TYPE, type_name)?;
+            writeln!(out, "    fn to_avro_value(self) -> {} {{", VALUE)?;
+            writeln!(out, "        {}::Fixed({}, self.0)", VALUE, size)?;
+            writeln!(out, "    }}")?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "    fn from_avro_value(value: {}) -> Result<Self, {}> {{",
+                VALUE, RESULT_ERROR
+            )?;
+            writeln!(out, "        match value {{")?;
+            writeln!(
+                out,
+                "            {}::Fixed({}, bytes) => Ok({}(bytes)),",
+                VALUE, size, type_name
+            )?;
+            writeln!(
+                out,
+                "            other => Err(::avro_rs::DecodeError::new(format!(\"Fixed({}) expected, got {{:?}}\", other)).into()),",
+                size
+            )?;
+            writeln!(out, "        }}")?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}")?;
+            if errors.contains(&name.fullname(None)) {
+                generate_error(name, out)?;
+            }
+        },
+        _ => (),
+    }
+    Ok(())
+}
+
+fn generate_record(
+    type_name: &str,
+    doc: &Option<String>,
+    fields: &[RecordField],
+    out: &mut String,
+) -> Result<(), Error> {
+    write_doc(doc, "", out)?;
+    writeln!(out, "#[derive(Debug, Clone, PartialEq)]")?;
+    if fields.is_empty() {
+        writeln!(out, "pub struct {} {{}}", type_name)?;
+    } else {
+        writeln!(out, "pub struct {} {{", type_name)?;
+        for field in fields {
+            write_doc(&field.doc, "    ", out)?;
+            writeln!(
+                out,
+                "    pub {}: {},",
+                to_field_name(&field.name),
+                rust_type(&field.schema)
+            )?;
+        }
+        writeln!(out, "}}")?;
+    }
+    writeln!(out)?;
+    writeln!(out, "impl {} for {} {{", AVRO_TYPE, type_name)?;
+    writeln!(out, "    fn to_avro_value(self) -> {} {{", VALUE)?;
+    if fields.is_empty() {
+        writeln!(out, "        {}::Record(vec![])", VALUE)?;
+    } else {
+        writeln!(out, "        {}::Record(vec![", VALUE)?;
+        for field in fields {
+            writeln!(
+                out,
+                "            ({:?}.to_owned(), {}::to_avro_value(self.{})),",
+                field.name,
+                AVRO_TYPE,
+                to_field_name(&field.name)
+            )?;
+        }
+        writeln!(out, "        ])")?;
+    }
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "    fn from_avro_value(value: {}) -> Result<Self, {}> {{",
+        VALUE, RESULT_ERROR
+    )?;
+    if fields.is_empty() {
+        writeln!(out, "        ::avro_rs::rpc::record_fields(value)?;")?;
+        writeln!(out, "        Ok({} {{}})", type_name)?;
+    } else {
+        writeln!(
+            out,
+            "        let mut fields = ::avro_rs::rpc::record_fields(value)?;"
+        )?;
+        writeln!(out, "        Ok({} {{", type_name)?;
+        for field in fields {
+            writeln!(
+                out,
+                "            {}: ::avro_rs::rpc::take_field(&mut fields, {:?})?,",
+                to_field_name(&field.name),
+                field.name
+            )?;
+        }
+        writeln!(out, "        }})")?;
+    }
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn generate_error(name: &Name, out: &mut String) -> Result<(), Error> {
+    let type_name = to_pascal_case(&name.name);
+    writeln!(out)?;
+    writeln!(
+        out,
+        "impl From<{}> for ::avro_rs::rpc::RemoteError {{",
+        type_name
+    )?;
+    writeln!(out, "    fn from(error: {}) -> Self {{", type_name)?;
+    writeln!(
+        out,
+        "        ::avro_rs::rpc::RemoteError::declared({:?}, {}::to_avro_value(error))",
+        name.fullname(None),
+        AVRO_TYPE
+    )?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn generate_client(
+    protocol: &Protocol,
+    protocol_name: &str,
+    messages: &[&Message],
+    out: &mut String,
+) -> Result<(), Error> {
+    writeln!(
+        out,
+        "/// Client of the `{}` protocol.",
+        protocol.name().name
+    )?;
+    writeln!(out, "pub trait {}Client {{", protocol_name)?;
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        write_doc(&message.doc, "    ", out)?;
+        writeln!(out, "    {};", signature(message, "&mut self"))?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "impl<T: ::avro_rs::rpc::Transceiver> {}Client for ::avro_rs::rpc::Requestor<T> {{",
+        protocol_name
+    )?;
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        let params = record_fields_of(&message.request)
+            .iter()
+            .map(|field| to_field_name(&field.name))
+            .collect::<Vec<_>>();
+        writeln!(out, "    {} {{", signature(message, "&mut self"))?;
+        if params.is_empty() {
+            writeln!(
+                out,
+                "        let request = {}Request {{}};",
+                to_pascal_case(&message.name)
+            )?;
+        } else {
+            writeln!(
+                out,
+                "        let request = {}Request {{ {} }};",
+                to_pascal_case(&message.name),
+                params.join(", ")
+            )?;
+        }
+        writeln!(
+            out,
+            "        let response = self.request({:?}, {}::to_avro_value(request))?;",
+            message.name, AVRO_TYPE
+        )?;
+        writeln!(out, "        {}::from_avro_value(response)", AVRO_TYPE)?;
+        writeln!(out, "    }}")?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn generate_server(
+    protocol: &Protocol,
+    protocol_name: &str,
+    protocol_const: &str,
+    messages: &[&Message],
+    out: &mut String,
+) -> Result<(), Error> {
+    let snake_name = to_snake_case(protocol_name);
+
+    writeln!(
+        out,
+        "/// Server of the `{}` protocol.",
+        protocol.name().name
+    )?;
+    writeln!(out, "///")?;
+    writeln!(
+        out,
+        "/// Errors converted from the declared errors of a message are sent back as such."
+    )?;
+    writeln!(
+        out,
+        "pub trait {}Server: Send + Sync + 'static {{",
+        protocol_name
+    )?;
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        write_doc(&message.doc, "    ", out)?;
+        writeln!(out, "    {};", signature(message, "&self"))?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "/// Create a `Requestor` speaking the `{}` protocol through `transceiver`.",
+        protocol.name().name
+    )?;
+    writeln!(
+        out,
+        "pub fn {}_requestor<T: ::avro_rs::rpc::Transceiver>(",
+        snake_name
+    )?;
+    writeln!(out, "    transceiver: T,")?;
+    writeln!(
+        out,
+        ") -> Result<::avro_rs::rpc::Requestor<T>, {}> {{",
+        RESULT_ERROR
+    )?;
+    writeln!(
+        out,
+        "    let protocol = ::avro_rs::rpc::Protocol::parse_str({})?;",
+        protocol_const
+    )?;
+    writeln!(
+        out,
+        "    Ok(::avro_rs::rpc::Requestor::new(protocol, transceiver))"
+    )?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "/// Create a `Responder` dispatching the requests of the `{}` protocol to `server`.",
+        protocol.name().name
+    )?;
+    writeln!(
+        out,
+        "pub fn {}_responder<S: {}Server>(server: S) -> Result<::avro_rs::rpc::Responder, {}> {{",
+        snake_name, protocol_name, RESULT_ERROR
+    )?;
+    writeln!(out, "    let server = ::std::sync::Arc::new(server);")?;
+    writeln!(
+        out,
+        "    let protocol = ::avro_rs::rpc::Protocol::parse_str({})?;",
+        protocol_const
+    )?;
+    writeln!(
+        out,
+        "    let mut responder = ::avro_rs::rpc::Responder::new(protocol);"
+    )?;
+    for message in messages {
+        let params = record_fields_of(&message.request)
+            .iter()
+            .map(|field| format!("request.{}", to_field_name(&field.name)))
+            .collect::<Vec<_>>();
+        writeln!(out, "    {{")?;
+        writeln!(out, "        let server = server.clone();")?;
+        writeln!(
+            out,
+            "        responder.add_handler({:?}, move |request| {{",
+            message.name
+        )?;
+        writeln!(
+            out,
+            "            let {}request: {}Request = {}::from_avro_value(request)?;",
+            if params.is_empty() { "_" } else { "" },
+            to_pascal_case(&message.name),
+            AVRO_TYPE
+        )?;
+        writeln!(
+            out,
+            "            server.{}({}).map({}::to_avro_value)",
+            to_field_name(&message.name),
+            params.join(", "),
+            AVRO_TYPE
+        )?;
+        writeln!(out, "        }})?;")?;
+        writeln!(out, "    }}")?;
+    }
+    writeln!(out, "    Ok(responder)")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn signature(message: &Message, receiver: &str) -> String {
+    let mut params = vec![receiver.to_owned()];
+    for field in record_fields_of(&message.request) {
+        params.push(format!(
+            "{}: {}",
+            to_field_name(&field.name),
+            rust_type(&field.schema)
+        ));
+    }
+    format!(
+        "fn {}({}) -> Result<{}, {}>",
+        to_field_name(&message.name),
+        params.join(", "),
+        rust_type(&message.response),
+        RESULT_ERROR
+    )
+}
+
+fn record_fields_of(schema: &Schema) -> &[RecordField] {
+    match *schema {
+        Schema::Record { ref fields, .. } => fields,
+        _ => &[],
+    }
+}
+
+// Collect the named types found in a schema, in order and without duplicates.
+fn collect_named<'a>(schema: &'a Schema, named: &mut Vec<&'a Schema>) {
+    match *schema {
+        Schema::Array(ref inner) | Schema::Map(ref inner) => collect_named(inner, named),
+        Schema::Union(ref union) => {
+            for variant in union.variants() {
+                collect_named(variant, named);
+            }
+        },
+        Schema::Record { ref fields, .. } if !contains_named(named, schema) => {
+            for field in fields {
+                collect_named(&field.schema, named);
+            }
+            named.push(schema);
+        },
+        Schema::Enum { .. } | Schema::Fixed { .. } if !contains_named(named, schema) => {
+            named.push(schema);
+        },
+        Schema::Logical(ref logical) => collect_named(logical.schema(), named),
+        _ => (),
+    }
+}
+
+fn contains_named(named: &[&Schema], schema: &Schema) -> bool {
+    let fullname = schema_name(schema).map(|name| name.fullname(None));
+    named
+        .iter()
+        .any(|other| schema_name(other).map(|name| name.fullname(None)) == fullname)
+}
+
+fn rust_type(schema: &Schema) -> String {
+    match *schema {
+        Schema::Null => "()".to_owned(),
+        Schema::Boolean => "bool".to_owned(),
+        Schema::Int => "i32".to_owned(),
+        Schema::Long => "i64".to_owned(),
+        Schema::Float => "f32".to_owned(),
+        Schema::Double => "f64".to_owned(),
+        Schema::Bytes => "Vec<u8>".to_owned(),
+        Schema::String => "String".to_owned(),
+        Schema::Array(ref inner) => format!("Vec<{}>", rust_type(inner)),
+        Schema::Map(ref inner) => format!(
+            "::std::collections::HashMap<String, {}>",
+            rust_type(inner)
+        ),
+        Schema::Union(ref union) => match union.variants() {
+            [Schema::Null, ref inner] | [ref inner, Schema::Null] => {
+                format!("Option<{}>", rust_type(inner))
+            },
+            _ => VALUE.to_owned(),
+        },
+        Schema::Record { ref name, .. }
+        | Schema::Enum { ref name, .. }
+        | Schema::Fixed { ref name, .. } => to_pascal_case(&name.name),
+        Schema::Logical(ref logical) => rust_type(logical.schema()),
+    }
+}
+
+fn write_doc(doc: &Option<String>, indent: &str, out: &mut String) -> Result<(), Error> {
+    if let Some(ref doc) = *doc {
+        for line in doc.lines() {
+            writeln!(out, "{}/// {}", indent, line.trim())?;
+        }
+    }
+    Ok(())
+}
+
+fn raw_string(s: &str) -> String {
+    let mut hashes = "#".to_owned();
+    while s.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+    format!("r{}\"{}\"{}", hashes, s, hashes)
+}
+
+fn to_pascal_case(name: &str) -> String {
+    let keep_case = name.chars().any(|c| c.is_lowercase());
+    let mut out = String::with_capacity(name.len());
+    let mut upper = true;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else if keep_case {
+            out.push(c);
+        } else {
+            out.extend(c.to_lowercase());
+        }
+    }
+    out
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() {
+            if previous_lower {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+            previous_lower = false;
+        } else {
+            out.push(c);
+            previous_lower = c.is_lowercase() || c.is_numeric();
+        }
+    }
+    out
+}
+
+const KEYWORDS: [&str; 50] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn to_field_name(name: &str) -> String {
+    let name = to_snake_case(name);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(to_pascal_case("greeting"), "Greeting");
+        assert_eq!(to_pascal_case("HandshakeMatch"), "HandshakeMatch");
+        assert_eq!(to_pascal_case("NOT_FOUND"), "NotFound");
+        assert_eq!(to_snake_case("clientHash"), "client_hash");
+        assert_eq!(to_snake_case("HTTPServer"), "httpserver");
+        assert_eq!(to_field_name("type"), "type_");
+        assert_eq!(raw_string("{}"), "r#\"{}\"#");
+        assert_eq!(raw_string("\"#"), "r##\"\"#\"##");
+    }
+
+    #[test]
+    fn test_avro_type() {
+        let value = Some(vec![1i32, 2]).to_avro_value();
+        assert_eq!(
+            value,
+            Value::Union(Box::new(Value::Array(vec![Value::Int(1), Value::Int(2)])))
+        );
+        assert_eq!(
+            Option::<Vec<i32>>::from_avro_value(value).unwrap(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            Option::<i32>::from_avro_value(Value::Union(Box::new(Value::Null))).unwrap(),
+            None
+        );
+        assert!(i64::from_avro_value(Value::Int(1)).is_err());
+
+        let mut fields = record_fields(Value::Record(vec![
+            ("a".to_owned(), Value::String("foo".to_owned())),
+        ])).unwrap();
+        assert_eq!(take_field::<String>(&mut fields, "a").unwrap(), "foo");
+        assert!(take_field::<String>(&mut fields, "a").is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let protocol =
+            Protocol::parse_str(include_str!("../../examples/greeter/greeter.avpr")).unwrap();
+        assert_eq!(
+            generate(&protocol).unwrap(),
+            include_str!("../../examples/greeter/generated.rs")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Logic handling the framing of Avro RPC messages.
+//!
+//! A message is sent as a series of buffers, each prefixed by its 4-byte big-endian length, and is
+//! terminated by an empty buffer.
+use std::io::{Read, Write};
+use std::mem;
+
+use error::Error;
+
+use util::safe_len;
+
+/// Maximum size of the buffers a message is split into when framing it.
+pub const FRAME_SIZE: usize = 8192;
+
+const LENGTH_SIZE: usize = 4;
+
+/// Frame a message, splitting it into buffers of at most `FRAME_SIZE` bytes.
+pub fn frame_message(message: &[u8]) -> Vec<u8> {
+    let frames = message.len() / FRAME_SIZE + 2;
+    let mut framed = Vec::with_capacity(message.len() + frames * LENGTH_SIZE);
+    for frame in message.chunks(FRAME_SIZE) {
+        framed.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        framed.extend_from_slice(frame);
+    }
+    framed.extend_from_slice(&[0u8; LENGTH_SIZE]);
+    framed
+}
+
+/// Frame a message and write it to anything implementing `io::Write`.
+///
+/// Return the number of bytes written.
+pub fn write_framed<W: Write>(writer: &mut W, message: &[u8]) -> Result<usize, Error> {
+    let mut written = 0;
+    for frame in message.chunks(FRAME_SIZE) {
+        writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+        writer.write_all(frame)?;
+        written += LENGTH_SIZE + frame.len();
+    }
+    writer.write_all(&[0u8; LENGTH_SIZE])?;
+    Ok(written + LENGTH_SIZE)
+}
+
+/// Read a framed message from anything implementing `io::Read`, returning the concatenation of
+/// its buffers.
+pub fn read_framed<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut message = Vec::new();
+    loop {
+        let mut length = [0u8; LENGTH_SIZE];
+        reader.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length == 0 {
+            return Ok(message)
+        }
+
+        let start = message.len();
+        message.resize(safe_len(start + length)?, 0);
+        reader.read_exact(&mut message[start..])?;
+    }
+}
+
+/// Incremental decoder of framed messages.
+///
+/// Unlike [`read_framed`](fn.read_framed.html), it does not perform any I/O itself: bytes are
+/// pushed as they are received, from whichever (possibly non-blocking) transport, and complete
+/// messages are pulled out.
+#[derive(Debug, Clone, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// Create an empty `FrameDecoder`.
+    pub fn new() -> FrameDecoder {
+        FrameDecoder::default()
+    }
+
+    /// Add received bytes to the decoder.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Return the next complete message, if all of its buffers have been received.
+    ///
+    /// A buffer which would make the message exceed the allocation limit is rejected as soon as
+    /// its length is received, so calling this after every push bounds what gets buffered.
+    pub fn next_message(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let mut consumed = 0;
+        let mut result = Ok(None);
+        while self.buf.len() - consumed >= LENGTH_SIZE {
+            let mut length = [0u8; LENGTH_SIZE];
+            length.copy_from_slice(&self.buf[consumed..consumed + LENGTH_SIZE]);
+            let length = u32::from_be_bytes(length) as usize;
+            if length == 0 {
+                consumed += LENGTH_SIZE;
+                result = Ok(Some(mem::take(&mut self.message)));
+                break
+            }
+            if let Err(e) = safe_len(self.message.len() + length) {
+                result = Err(e);
+                break
+            }
+            if self.buf.len() - consumed - LENGTH_SIZE < length {
+                break
+            }
+
+            let start = consumed + LENGTH_SIZE;
+            self.message.extend_from_slice(&self.buf[start..start + length]);
+            consumed = start + length;
+        }
+
+        // The buffers appended to the message are dropped, even on error, so that they are not
+        // appended again.
+        self.buf.drain(..consumed);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_message() {
+        assert_eq!(frame_message(&[]), vec![0, 0, 0, 0]);
+        assert_eq!(
+            frame_message(&[1, 2, 3]),
+            vec![0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0]
+        );
+
+        let message = vec![7u8; FRAME_SIZE + 1];
+        let framed = frame_message(&message);
+        assert_eq!(framed.len(), message.len() + 3 * LENGTH_SIZE);
+        assert_eq!(&framed[..4], &[0, 0, 0x20, 0]);
+        assert_eq!(&framed[FRAME_SIZE + 4..FRAME_SIZE + 9], &[0, 0, 0, 1, 7]);
+
+        let mut written = Vec::new();
+        assert_eq!(write_framed(&mut written, &message).unwrap(), framed.len());
+        assert_eq!(written, framed);
+        assert_eq!(read_framed(&mut &framed[..]).unwrap(), message);
+    }
+
+    #[test]
+    fn test_read_framed() {
+        let framed = [0, 0, 0, 2, 1, 2, 0, 0, 0, 1, 3, 0, 0, 0, 0];
+        assert_eq!(read_framed(&mut &framed[..]).unwrap(), vec![1, 2, 3]);
+
+        // missing terminating buffer
+        assert!(read_framed(&mut &framed[..11]).is_err());
+    }
+
+    #[test]
+    fn test_frame_decoder() {
+        let mut framed = frame_message(&[1, 2, 3]);
+        framed.extend(frame_message(&[]));
+        framed.extend(frame_message(&[4, 5]));
+
+        let mut decoder = FrameDecoder::new();
+        let mut messages = Vec::new();
+        for byte in framed {
+            decoder.push(&[byte]);
+            while let Some(message) = decoder.next_message().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(messages, vec![vec![1, 2, 3], vec![], vec![4, 5]]);
+        assert_eq!(decoder.next_message().unwrap(), None);
+    }
+
+    #[test]
+    fn test_frame_decoder_rejects_huge_buffers() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&[0, 0, 0, 1, 7]);
+        // Rejected before its bytes are received.
+        decoder.push(&[0xff, 0xff, 0xff, 0xf0, 7]);
+        match decoder.next_message().unwrap_err() {
+            Error::Allocation { requested, .. } => assert_eq!(requested, 0xffff_fff1),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(decoder.message, vec![7]);
+        assert_eq!(decoder.buf, vec![0xff, 0xff, 0xff, 0xf0, 7]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Logic handling the handshake of Avro RPC.
+//!
+//! Each request is preceded by a `HandshakeRequest` and each response by a `HandshakeResponse`,
+//! through which the client and the server agree on the protocols they speak, identified by their
+//! MD5 hash.
+use error::Error;
+
+use datum::write_avro_datum;
+use decode::decode;
+use schema::Schema;
+use types::Value;
+use util::DecodeError;
+
+static HANDSHAKE_REQUEST: &str = r#"
+    {
+        "type": "record",
+        "name": "HandshakeRequest",
+        "namespace": "org.apache.avro.ipc",
+        "fields": [
+            {"name": "clientHash", "type": {"type": "fixed", "name": "MD5", "size": 16}},
+            {"name": "clientProtocol", "type": ["null", "string"]},
+            {"name": "serverHash", "type": {"type": "fixed", "name": "MD5", "size": 16}},
+            {"name": "meta", "type": ["null", {"type": "map", "values": "bytes"}]}
+        ]
+    }
+"#;
+
+static HANDSHAKE_RESPONSE: &str = r#"
+    {
+        "type": "record",
+        "name": "HandshakeResponse",
+        "namespace": "org.apache.avro.ipc",
+        "fields": [
+            {
+                "name": "match",
+                "type": {"type": "enum", "name": "HandshakeMatch", "symbols": ["BOTH", "CLIENT", "NONE"]}
+            },
+            {"name": "serverProtocol", "type": ["null", "string"]},
+            {"name": "serverHash", "type": ["null", {"type": "fixed", "name": "MD5", "size": 16}]},
+            {"name": "meta", "type": ["null", {"type": "map", "values": "bytes"}]}
+        ]
+    }
+"#;
+
+const MATCH_SYMBOLS: [&str; 3] = ["BOTH", "CLIENT", "NONE"];
+
+/// Schemas of the handshake records.
+#[derive(Debug, Clone)]
+pub struct HandshakeSchemas {
+    request: Schema,
+    response: Schema,
+}
+
+impl HandshakeSchemas {
+    pub fn new() -> HandshakeSchemas {
+        HandshakeSchemas {
+            request: Schema::parse_str(HANDSHAKE_REQUEST).unwrap(),
+            response: Schema::parse_str(HANDSHAKE_RESPONSE).unwrap(),
+        }
+    }
+}
+
+/// Outcome of the handshake, as seen by the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HandshakeMatch {
+    /// Both protocols are known to the other side.
+    Both,
+    /// The client protocol is known but the client does not know the server protocol.
+    Client,
+    /// The client protocol is unknown: the client must send it along with the request.
+    None,
+}
+
+/// The handshake sent by a client ahead of a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandshakeRequest {
+    pub client_hash: [u8; 16],
+    pub client_protocol: Option<String>,
+    pub server_hash: [u8; 16],
+}
+
+/// The handshake sent by a server ahead of a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandshakeResponse {
+    pub match_: HandshakeMatch,
+    pub server_protocol: Option<String>,
+    pub server_hash: Option<[u8; 16]>,
+}
+
+impl HandshakeRequest {
+    pub fn write(&self, schemas: &HandshakeSchemas, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        let record = Value::Record(vec![
+            ("clientHash".to_owned(), md5_value(self.client_hash)),
+            (
+                "clientProtocol".to_owned(),
+                optional(self.client_protocol.clone().map(Value::String)),
+            ),
+            ("serverHash".to_owned(), md5_value(self.server_hash)),
+            ("meta".to_owned(), optional(None)),
+        ]);
+        write_avro_datum(&schemas.request, record, buffer)
+    }
+
+    pub fn read(schemas: &HandshakeSchemas, reader: &mut &[u8]) -> Result<Self, Error> {
+        let mut fields = record_fields(decode(&schemas.request, reader)?)?;
+        let server_hash = md5_from_value(fields.pop())?;
+        let client_protocol = string_from_value(fields.pop())?;
+        let client_hash = md5_from_value(fields.pop())?;
+        Ok(HandshakeRequest {
+            client_hash,
+            client_protocol,
+            server_hash,
+        })
+    }
+}
+
+impl HandshakeResponse {
+    pub fn write(&self, schemas: &HandshakeSchemas, buffer: &mut Vec<u8>) -> Result<(), Error> {
+        let index = match self.match_ {
+            HandshakeMatch::Both => 0,
+            HandshakeMatch::Client => 1,
+            HandshakeMatch::None => 2,
+        };
+        let record = Value::Record(vec![
+            (
+                "match".to_owned(),
+                Value::Enum(index, MATCH_SYMBOLS[index as usize].into()),
+            ),
+            (
+                "serverProtocol".to_owned(),
+                optional(self.server_protocol.clone().map(Value::String)),
+            ),
+            (
+                "serverHash".to_owned(),
+                optional(self.server_hash.map(md5_value)),
+            ),
+            ("meta".to_owned(), optional(None)),
+        ]);
+        write_avro_datum(&schemas.response, record, buffer)
+    }
+
+    pub fn read(schemas: &HandshakeSchemas, reader: &mut &[u8]) -> Result<Self, Error> {
+        let mut fields = record_fields(decode(&schemas.response, reader)?)?;
+        let server_hash = match fields.pop() {
+            Some(Value::Union(value)) => match *value {
+                Value::Null => None,
+                value => Some(md5_from_value(Some(value))?),
+            },
+            _ => return Err(DecodeError::new("invalid serverHash in handshake").into()),
+        };
+        let server_protocol = string_from_value(fields.pop())?;
+        let match_ = match fields.pop() {
+            Some(Value::Enum(0, _)) => HandshakeMatch::Both,
+            Some(Value::Enum(1, _)) => HandshakeMatch::Client,
+            Some(Value::Enum(2, _)) => HandshakeMatch::None,
+            _ => return Err(DecodeError::new("invalid match in handshake").into()),
+        };
+        Ok(HandshakeResponse {
+            match_,
+            server_protocol,
+            server_hash,
+        })
+    }
+}
+
+fn optional(value: Option<Value>) -> Value {
+    Value::Union(Box::new(value.unwrap_or(Value::Null)))
+}
+
+fn md5_value(hash: [u8; 16]) -> Value {
+    Value::Fixed(16, hash.to_vec())
+}
+
+// Return the values of the fields of a decoded handshake record, without `meta`.
+fn record_fields(record: Value) -> Result<Vec<Value>, Error> {
+    match record {
+        Value::Record(mut fields) => {
+            fields.pop();
+            Ok(fields.into_iter().map(|(_, value)| value).collect())
+        },
+        _ => Err(DecodeError::new("handshake must be a record").into()),
+    }
+}
+
+fn md5_from_value(value: Option<Value>) -> Result<[u8; 16], Error> {
+    match value {
+        Some(Value::Fixed(16, bytes)) => {
+            let mut hash = [0u8; 16];
+            hash.copy_from_slice(&bytes);
+            Ok(hash)
+        },
+        _ => Err(DecodeError::new("invalid MD5 in handshake").into()),
+    }
+}
+
+fn string_from_value(value: Option<Value>) -> Result<Option<String>, Error> {
+    match value {
+        Some(Value::Union(value)) => match *value {
+            Value::Null => Ok(None),
+            Value::String(s) => Ok(Some(s)),
+            _ => Err(DecodeError::new("invalid protocol in handshake").into()),
+        },
+        _ => Err(DecodeError::new("invalid protocol in handshake").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_round_trip() {
+        let schemas = HandshakeSchemas::new();
+
+        let request = HandshakeRequest {
+            client_hash: [1; 16],
+            client_protocol: Some("{}".to_owned()),
+            server_hash: [2; 16],
+        };
+        let mut buffer = Vec::new();
+        request.write(&schemas, &mut buffer).unwrap();
+        let mut expected = vec![1; 16];
+        expected.extend_from_slice(&[2, 4, b'{', b'}']);
+        expected.extend_from_slice(&[2; 16]);
+        expected.push(0);
+        assert_eq!(buffer, expected);
+        assert_eq!(HandshakeRequest::read(&schemas, &mut &buffer[..]).unwrap(), request);
+
+        for response in vec![
+            HandshakeResponse {
+                match_: HandshakeMatch::Both,
+                server_protocol: None,
+                server_hash: None,
+            },
+            HandshakeResponse {
+                match_: HandshakeMatch::Client,
+                server_protocol: Some("{}".to_owned()),
+                server_hash: Some([3; 16]),
+            },
+        ] {
+            let mut buffer = Vec::new();
+            response.write(&schemas, &mut buffer).unwrap();
+            let mut reader = &buffer[..];
+            assert_eq!(HandshakeResponse::read(&schemas, &mut reader).unwrap(), response);
+            assert!(reader.is_empty());
+        }
+    }
+}
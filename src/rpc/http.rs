@@ -0,0 +1,127 @@
+//! Logic handling the HTTP transport of Avro RPC.
+//!
+//! Each request is sent as the body of an HTTP `POST`, the response being the body of the HTTP
+//! response, both of them framed and of content type `avro/binary`.
+//!
+//! Only available with the `http` feature enabled.
+use std::io::Read;
+
+use error::Error;
+use ureq;
+
+use super::framing::{frame_message, read_framed};
+use super::{Responder, RpcError, Transceiver};
+
+/// Content type of Avro RPC requests and responses sent over HTTP.
+pub const AVRO_CONTENT_TYPE: &str = "avro/binary";
+
+/// A stateless `Transceiver` posting requests to an HTTP server.
+pub struct HttpTransceiver {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl HttpTransceiver {
+    /// Creates an `HttpTransceiver` posting requests to `url`.
+    pub fn new(url: &str) -> HttpTransceiver {
+        HttpTransceiver {
+            url: url.to_owned(),
+            agent: ureq::AgentBuilder::new().build(),
+        }
+    }
+}
+
+impl Transceiver for HttpTransceiver {
+    fn transceive(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        let response = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", AVRO_CONTENT_TYPE)
+            .send_bytes(&frame_message(request));
+        match response {
+            Ok(response) => read_framed(&mut response.into_reader()),
+            Err(ureq::Error::Status(status, response)) => Err(RpcError::new(format!(
+                "HTTP {}: {}",
+                status,
+                response.status_text()
+            )).into()),
+            Err(e) => Err(RpcError::new(e.to_string()).into()),
+        }
+    }
+}
+
+impl Responder {
+    /// Respond to the body of an HTTP request, returning the body of the HTTP response.
+    ///
+    /// This allows serving a `Responder` from any HTTP server, for requests sent by an
+    /// `HttpTransceiver` or by the HTTP transceivers of other Avro implementations.
+    pub fn respond_framed<R: Read>(&self, body: &mut R) -> Result<Vec<u8>, Error> {
+        let request = read_framed(body)?;
+        self.respond(&request).map(|response| frame_message(&response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc::{Protocol, Requestor};
+    use std::thread;
+    use test_http::{self, Request, Response};
+    use types::Value;
+
+    static PROTOCOL: &'static str = r#"
+        {
+            "protocol": "Echo",
+            "messages": {
+                "echo": {"request": [{"name": "s", "type": "string"}], "response": "string"}
+            }
+        }
+    "#;
+
+    /// Serve `requests` HTTP requests with an echo `Responder`.
+    fn serve(requests: usize) -> (String, thread::JoinHandle<Vec<Request>>) {
+        let mut responder = Responder::new(Protocol::parse_str(PROTOCOL).unwrap());
+        responder
+            .add_handler("echo", |request| match request {
+                Value::Record(mut fields) => Ok(fields.remove(0).1),
+                _ => unreachable!(),
+            }).unwrap();
+        test_http::serve(requests, move |request| Response {
+            content_type: Some(AVRO_CONTENT_TYPE),
+            ..Response::new(200, responder.respond_framed(&mut &request.body[..]).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_http_transceiver() {
+        let (url, handle) = serve(2);
+        let protocol = Protocol::parse_str(PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, HttpTransceiver::new(&(url + "/")));
+
+        for s in &["foo", "bar"] {
+            let request = Value::Record(vec![("s".to_owned(), Value::String(s.to_string()))]);
+            assert_eq!(
+                requestor.request("echo", request).unwrap(),
+                Value::String(s.to_string())
+            );
+        }
+        for request in handle.join().unwrap() {
+            assert_eq!(request.header("content-type"), Some(AVRO_CONTENT_TYPE));
+        }
+    }
+
+    #[test]
+    fn test_http_error() {
+        let (url, handle) = test_http::serve(1, |_| Response {
+            reason: "Service Unavailable",
+            ..Response::new(503, "")
+        });
+
+        let mut transceiver = HttpTransceiver::new(&(url + "/"));
+        assert_eq!(
+            transceiver.transceive(&[]).unwrap_err().to_string(),
+            "RPC error: HTTP 503: Service Unavailable"
+        );
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+//! Logic handling Avro RPC.
+//!
+//! A [`Protocol`](struct.Protocol.html) describes the messages a server answers to. Clients send
+//! requests through a [`Requestor`](struct.Requestor.html), on top of any
+//! [`Transceiver`](trait.Transceiver.html), and servers dispatch them to handlers registered in a
+//! [`Responder`](struct.Responder.html).
+//!
+//! More information about Avro RPC can be found in the
+//! [Avro specification](https://avro.apache.org/docs/current/spec.html#Protocol+Wire+Format)
+use std::collections::HashMap;
+
+use error::Error;
+
+use decode::skip;
+use encode::encode;
+use schema::Schema;
+use types::Value;
+
+mod codegen;
+mod framing;
+mod handshake;
+#[cfg(feature = "http")]
+mod http;
+mod protocol;
+mod requestor;
+mod responder;
+mod transport;
+
+pub use self::codegen::{generate, record_fields, take_field, AvroType};
+pub use self::framing::{frame_message, read_framed, write_framed, FrameDecoder, FRAME_SIZE};
+#[cfg(feature = "http")]
+pub use self::http::{HttpTransceiver, AVRO_CONTENT_TYPE};
+pub use self::protocol::{Message, Protocol};
+pub use self::requestor::Requestor;
+pub use self::responder::Responder;
+pub use self::transport::{LocalTransceiver, StreamTransceiver, Transceiver};
+
+/// Describes errors returned by the server in response to a request, as opposed to the errors
+/// happened while performing the call itself.
+///
+/// Handlers of a [`Responder`](struct.Responder.html) return a `RemoteError::Declared` to send one
+/// of the errors declared by their message back to the client. Any other error is sent as an
+/// undeclared error.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RemoteError {
+    /// An error declared by the message, along with the name of its type.
+    #[error("Remote error {name}: {value:?}")]
+    Declared { name: String, value: Value },
+    /// An error not declared by the message, described by a string.
+    #[error("Remote error: {0}")]
+    Undeclared(String),
+}
+
+impl RemoteError {
+    /// Create a `RemoteError::Declared` given the name of the error type and its value.
+    pub fn declared<S>(name: S, value: Value) -> RemoteError
+    where
+        S: Into<String>,
+    {
+        RemoteError::Declared {
+            name: name.into(),
+            value,
+        }
+    }
+}
+
+/// Describes errors happened while performing remote procedure calls.
+#[derive(Error, Debug)]
+#[error("RPC error: {0}")]
+pub struct RpcError(String);
+
+impl RpcError {
+    pub fn new<S>(msg: S) -> RpcError
+    where
+        S: Into<String>,
+    {
+        RpcError(msg.into())
+    }
+}
+
+impl From<RpcError> for Error {
+    fn from(error: RpcError) -> Error {
+        Error::Rpc { reason: error.0 }
+    }
+}
+
+// Write the (empty) metadata preceding requests and responses.
+fn write_metadata(buffer: &mut Vec<u8>) {
+    encode(
+        &Value::Map(HashMap::new()),
+        &Schema::Map(Box::new(Schema::Bytes)),
+        buffer,
+    );
+}
+
+// Read past the metadata preceding requests and responses.
+fn read_metadata(reader: &mut &[u8]) -> Result<(), Error> {
+    skip(&Schema::Map(Box::new(Schema::Bytes)), reader)
+}
@@ -0,0 +1,431 @@
+//! Logic for parsing and interacting with protocols in Avro format.
+use std::collections::HashMap;
+
+use error::Error;
+use md5;
+use serde::ser::{Serialize, Serializer};
+use serde_json::{self, Map, Value};
+
+use schema::{Documentation, Name, ParseSchemaError, Schema};
+use util::MapHelper;
+
+/// Represents an Avro protocol: a set of named types and of messages exchanged by a client and a
+/// server.
+///
+/// More information about Avro protocols can be found in the
+/// [Avro Specification](https://avro.apache.org/docs/current/spec.html#Protocol+Declaration)
+#[derive(Clone, Debug)]
+pub struct Protocol {
+    name: Name,
+    doc: Documentation,
+    types: Vec<Schema>,
+    messages: HashMap<String, Message>,
+    // The JSON the protocol was parsed from, which is what gets sent to the other side during the
+    // handshake and whose MD5 identifies the protocol.
+    json: Value,
+    md5: [u8; 16],
+}
+
+/// Represents a `message` of an Avro protocol.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    /// Name of the message.
+    pub name: String,
+    /// Documentation of the message.
+    pub doc: Documentation,
+    /// Schema of the request, as a `record` whose fields are the parameters of the message.
+    pub request: Schema,
+    /// Schema of the response.
+    pub response: Schema,
+    /// Schema of the errors, as a `union` whose first variant is a `string`, used for errors which
+    /// are not declared by the message.
+    pub errors: Schema,
+    /// Whether the message is one-way, in which case the client does not expect any response.
+    pub one_way: bool,
+}
+
+impl Protocol {
+    /// Create a `Protocol` from a string representing a JSON Avro protocol.
+    pub fn parse_str(input: &str) -> Result<Self, Error> {
+        let value = serde_json::from_str(input)?;
+        Self::parse(&value)
+    }
+
+    /// Create a `Protocol` from a `serde_json::Value` representing a JSON Avro protocol.
+    ///
+    /// **NOTE** Named types can be referenced by name once they have been declared in `types`, but
+    /// cannot reference themselves.
+    pub fn parse(value: &Value) -> Result<Self, Error> {
+        let complex = value
+            .as_object()
+            .ok_or_else(|| ParseSchemaError::new("Protocol must be a JSON object"))?;
+
+        let name = Name {
+            name: complex
+                .string("protocol")
+                .ok_or_else(|| ParseSchemaError::new("No `protocol` field"))?,
+            namespace: complex.string("namespace"),
+            aliases: None,
+        };
+        let mut names = NamedTypes {
+            namespace: name.namespace.clone(),
+            definitions: HashMap::new(),
+        };
+
+        let mut types = Vec::new();
+        if let Some(declared) = complex.get("types") {
+            let declared = declared
+                .as_array()
+                .ok_or_else(|| ParseSchemaError::new("`types` must be a JSON array"))?;
+            for type_ in declared {
+                let expanded = names.expand(type_);
+                let schema = Schema::parse(&expanded)?;
+                names.define(&schema, expanded);
+                types.push(schema);
+            }
+        }
+
+        let mut messages = HashMap::new();
+        if let Some(declared) = complex.get("messages") {
+            let declared = declared
+                .as_object()
+                .ok_or_else(|| ParseSchemaError::new("`messages` must be a JSON object"))?;
+            for (message_name, message) in declared {
+                let message = message.as_object().ok_or_else(|| {
+                    ParseSchemaError::new(format!("Message {} must be a JSON object", message_name))
+                })?;
+                messages.insert(
+                    message_name.clone(),
+                    Message::parse(message_name, message, &names)?,
+                );
+            }
+        }
+
+        let md5 = md5::compute(value.to_string().as_bytes()).0;
+
+        Ok(Protocol {
+            name,
+            doc: complex.doc(),
+            types,
+            messages,
+            json: value.clone(),
+            md5,
+        })
+    }
+
+    /// Get the name of the protocol.
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// Get the documentation of the protocol.
+    pub fn doc(&self) -> &Documentation {
+        &self.doc
+    }
+
+    /// Get the named types declared by the protocol.
+    pub fn types(&self) -> &[Schema] {
+        &self.types
+    }
+
+    /// Get the messages of the protocol, by name.
+    pub fn messages(&self) -> &HashMap<String, Message> {
+        &self.messages
+    }
+
+    /// Get the message with the given name, if the protocol declares it.
+    pub fn message(&self, name: &str) -> Option<&Message> {
+        self.messages.get(name)
+    }
+
+    /// Get the MD5 hash identifying the protocol during the handshake.
+    pub fn md5(&self) -> [u8; 16] {
+        self.md5
+    }
+}
+
+impl PartialEq for Protocol {
+    fn eq(&self, other: &Protocol) -> bool {
+        self.json == other.json
+    }
+}
+
+impl Serialize for Protocol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.json.serialize(serializer)
+    }
+}
+
+impl Message {
+    /// Parse a `serde_json::Value` into a `Message`.
+    fn parse(name: &str, message: &Map<String, Value>, names: &NamedTypes) -> Result<Self, Error> {
+        let params = message
+            .get("request")
+            .and_then(|request| request.as_array())
+            .ok_or_else(|| ParseSchemaError::new(format!("No `request` in message {}", name)))?;
+        let request = Schema::parse(&json!({
+            "type": "record",
+            "name": name,
+            "fields": params.iter().map(|param| names.expand(param)).collect::<Vec<_>>(),
+        }))?;
+
+        let response = message
+            .get("response")
+            .ok_or_else(|| ParseSchemaError::new(format!("No `response` in message {}", name)))
+            .map_err(Error::from)
+            .and_then(|response| Schema::parse(&names.expand(response)))?;
+
+        // The union of the errors always implicitly starts with `string`.
+        let mut errors = vec![Value::String("string".to_owned())];
+        if let Some(declared) = message.get("errors") {
+            let declared = declared.as_array().ok_or_else(|| {
+                ParseSchemaError::new(format!("`errors` of message {} must be an array", name))
+            })?;
+            errors.extend(declared.iter().map(|error| names.expand(error)));
+        }
+        let errors = Schema::parse(&Value::Array(errors))?;
+
+        let one_way = match message.get("one-way") {
+            Some(&Value::Bool(one_way)) => one_way,
+            Some(_) => {
+                return Err(ParseSchemaError::new(format!(
+                    "`one-way` of message {} must be a boolean",
+                    name
+                )).into())
+            },
+            None => false,
+        };
+        if one_way && (response != Schema::Null || message.contains_key("errors")) {
+            return Err(ParseSchemaError::new(format!(
+                "One-way message {} must have a null response and no errors",
+                name
+            )).into())
+        }
+
+        Ok(Message {
+            name: name.to_owned(),
+            doc: message.doc(),
+            request,
+            response,
+            errors,
+            one_way,
+        })
+    }
+
+    /// Find the declared error with the given name (or fullname), along with its position in the
+    /// union of errors.
+    pub(crate) fn declared_error(&self, name: &str) -> Option<(usize, &Schema)> {
+        let variants = match self.errors {
+            Schema::Union(ref union) => union.variants(),
+            _ => return None,
+        };
+        variants.iter().enumerate().skip(1).find(|&(_, schema)| {
+            schema_name(schema)
+                .is_some_and(|error| error.name == name || error.fullname(None) == name)
+        })
+    }
+}
+
+/// Return the name of a named `Schema`.
+pub(crate) fn schema_name(schema: &Schema) -> Option<&Name> {
+    match *schema {
+        Schema::Record { ref name, .. }
+        | Schema::Enum { ref name, .. }
+        | Schema::Fixed { ref name, .. } => Some(name),
+        Schema::Logical(ref logical) => schema_name(logical.schema()),
+        _ => None,
+    }
+}
+
+// Named types declared so far in a protocol, which references are expanded into.
+struct NamedTypes {
+    namespace: Option<String>,
+    definitions: HashMap<String, Value>,
+}
+
+impl NamedTypes {
+    fn define(&mut self, schema: &Schema, definition: Value) {
+        let name = match schema_name(schema) {
+            Some(name) => name,
+            None => return,
+        };
+        self.definitions
+            .insert(name.fullname(self.namespace.as_ref().map(|s| s.as_ref())), definition);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        match self.namespace {
+            Some(ref namespace) if !name.contains('.') => self
+                .definitions
+                .get(&format!("{}.{}", namespace, name))
+                .or_else(|| self.definitions.get(name)),
+            _ => self.definitions.get(name),
+        }
+    }
+
+    /// Replace the references to declared named types in a JSON schema (or record field) by their
+    /// definition, turning `error` declarations into records along the way.
+    ///
+    /// **NOTE** Named types nested in another one inherit the namespace of the protocol rather
+    /// than the one of their parent.
+    fn expand(&self, schema: &Value) -> Value {
+        match *schema {
+            Value::String(ref name) => self.lookup(name).cloned().unwrap_or_else(|| schema.clone()),
+            Value::Array(ref variants) => {
+                Value::Array(variants.iter().map(|variant| self.expand(variant)).collect())
+            },
+            Value::Object(ref complex) => {
+                let mut expanded = complex.clone();
+                for key in &["type", "items", "values"] {
+                    if let Some(value) = complex.get(*key) {
+                        expanded.insert((*key).to_owned(), self.expand(value));
+                    }
+                }
+                let named = match complex.get("type").and_then(|type_| type_.as_str()) {
+                    Some("error") => {
+                        expanded.insert("type".to_owned(), Value::String("record".to_owned()));
+                        true
+                    },
+                    Some("record") | Some("enum") | Some("fixed") => true,
+                    _ => false,
+                };
+                // Named types declared without a namespace belong to the one of the protocol.
+                if let Some(ref namespace) = self.namespace {
+                    let has_fullname = complex
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .is_some_and(|name| name.contains('.'));
+                    if named && !has_fullname && !complex.contains_key("namespace") {
+                        expanded.insert("namespace".to_owned(), Value::String(namespace.clone()));
+                    }
+                }
+                if let Some(Value::Array(fields)) = complex.get("fields") {
+                    expanded.insert(
+                        "fields".to_owned(),
+                        Value::Array(fields.iter().map(|field| self.expand(field)).collect()),
+                    );
+                }
+                Value::Object(expanded)
+            },
+            _ => schema.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PROTOCOL: &'static str = r#"
+        {
+            "protocol": "Greeter",
+            "namespace": "com.example",
+            "doc": "Says hello.",
+            "types": [
+                {"type": "record", "name": "Greeting", "fields": [
+                    {"name": "message", "type": "string"}
+                ]},
+                {"type": "error", "name": "Curse", "fields": [
+                    {"name": "message", "type": "string"}
+                ]}
+            ],
+            "messages": {
+                "hello": {
+                    "doc": "Say hello.",
+                    "request": [{"name": "greeting", "type": "Greeting"}],
+                    "response": "Greeting",
+                    "errors": ["Curse"]
+                },
+                "ping": {
+                    "request": [],
+                    "response": "null",
+                    "one-way": true
+                }
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_parse_protocol() {
+        let protocol = Protocol::parse_str(PROTOCOL).unwrap();
+        assert_eq!(protocol.name().name, "Greeter");
+        assert_eq!(protocol.name().namespace, Some("com.example".to_owned()));
+        assert_eq!(protocol.doc(), &Some("Says hello.".to_owned()));
+        assert_eq!(protocol.types().len(), 2);
+        assert_eq!(protocol.messages().len(), 2);
+
+        let greeting = Schema::parse_str(
+            r#"{"type": "record", "name": "Greeting", "namespace": "com.example", "fields": [
+                {"name": "message", "type": "string"}
+            ]}"#,
+        ).unwrap();
+        let curse = Schema::parse_str(
+            r#"{"type": "record", "name": "Curse", "namespace": "com.example", "fields": [
+                {"name": "message", "type": "string"}
+            ]}"#,
+        ).unwrap();
+        assert_eq!(protocol.types(), &[greeting.clone(), curse.clone()]);
+
+        let hello = protocol.message("hello").unwrap();
+        assert_eq!(hello.doc, Some("Say hello.".to_owned()));
+        assert_eq!(hello.response, greeting);
+        match hello.request {
+            Schema::Record { ref name, ref fields, .. } => {
+                assert_eq!(name.name, "hello");
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].schema, greeting);
+            },
+            ref other => panic!("Unexpected request: {:?}", other),
+        }
+        match hello.errors {
+            Schema::Union(ref union) => assert_eq!(union.variants(), &[Schema::String, curse]),
+            ref other => panic!("Unexpected errors: {:?}", other),
+        }
+
+        assert!(!hello.one_way);
+
+        let ping = protocol.message("ping").unwrap();
+        assert_eq!(ping.response, Schema::Null);
+        assert!(ping.one_way);
+        assert!(protocol.message("unknown").is_none());
+    }
+
+    #[test]
+    fn test_protocol_md5() {
+        let protocol = Protocol::parse_str(PROTOCOL).unwrap();
+        let json = serde_json::to_string(&protocol).unwrap();
+        assert_eq!(protocol.md5(), md5::compute(json.as_bytes()).0);
+        assert_eq!(Protocol::parse_str(&json).unwrap(), protocol);
+    }
+
+    #[test]
+    fn test_invalid_protocol() {
+        assert!(Protocol::parse_str(r#"{"namespace": "com.example"}"#).is_err());
+        assert!(
+            Protocol::parse_str(
+                r#"{"protocol": "P", "messages": {"m": {"request": [], "response": "Unknown"}}}"#
+            ).is_err()
+        );
+        assert!(
+            Protocol::parse_str(r#"{"protocol": "P", "messages": {"m": {"response": "null"}}}"#)
+                .is_err()
+        );
+        assert!(
+            Protocol::parse_str(
+                r#"{"protocol": "P", "messages": {
+                    "m": {"request": [], "response": "int", "one-way": true}
+                }}"#
+            ).is_err()
+        );
+        assert!(
+            Protocol::parse_str(
+                r#"{"protocol": "P", "messages": {
+                    "m": {"request": [], "response": "null", "errors": [], "one-way": true}
+                }}"#
+            ).is_err()
+        );
+    }
+}
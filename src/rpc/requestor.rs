@@ -0,0 +1,389 @@
+//! Logic handling the client side of Avro RPC.
+use error::Error;
+use serde_json;
+
+use datum::write_avro_datum;
+use decode::decode;
+use encode::encode;
+use schema::Schema;
+use types::{ToAvro, Value};
+use util::DecodeError;
+
+use super::handshake::{HandshakeMatch, HandshakeRequest, HandshakeResponse, HandshakeSchemas};
+use super::protocol::schema_name;
+use super::{read_metadata, write_metadata, Message, Protocol, RemoteError, RpcError, Transceiver};
+
+/// Client of an Avro RPC server.
+///
+/// The handshake is taken care of transparently: the local protocol is only sent to the server
+/// if it does not know it already, and responses are resolved against the local protocol if the
+/// server speaks a different one.
+pub struct Requestor<T> {
+    local: Protocol,
+    remote: Option<Protocol>,
+    transceiver: T,
+    handshake: HandshakeSchemas,
+    send_local_protocol: bool,
+    established: bool,
+}
+
+impl<T: Transceiver> Requestor<T> {
+    /// Creates a `Requestor` speaking the `protocol` to a server through `transceiver`.
+    pub fn new(protocol: Protocol, transceiver: T) -> Requestor<T> {
+        Requestor {
+            local: protocol,
+            remote: None,
+            transceiver,
+            handshake: HandshakeSchemas::new(),
+            send_local_protocol: false,
+            established: false,
+        }
+    }
+
+    /// Get a reference to the local `Protocol`.
+    pub fn local_protocol(&self) -> &Protocol {
+        &self.local
+    }
+
+    /// Get a reference to the `Protocol` of the server, if it is known to differ from the local
+    /// one.
+    pub fn remote_protocol(&self) -> Option<&Protocol> {
+        self.remote.as_ref()
+    }
+
+    /// Get a reference to the underlying `Transceiver`.
+    pub fn transceiver(&self) -> &T {
+        &self.transceiver
+    }
+
+    /// Send the request of the message `message_name`, a compatible value (implementing the
+    /// `ToAvro` trait) whose fields are the parameters of the message, and return its response.
+    ///
+    /// One-way messages sent through a stateful `Transceiver` once the handshake happened do not
+    /// wait for any response: `Value::Null` is returned straight away.
+    ///
+    /// Errors returned by the server are reported as [`RemoteError`](enum.RemoteError.html)s.
+    pub fn request<V: ToAvro>(&mut self, message_name: &str, request: V) -> Result<Value, Error> {
+        let mut call = Vec::new();
+        let one_way = {
+            let message = self.local_message(message_name)?;
+            write_metadata(&mut call);
+            encode(
+                &Value::String(message_name.to_owned()),
+                &Schema::String,
+                &mut call,
+            );
+            write_avro_datum(&message.request, request, &mut call)?;
+            message.one_way
+        };
+
+        // Once the handshake happened, one-way messages do not get any response.
+        if one_way && self.established && self.transceiver.is_stateful() {
+            self.transceiver.send(&call)?;
+            return Ok(Value::Null)
+        }
+
+        loop {
+            let handshake = !(self.established && self.transceiver.is_stateful());
+            let mut buffer = Vec::new();
+            if handshake {
+                self.handshake_request()?.write(&self.handshake, &mut buffer)?;
+            }
+            buffer.extend_from_slice(&call);
+
+            let response = self.transceiver.transceive(&buffer)?;
+            let mut reader = &response[..];
+            if handshake && !self.read_handshake(&mut reader)? {
+                continue
+            }
+            return self.read_response(message_name, &mut reader)
+        }
+    }
+
+    fn local_message(&self, message_name: &str) -> Result<&Message, Error> {
+        self.local.message(message_name).ok_or_else(|| {
+            RpcError::new(format!("unknown message {}", message_name)).into()
+        })
+    }
+
+    fn handshake_request(&self) -> Result<HandshakeRequest, Error> {
+        Ok(HandshakeRequest {
+            client_hash: self.local.md5(),
+            client_protocol: if self.send_local_protocol {
+                Some(serde_json::to_string(&self.local)?)
+            } else {
+                None
+            },
+            server_hash: self.remote.as_ref().unwrap_or(&self.local).md5(),
+        })
+    }
+
+    /// Read the handshake response, returning whether the request was processed by the server.
+    fn read_handshake(&mut self, reader: &mut &[u8]) -> Result<bool, Error> {
+        let response = HandshakeResponse::read(&self.handshake, reader)?;
+        if let Some(ref protocol) = response.server_protocol {
+            let protocol = Protocol::parse_str(protocol)?;
+            self.remote = if protocol.md5() == self.local.md5() {
+                None
+            } else {
+                Some(protocol)
+            };
+        }
+
+        match response.match_ {
+            HandshakeMatch::Both | HandshakeMatch::Client => {
+                self.send_local_protocol = false;
+                self.established = true;
+                Ok(true)
+            },
+            HandshakeMatch::None if !self.send_local_protocol => {
+                self.send_local_protocol = true;
+                Ok(false)
+            },
+            HandshakeMatch::None => {
+                Err(RpcError::new("server does not accept the client protocol").into())
+            },
+        }
+    }
+
+    fn read_response(&self, message_name: &str, reader: &mut &[u8]) -> Result<Value, Error> {
+        let local = self.local_message(message_name)?;
+        let remote = match self.remote {
+            Some(ref protocol) => protocol.message(message_name).ok_or_else(|| {
+                RpcError::new(format!("message {} unknown to the server", message_name))
+            })?,
+            None => local,
+        };
+
+        read_metadata(reader)?;
+        let is_error = match decode(&Schema::Boolean, reader)? {
+            Value::Boolean(is_error) => is_error,
+            _ => unreachable!(),
+        };
+
+        if is_error {
+            Err(read_error(remote, local, reader)?.into())
+        } else {
+            resolve(decode(&remote.response, reader)?, remote, local, |m| &m.response)
+        }
+    }
+}
+
+// Read an error written with the union of errors of the remote message.
+fn read_error(remote: &Message, local: &Message, reader: &mut &[u8]) -> Result<RemoteError, Error> {
+    let variants = match remote.errors {
+        Schema::Union(ref union) => union.variants(),
+        _ => unreachable!(),
+    };
+    let index = match decode(&Schema::Long, reader)? {
+        Value::Long(index) => index as usize,
+        _ => unreachable!(),
+    };
+    let schema = variants
+        .get(index)
+        .ok_or_else(|| DecodeError::new(format!("error index {} out of bounds", index)))?;
+    let value = decode(schema, reader)?;
+
+    match (value, schema_name(schema)) {
+        (Value::String(message), None) => Ok(RemoteError::Undeclared(message)),
+        (value, Some(name)) => {
+            let name = name.fullname(None);
+            let value = match local.declared_error(&name) {
+                Some((_, local_schema)) if local_schema != schema => value.resolve(local_schema)?,
+                _ => value,
+            };
+            Ok(RemoteError::Declared { name, value })
+        },
+        _ => Err(DecodeError::new("invalid error").into()),
+    }
+}
+
+// Resolve a value written with a schema of the remote message against the local one.
+fn resolve<F>(value: Value, remote: &Message, local: &Message, schema: F) -> Result<Value, Error>
+where
+    F: Fn(&Message) -> &Schema,
+{
+    if schema(remote) == schema(local) {
+        Ok(value)
+    } else {
+        value.resolve(schema(local))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc::{LocalTransceiver, Responder};
+
+    static SERVER_PROTOCOL: &'static str = r#"
+        {
+            "protocol": "Echo",
+            "messages": {
+                "echo": {"request": [{"name": "n", "type": "int"}], "response": "int"}
+            }
+        }
+    "#;
+
+    static CLIENT_PROTOCOL: &'static str = r#"
+        {
+            "protocol": "Echo",
+            "doc": "An older client.",
+            "messages": {
+                "echo": {"request": [{"name": "n", "type": "int"}], "response": "long"}
+            }
+        }
+    "#;
+
+    // Count the requests going through a transceiver.
+    struct Counting<T> {
+        inner: T,
+        count: usize,
+    }
+
+    impl<T: Transceiver> Transceiver for Counting<T> {
+        fn transceive(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+            self.count += 1;
+            self.inner.transceive(request)
+        }
+    }
+
+    fn echo() -> Responder {
+        let mut responder = Responder::new(Protocol::parse_str(SERVER_PROTOCOL).unwrap());
+        responder
+            .add_handler("echo", |request| match request {
+                Value::Record(mut fields) => Ok(fields.remove(0).1),
+                _ => unreachable!(),
+            }).unwrap();
+        responder
+    }
+
+    fn request(n: i32) -> Value {
+        Value::Record(vec![("n".to_owned(), Value::Int(n))])
+    }
+
+    #[test]
+    fn test_same_protocol() {
+        let responder = echo();
+        let transceiver = Counting {
+            inner: LocalTransceiver::new(&responder),
+            count: 0,
+        };
+        let protocol = Protocol::parse_str(SERVER_PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, transceiver);
+
+        assert_eq!(requestor.request("echo", request(7)).unwrap(), Value::Int(7));
+        assert!(requestor.remote_protocol().is_none());
+        assert_eq!(requestor.transceiver().count, 1);
+    }
+
+    #[test]
+    fn test_different_protocols() {
+        let responder = echo();
+        let transceiver = Counting {
+            inner: LocalTransceiver::new(&responder),
+            count: 0,
+        };
+        let protocol = Protocol::parse_str(CLIENT_PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, transceiver);
+
+        // The server does not know the client protocol yet: it has to be sent.
+        assert_eq!(requestor.request("echo", request(7)).unwrap(), Value::Long(7));
+        assert_eq!(requestor.remote_protocol(), Some(responder.protocol()));
+        assert_eq!(requestor.transceiver().count, 2);
+
+        assert_eq!(requestor.request("echo", request(8)).unwrap(), Value::Long(8));
+        assert_eq!(requestor.transceiver().count, 3);
+
+        // Another client speaking the same protocol is already known to the server.
+        let protocol = Protocol::parse_str(CLIENT_PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, LocalTransceiver::new(&responder));
+        assert_eq!(requestor.request("echo", request(9)).unwrap(), Value::Long(9));
+    }
+
+    static ERRORS_PROTOCOL: &'static str = r#"
+        {
+            "protocol": "Checker",
+            "namespace": "com.example",
+            "types": [
+                {"type": "error", "name": "Invalid", "fields": [
+                    {"name": "reason", "type": "string"}
+                ]},
+                {"type": "enum", "name": "Code", "symbols": ["NEGATIVE", "ZERO"]}
+            ],
+            "messages": {
+                "check": {
+                    "request": [{"name": "n", "type": "int"}],
+                    "response": "null",
+                    "errors": ["Invalid", "Code"]
+                }
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_declared_errors() {
+        let mut responder = Responder::new(Protocol::parse_str(ERRORS_PROTOCOL).unwrap());
+        responder
+            .add_handler("check", |request| {
+                let n = match request {
+                    Value::Record(ref fields) => fields[0].1.clone(),
+                    _ => unreachable!(),
+                };
+                match n {
+                    Value::Int(0) => Err(RemoteError::declared(
+                        "Code",
+                        Value::Enum(1, "ZERO".into()),
+                    ).into()),
+                    Value::Int(1) => Err(RemoteError::declared(
+                        "com.example.Invalid",
+                        Value::Record(vec![("reason".to_owned(), Value::String("one".to_owned()))]),
+                    ).into()),
+                    Value::Int(2) => Err(RemoteError::declared("Unknown", Value::Null).into()),
+                    Value::Int(3) => Err(RemoteError::Undeclared("three".to_owned()).into()),
+                    _ => Ok(Value::Null),
+                }
+            }).unwrap();
+
+        let protocol = Protocol::parse_str(ERRORS_PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, LocalTransceiver::new(&responder));
+        let mut check = |n| {
+            requestor
+                .request("check", request(n))
+                .map_err(|e| match e {
+                    Error::Remote(e) => e,
+                    other => panic!("unexpected error {:?}", other),
+                })
+        };
+
+        assert_eq!(check(5), Ok(Value::Null));
+        assert_eq!(
+            check(0),
+            Err(RemoteError::declared(
+                "com.example.Code",
+                Value::Enum(1, "ZERO".into())
+            ))
+        );
+        assert_eq!(
+            check(1),
+            Err(RemoteError::declared(
+                "com.example.Invalid",
+                Value::Record(vec![("reason".to_owned(), Value::String("one".to_owned()))]),
+            ))
+        );
+        assert_eq!(
+            check(2),
+            Err(RemoteError::Undeclared("Remote error Unknown: Null".to_owned()))
+        );
+        assert_eq!(check(3), Err(RemoteError::Undeclared("three".to_owned())));
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let responder = echo();
+        let protocol = Protocol::parse_str(SERVER_PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, LocalTransceiver::new(&responder));
+
+        assert!(requestor.request("unknown", request(7)).is_err());
+        assert!(requestor.request("echo", Value::Int(7)).is_err());
+    }
+}
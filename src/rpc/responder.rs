@@ -0,0 +1,353 @@
+//! Logic handling the server side of Avro RPC.
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use error::Error;
+use serde_json;
+
+use datum::write_avro_datum;
+use decode::decode;
+use encode::encode;
+use schema::Schema;
+use types::Value;
+
+use super::framing::{read_framed, write_framed};
+use super::handshake::{HandshakeMatch, HandshakeRequest, HandshakeResponse, HandshakeSchemas};
+use super::{read_metadata, write_metadata, Message, Protocol, RemoteError, RpcError};
+
+type Handler = Box<dyn Fn(Value) -> Result<Value, Error> + Send + Sync>;
+
+// The response to a request, if any, along with the protocol of the client, if it was accepted.
+type Response = (Option<Vec<u8>>, Option<Arc<Protocol>>);
+
+/// Server of an Avro protocol, dispatching requests to the handler registered for their message.
+///
+/// Requests of clients speaking a different version of the protocol are resolved against the
+/// local one. The protocols of such clients are cached by MD5 hash, so that they only need to be
+/// sent once.
+pub struct Responder {
+    local: Arc<Protocol>,
+    handlers: HashMap<String, Handler>,
+    remotes: Mutex<HashMap<[u8; 16], Arc<Protocol>>>,
+    handshake: HandshakeSchemas,
+}
+
+impl Responder {
+    /// Creates a `Responder` serving `protocol`, without any handler.
+    pub fn new(protocol: Protocol) -> Responder {
+        Responder {
+            local: Arc::new(protocol),
+            handlers: HashMap::new(),
+            remotes: Mutex::new(HashMap::new()),
+            handshake: HandshakeSchemas::new(),
+        }
+    }
+
+    /// Get a reference to the local `Protocol`.
+    pub fn protocol(&self) -> &Protocol {
+        &self.local
+    }
+
+    /// Register the handler of the message `message_name`.
+    ///
+    /// The handler is given the request, a `Value::Record` whose fields are the parameters of the
+    /// message, and returns the response. Errors returned by the handler are sent back to the
+    /// client.
+    pub fn add_handler<F>(&mut self, message_name: &str, handler: F) -> Result<(), Error>
+    where
+        F: Fn(Value) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        if self.local.message(message_name).is_none() {
+            return Err(RpcError::new(format!("unknown message {}", message_name)).into())
+        }
+        self.handlers.insert(message_name.to_owned(), Box::new(handler));
+        Ok(())
+    }
+
+    /// Respond to a request (a whole, unframed message) received through a stateless transport,
+    /// where each request is preceded by a handshake.
+    pub fn respond(&self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.respond_to(request, None)
+            .map(|(response, _)| response.unwrap_or_default())
+    }
+
+    /// Serve the requests of a single client connected through `stream`, until it disconnects.
+    ///
+    /// Only the first request of the connection is preceded by a handshake. One-way messages
+    /// following it do not get any response.
+    pub fn serve<S: Read + Write>(&self, stream: &mut S) -> Result<(), Error> {
+        let mut remote = None;
+        loop {
+            let request = match read_framed(stream) {
+                Ok(request) => request,
+                Err(Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            let (response, negotiated) = self.respond_to(&request, remote.as_ref())?;
+            if remote.is_none() {
+                remote = negotiated;
+            }
+            if let Some(response) = response {
+                write_framed(stream, &response)?;
+                stream.flush()?;
+            }
+        }
+    }
+
+    /// Respond to a request, with the protocol of the client if the handshake already happened.
+    /// Return the response, unless there is none to send, along with the protocol of the client,
+    /// if it was accepted.
+    fn respond_to(
+        &self,
+        request: &[u8],
+        remote: Option<&Arc<Protocol>>,
+    ) -> Result<Response, Error> {
+        let mut reader = request;
+        let mut buffer = Vec::new();
+        let established = remote.is_some();
+        let remote = match remote {
+            Some(remote) => remote.clone(),
+            None => {
+                let handshake = HandshakeRequest::read(&self.handshake, &mut reader)?;
+                match self.accept(&handshake, &mut buffer)? {
+                    Some(remote) => remote,
+                    None => return Ok((Some(buffer), None)),
+                }
+            },
+        };
+
+        read_metadata(&mut reader)?;
+        let message_name = match decode(&Schema::String, &mut reader)? {
+            Value::String(message_name) => message_name,
+            _ => unreachable!(),
+        };
+        let message = self.local.message(&message_name);
+
+        // Once the handshake happened, one-way messages do not get any response, errors included.
+        if let Some(message) = message {
+            if message.one_way && established {
+                let _ = self.call(&remote, message, &mut reader);
+                return Ok((None, Some(remote)))
+            }
+        }
+
+        write_metadata(&mut buffer);
+        let response = message
+            .ok_or_else(|| RpcError::new(format!("unknown message {}", message_name)).into())
+            .and_then(|message| {
+                let response = self.call(&remote, message, &mut reader)?;
+                let mut encoded = vec![0];
+                write_avro_datum(&message.response, response, &mut encoded)?;
+                Ok(encoded)
+            });
+        match response {
+            Ok(response) => buffer.extend_from_slice(&response),
+            Err(e) => write_error(&e, message, &mut buffer),
+        }
+        Ok((Some(buffer), Some(remote)))
+    }
+
+    /// Write the response to a handshake, returning the protocol of the client if it is known.
+    fn accept(
+        &self,
+        handshake: &HandshakeRequest,
+        buffer: &mut Vec<u8>,
+    ) -> Result<Option<Arc<Protocol>>, Error> {
+        let mut remote = if handshake.client_hash == self.local.md5() {
+            Some(self.local.clone())
+        } else {
+            self.remotes
+                .lock()
+                .unwrap()
+                .get(&handshake.client_hash)
+                .cloned()
+        };
+        if remote.is_none() {
+            if let Some(ref protocol) = handshake.client_protocol {
+                let protocol = Arc::new(Protocol::parse_str(protocol)?);
+                self.remotes
+                    .lock()
+                    .unwrap()
+                    .insert(handshake.client_hash, protocol.clone());
+                remote = Some(protocol);
+            }
+        }
+
+        let match_ = match remote {
+            None => HandshakeMatch::None,
+            Some(_) if handshake.server_hash == self.local.md5() => HandshakeMatch::Both,
+            Some(_) => HandshakeMatch::Client,
+        };
+        let response = if match_ == HandshakeMatch::Both {
+            HandshakeResponse {
+                match_,
+                server_protocol: None,
+                server_hash: None,
+            }
+        } else {
+            HandshakeResponse {
+                match_,
+                server_protocol: Some(serde_json::to_string(&*self.local)?),
+                server_hash: Some(self.local.md5()),
+            }
+        };
+        response.write(&self.handshake, buffer)?;
+        Ok(remote)
+    }
+
+    /// Decode the request of `message` and dispatch it to its handler, returning its response.
+    fn call(&self, remote: &Protocol, message: &Message, reader: &mut &[u8]) -> Result<Value, Error> {
+        let request = if remote.md5() == self.local.md5() {
+            decode(&message.request, reader)?
+        } else {
+            let writer = remote.message(&message.name).ok_or_else(|| {
+                RpcError::new(format!("message {} unknown to the client", message.name))
+            })?;
+            decode(&writer.request, reader)?.resolve(&message.request)?
+        };
+
+        let handler = self
+            .handlers
+            .get(&message.name)
+            .ok_or_else(|| RpcError::new(format!("no handler for message {}", message.name)))?;
+        handler(request)
+    }
+}
+
+// Errors not declared by the message are sent as strings, the first variant of its errors.
+fn write_error(error: &Error, message: Option<&Message>, buffer: &mut Vec<u8>) {
+    buffer.push(1);
+    let description = match *error {
+        Error::Remote(RemoteError::Declared { ref name, ref value }) => {
+            let declared = message.and_then(|message| message.declared_error(name));
+            if let Some((index, schema)) = declared {
+                if value.validate(schema) {
+                    encode(&Value::Long(index as i64), &Schema::Long, buffer);
+                    encode(value, schema, buffer);
+                    return
+                }
+            }
+            error.to_string()
+        },
+        Error::Remote(RemoteError::Undeclared(ref description)) => description.clone(),
+        _ => error.to_string(),
+    };
+    encode(&Value::Long(0), &Schema::Long, buffer);
+    encode(&Value::String(description), &Schema::String, buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc::{LocalTransceiver, Requestor, StreamTransceiver};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    static PROTOCOL: &'static str = r#"
+        {
+            "protocol": "Calculator",
+            "messages": {
+                "add": {
+                    "request": [{"name": "a", "type": "int"}, {"name": "b", "type": "int"}],
+                    "response": "int"
+                },
+                "div": {
+                    "request": [{"name": "a", "type": "int"}, {"name": "b", "type": "int"}],
+                    "response": "int"
+                },
+                "store": {
+                    "request": [{"name": "a", "type": "int"}, {"name": "b", "type": "int"}],
+                    "response": "null",
+                    "one-way": true
+                }
+            }
+        }
+    "#;
+
+    fn int_param(request: &Value, position: usize) -> i32 {
+        match *request {
+            Value::Record(ref fields) => match fields[position].1 {
+                Value::Int(i) => i,
+                _ => panic!("int expected"),
+            },
+            _ => panic!("record expected"),
+        }
+    }
+
+    fn calculator(stored: Arc<Mutex<Vec<i32>>>) -> Responder {
+        let mut responder = Responder::new(Protocol::parse_str(PROTOCOL).unwrap());
+        responder
+            .add_handler("store", move |request| {
+                stored.lock().unwrap().push(int_param(&request, 0));
+                Err(Error::custom("never sent back"))
+            }).unwrap();
+        responder
+            .add_handler("add", |request| {
+                Ok(Value::Int(int_param(&request, 0) + int_param(&request, 1)))
+            }).unwrap();
+        responder
+            .add_handler("div", |request| match int_param(&request, 1) {
+                0 => Err(Error::custom("division by zero")),
+                b => Ok(Value::Int(int_param(&request, 0) / b)),
+            }).unwrap();
+        responder
+    }
+
+    fn params(a: i32, b: i32) -> Value {
+        Value::Record(vec![
+            ("a".to_owned(), Value::Int(a)),
+            ("b".to_owned(), Value::Int(b)),
+        ])
+    }
+
+    #[test]
+    fn test_add_unknown_handler() {
+        let mut responder = calculator(Arc::new(Mutex::new(Vec::new())));
+        assert!(responder.add_handler("sub", |request| Ok(request)).is_err());
+    }
+
+    #[test]
+    fn test_one_way_stateless() {
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let responder = calculator(stored.clone());
+        let protocol = Protocol::parse_str(PROTOCOL).unwrap();
+        let mut requestor = Requestor::new(protocol, LocalTransceiver::new(&responder));
+
+        // Stateless transports always get a response, which carries the handshake.
+        assert!(requestor.request("store", params(1, 0)).is_err());
+        assert!(requestor.request("store", params(2, 0)).is_err());
+        assert_eq!(*stored.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let responder = calculator(stored.clone());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            responder.serve(&mut stream).unwrap();
+        });
+
+        let transceiver = StreamTransceiver::new(TcpStream::connect(address).unwrap());
+        let mut requestor = Requestor::new(Protocol::parse_str(PROTOCOL).unwrap(), transceiver);
+        assert_eq!(requestor.request("add", params(1, 2)).unwrap(), Value::Int(3));
+        assert_eq!(requestor.request("div", params(6, 2)).unwrap(), Value::Int(3));
+        assert_eq!(
+            requestor.request("div", params(6, 0)).unwrap_err().to_string(),
+            "Remote error: division by zero"
+        );
+        assert!(requestor.request("mul", params(6, 0)).is_err());
+        assert_eq!(requestor.request("add", params(2, 2)).unwrap(), Value::Int(4));
+        assert_eq!(requestor.request("store", params(5, 0)).unwrap(), Value::Null);
+        assert_eq!(requestor.request("store", params(6, 0)).unwrap(), Value::Null);
+        assert_eq!(requestor.request("add", params(3, 2)).unwrap(), Value::Int(5));
+
+        drop(requestor);
+        handle.join().unwrap();
+        assert_eq!(*stored.lock().unwrap(), vec![5, 6]);
+    }
+}
@@ -0,0 +1,80 @@
+//! Logic handling the transport of Avro RPC messages.
+use std::io::{Read, Write};
+
+use error::Error;
+
+use super::framing::{read_framed, write_framed};
+use super::Responder;
+
+/// Transport of the requests of a [`Requestor`](struct.Requestor.html) to a server.
+pub trait Transceiver {
+    /// Send a request to the server and return its response, both being whole (unframed) messages.
+    fn transceive(&mut self, request: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Send a request to the server without waiting for any response, for one-way messages. By
+    /// default, the request is transceived and its response ignored.
+    fn send(&mut self, request: &[u8]) -> Result<(), Error> {
+        self.transceive(request).map(|_| ())
+    }
+
+    /// Whether requests all go through the same connection, in which case the handshake only
+    /// happens ahead of the first one. `false` by default.
+    fn is_stateful(&self) -> bool {
+        false
+    }
+}
+
+/// A stateful `Transceiver` framing messages over a stream, such as a `TcpStream`, whose other end
+/// is served by [`Responder::serve`](struct.Responder.html#method.serve).
+#[derive(Debug)]
+pub struct StreamTransceiver<S> {
+    stream: S,
+}
+
+impl<S: Read + Write> StreamTransceiver<S> {
+    /// Creates a `StreamTransceiver` given a connected stream.
+    pub fn new(stream: S) -> StreamTransceiver<S> {
+        StreamTransceiver { stream }
+    }
+
+    /// Consume the `StreamTransceiver`, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read + Write> Transceiver for StreamTransceiver<S> {
+    fn transceive(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        write_framed(&mut self.stream, request)?;
+        self.stream.flush()?;
+        read_framed(&mut self.stream)
+    }
+
+    fn send(&mut self, request: &[u8]) -> Result<(), Error> {
+        write_framed(&mut self.stream, request)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+}
+
+/// A `Transceiver` handing requests straight to a `Responder` living in the same process.
+pub struct LocalTransceiver<'a> {
+    responder: &'a Responder,
+}
+
+impl<'a> LocalTransceiver<'a> {
+    /// Creates a `LocalTransceiver` sending requests to `responder`.
+    pub fn new(responder: &'a Responder) -> LocalTransceiver<'a> {
+        LocalTransceiver { responder }
+    }
+}
+
+impl<'a> Transceiver for LocalTransceiver<'a> {
+    fn transceive(&mut self, request: &[u8]) -> Result<Vec<u8>, Error> {
+        self.responder.respond(request)
+    }
+}
@@ -1,17 +1,26 @@
 //! Logic for parsing and interacting with schemas in Avro format.
 use std::borrow::Cow;
-use std::collections::HashMap;
-
-use failure::Error;
-use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use error::Error;
+use logical::{self, LogicalType};
+use md5;
+use serde::ser::{Error as SerError, Serialize, SerializeMap, SerializeSeq, Serializer};
 use serde_json::{self, Map, Value};
+use sha2::{Digest, Sha256};
 
 use types;
 use util::MapHelper;
 
 /// Describes errors happened while parsing Avro schemas.
-#[derive(Fail, Debug)]
-#[fail(display = "Failed to parse schema: {}", _0)]
+#[derive(Error, Debug)]
+#[error("Failed to parse schema: {0}")]
 pub struct ParseSchemaError(String);
 
 impl ParseSchemaError {
@@ -23,6 +32,12 @@ impl ParseSchemaError {
     }
 }
 
+impl From<ParseSchemaError> for Error {
+    fn from(error: ParseSchemaError) -> Error {
+        Error::SchemaParse { reason: error.0 }
+    }
+}
+
 /// Represents any valid Avro schema
 /// More information about Avro schemas can be found in the
 /// [Avro Specification](https://avro.apache.org/docs/current/spec.html#schemas)
@@ -66,13 +81,19 @@ pub enum Schema {
         lookup: HashMap<String, usize>,
     },
     /// An `enum` Avro schema.
+    ///
+    /// The `default` symbol is used when resolving a writer symbol unknown to the reader.
     Enum {
         name: Name,
         doc: Documentation,
         symbols: Vec<String>,
+        default: Option<String>,
     },
     /// A `fixed` Avro schema.
     Fixed { name: Name, size: usize },
+    /// A schema annotated with a `logicalType` registered with
+    /// [`register_logical_type`](../fn.register_logical_type.html).
+    Logical(LogicalSchema),
 }
 
 /// This type is used to simplify enum variant comparison between `Schema` and `types::Value`.
@@ -120,6 +141,7 @@ impl<'a> From<&'a Schema> for SchemaKind {
             Schema::Record { .. } => SchemaKind::Record,
             Schema::Enum { .. } => SchemaKind::Enum,
             Schema::Fixed { .. } => SchemaKind::Fixed,
+            Schema::Logical(ref logical) => SchemaKind::from(logical.schema()),
         }
     }
 }
@@ -138,7 +160,7 @@ impl<'a> From<&'a types::Value> for SchemaKind {
             types::Value::String(_) => SchemaKind::String,
             types::Value::Array(_) => SchemaKind::Array,
             types::Value::Map(_) => SchemaKind::Map,
-            types::Value::Union(_) => SchemaKind::Union,
+            types::Value::Union(_) | types::Value::UnionBranch(..) => SchemaKind::Union,
             types::Value::Record(_) => SchemaKind::Record,
             types::Value::Enum(_, _) => SchemaKind::Enum,
             types::Value::Fixed(_, _) => SchemaKind::Fixed,
@@ -238,9 +260,8 @@ pub struct RecordField {
     pub default: Option<Value>,
     /// Schema of the field.
     pub schema: Schema,
-    /// Order of the field.
-    ///
-    /// **NOTE** This currently has no effect.
+    /// Order of the field, when comparing records with
+    /// [`compare_encoded`](../fn.compare_encoded.html).
     pub order: RecordFieldOrder,
     /// Position of the field in the list of `field` of its parent `Schema`
     pub position: usize,
@@ -256,18 +277,34 @@ pub enum RecordFieldOrder {
 
 impl RecordField {
     /// Parse a `serde_json::Value` into a `RecordField`.
-    fn parse(field: &Map<String, Value>, position: usize) -> Result<Self, Error> {
+    fn parse(
+        field: &Map<String, Value>,
+        position: usize,
+        parser: &mut Parser,
+    ) -> Result<Self, Error> {
         let name = field
             .name()
             .ok_or_else(|| ParseSchemaError::new("No `name` in record field"))?;
+        if parser.is_strict() && !is_valid_name(&name) {
+            return Err(ParseSchemaError::new(format!("Invalid field name {}", name)).into())
+        }
 
-        // TODO: "type" = "<record name>"
         let schema = field
             .get("type")
             .ok_or_else(|| ParseSchemaError::new("No `type` in record field").into())
-            .and_then(|type_| Schema::parse(type_))?;
+            .and_then(|type_| parser.parse_at(type_, &format!(".fields[{}].type", position)))?;
 
         let default = field.get("default").cloned();
+        if let Some(ref default) = default {
+            if parser.options.mode != ParseMode::Permissive && !is_valid_default(&schema, default) {
+                return Err(ParseSchemaError::new(format!(
+                    "Invalid default {} for field {} of type {}",
+                    default,
+                    name,
+                    serde_json::to_string(&schema)?
+                )).into())
+            }
+        }
 
         let order = field
             .get("order")
@@ -341,6 +378,23 @@ impl UnionSchema {
             .cloned()
             .map(|i| (i, &self.schemas[i]))
     }
+
+    // The position and the schema of the branch of a (`Union` or `UnionBranch`) value, with the
+    // value of the branch.
+    pub(crate) fn find_branch<'v>(
+        &self,
+        value: &'v ::types::Value,
+    ) -> Option<(usize, &Schema, &'v ::types::Value)> {
+        match *value {
+            ::types::Value::Union(ref inner) => {
+                self.find_schema(inner).map(|(i, schema)| (i, schema, &**inner))
+            },
+            ::types::Value::UnionBranch(i, ref inner) => {
+                self.schemas.get(i).map(|schema| (i, schema, &**inner))
+            },
+            _ => None,
+        }
+    }
 }
 
 // No need to compare variant_index, it is derivative of schemas.
@@ -350,22 +404,105 @@ impl PartialEq for UnionSchema {
     }
 }
 
+/// A schema annotated with a logical type, see [`LogicalType`](../trait.LogicalType.html).
+#[derive(Clone)]
+pub struct LogicalSchema {
+    logical_type: Arc<dyn LogicalType>,
+    schema: Box<Schema>,
+}
+
+impl LogicalSchema {
+    /// Annotate `schema` with `logical_type`, if it may annotate it.
+    pub fn new(logical_type: Arc<dyn LogicalType>, schema: Schema) -> Result<Self, Error> {
+        logical_type.check_schema(&schema).map_err(|reason| {
+            ParseSchemaError::new(format!(
+                "Invalid logical type {}: {}",
+                logical_type.name(),
+                reason
+            ))
+        })?;
+        Ok(LogicalSchema {
+            logical_type,
+            schema: Box::new(schema),
+        })
+    }
+
+    /// Returns the name of the logical type.
+    pub fn name(&self) -> &str {
+        self.logical_type.name()
+    }
+
+    /// Returns the logical type.
+    pub fn logical_type(&self) -> &dyn LogicalType {
+        &*self.logical_type
+    }
+
+    /// Returns the annotated schema, which values are encoded with.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+impl fmt::Debug for LogicalSchema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LogicalSchema")
+            .field("name", &self.name())
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+// Logical types are compared by name, as registered.
+impl PartialEq for LogicalSchema {
+    fn eq(&self, other: &LogicalSchema) -> bool {
+        self.name() == other.name() && self.schema == other.schema
+    }
+}
+
 impl Schema {
     /// Create a `Schema` from a string representing a JSON Avro schema.
     pub fn parse_str(input: &str) -> Result<Self, Error> {
+        Self::parse_str_with(input, &ParseOptions::default())
+    }
+
+    /// Create a `Schema` from a string representing a JSON Avro schema, with the given
+    /// `ParseOptions`.
+    pub fn parse_str_with(input: &str, options: &ParseOptions) -> Result<Self, Error> {
         let value = serde_json::from_str(input)?;
-        Self::parse(&value)
+        Self::parse_with(&value, options)
     }
 
     /// Create a `Schema` from a `serde_json::Value` representing a JSON Avro
     /// schema.
     pub fn parse(value: &Value) -> Result<Self, Error> {
-        match *value {
-            Value::String(ref t) => Schema::parse_primitive(t.as_str()),
-            Value::Object(ref data) => Schema::parse_complex(data),
-            Value::Array(ref data) => Schema::parse_union(data),
-            _ => Err(ParseSchemaError::new("Must be a JSON string, object or array").into()),
+        Self::parse_with(value, &ParseOptions::default())
+    }
+
+    /// Create a `Schema` from a `serde_json::Value` representing a JSON Avro
+    /// schema, with the given `ParseOptions`.
+    pub fn parse_with(value: &Value, options: &ParseOptions) -> Result<Self, Error> {
+        Parser::new(options).parse(value)
+    }
+
+    /// Create `Schema`s from strings representing JSON Avro schemas, each of which may reference
+    /// the named types defined by the previous ones.
+    pub fn parse_list(inputs: &[&str]) -> Result<Vec<Self>, Error> {
+        Self::parse_list_with(inputs, &ParseOptions::default())
+    }
+
+    /// Create `Schema`s from strings representing JSON Avro schemas, each of which may reference
+    /// the named types defined by the previous ones, with the given `ParseOptions`.
+    ///
+    /// The `i`-th schema is located at `$[i]` in the errors.
+    pub fn parse_list_with(inputs: &[&str], options: &ParseOptions) -> Result<Vec<Self>, Error> {
+        let mut parser = Parser::new(options);
+        let mut schemas = Vec::with_capacity(inputs.len());
+        for (i, input) in inputs.iter().enumerate() {
+            let value = serde_json::from_str(input)?;
+            parser.location = format!("$[{}]", i);
+            schemas.push(parser.parse(&value)?);
         }
+        Ok(schemas)
     }
 
     /// Converts `self` into its [Parsing Canonical Form].
@@ -374,12 +511,309 @@ impl Schema {
     /// https://avro.apache.org/docs/1.8.2/spec.html#Parsing+Canonical+Form+for+Schemas
     pub fn canonical_form(&self) -> String {
         let json = serde_json::to_value(self).unwrap();
-        parsing_canonical_form(&json)
+        parsing_canonical_form(&json, None)
+    }
+
+    /// Compute the 64-bit Rabin fingerprint (CRC-64-AVRO) of the [Parsing Canonical Form] of
+    /// `self`.
+    ///
+    /// [Parsing Canonical Form]:
+    /// https://avro.apache.org/docs/1.8.2/spec.html#schema_fingerprints
+    pub fn rabin_fingerprint(&self) -> u64 {
+        rabin_fingerprint(self.canonical_form().as_bytes())
+    }
+
+    /// Compute the MD5 fingerprint of the [Parsing Canonical Form] of `self`.
+    ///
+    /// [Parsing Canonical Form]:
+    /// https://avro.apache.org/docs/1.8.2/spec.html#schema_fingerprints
+    pub fn md5_fingerprint(&self) -> [u8; 16] {
+        md5::compute(self.canonical_form().as_bytes()).0
+    }
+
+    /// Compute the SHA-256 fingerprint of the [Parsing Canonical Form] of `self`.
+    ///
+    /// [Parsing Canonical Form]:
+    /// https://avro.apache.org/docs/1.8.2/spec.html#schema_fingerprints
+    pub fn sha256_fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(self.canonical_form().as_bytes()).into()
+    }
+
+    /// Get the name of a `record`, `enum` or `fixed` schema.
+    pub fn name(&self) -> Option<&Name> {
+        match *self {
+            Schema::Record { ref name, .. }
+            | Schema::Enum { ref name, .. }
+            | Schema::Fixed { ref name, .. } => Some(name),
+            Schema::Logical(ref logical) => logical.schema().name(),
+            _ => None,
+        }
+    }
+
+    /// Get the namespace of a named schema, either declared or part of a dotted name.
+    ///
+    /// **NOTE** A namespace inherited from an enclosing named type is not known to the schema
+    /// itself, see [`get_named`](#method.get_named).
+    pub fn namespace(&self) -> Option<&str> {
+        self.name().and_then(|name| match name.name.rfind('.') {
+            Some(index) => Some(&name.name[..index]),
+            None => name.namespace.as_deref(),
+        })
+    }
+
+    /// Get the fullname of a named schema, see [`namespace`](#method.namespace).
+    pub fn fullname(&self) -> Option<String> {
+        self.name().map(|name| name.fullname(None))
+    }
+
+    /// Get the documentation of a `record` or `enum` schema.
+    pub fn doc(&self) -> Option<&str> {
+        match *self {
+            Schema::Record { ref doc, .. } | Schema::Enum { ref doc, .. } => doc.as_deref(),
+            Schema::Logical(ref logical) => logical.schema().doc(),
+            _ => None,
+        }
+    }
+
+    /// Look up a named type defined anywhere in this schema by its fullname, e.g.
+    /// `schema.get_named("com.example.Address")`.
+    ///
+    /// Nested types without a namespace inherit the one of their enclosing named type.
+    pub fn get_named(&self, fullname: &str) -> Option<&Schema> {
+        get_named(self, fullname, None)
+    }
+
+    /// Build a reader schema keeping only the given fields of this record schema, e.g.
+    /// `["id", "user.name"]`, to only decode those fields of the data.
+    ///
+    /// Nested fields are reached with dotted paths, through records and the record branches of
+    /// unions. Names, namespaces, documentation and defaults are preserved.
+    pub fn project(&self, paths: &[&str]) -> Result<Schema, Error> {
+        let paths = paths
+            .iter()
+            .map(|path| path.split('.').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        project(self, &paths.iter().map(|path| &path[..]).collect::<Vec<_>>())
+    }
+
+    /// Infer a record schema from sample JSON objects, as a starting point to describe an
+    /// existing JSON dataset.
+    ///
+    /// Numbers are widened from `int` to `long` to `double` as needed, values of different types
+    /// become unions, and fields which are `null` or missing in some samples become nullable,
+    /// with a `null` default. Field names are made valid Avro names, and nested records are
+    /// named after their path, e.g. `root_address`.
+    pub fn infer(samples: &[Value]) -> Result<Schema, Error> {
+        let mut inferred = InferredType::default();
+        for sample in samples {
+            if !sample.is_object() {
+                return Err(ParseSchemaError::new(format!(
+                    "Cannot infer a record schema from {}",
+                    sample
+                )).into())
+            }
+            inferred.add(sample);
+        }
+        match inferred.fields {
+            Some(ref fields) => Ok(infer_record("root", fields)),
+            None => Err(ParseSchemaError::new("Cannot infer a schema without samples").into()),
+        }
+    }
+}
+
+/// How closely a schema must follow the Avro specification, see
+/// [`ParseOptions::mode`](struct.ParseOptions.html#method.mode).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseMode {
+    /// On top of the `Standard` checks, names, namespaces, aliases and enum symbols must be valid
+    /// Avro names, enum symbols unique, record fields JSON objects, primitive types written as
+    /// objects may only carry a `logicalType` (and its `precision` and `scale`), and named types
+    /// may only be defined once.
+    Strict,
+    /// Field and enum defaults must match their type, and named types defined several times must
+    /// be defined identically.
+    Standard,
+    /// Invalid field defaults are accepted as they are, and invalid enum defaults ignored, as
+    /// the Java implementation does in practice.
+    Permissive,
+}
+
+/// Options of [`Schema::parse_with`](enum.Schema.html#method.parse_with).
+///
+/// The limits protect against schemas from untrusted sources, which might be nested deeply or
+/// define an enormous number of types.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    max_depth: usize,
+    max_named_types: usize,
+    mode: ParseMode,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            max_depth: 128,
+            max_named_types: 10_000,
+            mode: ParseMode::Standard,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Create `ParseOptions` with the default limits: schemas nested at most 128 levels deep,
+    /// defining at most 10000 named types, parsed in `ParseMode::Standard`.
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Set the maximum nesting depth of complex types and unions.
+    pub fn max_depth(mut self, max_depth: usize) -> ParseOptions {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set the maximum number of records, enums and fixed types defined by a schema.
+    pub fn max_named_types(mut self, max_named_types: usize) -> ParseOptions {
+        self.max_named_types = max_named_types;
+        self
+    }
+
+    /// Set how closely schemas must follow the Avro specification.
+    pub fn mode(mut self, mode: ParseMode) -> ParseOptions {
+        self.mode = mode;
+        self
+    }
+}
+
+// State of the parsing of a single schema.
+struct Parser<'a> {
+    options: &'a ParseOptions,
+    depth: usize,
+    named_types: usize,
+    // Enclosing namespace of the types being parsed.
+    namespace: Option<String>,
+    // Named types defined so far, by fullname, which later references are replaced with.
+    names: HashMap<String, Schema>,
+    // Where the type being parsed is, e.g. `$.fields[0].type`.
+    location: String,
+    // Where the named types were defined, by fullname.
+    locations: HashMap<String, String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(options: &'a ParseOptions) -> Parser<'a> {
+        Parser {
+            options,
+            depth: 0,
+            named_types: 0,
+            namespace: None,
+            names: HashMap::new(),
+            location: "$".to_owned(),
+            locations: HashMap::new(),
+        }
+    }
+
+    /// Record a named type, so that it can be referenced by its fullname afterwards.
+    ///
+    /// Named types may only be defined once, except for identical definitions outside of
+    /// `ParseMode::Strict`.
+    fn define(&mut self, schema: Schema) -> Result<Schema, Error> {
+        if let Some(name) = schema.name() {
+            let fullname = name.fullname(self.namespace.as_deref());
+            if let Some(defined) = self.names.get(&fullname) {
+                let defined = match *defined {
+                    Schema::Logical(ref logical) => logical.schema(),
+                    ref defined => defined,
+                };
+                if *defined == schema && !self.is_strict() {
+                    return Ok(schema)
+                }
+                return Err(Error::SchemaRedefinition {
+                    first: self.locations[&fullname].clone(),
+                    second: self.location.clone(),
+                    name: fullname,
+                })
+            }
+            self.locations.insert(fullname.clone(), self.location.clone());
+            self.names.insert(fullname, schema.clone());
+        }
+        Ok(schema)
+    }
+
+    /// Look up a reference to a named type defined before, e.g. `"Address"` in the enclosing
+    /// namespace or `"com.example.Address"`.
+    fn lookup(&self, name: &str) -> Option<Schema> {
+        let in_namespace = match self.namespace {
+            Some(ref namespace) if !name.contains('.') => self
+                .names
+                .get(&format!("{}.{}", namespace, name))
+                .cloned(),
+            _ => None,
+        };
+        in_namespace.or_else(|| self.names.get(name).cloned())
+    }
+
+    /// Parse the `serde_json::Value` found at `segment` (e.g. `.items`) of the type being parsed.
+    fn parse_at(&mut self, value: &Value, segment: &str) -> Result<Schema, Error> {
+        let len = self.location.len();
+        self.location.push_str(segment);
+        let schema = self.parse(value);
+        self.location.truncate(len);
+        schema
+    }
+
+    /// Parse a `serde_json::Value` representing a JSON Avro schema into a `Schema`.
+    fn parse(&mut self, value: &Value) -> Result<Schema, Error> {
+        if let Value::String(ref t) = *value {
+            return self.parse_primitive(t.as_str())
+        }
+
+        if self.depth == self.options.max_depth {
+            return Err(Error::SchemaDepth {
+                limit: self.options.max_depth,
+            })
+        }
+        self.depth += 1;
+        let schema = match *value {
+            Value::Object(ref data) => self.parse_complex(data),
+            Value::Array(ref data) => self.parse_union(data),
+            _ => Err(ParseSchemaError::new("Must be a JSON string, object or array").into()),
+        };
+        self.depth -= 1;
+        schema
+    }
+
+    /// Parse the `Name` of a named type, counting it against the limit of named types.
+    fn parse_name(&mut self, complex: &Map<String, Value>) -> Result<Name, Error> {
+        if self.named_types == self.options.max_named_types {
+            return Err(Error::SchemaNamedTypes {
+                limit: self.options.max_named_types,
+            })
+        }
+        self.named_types += 1;
+        let name = Name::parse(complex)?;
+        if self.is_strict() {
+            let namespace = name.namespace.iter().filter(|namespace| !namespace.is_empty());
+            for fullname in Some(&name.name)
+                .into_iter()
+                .chain(namespace)
+                .chain(name.aliases.iter().flatten())
+            {
+                if !fullname.split('.').all(is_valid_name) {
+                    return Err(ParseSchemaError::new(format!("Invalid name {}", fullname)).into())
+                }
+            }
+        }
+        Ok(name)
+    }
+
+    fn is_strict(&self) -> bool {
+        self.options.mode == ParseMode::Strict
     }
 
     /// Parse a `serde_json::Value` representing a primitive Avro type into a
     /// `Schema`.
-    fn parse_primitive(primitive: &str) -> Result<Self, Error> {
+    fn parse_primitive(&self, primitive: &str) -> Result<Schema, Error> {
         match primitive {
             "null" => Ok(Schema::Null),
             "boolean" => Ok(Schema::Boolean),
@@ -389,7 +823,9 @@ impl Schema {
             "float" => Ok(Schema::Float),
             "bytes" => Ok(Schema::Bytes),
             "string" => Ok(Schema::String),
-            other => Err(ParseSchemaError::new(format!("Unknown type: {}", other)).into()),
+            other => self
+                .lookup(other)
+                .ok_or_else(|| ParseSchemaError::new(format!("Unknown type: {}", other)).into()),
         }
     }
 
@@ -398,18 +834,64 @@ impl Schema {
     ///
     /// Avro supports "recursive" definition of types.
     /// e.g: {"type": {"type": "string"}}
-    fn parse_complex(complex: &Map<String, Value>) -> Result<Self, Error> {
+    fn parse_complex(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
+        let schema = self.parse_type(complex)?;
+        let logical_type = complex
+            .get("logicalType")
+            .and_then(|logical_type| logical_type.as_str())
+            .and_then(logical::logical_type);
+        match logical_type {
+            Some(logical_type) => self.annotate(logical_type, schema, complex.contains_key("name")),
+            None => Ok(schema),
+        }
+    }
+
+    /// Annotate a schema with a registered logical type, or leave it as it is if the logical type
+    /// may not annotate it (outside of `ParseMode::Strict`).
+    ///
+    /// The named types `defined` by the annotated schema are referenced with their logical type.
+    fn annotate(
+        &mut self,
+        logical_type: Arc<dyn LogicalType>,
+        schema: Schema,
+        defined: bool,
+    ) -> Result<Schema, Error> {
+        let logical = match LogicalSchema::new(logical_type, schema.clone()) {
+            Ok(logical) => Schema::Logical(logical),
+            Err(e) if self.is_strict() => return Err(e),
+            Err(_) => return Ok(schema),
+        };
+        if let (true, Some(name)) = (defined, schema.name()) {
+            let fullname = name.fullname(self.namespace.as_deref());
+            self.names.insert(fullname, logical.clone());
+        }
+        Ok(logical)
+    }
+
+    /// Parse a `serde_json::Value` representing a complex Avro type, regardless of any logical
+    /// type.
+    fn parse_type(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
         match complex.get("type") {
             Some(&Value::String(ref t)) => match t.as_str() {
-                "record" => Schema::parse_record(complex),
-                "enum" => Schema::parse_enum(complex),
-                "array" => Schema::parse_array(complex),
-                "map" => Schema::parse_map(complex),
-                "fixed" => Schema::parse_fixed(complex),
-                other => Schema::parse_primitive(other),
+                "record" => self.parse_record(complex),
+                "enum" => self.parse_enum(complex),
+                "array" => self.parse_array(complex),
+                "map" => self.parse_map(complex),
+                "fixed" => self.parse_fixed(complex),
+                other => {
+                    let known = ["type", "logicalType", "precision", "scale"];
+                    let unknown = complex.keys().find(|key| !known.contains(&key.as_str()));
+                    if let (true, Some(key)) = (self.is_strict(), unknown) {
+                        return Err(ParseSchemaError::new(format!(
+                            "Unknown attribute {} of type {}",
+                            key, other
+                        )).into())
+                    }
+                    self.parse_primitive(other)
+                },
             },
             Some(&Value::Object(ref data)) => match data.get("type") {
-                Some(ref value) => Schema::parse(value),
+                Some(value) => self.parse_at(value, ".type"),
                 None => Err(
                     ParseSchemaError::new(format!("Unknown complex type: {:?}", complex)).into(),
                 ),
@@ -420,29 +902,46 @@ impl Schema {
 
     /// Parse a `serde_json::Value` representing a Avro record type into a
     /// `Schema`.
-    fn parse_record(complex: &Map<String, Value>) -> Result<Self, Error> {
-        let name = Name::parse(complex)?;
+    fn parse_record(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
+        let name = self.parse_name(complex)?;
 
         let mut lookup = HashMap::new();
 
-        let fields: Vec<RecordField> = complex
+        // Fields are parsed in the namespace of the record.
+        let namespace = match name.name.rfind('.') {
+            Some(index) => Some(name.name[..index].to_owned()),
+            None => name.namespace.clone().or_else(|| self.namespace.clone()),
+        };
+        let enclosing = ::std::mem::replace(&mut self.namespace, namespace);
+        let fields: Result<Vec<RecordField>, Error> = complex
             .get("fields")
             .and_then(|fields| fields.as_array())
             .ok_or_else(|| ParseSchemaError::new("No `fields` in record").into())
             .and_then(|fields| {
+                if self.is_strict() && fields.iter().any(|field| !field.is_object()) {
+                    return Err(ParseSchemaError::new("Record fields must be objects").into())
+                }
                 fields
                     .iter()
                     .filter_map(|field| field.as_object())
                     .enumerate()
-                    .map(|(position, field)| RecordField::parse(field, position))
+                    .map(|(position, field)| RecordField::parse(field, position, self))
                     .collect::<Result<_, _>>()
-            })?;
+            });
+        self.namespace = enclosing;
+        let fields = fields?;
 
         for field in &fields {
-            lookup.insert(field.name.clone(), field.position);
+            if lookup.insert(field.name.clone(), field.position).is_some() {
+                return Err(ParseSchemaError::new(format!(
+                    "Duplicate field name {} in record {}",
+                    field.name,
+                    name.fullname(None)
+                )).into())
+            }
         }
 
-        Ok(Schema::Record {
+        self.define(Schema::Record {
             name,
             doc: complex.doc(),
             fields,
@@ -452,10 +951,10 @@ impl Schema {
 
     /// Parse a `serde_json::Value` representing a Avro enum type into a
     /// `Schema`.
-    fn parse_enum(complex: &Map<String, Value>) -> Result<Self, Error> {
-        let name = Name::parse(complex)?;
+    fn parse_enum(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
+        let name = self.parse_name(complex)?;
 
-        let symbols = complex
+        let symbols: Vec<String> = complex
             .get("symbols")
             .and_then(|v| v.as_array())
             .ok_or_else(|| ParseSchemaError::new("No `symbols` field in enum"))
@@ -466,55 +965,85 @@ impl Schema {
                     .collect::<Option<_>>()
                     .ok_or_else(|| ParseSchemaError::new("Unable to parse `symbols` in enum"))
             })?;
+        if self.is_strict() {
+            for (i, symbol) in symbols.iter().enumerate() {
+                if !is_valid_name(symbol) || symbols[..i].contains(symbol) {
+                    return Err(
+                        ParseSchemaError::new(format!("Invalid enum symbol {}", symbol)).into(),
+                    )
+                }
+            }
+        }
 
-        Ok(Schema::Enum {
+        let default = match complex.get("default") {
+            Some(default) => match default.as_str() {
+                Some(default) if symbols.iter().any(|symbol| symbol == default) => {
+                    Some(default.to_owned())
+                },
+                _ if self.options.mode == ParseMode::Permissive => None,
+                Some(default) => {
+                    return Err(ParseSchemaError::new(format!(
+                        "Enum default {} is not among symbols {:?}",
+                        default, symbols
+                    )).into())
+                },
+                None => {
+                    return Err(ParseSchemaError::new("Unable to parse `default` in enum").into())
+                },
+            },
+            None => None,
+        };
+
+        self.define(Schema::Enum {
             name,
             doc: complex.doc(),
             symbols,
+            default,
         })
     }
 
     /// Parse a `serde_json::Value` representing a Avro array type into a
     /// `Schema`.
-    fn parse_array(complex: &Map<String, Value>) -> Result<Self, Error> {
+    fn parse_array(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
         complex
             .get("items")
             .ok_or_else(|| ParseSchemaError::new("No `items` in array").into())
-            .and_then(|items| Schema::parse(items))
+            .and_then(|items| self.parse_at(items, ".items"))
             .map(|schema| Schema::Array(Box::new(schema)))
     }
 
     /// Parse a `serde_json::Value` representing a Avro map type into a
     /// `Schema`.
-    fn parse_map(complex: &Map<String, Value>) -> Result<Self, Error> {
+    fn parse_map(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
         complex
             .get("values")
             .ok_or_else(|| ParseSchemaError::new("No `values` in map").into())
-            .and_then(|items| Schema::parse(items))
+            .and_then(|values| self.parse_at(values, ".values"))
             .map(|schema| Schema::Map(Box::new(schema)))
     }
 
     /// Parse a `serde_json::Value` representing a Avro union type into a
     /// `Schema`.
-    fn parse_union(items: &[Value]) -> Result<Self, Error> {
+    fn parse_union(&mut self, items: &[Value]) -> Result<Schema, Error> {
         items
             .iter()
-            .map(Schema::parse)
+            .enumerate()
+            .map(|(i, item)| self.parse_at(item, &format!("[{}]", i)))
             .collect::<Result<Vec<_>, _>>()
             .and_then(|schemas| Ok(Schema::Union(UnionSchema::new(schemas)?)))
     }
 
     /// Parse a `serde_json::Value` representing a Avro fixed type into a
     /// `Schema`.
-    fn parse_fixed(complex: &Map<String, Value>) -> Result<Self, Error> {
-        let name = Name::parse(complex)?;
+    fn parse_fixed(&mut self, complex: &Map<String, Value>) -> Result<Schema, Error> {
+        let name = self.parse_name(complex)?;
 
         let size = complex
             .get("size")
             .and_then(|v| v.as_i64())
             .ok_or_else(|| ParseSchemaError::new("No `size` in fixed"))?;
 
-        Ok(Schema::Fixed {
+        self.define(Schema::Fixed {
             name,
             size: size as usize,
         })
@@ -526,7 +1055,111 @@ impl Serialize for Schema {
     where
         S: Serializer,
     {
-        match *self {
+        SchemaJson::new(self, None, &RefCell::new(HashSet::new())).serialize(serializer)
+    }
+}
+
+/// Parse a JSON Avro schema, same as [`Schema::parse_str`](#method.parse_str).
+impl FromStr for Schema {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Schema, Error> {
+        Schema::parse_str(input)
+    }
+}
+
+/// Display the schema as compact JSON.
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl Serialize for RecordField {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        FieldJson {
+            field: self,
+            namespace: None,
+            defined: &RefCell::new(HashSet::new()),
+        }.serialize(serializer)
+    }
+}
+
+// JSON serialization of a schema: named types are defined the first time they appear, and
+// referenced by their fullname afterwards.
+struct SchemaJson<'a> {
+    schema: &'a Schema,
+    // Enclosing namespace.
+    namespace: Option<&'a str>,
+    // Fullnames of the named types defined so far.
+    defined: &'a RefCell<HashSet<String>>,
+}
+
+struct FieldJson<'a> {
+    field: &'a RecordField,
+    namespace: Option<&'a str>,
+    defined: &'a RefCell<HashSet<String>>,
+}
+
+impl<'a> SchemaJson<'a> {
+    fn new(
+        schema: &'a Schema,
+        namespace: Option<&'a str>,
+        defined: &'a RefCell<HashSet<String>>,
+    ) -> SchemaJson<'a> {
+        SchemaJson {
+            schema,
+            namespace,
+            defined,
+        }
+    }
+
+    fn nested(&self, schema: &'a Schema) -> SchemaJson<'a> {
+        SchemaJson::new(schema, self.namespace, self.defined)
+    }
+}
+
+impl<'a> Serialize for SchemaJson<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Annotated schemas are defined (or referenced) in turn.
+        let name = match *self.schema {
+            Schema::Logical(_) => None,
+            ref schema => schema.name(),
+        };
+        if let Some(name) = name {
+            let fullname = name.fullname(self.namespace);
+            if !self.defined.borrow_mut().insert(fullname.clone()) {
+                return serializer.serialize_str(&fullname)
+            }
+        }
+
+        match *self.schema {
+            Schema::Logical(ref logical) => {
+                let logical_type = Value::String(logical.name().to_owned());
+                let schema = serde_json::to_value(self.nested(logical.schema()))
+                    .map_err(SerError::custom)?;
+                match schema {
+                    Value::Object(mut map) => {
+                        map.insert("logicalType".to_owned(), logical_type);
+                        map.serialize(serializer)
+                    },
+                    // References to named types carry the logical type of their definition.
+                    Value::String(_) if logical.schema().name().is_some() => {
+                        schema.serialize(serializer)
+                    },
+                    Value::String(primitive) => {
+                        json!({"type": primitive, "logicalType": logical_type})
+                            .serialize(serializer)
+                    },
+                    schema => schema.serialize(serializer),
+                }
+            },
             Schema::Null => serializer.serialize_str("null"),
             Schema::Boolean => serializer.serialize_str("boolean"),
             Schema::Int => serializer.serialize_str("int"),
@@ -538,20 +1171,20 @@ impl Serialize for Schema {
             Schema::Array(ref inner) => {
                 let mut map = serializer.serialize_map(Some(2))?;
                 map.serialize_entry("type", "array")?;
-                map.serialize_entry("items", &*inner.clone())?;
+                map.serialize_entry("items", &self.nested(inner))?;
                 map.end()
             },
             Schema::Map(ref inner) => {
                 let mut map = serializer.serialize_map(Some(2))?;
                 map.serialize_entry("type", "map")?;
-                map.serialize_entry("values", &*inner.clone())?;
+                map.serialize_entry("values", &self.nested(inner))?;
                 map.end()
             },
             Schema::Union(ref inner) => {
                 let variants = inner.variants();
                 let mut seq = serializer.serialize_seq(Some(variants.len()))?;
                 for v in variants {
-                    seq.serialize_element(v)?;
+                    seq.serialize_element(&self.nested(v))?;
                 }
                 seq.end()
             },
@@ -573,23 +1206,42 @@ impl Serialize for Schema {
                 if let Some(ref aliases) = name.aliases {
                     map.serialize_entry("aliases", aliases)?;
                 }
-                map.serialize_entry("fields", fields)?;
+                // Fields are serialized in the namespace of the record.
+                let namespace = self.schema.namespace().or(self.namespace);
+                let fields = fields
+                    .iter()
+                    .map(|field| FieldJson {
+                        field,
+                        namespace,
+                        defined: self.defined,
+                    }).collect::<Vec<_>>();
+                map.serialize_entry("fields", &fields)?;
                 map.end()
             },
             Schema::Enum {
                 ref name,
                 ref symbols,
+                ref default,
                 ..
             } => {
                 let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "enum")?;
+                if let Some(ref n) = name.namespace {
+                    map.serialize_entry("namespace", n)?;
+                }
                 map.serialize_entry("name", &name.name)?;
                 map.serialize_entry("symbols", symbols)?;
+                if let Some(ref default) = *default {
+                    map.serialize_entry("default", default)?;
+                }
                 map.end()
             },
             Schema::Fixed { ref name, ref size } => {
                 let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "fixed")?;
+                if let Some(ref n) = name.namespace {
+                    map.serialize_entry("namespace", n)?;
+                }
                 map.serialize_entry("name", &name.name)?;
                 map.serialize_entry("size", size)?;
                 map.end()
@@ -598,16 +1250,19 @@ impl Serialize for Schema {
     }
 }
 
-impl Serialize for RecordField {
+impl<'a> Serialize for FieldJson<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         let mut map = serializer.serialize_map(None)?;
-        map.serialize_entry("name", &self.name)?;
-        map.serialize_entry("type", &self.schema)?;
+        map.serialize_entry("name", &self.field.name)?;
+        map.serialize_entry(
+            "type",
+            &SchemaJson::new(&self.field.schema, self.namespace, self.defined),
+        )?;
 
-        if let Some(ref default) = self.default {
+        if let Some(ref default) = self.field.default {
             map.serialize_entry("default", default)?;
         }
 
@@ -615,20 +1270,322 @@ impl Serialize for RecordField {
     }
 }
 
+/// Whether `name` is a valid Avro name (without namespace).
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether the JSON `default` of a record field is valid for its `schema`: a union default
+/// corresponds to the first branch, and bytes and fixed defaults are strings of code points
+/// 0-255.
+fn is_valid_default(schema: &Schema, default: &Value) -> bool {
+    let bytes = |s: &str| s.chars().all(|c| (c as u32) <= 0xff);
+    match (schema, default) {
+        (Schema::Null, Value::Null) | (Schema::Boolean, Value::Bool(_)) => true,
+        (Schema::Int, Value::Number(n)) => n
+            .as_i64()
+            .is_some_and(|n| n >= i64::from(i32::MIN) && n <= i64::from(i32::MAX)),
+        (Schema::Long, Value::Number(n)) => n.as_i64().is_some(),
+        (Schema::Float, Value::Number(_)) | (Schema::Double, Value::Number(_)) => true,
+        (Schema::Bytes, Value::String(s)) => bytes(s),
+        (Schema::Fixed { size, .. }, Value::String(s)) => bytes(s) && s.chars().count() == *size,
+        (Schema::String, Value::String(_)) => true,
+        (Schema::Enum { symbols, .. }, Value::String(s)) => symbols.contains(s),
+        (Schema::Array(items), Value::Array(values)) => {
+            values.iter().all(|value| is_valid_default(items, value))
+        },
+        (Schema::Map(items), Value::Object(values)) => {
+            values.values().all(|value| is_valid_default(items, value))
+        },
+        (Schema::Union(union), default) => union
+            .variants()
+            .first()
+            .is_some_and(|first| is_valid_default(first, default)),
+        (Schema::Record { fields, .. }, Value::Object(values)) => fields.iter().all(|field| {
+            match values.get(&field.name) {
+                Some(value) => is_valid_default(&field.schema, value),
+                None => field.default.is_some(),
+            }
+        }),
+        (Schema::Logical(logical), default) => is_valid_default(logical.schema(), default),
+        _ => false,
+    }
+}
+
+fn get_named<'a>(
+    schema: &'a Schema,
+    fullname: &str,
+    namespace: Option<&str>,
+) -> Option<&'a Schema> {
+    let namespace = match schema.name() {
+        Some(name) => {
+            if name.fullname(namespace) == fullname {
+                return Some(schema)
+            }
+            schema.namespace().or(namespace)
+        },
+        None => namespace,
+    };
+    match *schema {
+        Schema::Array(ref inner) | Schema::Map(ref inner) => get_named(inner, fullname, namespace),
+        Schema::Union(ref union) => union
+            .variants()
+            .iter()
+            .filter_map(|variant| get_named(variant, fullname, namespace))
+            .next(),
+        Schema::Record { ref fields, .. } => fields
+            .iter()
+            .filter_map(|field| get_named(&field.schema, fullname, namespace))
+            .next(),
+        Schema::Logical(ref logical) => get_named(logical.schema(), fullname, namespace),
+        _ => None,
+    }
+}
+
+fn project(schema: &Schema, paths: &[&[&str]]) -> Result<Schema, Error> {
+    match *schema {
+        Schema::Record {
+            ref name,
+            ref doc,
+            ref fields,
+            ..
+        } => {
+            if let Some(path) = paths
+                .iter()
+                .find(|path| fields.iter().all(|field| field.name != path[0]))
+            {
+                return Err(ParseSchemaError::new(format!(
+                    "No field {} in record {}",
+                    path[0], name.name
+                )).into())
+            }
+
+            let mut projected = Vec::new();
+            for field in fields {
+                let nested = paths
+                    .iter()
+                    .filter(|path| path[0] == field.name)
+                    .map(|path| &path[1..])
+                    .collect::<Vec<_>>();
+                if nested.is_empty() {
+                    continue
+                }
+                // A field selected as a whole keeps all of its nested fields.
+                let schema = if nested.iter().any(|path| path.is_empty()) {
+                    field.schema.clone()
+                } else {
+                    project(&field.schema, &nested)?
+                };
+                projected.push(RecordField {
+                    name: field.name.clone(),
+                    doc: field.doc.clone(),
+                    default: field.default.clone(),
+                    schema,
+                    order: field.order.clone(),
+                    position: projected.len(),
+                });
+            }
+
+            let lookup = projected
+                .iter()
+                .map(|field| (field.name.clone(), field.position))
+                .collect();
+            Ok(Schema::Record {
+                name: name.clone(),
+                doc: doc.clone(),
+                fields: projected,
+                lookup,
+            })
+        },
+        Schema::Union(ref union)
+            if union
+                .variants()
+                .iter()
+                .any(|variant| SchemaKind::from(variant) == SchemaKind::Record) =>
+        {
+            let variants = union
+                .variants()
+                .iter()
+                .map(|variant| match *variant {
+                    Schema::Record { .. } => project(variant, paths),
+                    _ => Ok(variant.clone()),
+                }).collect::<Result<_, _>>()?;
+            Ok(Schema::Union(UnionSchema::new(variants)?))
+        },
+        // Projected values are no longer of the logical type.
+        Schema::Logical(ref logical) => project(logical.schema(), paths),
+        _ => Err(ParseSchemaError::new(format!(
+            "Cannot project fields {:?} of a non-record schema",
+            paths.iter().map(|path| path.join(".")).collect::<Vec<_>>()
+        )).into()),
+    }
+}
+
+// The types of the values seen at the same place across samples, see `Schema::infer`.
+#[derive(Default)]
+struct InferredType {
+    null: bool,
+    boolean: bool,
+    // The widest of `Int`, `Long` and `Double` seen.
+    number: Option<Schema>,
+    string: bool,
+    items: Option<Box<InferredType>>,
+    // In order of first appearance.
+    fields: Option<Vec<(String, InferredType)>>,
+}
+
+impl InferredType {
+    fn add(&mut self, json: &Value) {
+        match *json {
+            Value::Null => self.null = true,
+            Value::Bool(_) => self.boolean = true,
+            Value::Number(ref n) => {
+                let number = match n.as_i64() {
+                    Some(i) if i == i64::from(i as i32) => Schema::Int,
+                    Some(_) => Schema::Long,
+                    None => Schema::Double,
+                };
+                self.number = Some(match (self.number.take(), number) {
+                    (Some(Schema::Double), _) | (_, Schema::Double) => Schema::Double,
+                    (Some(Schema::Long), _) | (_, Schema::Long) => Schema::Long,
+                    _ => Schema::Int,
+                });
+            },
+            Value::String(_) => self.string = true,
+            Value::Array(ref items) => {
+                let inferred = self.items.get_or_insert_with(Default::default);
+                for item in items {
+                    inferred.add(item);
+                }
+            },
+            Value::Object(ref object) => {
+                let seen = self.fields.is_some();
+                let fields = self.fields.get_or_insert_with(Vec::new);
+                for (name, inferred) in fields.iter_mut() {
+                    if !object.contains_key(name) {
+                        inferred.null = true;
+                    }
+                }
+                for (name, value) in object {
+                    match fields.iter().position(|field| field.0 == *name) {
+                        Some(position) => fields[position].1.add(value),
+                        None => {
+                            // Missing from the objects seen before.
+                            let mut inferred = InferredType {
+                                null: seen,
+                                ..Default::default()
+                            };
+                            inferred.add(value);
+                            fields.push((name.clone(), inferred));
+                        },
+                    }
+                }
+            },
+        }
+    }
+
+    fn schema(&self, name: &str) -> Schema {
+        let mut variants = Vec::new();
+        if self.boolean {
+            variants.push(Schema::Boolean);
+        }
+        if let Some(ref number) = self.number {
+            variants.push(number.clone());
+        }
+        if self.string {
+            variants.push(Schema::String);
+        }
+        if let Some(ref items) = self.items {
+            variants.push(Schema::Array(Box::new(
+                items.schema(&format!("{}_item", name)),
+            )));
+        }
+        if let Some(ref fields) = self.fields {
+            variants.push(infer_record(name, fields));
+        }
+        if variants.is_empty() {
+            return Schema::Null
+        }
+        if variants.len() == 1 && !self.null {
+            return variants.remove(0)
+        }
+        if self.null {
+            variants.insert(0, Schema::Null);
+        }
+        Schema::Union(UnionSchema::new(variants).expect("Variants of distinct kinds"))
+    }
+}
+
+fn infer_record(name: &str, inferred: &[(String, InferredType)]) -> Schema {
+    let mut fields: Vec<RecordField> = Vec::new();
+    for (field_name, inferred) in inferred {
+        let mut field_name = field_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        if !is_valid_name(&field_name) {
+            field_name.insert(0, '_');
+        }
+        while fields.iter().any(|field| field.name == field_name) {
+            field_name.push('_');
+        }
+        let schema = inferred.schema(&format!("{}_{}", name, field_name));
+        let default = match schema {
+            Schema::Null | Schema::Union(_) if inferred.null => Some(Value::Null),
+            _ => None,
+        };
+        fields.push(RecordField {
+            name: field_name,
+            doc: None,
+            default,
+            schema,
+            order: RecordFieldOrder::Ascending,
+            position: fields.len(),
+        });
+    }
+    Schema::Record {
+        name: Name::new(name),
+        doc: None,
+        lookup: fields
+            .iter()
+            .map(|field| (field.name.clone(), field.position))
+            .collect(),
+        fields,
+    }
+}
+
 /// Parses a **valid** avro schema into the Parsing Canonical Form.
 /// https://avro.apache.org/docs/1.8.2/spec.html#Parsing+Canonical+Form+for+Schemas
-fn parsing_canonical_form(schema: &serde_json::Value) -> String {
+///
+/// `namespace` is the one enclosing `schema`, that its named types are defined in unless they have
+/// their own.
+fn parsing_canonical_form(schema: &serde_json::Value, namespace: Option<&str>) -> String {
     match schema {
-        serde_json::Value::Object(map) => pcf_map(map),
+        serde_json::Value::Object(map) => pcf_map(map, namespace),
         serde_json::Value::String(s) => pcf_string(s),
-        serde_json::Value::Array(v) => pcf_array(v),
+        serde_json::Value::Array(v) => pcf_array(v, namespace),
         _ => unreachable!(),
     }
 }
 
-fn pcf_map(schema: &Map<String, serde_json::Value>) -> String {
-    // Look for the namespace variant up front.
-    let ns = schema.get("namespace").and_then(|v| v.as_str());
+fn pcf_map(schema: &Map<String, serde_json::Value>, enclosing: Option<&str>) -> String {
+    // The namespace of a named type, which also encloses the types defined within it: the one of
+    // its fullname, else its own, else the enclosing one. An empty namespace is the null one.
+    let named = matches!(
+        schema.get("type").and_then(|v| v.as_str()),
+        Some("record") | Some("enum") | Some("fixed")
+    );
+    let ns = match schema.get("name").and_then(|v| v.as_str()) {
+        Some(name) if named => match name.rfind('.') {
+            Some(dot) => Some(&name[..dot]),
+            None => schema.get("namespace").and_then(|v| v.as_str()).or(enclosing),
+        },
+        _ => enclosing,
+    }.filter(|ns| !ns.is_empty());
     let mut fields = Vec::new();
     for (k, v) in schema {
         // Reduce primitive types to their simple form. ([PRIMITIVE] rule)
@@ -649,7 +1606,7 @@ fn pcf_map(schema: &Map<String, serde_json::Value>) -> String {
             // Invariant: Only valid schemas. Must be a string.
             let name = v.as_str().unwrap();
             let n = match ns {
-                Some(namespace) if !name.contains('.') => {
+                Some(namespace) if named && !name.contains('.') => {
                     Cow::Owned(format!("{}.{}", namespace, name))
                 },
                 _ => Cow::Borrowed(name),
@@ -672,7 +1629,7 @@ fn pcf_map(schema: &Map<String, serde_json::Value>) -> String {
         // For anything else, recursively process the result.
         fields.push((
             k,
-            format!("{}:{}", pcf_string(k), parsing_canonical_form(v)),
+            format!("{}:{}", pcf_string(k), parsing_canonical_form(v, ns)),
         ));
     }
 
@@ -686,10 +1643,10 @@ fn pcf_map(schema: &Map<String, serde_json::Value>) -> String {
     format!("{{{}}}", inter)
 }
 
-fn pcf_array(arr: &[serde_json::Value]) -> String {
+fn pcf_array(arr: &[serde_json::Value], namespace: Option<&str>) -> String {
     let inter = arr
         .iter()
-        .map(parsing_canonical_form)
+        .map(|schema| parsing_canonical_form(schema, namespace))
         .collect::<Vec<String>>()
         .join(",");
     format!("[{}]", inter)
@@ -715,6 +1672,32 @@ fn field_ordering_position(field: &str) -> Option<usize> {
     Some(v)
 }
 
+const RABIN_EMPTY: u64 = 0xc15d_213a_a4d7_a795;
+const RABIN_TABLE: [u64; 256] = rabin_table();
+
+const fn rabin_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut fp = i as u64;
+        let mut j = 0;
+        while j < 8 {
+            fp = (fp >> 1) ^ (RABIN_EMPTY & (fp & 1).wrapping_neg());
+            j += 1;
+        }
+        table[i] = fp;
+        i += 1;
+    }
+    table
+}
+
+/// Compute the CRC-64-AVRO fingerprint of `bytes`.
+fn rabin_fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(RABIN_EMPTY, |fp, &b| {
+        (fp >> 8) ^ RABIN_TABLE[((fp ^ u64::from(b)) & 0xff) as usize]
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -833,6 +1816,91 @@ mod tests {
         assert_eq!(expected, schema);
     }
 
+    #[test]
+    fn test_record_schema_duplicate_fields() {
+        let schema = r#"
+            {
+                "type": "record",
+                "name": "test",
+                "namespace": "com.example",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": "string"},
+                    {"name": "a", "type": "string"}
+                ]
+            }
+        "#;
+        let error = Schema::parse_str(schema).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse schema: Duplicate field name a in record com.example.test"
+        );
+        let permissive = ParseOptions::new().mode(ParseMode::Permissive);
+        assert!(Schema::parse_str_with(schema, &permissive).is_err());
+    }
+
+    #[test]
+    fn test_named_type_redefinition() {
+        let redefinition = |error: Error| match error {
+            Error::SchemaRedefinition {
+                name,
+                first,
+                second,
+            } => (name, first, second),
+            other => panic!("unexpected error {:?}", other),
+        };
+
+        let conflicting = r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": {"type": "fixed", "name": "md5", "size": 16}},
+                    {"name": "b", "type": ["null", {"type": "fixed", "name": "md5", "size": 8}]}
+                ]
+            }
+        "#;
+        assert_eq!(
+            redefinition(Schema::parse_str(conflicting).unwrap_err()),
+            (
+                "md5".to_owned(),
+                "$.fields[0].type".to_owned(),
+                "$.fields[1].type[1]".to_owned()
+            )
+        );
+        let permissive = ParseOptions::new().mode(ParseMode::Permissive);
+        assert!(Schema::parse_str_with(conflicting, &permissive).is_err());
+
+        // Identical definitions are only rejected in strict mode.
+        let identical = conflicting.replace("8}", "16}");
+        assert!(Schema::parse_str(&identical).is_ok());
+        let strict = ParseOptions::new().mode(ParseMode::Strict);
+        assert_eq!(
+            redefinition(Schema::parse_str_with(&identical, &strict).unwrap_err()).0,
+            "md5"
+        );
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let schemas = Schema::parse_list(&[
+            r#"{"type": "fixed", "name": "md5", "namespace": "com.example", "size": 16}"#,
+            r#"{"type": "array", "items": "com.example.md5"}"#,
+        ]).unwrap();
+        assert_eq!(schemas.len(), 2);
+        assert_eq!(schemas[1], Schema::Array(Box::new(schemas[0].clone())));
+
+        let error = Schema::parse_list(&[
+            r#"{"type": "fixed", "name": "md5", "size": 16}"#,
+            r#"{"type": "map", "values": {"type": "enum", "name": "md5", "symbols": ["a"]}}"#,
+        ]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse schema: md5 is defined at both $[0] and $[1].values"
+        );
+        assert!(Schema::parse_list(&[r#""md5""#]).is_err());
+    }
+
     #[test]
     fn test_enum_schema() {
         let schema = Schema::parse_str(
@@ -848,11 +1916,35 @@ mod tests {
                 "clubs".to_owned(),
                 "hearts".to_owned(),
             ],
+            default: None,
         };
 
         assert_eq!(expected, schema);
     }
 
+    #[test]
+    fn test_enum_default() {
+        let input =
+            r#"{"type":"enum","name":"Suit","symbols":["diamonds","spades"],"default":"spades"}"#;
+        let schema = Schema::parse_str(input).unwrap();
+        match schema {
+            Schema::Enum { ref default, .. } => assert_eq!(default.as_deref(), Some("spades")),
+            ref other => panic!("unexpected schema {:?}", other),
+        }
+        assert_eq!(serde_json::to_string(&schema).unwrap(), input);
+
+        assert!(
+            Schema::parse_str(
+                r#"{"type": "enum", "name": "Suit", "symbols": ["diamonds"], "default": "clubs"}"#
+            ).is_err()
+        );
+        assert!(
+            Schema::parse_str(
+                r#"{"type": "enum", "name": "Suit", "symbols": ["diamonds"], "default": 0}"#
+            ).is_err()
+        );
+    }
+
     #[test]
     fn test_fixed_schema() {
         let schema = Schema::parse_str(r#"{"type": "fixed", "name": "test", "size": 16}"#).unwrap();
@@ -865,6 +1957,42 @@ mod tests {
         assert_eq!(expected, schema);
     }
 
+    #[test]
+    fn test_parse_limits() {
+        let nested = |depth| {
+            (0..depth).fold("\"int\"".to_owned(), |schema, _| {
+                format!(r#"{{"type": "array", "items": {}}}"#, schema)
+            })
+        };
+        let options = ParseOptions::new().max_depth(8);
+        assert!(Schema::parse_str_with(&nested(8), &options).is_ok());
+        match Schema::parse_str_with(&nested(9), &options).unwrap_err() {
+            Error::SchemaDepth { limit } => assert_eq!(limit, 8),
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        // A record holding `n` fixed fields defines `n + 1` named types.
+        let record = |n| {
+            let fields = (0..n)
+                .map(|i| {
+                    format!(
+                        r#"{{"name": "f{0}", "type": {{"type": "fixed", "name": "f{0}", "size": 1}}}}"#,
+                        i
+                    )
+                }).collect::<Vec<_>>();
+            format!(
+                r#"{{"type": "record", "name": "r", "fields": [{}]}}"#,
+                fields.join(", ")
+            )
+        };
+        let options = ParseOptions::new().max_named_types(3);
+        assert!(Schema::parse_str_with(&record(2), &options).is_ok());
+        match Schema::parse_str_with(&record(3), &options).unwrap_err() {
+            Error::SchemaNamedTypes { limit } => assert_eq!(limit, 3),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
     #[test]
     fn test_no_documentation() {
         let schema =
@@ -893,8 +2021,455 @@ mod tests {
         assert_eq!("Some documentation".to_owned(), doc.unwrap());
     }
 
+    #[test]
+    fn test_accessors() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "Person",
+                "namespace": "com.example",
+                "doc": "A person",
+                "fields": [
+                    {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 16}},
+                    {
+                        "name": "addresses",
+                        "type": {"type": "array", "items": {
+                            "type": "record",
+                            "name": "Address",
+                            "fields": [{"name": "kind", "type": ["null", {
+                                "type": "enum",
+                                "name": "org.other.Kind",
+                                "symbols": ["home", "work"]
+                            }]}]
+                        }}
+                    }
+                ]
+            }
+        "#,
+        ).unwrap();
+
+        assert_eq!(schema.name().map(|name| name.name.as_str()), Some("Person"));
+        assert_eq!(schema.namespace(), Some("com.example"));
+        assert_eq!(schema.fullname().as_deref(), Some("com.example.Person"));
+        assert_eq!(schema.doc(), Some("A person"));
+        assert_eq!(schema.get_named("com.example.Person"), Some(&schema));
+
+        let address = schema.get_named("com.example.Address").unwrap();
+        assert_eq!(address.fullname().as_deref(), Some("Address"));
+        assert_eq!(address.doc(), None);
+        let kind = schema.get_named("org.other.Kind").unwrap();
+        assert_eq!(kind.namespace(), Some("org.other"));
+        assert_eq!(kind.fullname().as_deref(), Some("org.other.Kind"));
+        assert!(schema.get_named("com.example.Id").is_some());
+        assert!(schema.get_named("Address").is_none());
+        assert!(schema.get_named("com.example.Kind").is_none());
+
+        assert_eq!(Schema::Long.name(), None);
+        assert_eq!(Schema::Long.fullname(), None);
+        assert_eq!(Schema::Long.get_named("Long"), None);
+    }
+
+    #[test]
+    fn test_field_defaults() {
+        let field = |schema: &str, default: &str| {
+            Schema::parse_str(&format!(
+                r#"{{"type": "record", "name": "r", "fields": [
+                    {{"name": "f", "type": {}, "default": {}}}
+                ]}}"#,
+                schema, default
+            ))
+        };
+
+        for &(schema, default) in &[
+            (r#""null""#, "null"),
+            (r#""boolean""#, "true"),
+            (r#""int""#, "-2147483648"),
+            (r#""long""#, "2147483648"),
+            (r#""float""#, "1"),
+            (r#""double""#, "0.5"),
+            (r#""bytes""#, r#""\u00ff""#),
+            (r#""string""#, r#""foo""#),
+            (r#"{"type": "fixed", "name": "f", "size": 2}"#, r#""ab""#),
+            (r#"{"type": "enum", "name": "e", "symbols": ["a", "b"]}"#, r#""b""#),
+            (r#"{"type": "array", "items": "int"}"#, "[1, 2]"),
+            (r#"{"type": "map", "values": "int"}"#, r#"{"a": 1}"#),
+            (r#"["null", "int"]"#, "null"),
+            (r#"["int", "null"]"#, "1"),
+            (
+                r#"{"type": "record", "name": "n", "fields": [
+                    {"name": "a", "type": "int"},
+                    {"name": "b", "type": "int", "default": 0}
+                ]}"#,
+                r#"{"a": 1}"#,
+            ),
+        ] {
+            assert!(field(schema, default).is_ok(), "{} for {}", default, schema);
+        }
+
+        for &(schema, default) in &[
+            (r#""null""#, "0"),
+            (r#""int""#, r#""1""#),
+            (r#""int""#, "2147483648"),
+            (r#""long""#, "0.5"),
+            (r#""bytes""#, r#""\u0100""#),
+            (r#""string""#, "1"),
+            (r#"{"type": "fixed", "name": "f", "size": 2}"#, r#""abc""#),
+            (r#"{"type": "enum", "name": "e", "symbols": ["a", "b"]}"#, r#""c""#),
+            (r#"{"type": "array", "items": "int"}"#, r#"["a"]"#),
+            (r#"{"type": "map", "values": "int"}"#, r#"{"a": "b"}"#),
+            // A union default corresponds to its first branch.
+            (r#"["null", "int"]"#, "1"),
+            (
+                r#"{"type": "record", "name": "n", "fields": [{"name": "a", "type": "int"}]}"#,
+                "{}",
+            ),
+        ] {
+            assert!(field(schema, default).is_err(), "{} for {}", default, schema);
+        }
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        let parse = |mode: ParseMode, input: &str| {
+            Schema::parse_str_with(input, &ParseOptions::new().mode(mode)).is_ok()
+        };
+
+        // (schema, strict, standard, permissive)
+        for &(input, strict, standard, permissive) in &[
+            (
+                r#"{"type": "record", "name": "a.b_1", "aliases": ["c"], "fields": []}"#,
+                true,
+                true,
+                true,
+            ),
+            (r#"{"type": "record", "name": "a-b", "fields": []}"#, false, true, true),
+            (
+                r#"{"type": "record", "name": "a", "namespace": "1b", "fields": []}"#,
+                false,
+                true,
+                true,
+            ),
+            (
+                r#"{"type": "record", "name": "a", "aliases": ["$"], "fields": []}"#,
+                false,
+                true,
+                true,
+            ),
+            (
+                r#"{"type": "record", "name": "a", "fields": [{"name": "b.c", "type": "int"}]}"#,
+                false,
+                true,
+                true,
+            ),
+            (r#"{"type": "record", "name": "a", "fields": [1]}"#, false, true, true),
+            (
+                r#"{"type": "record", "name": "a", "fields": [
+                    {"name": "b", "type": "int", "default": "c"}
+                ]}"#,
+                false,
+                false,
+                true,
+            ),
+            (r#"{"type": "enum", "name": "e", "symbols": ["a", "a"]}"#, false, true, true),
+            (r#"{"type": "enum", "name": "e", "symbols": ["a b"]}"#, false, true, true),
+            (
+                r#"{"type": "enum", "name": "e", "symbols": ["a"], "default": "b"}"#,
+                false,
+                false,
+                true,
+            ),
+            (r#"{"type": "long", "logicalType": "timestamp-millis"}"#, true, true, true),
+            (r#"{"type": "long", "connect.name": "foo"}"#, false, true, true),
+        ] {
+            assert_eq!(parse(ParseMode::Strict, input), strict, "strict {}", input);
+            assert_eq!(parse(ParseMode::Standard, input), standard, "standard {}", input);
+            assert_eq!(parse(ParseMode::Permissive, input), permissive, "permissive {}", input);
+        }
+
+        let schema = Schema::parse_str_with(
+            r#"{"type": "enum", "name": "e", "symbols": ["a"], "default": "b"}"#,
+            &ParseOptions::new().mode(ParseMode::Permissive),
+        ).unwrap();
+        match schema {
+            Schema::Enum { ref default, .. } => assert_eq!(*default, None),
+            ref other => panic!("unexpected schema {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_named_references() {
+        let input = r#"
+            {
+                "type": "record",
+                "name": "Person",
+                "namespace": "com.example",
+                "fields": [
+                    {"name": "home", "type": {
+                        "type": "record",
+                        "name": "Address",
+                        "fields": [
+                            {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 2}}
+                        ]
+                    }},
+                    {"name": "work", "type": ["null", "Address"]},
+                    {"name": "other", "type": {"type": "array", "items": "com.example.Address"}},
+                    {"name": "id", "type": "Id"}
+                ]
+            }
+        "#;
+        let schema = Schema::parse_str(input).unwrap();
+        let address = schema.get_named("com.example.Address").unwrap().clone();
+        match schema {
+            Schema::Record { ref fields, .. } => {
+                assert_eq!(fields[0].schema, address);
+                assert_eq!(
+                    fields[1].schema,
+                    Schema::Union(UnionSchema::new(vec![Schema::Null, address.clone()]).unwrap())
+                );
+                assert_eq!(fields[2].schema, Schema::Array(Box::new(address.clone())));
+                assert_eq!(Some(&fields[3].schema), schema.get_named("com.example.Id"));
+            },
+            ref other => panic!("unexpected schema {:?}", other),
+        }
+
+        // Defined once, then referenced by fullname.
+        let json = serde_json::to_value(&schema).unwrap();
+        assert_eq!(
+            json["fields"][1]["type"],
+            serde_json::json!(["null", "com.example.Address"])
+        );
+        assert_eq!(json["fields"][2]["type"]["items"], "com.example.Address");
+        assert_eq!(json["fields"][3]["type"], "com.example.Id");
+        assert_eq!(Schema::parse(&json).unwrap(), schema);
+        assert_eq!(
+            schema.canonical_form(),
+            Schema::parse_str(&schema.canonical_form())
+                .unwrap()
+                .canonical_form()
+        );
+
+        // Unknown, or defined later.
+        assert!(
+            Schema::parse_str(
+                r#"{"type": "record", "name": "r", "fields": [{"name": "a", "type": "Unknown"}]}"#
+            ).is_err()
+        );
+        assert!(
+            Schema::parse_str(
+                r#"{"type": "record", "name": "r", "fields": [
+                    {"name": "a", "type": "f"},
+                    {"name": "b", "type": {"type": "fixed", "name": "f", "size": 1}}
+                ]}"#
+            ).is_err()
+        );
+    }
+
+    #[test]
+    fn test_rabin_fingerprint() {
+        // Test vectors from the Avro specification test suite.
+        assert_eq!(Schema::Null.rabin_fingerprint(), 7_195_948_357_588_979_594);
+        assert_eq!(Schema::Int.rabin_fingerprint(), 8_247_732_601_305_521_295);
+
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long", "default": 42},
+                    {"name": "b", "type": "string"}
+                ]
+            }
+        "#,
+        ).unwrap();
+        assert_eq!(schema.rabin_fingerprint(), 0x472c_5f61_0cc2_c6e8);
+    }
+
+    #[test]
+    fn test_digest_fingerprints() {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        let schema = Schema::parse_str(r#"{"type": "fixed", "name": "md5", "size": 16}"#).unwrap();
+        assert_eq!(
+            schema.md5_fingerprint(),
+            md5::compute(r#"{"name":"md5","type":"fixed","size":16}"#).0
+        );
+        // The SHA-256 test vector of FIPS 180-2.
+        assert_eq!(
+            hex(&Sha256::digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            schema.sha256_fingerprint(),
+            <[u8; 32]>::from(Sha256::digest(br#"{"name":"md5","type":"fixed","size":16}"#))
+        );
+    }
+
+    #[test]
+    fn test_fingerprints_of_nested_named_types() {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        // Named types defined within another are in its namespace unless they have their own.
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "namespace": "a", "fields": [
+                {"name": "f", "type": {"type": "record", "name": "s", "fields": [
+                    {"name": "x", "type": {"type": "enum", "name": "b.e", "symbols": ["A", "B"]}}
+                ]}},
+                {"name": "g", "type": ["null", "s"]},
+                {"name": "h", "type": {"type": "map", "values": "b.e"}}
+            ]}"#,
+        ).unwrap();
+        assert_eq!(
+            schema.canonical_form(),
+            concat!(
+                r#"{"name":"a.r","type":"record","fields":[{"name":"f","type":{"name":"a.s","#,
+                r#""type":"record","fields":[{"name":"x","type":{"name":"b.e","type":"enum","#,
+                r#""symbols":["A","B"]}}]}},{"name":"g","type":["null","a.s"]},"#,
+                r#"{"name":"h","type":{"type":"map","values":"b.e"}}]}"#,
+            )
+        );
+        // Computed by Java, with the code of the specification and `java.security.MessageDigest`.
+        assert_eq!(schema.rabin_fingerprint(), 0x0a49_903f_b432_9d1f);
+        assert_eq!(hex(&schema.md5_fingerprint()), "5a52ea431ae59987d5a41fc5c575cc42");
+        assert_eq!(
+            hex(&schema.sha256_fingerprint()),
+            "fa0a6d482f23f21e6cbb372e5a755c079264a16d79607306365bcf91b5d693c2"
+        );
+    }
+
     // Tests to ensure Schema is Send + Sync. These tests don't need to _do_ anything, if they can
     // compile, they pass.
+    #[test]
+    fn test_from_str_and_display() {
+        let schema = r#"{"type":"map","values":{"type":"enum","name":"e","symbols":["a","b"]}}"#;
+        let parsed = schema.parse::<Schema>().unwrap();
+        assert_eq!(parsed, Schema::parse_str(schema).unwrap());
+        assert_eq!(parsed.to_string(), schema);
+
+        assert!("{".parse::<Schema>().is_err());
+    }
+
+    #[test]
+    fn test_infer() {
+        let samples = vec![
+            json!({"id": 1, "name": "foo", "score": 1, "tags": ["a"], "address": {"zip": 1}}),
+            json!({"id": 5000000000i64, "name": null, "score": 2.5, "tags": [], "my-key": true}),
+            json!({"id": 2, "name": "bar", "score": "n/a", "tags": ["b", 3], "address": null}),
+        ];
+        let expected = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "root",
+                "fields": [
+                    {
+                        "name": "address",
+                        "type": [
+                            "null",
+                            {
+                                "type": "record",
+                                "name": "root_address",
+                                "fields": [{"name": "zip", "type": "int"}]
+                            }
+                        ],
+                        "default": null
+                    },
+                    {"name": "id", "type": "long"},
+                    {"name": "name", "type": ["null", "string"], "default": null},
+                    {"name": "score", "type": ["double", "string"]},
+                    {"name": "tags", "type": {"type": "array", "items": ["int", "string"]}},
+                    {"name": "my_key", "type": ["null", "boolean"], "default": null}
+                ]
+            }
+        "#,
+        ).unwrap();
+        // Fields come in the order of the keys of the JSON objects, sorted by serde_json.
+        let inferred = Schema::infer(&samples).unwrap();
+        assert_eq!(inferred, expected);
+        assert_eq!(
+            Schema::parse_str(&serde_json::to_string(&inferred).unwrap()).unwrap(),
+            inferred
+        );
+
+        assert!(Schema::infer(&[]).is_err());
+        assert!(Schema::infer(&[json!([1])]).is_err());
+    }
+
+    #[test]
+    fn test_project() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "event",
+                "namespace": "org.example",
+                "fields": [
+                    {"name": "id", "type": "long"},
+                    {"name": "kind", "type": "string", "default": "click"},
+                    {
+                        "name": "user",
+                        "type": ["null", {
+                            "type": "record",
+                            "name": "user",
+                            "fields": [
+                                {"name": "name", "type": "string"},
+                                {"name": "age", "type": "int"}
+                            ]
+                        }]
+                    },
+                    {"name": "tags", "type": {"type": "array", "items": "string"}}
+                ]
+            }
+        "#,
+        ).unwrap();
+
+        let projected = schema.project(&["user.name", "kind"]).unwrap();
+        let expected = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "event",
+                "namespace": "org.example",
+                "fields": [
+                    {"name": "kind", "type": "string", "default": "click"},
+                    {
+                        "name": "user",
+                        "type": ["null", {
+                            "type": "record",
+                            "name": "user",
+                            "fields": [{"name": "name", "type": "string"}]
+                        }]
+                    }
+                ]
+            }
+        "#,
+        ).unwrap();
+        assert_eq!(projected, expected);
+        match projected {
+            Schema::Record {
+                ref name,
+                ref lookup,
+                ..
+            } => {
+                assert_eq!(name.namespace.as_deref(), Some("org.example"));
+                assert_eq!(lookup.get("user"), Some(&1));
+            },
+            ref other => panic!("unexpected schema {:?}", other),
+        }
+
+        // A field selected as a whole.
+        let field_schema = |schema: &Schema, position: usize| match *schema {
+            Schema::Record { ref fields, .. } => fields[position].schema.clone(),
+            _ => unreachable!(),
+        };
+        let projected = schema.project(&["user", "user.age"]).unwrap();
+        assert_eq!(field_schema(&projected, 0), field_schema(&schema, 2));
+
+        assert!(schema.project(&["unknown"]).is_err());
+        assert!(schema.project(&["user.unknown"]).is_err());
+        assert!(schema.project(&["tags.name"]).is_err());
+        assert!(Schema::Long.project(&["id"]).is_err());
+    }
+
     #[test]
     fn test_schema_is_send() {
         fn send<S: Send>(_s: S) {}
@@ -6,6 +6,7 @@ use std::iter::once;
 
 use serde::ser::{self, Error as SerdeError, Serialize};
 
+use conversion::{self, CONVERTED};
 use types::{ToAvro, Value};
 
 #[cfg(feature = "unsigned_long_as_fixed")]
@@ -194,17 +195,20 @@ impl<'b> ser::Serializer for &'b mut Serializer {
         index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::Enum(index as i32, variant.to_string()))
+        Ok(Value::Enum(index as i32, variant.into()))
     }
 
     fn serialize_newtype_struct<T: ?Sized>(
         self,
-        _: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
         T: Serialize,
     {
+        if name == CONVERTED {
+            return conversion::capture(|| value.serialize(self))
+        }
         value.serialize(self)
     }
 
@@ -367,7 +371,7 @@ impl ser::SerializeMap for MapSerializer {
         let mut items = HashMap::new();
         for (key, index) in self.indices {
             if let Some(value) = self.values.get(index) {
-                items.insert(key, value.clone());
+                items.insert(key.into(), value.clone());
             }
         }
 
@@ -427,6 +431,9 @@ pub fn to_value<S: Serialize>(value: S) -> Result<Value, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use datum::{from_avro_datum, to_avro_datum};
+    use de::from_value;
+    use schema::Schema;
 
     #[derive(Debug, Deserialize, Serialize)]
     struct Test {
@@ -434,6 +441,12 @@ mod tests {
         b: String,
     }
 
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Nullable {
+        a: Option<String>,
+        b: Option<Vec<i64>>,
+    }
+
     #[test]
     fn test_to_value() {
         let test = Test {
@@ -448,6 +461,43 @@ mod tests {
         assert_eq!(to_value(test).unwrap(), expected);
     }
 
+    #[test]
+    fn test_serialize_option_null_branch() {
+        // The branches of `Option`s are the ones of the schema, wherever `null` is.
+        for &(null_first, null_index) in &[(true, 0u8), (false, 2u8)] {
+            let union = |schema: &str| {
+                if null_first {
+                    format!(r#"["null", {}]"#, schema)
+                } else {
+                    format!(r#"[{}, "null"]"#, schema)
+                }
+            };
+            let schema = Schema::parse_str(&format!(
+                r#"{{"type": "record", "name": "test", "fields": [
+                    {{"name": "a", "type": {}}},
+                    {{"name": "b", "type": {}}}
+                ]}}"#,
+                union(r#""string""#),
+                union(r#"{"type": "array", "items": "long"}"#),
+            )).unwrap();
+
+            let none = Nullable { a: None, b: None };
+            let encoded = to_avro_datum(&schema, to_value(&none).unwrap()).unwrap();
+            assert_eq!(encoded, vec![null_index, null_index]);
+            let decoded = from_avro_datum(&schema, &mut &encoded[..], None).unwrap();
+            assert_eq!(from_value::<Nullable>(&decoded).unwrap(), none);
+
+            let some = Nullable {
+                a: Some("foo".to_owned()),
+                b: Some(vec![27]),
+            };
+            let encoded = to_avro_datum(&schema, to_value(&some).unwrap()).unwrap();
+            assert_eq!(encoded[0], 2 - null_index);
+            let decoded = from_avro_datum(&schema, &mut &encoded[..], None).unwrap();
+            assert_eq!(from_value::<Nullable>(&decoded).unwrap(), some);
+        }
+    }
+
     #[cfg(feature = "unsigned_long_as_fixed")]
     #[derive(Debug, Deserialize, Serialize)]
     struct TestLong {
@@ -0,0 +1,382 @@
+//! Logic handling the single-object encoding of Avro datums.
+//!
+//! A single object is made of the 2-byte marker `C3 01`, followed by the 8-byte little-endian
+//! CRC-64-AVRO fingerprint of the writer schema and by the Avro binary encoding of the datum.
+//!
+//! More information about single-object encoding can be found in the
+//! [Avro specification](https://avro.apache.org/docs/current/spec.html#single_object_encoding)
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use datum::{from_avro_datum, to_avro_datum, DatumReader, DatumWriter};
+use error::Error;
+
+use reader::from_avro_datum_with;
+use resolver::Resolver;
+use schema::Schema;
+use store::SchemaStore;
+use types::{ToAvro, Value};
+use util::DecodeError;
+
+const SINGLE_OBJECT_MAGIC: [u8; 2] = [0xC3, 0x01];
+const SINGLE_OBJECT_HEADER_SIZE: usize = 10;
+
+/// Build the single-object header for a writer schema with the given Rabin fingerprint.
+pub fn single_object_header(fingerprint: u64) -> [u8; SINGLE_OBJECT_HEADER_SIZE] {
+    let mut header = [0u8; SINGLE_OBJECT_HEADER_SIZE];
+    header[..2].copy_from_slice(&SINGLE_OBJECT_MAGIC);
+    header[2..].copy_from_slice(&fingerprint.to_le_bytes());
+    header
+}
+
+/// Read a single-object header, returning the Rabin fingerprint of the writer schema.
+pub fn read_single_object_header<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut header = [0u8; SINGLE_OBJECT_HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+
+    if header[..2] != SINGLE_OBJECT_MAGIC {
+        return Err(DecodeError::new("wrong magic in single object header").into())
+    }
+
+    let mut fingerprint = [0u8; 8];
+    fingerprint.copy_from_slice(&header[2..]);
+    Ok(u64::from_le_bytes(fingerprint))
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) using the single-object
+/// encoding, also performing schema validation.
+pub fn to_single_object<T: ToAvro>(schema: &Schema, value: T) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    write_single_object(schema, value, &mut buffer)?;
+    Ok(buffer)
+}
+
+/// Encode a compatible value (implementing the `ToAvro` trait) using the single-object
+/// encoding and write it to anything implementing `io::Write`, also performing schema
+/// validation.
+///
+/// Return the number of bytes written.
+pub fn write_single_object<T: ToAvro, W: Write>(
+    schema: &Schema,
+    value: T,
+    writer: &mut W,
+) -> Result<usize, Error> {
+    // Encode first, so that nothing gets written if the value does not match the schema.
+    let datum = to_avro_datum(schema, value)?;
+    let header = single_object_header(schema.rabin_fingerprint());
+
+    writer.write_all(&header)?;
+    writer.write_all(&datum)?;
+
+    Ok(header.len() + datum.len())
+}
+
+/// Decode a `Value` encoded using the single-object encoding from anything implementing
+/// `io::Read`.
+///
+/// The writer `Schema` is looked up in `store` by the Rabin fingerprint found in the header.
+///
+/// In case a reader `Schema` is provided, schema resolution will also be performed.
+pub fn from_single_object<R, S>(
+    reader: &mut R,
+    store: &S,
+    reader_schema: Option<&Schema>,
+) -> Result<Value, Error>
+where
+    R: Read,
+    S: SchemaStore + ?Sized,
+{
+    let fingerprint = read_single_object_header(reader)?;
+    let schema = store.get_by_fingerprint(fingerprint).ok_or_else(|| {
+        DecodeError::new(format!("unknown schema fingerprint {:#018x}", fingerprint))
+    })?;
+
+    from_avro_datum(&schema, reader, reader_schema)
+}
+
+/// Reader for a stream of single objects, possibly written with different writer schemas.
+///
+/// The writer `Schema` of every fingerprint met is looked up in the `SchemaStore` only once, and
+/// so is its [`Resolver`](struct.Resolver.html) against the reader `Schema`, if they differ.
+pub struct MessageReader<'a, S> {
+    store: S,
+    reader_schema: Option<&'a Schema>,
+    plans: HashMap<u64, DecodePlan>,
+}
+
+// How to decode the single objects with a given fingerprint.
+struct DecodePlan {
+    writer_schema: Arc<Schema>,
+    resolver: Option<Resolver>,
+}
+
+impl<'a, S: SchemaStore> MessageReader<'a, S> {
+    /// Creates a `MessageReader` looking up writer schemas in `store`.
+    /// No reader `Schema` will be set.
+    pub fn new(store: S) -> MessageReader<'a, S> {
+        MessageReader {
+            store,
+            reader_schema: None,
+            plans: HashMap::new(),
+        }
+    }
+
+    /// Creates a `MessageReader` given a reader `Schema` and a `store` to look up writer schemas.
+    pub fn with_schema(schema: &'a Schema, store: S) -> MessageReader<'a, S> {
+        MessageReader {
+            store,
+            reader_schema: Some(schema),
+            plans: HashMap::new(),
+        }
+    }
+
+    /// Get a reference to the optional reader `Schema`.
+    pub fn reader_schema(&self) -> Option<&Schema> {
+        self.reader_schema
+    }
+
+    /// Decode a single object from anything implementing `io::Read`, also performing schema
+    /// resolution if a reader `Schema` was given and the writer `Schema` differs from it.
+    pub fn read<R: Read>(&mut self, reader: &mut R) -> Result<Value, Error> {
+        let plan = self.plan(reader)?;
+        from_avro_datum_with(&plan.writer_schema, reader, plan.resolver.as_ref())
+    }
+
+    /// Decode a single object from anything implementing `io::Read` with `datum_reader`, see
+    /// [`DatumReader`](trait.DatumReader.html).
+    ///
+    /// `datum_reader` is given the writer `Schema` and is in charge of the resolution, the reader
+    /// `Schema` being not used.
+    pub fn read_with<D, R>(
+        &mut self,
+        datum_reader: &mut D,
+        reader: &mut R,
+    ) -> Result<D::Datum, Error>
+    where
+        D: DatumReader,
+        R: Read,
+    {
+        let plan = self.plan(reader)?;
+        datum_reader.read(&plan.writer_schema, reader)
+    }
+
+    // Read the header of a single object, returning how to decode the rest of it.
+    fn plan<R: Read>(&mut self, reader: &mut R) -> Result<&DecodePlan, Error> {
+        let fingerprint = read_single_object_header(reader)?;
+        if !self.plans.contains_key(&fingerprint) {
+            let writer_schema = self.store.get_by_fingerprint(fingerprint).ok_or_else(|| {
+                DecodeError::new(format!("unknown schema fingerprint {:#018x}", fingerprint))
+            })?;
+            let resolver = self
+                .reader_schema
+                .filter(|&schema| *writer_schema != *schema)
+                .map(|schema| Resolver::new(&writer_schema, schema));
+            self.plans.insert(
+                fingerprint,
+                DecodePlan {
+                    writer_schema,
+                    resolver,
+                },
+            );
+        }
+
+        Ok(&self.plans[&fingerprint])
+    }
+}
+
+/// Writer of single objects sharing the same writer `Schema`.
+///
+/// The fingerprint of the schema, and thus the header of the single objects, is only computed
+/// once upon creation of the `MessageWriter`.
+pub struct MessageWriter<'a> {
+    schema: &'a Schema,
+    header: [u8; SINGLE_OBJECT_HEADER_SIZE],
+}
+
+impl<'a> MessageWriter<'a> {
+    /// Creates a `MessageWriter` for the given writer `Schema`.
+    pub fn new(schema: &'a Schema) -> MessageWriter<'a> {
+        MessageWriter {
+            schema,
+            header: single_object_header(schema.rabin_fingerprint()),
+        }
+    }
+
+    /// Get a reference to the writer `Schema`.
+    pub fn schema(&self) -> &'a Schema {
+        self.schema
+    }
+
+    /// Get the Rabin fingerprint of the writer `Schema`.
+    pub fn fingerprint(&self) -> u64 {
+        let mut fingerprint = [0u8; 8];
+        fingerprint.copy_from_slice(&self.header[2..]);
+        u64::from_le_bytes(fingerprint)
+    }
+
+    /// Encode a compatible value (implementing the `ToAvro` trait) as a single object, also
+    /// performing schema validation.
+    pub fn to_vec<T: ToAvro>(&self, value: T) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::new();
+        self.write(value, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Encode a compatible value (implementing the `ToAvro` trait) as a single object and write
+    /// it to anything implementing `io::Write`, also performing schema validation.
+    ///
+    /// Return the number of bytes written.
+    pub fn write<T: ToAvro, W: Write>(&self, value: T, writer: &mut W) -> Result<usize, Error> {
+        let datum = to_avro_datum(self.schema, value)?;
+
+        writer.write_all(&self.header)?;
+        writer.write_all(&datum)?;
+
+        Ok(self.header.len() + datum.len())
+    }
+
+    /// Encode `datum` with `datum_writer` as a single object and write it to anything
+    /// implementing `io::Write`, see [`DatumWriter`](trait.DatumWriter.html).
+    ///
+    /// Return the number of bytes written.
+    pub fn write_with<T, D, W>(
+        &self,
+        datum_writer: &mut D,
+        datum: &T,
+        writer: &mut W,
+    ) -> Result<usize, Error>
+    where
+        T: ?Sized,
+        D: DatumWriter<T>,
+        W: Write,
+    {
+        let mut buffer = Vec::new();
+        datum_writer.write(self.schema, datum, &mut buffer)?;
+
+        writer.write_all(&self.header)?;
+        writer.write_all(&buffer)?;
+
+        Ok(self.header.len() + buffer.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use store::MemorySchemaStore;
+    use types::Record;
+
+    static SCHEMA: &'static str = r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long", "default": 42},
+                    {"name": "b", "type": "string"}
+                ]
+            }
+        "#;
+
+    #[test]
+    fn test_single_object_round_trip() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        let expected = record.clone().avro();
+
+        let encoded = to_single_object(&schema, record).unwrap();
+        assert_eq!(
+            &encoded[..10],
+            &[0xC3, 0x01, 0xe8, 0xc6, 0xc2, 0x0c, 0x61, 0x5f, 0x2c, 0x47]
+        );
+        assert_eq!(&encoded[10..], &[54, 6, 102, 111, 111]);
+
+        let mut store = MemorySchemaStore::new();
+        store.insert(schema);
+        let decoded = from_single_object(&mut &encoded[..], &store, None).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_single_object_errors() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        let encoded = to_single_object(&schema, record).unwrap();
+
+        // unknown fingerprint
+        let mut store = MemorySchemaStore::new();
+        assert!(from_single_object(&mut &encoded[..], &store, None).is_err());
+
+        // wrong magic
+        store.insert(schema.clone());
+        let mut invalid = encoded.clone();
+        invalid[0] = 0;
+        assert!(from_single_object(&mut &invalid[..], &store, None).is_err());
+
+        // invalid value
+        let mut buffer = Vec::new();
+        assert!(write_single_object(&schema, Value::Long(27), &mut buffer).is_err());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_message_reader_writer() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "b", "type": "string"},
+                    {"name": "c", "type": "int", "default": 3}
+                ]
+            }
+        "#,
+        ).unwrap();
+
+        let mut store = MemorySchemaStore::new();
+        store.insert(schema.clone());
+        store.insert(reader_schema.clone());
+
+        let writer = MessageWriter::new(&schema);
+        assert_eq!(writer.fingerprint(), schema.rabin_fingerprint());
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        let encoded = writer.to_vec(record.clone()).unwrap();
+        assert_eq!(encoded, to_single_object(&schema, record).unwrap());
+
+        let mut record = Record::new(&reader_schema).unwrap();
+        record.put("b", "bar");
+        record.put("c", 5);
+        let mut encoded_reader = Vec::new();
+        MessageWriter::new(&reader_schema)
+            .write(record.clone(), &mut encoded_reader)
+            .unwrap();
+
+        let mut reader = MessageReader::with_schema(&reader_schema, &store);
+        let expected = Value::Record(vec![
+            ("b".to_owned(), Value::String("foo".to_owned())),
+            ("c".to_owned(), Value::Int(3)),
+        ]);
+        for _ in 0..2 {
+            assert_eq!(reader.read(&mut &encoded[..]).unwrap(), expected);
+            assert_eq!(
+                reader.read(&mut &encoded_reader[..]).unwrap(),
+                record.clone().avro()
+            );
+        }
+        assert_eq!(reader.plans.len(), 2);
+        assert!(reader.plans[&schema.rabin_fingerprint()].resolver.is_some());
+        assert!(reader.plans[&reader_schema.rabin_fingerprint()].resolver.is_none());
+
+        let mut invalid = encoded.clone();
+        invalid[2] ^= 1;
+        assert!(reader.read(&mut &invalid[..]).is_err());
+    }
+}
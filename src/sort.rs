@@ -0,0 +1,401 @@
+//! Logic comparing Avro data in its binary encoding, following the sort order of the
+//! specification, and merging sorted object container files.
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+
+use datum::to_avro_datum;
+use decode::skip;
+use error::Error;
+use reader::Reader;
+use schema::{RecordFieldOrder, Schema};
+use util::{zag_i64, DecodeError};
+use writer::Writer;
+use Codec;
+
+/// Compare two datums encoded with `schema` following the sort order of the Avro specification,
+/// without decoding them into `Value`s.
+///
+/// Record fields are compared in order, as their `order` attribute says; unions first by
+/// branch; arrays item by item; strings, bytes and fixed values byte by byte; and floats and
+/// doubles like Java does (`NaN` sorts last). Maps cannot be compared, unless in an ignored field.
+pub fn compare_encoded(schema: &Schema, a: &[u8], b: &[u8]) -> Result<Ordering, Error> {
+    compare(schema, &mut &a[..], &mut &b[..])
+}
+
+// Compare the datums at the start of `a` and `b`, advancing past both if they are equal.
+fn compare(schema: &Schema, a: &mut &[u8], b: &mut &[u8]) -> Result<Ordering, Error> {
+    Ok(match *schema {
+        Schema::Null => Ordering::Equal,
+        Schema::Boolean => take(a, 1)?.cmp(take(b, 1)?),
+        Schema::Int | Schema::Long | Schema::Enum { .. } => zag_i64(a)?.cmp(&zag_i64(b)?),
+        Schema::Float => {
+            let (a, b) = (take(a, 4)?, take(b, 4)?);
+            let float = |bytes: &[u8]| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            total_cmp(float(a).is_nan(), float(b).is_nan(), || float(a).total_cmp(&float(b)))
+        },
+        Schema::Double => {
+            let (a, b) = (take(a, 8)?, take(b, 8)?);
+            let double = |bytes: &[u8]| {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(bytes);
+                f64::from_le_bytes(buf)
+            };
+            total_cmp(double(a).is_nan(), double(b).is_nan(), || {
+                double(a).total_cmp(&double(b))
+            })
+        },
+        Schema::Bytes | Schema::String => {
+            let (a_len, b_len) = (length(a)?, length(b)?);
+            take(a, a_len)?.cmp(take(b, b_len)?)
+        },
+        Schema::Fixed { size, .. } => take(a, size)?.cmp(take(b, size)?),
+        Schema::Logical(ref logical) => compare(logical.schema(), a, b)?,
+        Schema::Array(ref items) => {
+            let (mut a_left, mut b_left) = (0, 0);
+            loop {
+                if a_left == 0 {
+                    a_left = block_len(a)?;
+                }
+                if b_left == 0 {
+                    b_left = block_len(b)?;
+                }
+                match (a_left, b_left) {
+                    (0, 0) => break Ordering::Equal,
+                    (0, _) => break Ordering::Less,
+                    (_, 0) => break Ordering::Greater,
+                    _ => {},
+                }
+                match compare(items, a, b)? {
+                    Ordering::Equal => {},
+                    ordering => break ordering,
+                }
+                a_left -= 1;
+                b_left -= 1;
+            }
+        },
+        Schema::Map(_) => return Err(DecodeError::new("maps cannot be compared").into()),
+        Schema::Union(ref union) => {
+            let (a_index, b_index) = (zag_i64(a)?, zag_i64(b)?);
+            if a_index != b_index {
+                return Ok(a_index.cmp(&b_index))
+            }
+            match union.variants().get(a_index as usize) {
+                Some(variant) if a_index >= 0 => compare(variant, a, b)?,
+                _ => return Err(DecodeError::new("union index out of bounds").into()),
+            }
+        },
+        Schema::Record { ref fields, .. } => {
+            for field in fields {
+                let ordering = match field.order {
+                    RecordFieldOrder::Ascending => compare(&field.schema, a, b)?,
+                    RecordFieldOrder::Descending => compare(&field.schema, a, b)?.reverse(),
+                    RecordFieldOrder::Ignore => {
+                        skip(&field.schema, a)?;
+                        skip(&field.schema, b)?;
+                        Ordering::Equal
+                    },
+                };
+                if ordering != Ordering::Equal {
+                    return Ok(ordering)
+                }
+            }
+            Ordering::Equal
+        },
+    })
+}
+
+// `NaN` sorts after any other float.
+fn total_cmp<F: FnOnce() -> Ordering>(a_nan: bool, b_nan: bool, cmp: F) -> Ordering {
+    match (a_nan, b_nan) {
+        (false, false) => cmp(),
+        (a_nan, b_nan) => a_nan.cmp(&b_nan),
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], Error> {
+    if input.len() < n {
+        return Err(DecodeError::new("unexpected end of datum").into())
+    }
+    let (taken, rest) = input.split_at(n);
+    *input = rest;
+    Ok(taken)
+}
+
+fn length(input: &mut &[u8]) -> Result<usize, Error> {
+    match zag_i64(input)? {
+        len if len < 0 => Err(DecodeError::new("negative length").into()),
+        len => Ok(len as usize),
+    }
+}
+
+// Read the item count of an array block, the block size following negative counts included.
+fn block_len(input: &mut &[u8]) -> Result<usize, Error> {
+    let len = zag_i64(input)?;
+    if len < 0 {
+        zag_i64(input)?;
+    }
+    Ok(len.unsigned_abs() as usize)
+}
+
+// A sorted input of `merge_sorted`, yielding its datums encoded with the output schema.
+enum Input<'a, R> {
+    // Written with the output schema: the datums are sliced out of the data blocks.
+    Raw {
+        reader: Reader<'a, R>,
+        block: Vec<u8>,
+        position: usize,
+        left: usize,
+    },
+    // Written with another schema: the datums are resolved and encoded anew.
+    Resolved(Reader<'a, R>),
+}
+
+impl<'a, R: Read> Input<'a, R> {
+    fn next_datum(&mut self, schema: &Schema) -> Result<Option<Vec<u8>>, Error> {
+        match *self {
+            Input::Raw {
+                ref mut reader,
+                ref mut block,
+                ref mut position,
+                ref mut left,
+            } => {
+                while *left == 0 {
+                    match reader.read_block_data()? {
+                        Some((count, data)) => {
+                            *block = data;
+                            *position = 0;
+                            *left = count;
+                        },
+                        None => return Ok(None),
+                    }
+                }
+                let mut rest = &block[*position..];
+                skip(schema, &mut rest)?;
+                let end = block.len() - rest.len();
+                let datum = block[*position..end].to_vec();
+                *position = end;
+                *left -= 1;
+                Ok(Some(datum))
+            },
+            Input::Resolved(ref mut reader) => match reader.next() {
+                Some(value) => to_avro_datum(schema, value?).map(Some),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+/// Merge object container files whose records are sorted by the sort order of `schema` (see
+/// [`compare_encoded`](fn.compare_encoded.html)) into one sorted container file written into
+/// `output`, returning the number of records written.
+///
+/// Records are compared in their binary encoding, and those of the inputs written with `schema`
+/// are copied without being decoded; the others are resolved against `schema` first. Records
+/// comparing equal are written in the order of the inputs. The output takes the codec and sync
+/// marker of the first input.
+pub fn merge_sorted<R: Read, W: Write>(
+    inputs: Vec<R>,
+    output: W,
+    schema: &Schema,
+) -> Result<u64, Error> {
+    let mut readers = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let reader = Reader::with_schema(schema, input)?;
+        readers.push(if reader.writer_schema() == schema {
+            Input::Raw {
+                reader,
+                block: Vec::new(),
+                position: 0,
+                left: 0,
+            }
+        } else {
+            Input::Resolved(reader)
+        });
+    }
+    let (codec, marker) = match readers.first() {
+        Some(Input::Raw { reader, .. }) | Some(Input::Resolved(reader)) => {
+            reader.codec_and_marker()
+        },
+        None => (Codec::Null, [0; 16]),
+    };
+
+    let mut heads = Vec::with_capacity(readers.len());
+    for reader in &mut readers {
+        heads.push(reader.next_datum(schema)?);
+    }
+    let mut writer = Writer::with_marker(schema, output, codec, marker)?;
+    let mut records = 0;
+    loop {
+        // The inputs are few: their heads are compared one by one.
+        let mut min: Option<usize> = None;
+        for (i, head) in heads.iter().enumerate() {
+            let head = match *head {
+                Some(ref head) => head,
+                None => continue,
+            };
+            if let Some(j) = min {
+                let min_head = heads[j].as_ref().unwrap();
+                if compare_encoded(schema, head, min_head)? != Ordering::Less {
+                    continue
+                }
+            }
+            min = Some(i);
+        }
+        let i = match min {
+            Some(i) => i,
+            None => break,
+        };
+        let datum = heads[i].take().unwrap();
+        writer.append_encoded(&datum)?;
+        records += 1;
+        heads[i] = readers[i].next_datum(schema)?;
+    }
+    writer.flush()?;
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Value;
+
+    fn cmp(schema: &Schema, a: Value, b: Value) -> Ordering {
+        let a = to_avro_datum(schema, a).unwrap();
+        let b = to_avro_datum(schema, b).unwrap();
+        compare_encoded(schema, &a, &b).unwrap()
+    }
+
+    #[test]
+    fn test_compare_encoded() {
+        use self::Ordering::*;
+        use types::Value::*;
+
+        assert_eq!(cmp(&Schema::Long, Long(-3), Long(2)), Less);
+        assert_eq!(cmp(&Schema::Long, Long(300), Long(2)), Greater);
+        assert_eq!(cmp(&Schema::Boolean, Boolean(false), Boolean(true)), Less);
+        assert_eq!(cmp(&Schema::Double, Double(-0.5), Double(0.25)), Less);
+        assert_eq!(cmp(&Schema::Double, Double(::std::f64::NAN), Double(1e300)), Greater);
+        assert_eq!(cmp(&Schema::Float, Float(1.5), Float(1.5)), Equal);
+        assert_eq!(
+            cmp(&Schema::String, String("ab".into()), String("b".into())),
+            Less
+        );
+        assert_eq!(cmp(&Schema::Bytes, Bytes(vec![1, 2]), Bytes(vec![1])), Greater);
+
+        let array = Schema::parse_str(r#"{"type": "array", "items": "int"}"#).unwrap();
+        assert_eq!(cmp(&array, Array(vec![Int(1)]), Array(vec![Int(1), Int(0)])), Less);
+        assert_eq!(cmp(&array, Array(vec![Int(2)]), Array(vec![Int(1), Int(0)])), Greater);
+        assert_eq!(cmp(&array, Array(vec![]), Array(vec![])), Equal);
+
+        let union = Schema::parse_str(r#"["null", "long"]"#).unwrap();
+        assert_eq!(cmp(&union, Union(Box::new(Long(-9))), Union(Box::new(Null))), Greater);
+
+        let record = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "a", "type": "string", "order": "ignore"},
+                {"name": "b", "type": "long", "order": "descending"},
+                {"name": "c", "type": {"type": "map", "values": "int"}, "order": "ignore"},
+                {"name": "d", "type": "int"}
+            ]}"#,
+        ).unwrap();
+        let r = |a: &str, b, d| {
+            Record(vec![
+                ("a".to_owned(), String(a.to_owned())),
+                ("b".to_owned(), Long(b)),
+                ("c".to_owned(), Map(Default::default())),
+                ("d".to_owned(), Int(d)),
+            ])
+        };
+        assert_eq!(cmp(&record, r("x", 1, 5), r("y", 1, 5)), Equal);
+        assert_eq!(cmp(&record, r("x", 2, 5), r("x", 1, 9)), Less);
+        assert_eq!(cmp(&record, r("x", 1, 5), r("x", 1, 9)), Less);
+
+        let map = Schema::parse_str(r#"{"type": "map", "values": "int"}"#).unwrap();
+        let empty = to_avro_datum(&map, Map(Default::default())).unwrap();
+        assert!(compare_encoded(&map, &empty, &empty).is_err());
+        assert!(compare_encoded(&Schema::String, &[4, b'a'], &[4, b'a']).is_err());
+    }
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn test_merge_sorted() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "key", "type": "long"},
+                {"name": "input", "type": "int", "order": "ignore"}
+            ]}"#,
+        ).unwrap();
+        // The same records, written with another schema.
+        let other = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "input", "type": "int"},
+                {"name": "key", "type": "long"},
+                {"name": "extra", "type": "string"}
+            ]}"#,
+        ).unwrap();
+        let write = |input: i32, keys: &[i64]| {
+            let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Deflate, [1; 16])
+                .unwrap();
+            for &key in keys {
+                writer
+                    .append(Value::Record(vec![
+                        ("key".to_owned(), Value::Long(key)),
+                        ("input".to_owned(), Value::Int(input)),
+                    ])).unwrap();
+                if key % 2 == 0 {
+                    writer.flush().unwrap();
+                }
+            }
+            writer.flush().unwrap();
+            writer.into_inner()
+        };
+        let mut resolved = Writer::with_marker(&other, Vec::new(), Codec::Null, [2; 16]).unwrap();
+        for &key in &[2i64, 3] {
+            resolved
+                .append(Value::Record(vec![
+                    ("input".to_owned(), Value::Int(2)),
+                    ("key".to_owned(), Value::Long(key)),
+                    ("extra".to_owned(), Value::String("x".to_owned())),
+                ])).unwrap();
+        }
+        resolved.flush().unwrap();
+
+        let inputs = vec![
+            write(0, &[1, 2, 4, 7, 8]),
+            write(1, &[0, 2, 3, 9]),
+            resolved.into_inner(),
+            write(3, &[]),
+        ];
+        let mut output = Vec::new();
+        let inputs = inputs.iter().map(|input| &input[..]).collect();
+        let records = merge_sorted(inputs, &mut output, &schema).unwrap();
+        assert_eq!(records, 11);
+
+        let merged = Reader::new(&output[..])
+            .unwrap()
+            .map(|value| match value.unwrap() {
+                Value::Record(fields) => match (&fields[0].1, &fields[1].1) {
+                    (&Value::Long(key), &Value::Int(input)) => (key, input),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            }).collect::<Vec<_>>();
+        assert_eq!(
+            merged,
+            vec![
+                (0, 1),
+                (1, 0),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (3, 1),
+                (3, 2),
+                (4, 0),
+                (7, 0),
+                (8, 0),
+                (9, 1),
+            ]
+        );
+        assert_eq!(&output[output.len() - 16..], &[1; 16]);
+    }
+}
@@ -0,0 +1,320 @@
+//! Logic for the statistics a `Writer` can store in the metadata of object container files, so
+//! that query planners can skip files without scanning them.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde_json::{self, Map, Value as JsonValue};
+
+use error::Error;
+use json::{from_avro_json_value, to_avro_json};
+use schema::Schema;
+use types::Value;
+use util::DecodeError;
+
+/// Key of the statistics in the metadata of object container files.
+pub const STATISTICS_KEY: &str = "avro_rs.statistics";
+
+// Bounds of strings and bytes longer than this are not kept, to keep the metadata small.
+const MAX_BOUND_LEN: usize = 64;
+
+/// Statistics of the records of an object container file, see
+/// [`Writer::with_statistics`](struct.Writer.html#method.with_statistics).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileStatistics {
+    /// Number of records.
+    pub records: u64,
+    /// Statistics of the fields of primitive types (or unions of `null` and a primitive type) of
+    /// the records, by name.
+    pub fields: HashMap<String, FieldStatistics>,
+}
+
+/// Statistics of a field over the records of an object container file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldStatistics {
+    /// Smallest value of the field, if any (and short enough to be kept).
+    pub min: Option<Value>,
+    /// Largest value of the field, if any (and short enough to be kept).
+    pub max: Option<Value>,
+    /// Number of records where the field is `null`.
+    pub nulls: u64,
+}
+
+// A field the statistics are tracked of, with the schema of its non-null values.
+struct TrackedField {
+    name: String,
+    schema: Schema,
+}
+
+// Statistics of the records appended to a `Writer`.
+pub(crate) struct Tracker {
+    fields: Vec<TrackedField>,
+    statistics: FileStatistics,
+}
+
+impl Tracker {
+    pub(crate) fn new(schema: &Schema) -> Tracker {
+        let fields = match *schema {
+            Schema::Record { ref fields, .. } => fields
+                .iter()
+                .filter_map(|field| {
+                    tracked_schema(&field.schema).map(|schema| TrackedField {
+                        name: field.name.clone(),
+                        schema: schema.clone(),
+                    })
+                }).collect(),
+            _ => Vec::new(),
+        };
+        let statistics = FileStatistics {
+            records: 0,
+            fields: fields
+                .iter()
+                .map(|field| (field.name.clone(), FieldStatistics::default()))
+                .collect(),
+        };
+        Tracker { fields, statistics }
+    }
+
+    pub(crate) fn statistics(&self) -> &FileStatistics {
+        &self.statistics
+    }
+
+    // Account for `record`, already validated against the schema.
+    pub(crate) fn update(&mut self, record: &Value) {
+        self.statistics.records += 1;
+        let record = match *record {
+            Value::Record(ref record) => record,
+            _ => return,
+        };
+        for (name, value) in record {
+            let field = match self.statistics.fields.get_mut(name) {
+                Some(field) => field,
+                None => continue,
+            };
+            let value = match *value {
+                Value::Union(ref value) | Value::UnionBranch(_, ref value) => value,
+                ref value => value,
+            };
+            match *value {
+                Value::Null => field.nulls += 1,
+                Value::Float(f) if f.is_nan() || f.is_infinite() => {},
+                Value::Double(d) if d.is_nan() || d.is_infinite() => {},
+                ref value => {
+                    let is_min = |min: &Value| compare(value, min) == Ordering::Less;
+                    if field.min.as_ref().is_none_or(is_min) {
+                        field.min = Some(value.clone());
+                    }
+                    let is_max = |max: &Value| compare(value, max) == Ordering::Greater;
+                    if field.max.as_ref().is_none_or(is_max) {
+                        field.max = Some(value.clone());
+                    }
+                },
+            }
+        }
+    }
+
+    // An upper bound of the size of the statistics as JSON, for them to be written over the space
+    // reserved in the header once known.
+    pub(crate) fn reserved_len(&self) -> usize {
+        // Escaped bounds take up to 6 bytes per byte, numbers less than 32 bytes, and counts 20.
+        let bound = 6 * MAX_BOUND_LEN + 2;
+        let per_field = 2 * bound + 64;
+        64 + self
+            .fields
+            .iter()
+            .map(|field| field.name.len() + per_field)
+            .sum::<usize>()
+    }
+
+    // The statistics as JSON, padded with spaces to `reserved_len`.
+    pub(crate) fn to_json(&self) -> Result<Vec<u8>, Error> {
+        let mut fields = Map::new();
+        for field in &self.fields {
+            let statistics = &self.statistics.fields[&field.name];
+            let mut json = Map::new();
+            for &(key, bound) in &[("min", &statistics.min), ("max", &statistics.max)] {
+                if let Some(bound) = bound.as_ref().filter(|b| bound_len(b) <= MAX_BOUND_LEN) {
+                    let bound = to_avro_json(&field.schema, bound.clone())?;
+                    json.insert(key.to_owned(), serde_json::from_str(&bound)?);
+                }
+            }
+            json.insert("nulls".to_owned(), statistics.nulls.into());
+            fields.insert(field.name.clone(), JsonValue::Object(json));
+        }
+        let mut json = Map::new();
+        json.insert("records".to_owned(), self.statistics.records.into());
+        json.insert("fields".to_owned(), JsonValue::Object(fields));
+
+        let mut bytes = serde_json::to_vec(&json)?;
+        let reserved = self.reserved_len();
+        if bytes.len() > reserved {
+            return Err(Error::custom("statistics larger than the space reserved for them"))
+        }
+        bytes.resize(reserved, b' ');
+        Ok(bytes)
+    }
+}
+
+// Parse the statistics of an object container file from the value of its `STATISTICS_KEY`
+// metadata, given its schema.
+pub(crate) fn parse_statistics(schema: &Schema, bytes: &[u8]) -> Result<FileStatistics, Error> {
+    let invalid = || Error::from(DecodeError::new("invalid statistics metadata"));
+    let json: JsonValue = serde_json::from_slice(bytes)?;
+    let records = json["records"].as_u64().ok_or_else(invalid)?;
+    let tracker = Tracker::new(schema);
+    let mut fields = HashMap::new();
+    if let Some(json_fields) = json["fields"].as_object() {
+        for field in &tracker.fields {
+            let json = match json_fields.get(&field.name) {
+                Some(json) => json,
+                None => continue,
+            };
+            let bound = |key: &str| match json.get(key) {
+                Some(bound) => from_avro_json_value(&field.schema, bound).map(Some),
+                None => Ok(None),
+            };
+            let statistics = FieldStatistics {
+                min: bound("min")?,
+                max: bound("max")?,
+                nulls: json["nulls"].as_u64().ok_or_else(invalid)?,
+            };
+            fields.insert(field.name.clone(), statistics);
+        }
+    }
+    Ok(FileStatistics { records, fields })
+}
+
+// The schema of the values of a field statistics are tracked of, if any.
+fn tracked_schema(schema: &Schema) -> Option<&Schema> {
+    match *schema {
+        Schema::Boolean
+        | Schema::Int
+        | Schema::Long
+        | Schema::Float
+        | Schema::Double
+        | Schema::Bytes
+        | Schema::String => Some(schema),
+        Schema::Union(ref union) => match union.variants() {
+            // Unions cannot directly contain other unions.
+            [Schema::Null, variant] | [variant, Schema::Null] => tracked_schema(variant),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn bound_len(value: &Value) -> usize {
+    match *value {
+        Value::String(ref s) => s.len(),
+        Value::Bytes(ref bytes) => bytes.len(),
+        _ => 0,
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Int(a), Value::Int(b)) => a.cmp(b),
+        (Value::Long(a), Value::Long(b)) => a.cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+        (Value::Double(a), Value::Double(b)) => a.total_cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(all(test, feature = "rand"))]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use reader::Reader;
+    use writer::Writer;
+    use Codec;
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn test_statistics() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "b", "type": ["null", "string"]},
+                {"name": "c", "type": "double"},
+                {"name": "d", "type": {"type": "array", "items": "int"}}
+            ]}"#,
+        ).unwrap();
+        let mut writer = Writer::with_statistics(&schema, Cursor::new(Vec::new()), Codec::Deflate)
+            .unwrap();
+        let rows = vec![
+            (5, Some("foo"), 1.5),
+            (-2, None, ::std::f64::NAN),
+            (9, Some("bar"), -0.5),
+            (0, None, 7.0),
+        ];
+        for &(a, b, c) in &rows {
+            let b = b.map(|b| Value::String(b.to_owned()));
+            writer
+                .append(Value::Record(vec![
+                    ("a".to_owned(), Value::Long(a)),
+                    ("b".to_owned(), Value::Union(Box::new(b.unwrap_or(Value::Null)))),
+                    ("c".to_owned(), Value::Double(c)),
+                    ("d".to_owned(), Value::Array(vec![])),
+                ])).unwrap();
+            // A block per record.
+            writer.flush().unwrap();
+        }
+        assert_eq!(writer.statistics().unwrap().records, 4);
+        let encoded = writer.close().unwrap().into_inner();
+
+        let reader = Reader::new(&encoded[..]).unwrap();
+        let statistics = reader.statistics().unwrap().unwrap();
+        assert_eq!(statistics.records, 4);
+        assert_eq!(statistics.fields.len(), 3);
+        assert_eq!(
+            statistics.fields["a"],
+            FieldStatistics {
+                min: Some(Value::Long(-2)),
+                max: Some(Value::Long(9)),
+                nulls: 0,
+            }
+        );
+        assert_eq!(
+            statistics.fields["b"],
+            FieldStatistics {
+                min: Some(Value::String("bar".to_owned())),
+                max: Some(Value::String("foo".to_owned())),
+                nulls: 2,
+            }
+        );
+        assert_eq!(statistics.fields["c"].min, Some(Value::Double(-0.5)));
+        assert_eq!(statistics.fields["c"].max, Some(Value::Double(7.0)));
+        assert_eq!(reader.count(), 4);
+    }
+
+    #[test]
+    fn test_statistics_long_bounds() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "r", "fields": [{"name": "s", "type": "string"}]}"#,
+        ).unwrap();
+        let mut writer = Writer::with_statistics(&schema, Cursor::new(Vec::new()), Codec::Null)
+            .unwrap();
+        for s in &["\u{1}".repeat(64), "\u{2}".repeat(65)] {
+            writer
+                .append(Value::Record(vec![("s".to_owned(), Value::String(s.clone()))]))
+                .unwrap();
+        }
+        let encoded = writer.close().unwrap().into_inner();
+        let reader = Reader::new(&encoded[..]).unwrap();
+        let statistics = reader.statistics().unwrap().unwrap();
+        assert_eq!(
+            statistics.fields["s"].min,
+            Some(Value::String("\u{1}".repeat(64)))
+        );
+        assert_eq!(statistics.fields["s"].max, None);
+        assert_eq!(reader.count(), 2);
+
+        let writer = Writer::new(&schema, Vec::new()).unwrap();
+        assert!(writer.statistics().is_none());
+        let encoded = writer.into_inner();
+        assert!(Reader::new(&encoded[..]).unwrap().statistics().unwrap().is_none());
+    }
+}
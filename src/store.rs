@@ -0,0 +1,99 @@
+//! Logic for looking up writer schemas when decoding framed datums.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use schema::Schema;
+
+/// Lookup of writer schemas, as needed to decode single objects (by Rabin fingerprint) and
+/// Confluent-framed messages (by schema registry id).
+///
+/// Implement this trait to back the lookup with a schema registry, a database or an embedded map.
+/// Both methods return `None` by default, so that a store only needs to implement the lookup it
+/// supports.
+pub trait SchemaStore {
+    /// Return the schema whose Rabin fingerprint is `fingerprint`, if known.
+    fn get_by_fingerprint(&self, fingerprint: u64) -> Option<Arc<Schema>> {
+        let _ = fingerprint;
+        None
+    }
+
+    /// Return the schema registered with the given `id`, if known.
+    fn get_by_id(&self, id: u32) -> Option<Arc<Schema>> {
+        let _ = id;
+        None
+    }
+}
+
+impl<S: SchemaStore + ?Sized> SchemaStore for &S {
+    fn get_by_fingerprint(&self, fingerprint: u64) -> Option<Arc<Schema>> {
+        (**self).get_by_fingerprint(fingerprint)
+    }
+
+    fn get_by_id(&self, id: u32) -> Option<Arc<Schema>> {
+        (**self).get_by_id(id)
+    }
+}
+
+/// A `SchemaStore` keeping its schemas in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySchemaStore {
+    by_fingerprint: HashMap<u64, Arc<Schema>>,
+    by_id: HashMap<u32, Arc<Schema>>,
+}
+
+impl MemorySchemaStore {
+    /// Create an empty `MemorySchemaStore`.
+    pub fn new() -> MemorySchemaStore {
+        MemorySchemaStore::default()
+    }
+
+    /// Add a schema to the store, making it available by fingerprint.
+    pub fn insert(&mut self, schema: Schema) -> Arc<Schema> {
+        let fingerprint = schema.rabin_fingerprint();
+        self.by_fingerprint
+            .entry(fingerprint)
+            .or_insert_with(|| Arc::new(schema))
+            .clone()
+    }
+
+    /// Add a schema to the store, making it available both by fingerprint and by `id`.
+    pub fn insert_with_id(&mut self, id: u32, schema: Schema) -> Arc<Schema> {
+        let schema = self.insert(schema);
+        self.by_id.insert(id, schema.clone());
+        schema
+    }
+}
+
+impl SchemaStore for MemorySchemaStore {
+    fn get_by_fingerprint(&self, fingerprint: u64) -> Option<Arc<Schema>> {
+        self.by_fingerprint.get(&fingerprint).cloned()
+    }
+
+    fn get_by_id(&self, id: u32) -> Option<Arc<Schema>> {
+        self.by_id.get(&id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_schema_store() {
+        let mut store = MemorySchemaStore::new();
+        store.insert(Schema::Int);
+        store.insert_with_id(42, Schema::String);
+
+        assert_eq!(
+            store.get_by_fingerprint(Schema::Int.rabin_fingerprint()),
+            Some(Arc::new(Schema::Int))
+        );
+        assert_eq!(
+            store.get_by_fingerprint(Schema::String.rabin_fingerprint()),
+            Some(Arc::new(Schema::String))
+        );
+        assert_eq!(store.get_by_id(42), Some(Arc::new(Schema::String)));
+        assert_eq!(store.get_by_id(1), None);
+        assert_eq!(store.get_by_fingerprint(0), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! A minimal HTTP server for the tests of the HTTP clients (schema registry and RPC).
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+/// An HTTP request received by `serve`.
+pub(crate) struct Request {
+    /// The request line, e.g. `GET /schemas/ids/1 HTTP/1.1`.
+    pub(crate) line: String,
+    /// The headers, as sent.
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the header `name`, whatever its case.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|header| header.0.eq_ignore_ascii_case(name))
+            .map(|header| &header.1[..])
+    }
+}
+
+/// An HTTP response sent by `serve`.
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) reason: &'static str,
+    pub(crate) content_type: Option<&'static str>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    /// A response with `status`, a reason of `OK` and no content type.
+    pub(crate) fn new<B: Into<Vec<u8>>>(status: u16, body: B) -> Response {
+        Response {
+            status,
+            reason: "OK",
+            content_type: None,
+            body: body.into(),
+        }
+    }
+}
+
+/// Serve `requests` HTTP requests on a local port, one connection each, with the responses of
+/// `respond`, returning the base URL of the server (without trailing `/`) and a handle to the
+/// requests received.
+pub(crate) fn serve<F>(requests: usize, mut respond: F) -> (String, JoinHandle<Vec<Request>>)
+where
+    F: FnMut(&Request) -> Response + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut received = Vec::new();
+        for _ in 0..requests {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                match header.find(':') {
+                    Some(colon) => headers.push((
+                        header[..colon].to_owned(),
+                        header[colon + 1..].trim().to_owned(),
+                    )),
+                    None => break,
+                }
+            }
+            let mut request = Request {
+                line: line.trim().to_owned(),
+                headers,
+                body: Vec::new(),
+            };
+            let length = request.header("content-length").map_or(0, |n| n.parse().unwrap());
+            request.body = vec![0; length];
+            reader.read_exact(&mut request.body).unwrap();
+
+            let response = respond(&request);
+            write!(stream, "HTTP/1.1 {} {}\r\n", response.status, response.reason).unwrap();
+            if let Some(content_type) = response.content_type {
+                write!(stream, "Content-Type: {}\r\n", content_type).unwrap();
+            }
+            write!(
+                stream,
+                "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                response.body.len()
+            ).unwrap();
+            stream.write_all(&response.body).unwrap();
+            received.push(request);
+        }
+        received
+    });
+    (url, handle)
+}
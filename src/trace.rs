@@ -0,0 +1,184 @@
+//! Instrumentation with [`tracing`](https://docs.rs/tracing) spans and events, behind the
+//! `tracing` feature: the macros expand to nothing without it.
+
+/// Enter a `DEBUG` span until the end of the enclosing block.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($($arg:tt)+) => {
+        let _span = ::tracing::debug_span!($($arg)+).entered();
+    };
+}
+
+// Only used beyond the encode/decode core.
+#[cfg(not(feature = "tracing"))]
+#[cfg_attr(not(feature = "std"), allow(unused_macros))]
+macro_rules! trace_span {
+    ($($arg:tt)+) => {
+        trace_fields!($($arg)+);
+    };
+}
+
+/// Emit an event, e.g. `trace_event!(DEBUG, count = n, "message")`.
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        ::tracing::event!(::tracing::Level::$level, $($arg)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        trace_fields!($($arg)+)
+    };
+}
+
+// Borrow the values of the fields without the `tracing` feature, so that variables only used by
+// the instrumentation do not go unused.
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_fields {
+    () => {
+        ()
+    };
+    ($message:literal) => {
+        ()
+    };
+    ($message:literal, $($rest:tt)*) => {
+        trace_fields!($($rest)*)
+    };
+    ($name:ident = ?$value:expr) => {
+        trace_fields!($name = $value)
+    };
+    ($name:ident = ?$value:expr, $($rest:tt)*) => {
+        trace_fields!($name = $value, $($rest)*)
+    };
+    ($name:ident = %$value:expr) => {
+        trace_fields!($name = $value)
+    };
+    ($name:ident = %$value:expr, $($rest:tt)*) => {
+        trace_fields!($name = $value, $($rest)*)
+    };
+    ($name:ident = $value:expr) => {{
+        let _ = &$value;
+    }};
+    ($name:ident = $value:expr, $($rest:tt)*) => {{
+        let _ = &$value;
+        trace_fields!($($rest)*)
+    }};
+    ($name:ident) => {{
+        let _ = &$name;
+    }};
+    ($name:ident, $($rest:tt)*) => {{
+        let _ = &$name;
+        trace_fields!($($rest)*)
+    }};
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::subscriber::with_default;
+    use tracing::{Event, Metadata, Subscriber};
+
+    use schema::Schema;
+    use types::{Record as AvroRecord, Value};
+    use {Codec, Reader, Writer};
+
+    // Records the names of the spans, and the messages of the events.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    struct Message(String);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name().to_owned());
+            Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0.lock().unwrap().push(message.0);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_tracing() {
+        let writer_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "a", "type": "long"}]}"#,
+        ).unwrap();
+        let reader_schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": ["null", "long"]},
+                    {"name": "b", "type": "string", "default": "foo"}
+                ]
+            }
+            "#,
+        ).unwrap();
+
+        let recorder = Recorder::default();
+        with_default(recorder.clone(), || {
+            let mut writer =
+                Writer::with_codec(&writer_schema, Vec::new(), Codec::Deflate).unwrap();
+            let mut record = AvroRecord::new(&writer_schema).unwrap();
+            record.put("a", 27i64);
+            writer.append(record).unwrap();
+            writer.flush().unwrap();
+
+            let input = writer.into_inner();
+            let values = Reader::with_schema(&reader_schema, &input[..])
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(
+                values,
+                vec![Value::Record(vec![
+                    ("a".to_owned(), Value::Long(27)),
+                    ("b".to_owned(), Value::String("foo".to_owned())),
+                ])]
+            );
+        });
+
+        let names = recorder.0.lock().unwrap();
+        for name in &[
+            "write_block",
+            "compressed block",
+            "read_block",
+            "decompressed block",
+            "chose union branch",
+            "applied field default",
+        ] {
+            assert!(names.iter().any(|n| n == name), "missing {}", name);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Logic converting object container files to another codec block by block, without decoding
+//! their records.
+use std::io::{Read, Write};
+
+use error::Error;
+use reader::Reader;
+use writer::Writer;
+use Codec;
+
+/// Read the object container file of `input` and write it to `output` compressed with `codec`,
+/// returning the number of records written.
+///
+/// Every data block is decompressed and compressed anew as a whole, with the same record count,
+/// so that the records themselves are never decoded nor validated and the conversion is bound by
+/// I/O and the codecs rather than by decoding. The schema and sync marker are kept, but the user
+/// metadata of the header is not.
+pub fn transcode<R: Read, W: Write>(input: R, output: W, codec: Codec) -> Result<u64, Error> {
+    let mut reader = Reader::new(input)?;
+    let (_, marker) = reader.codec_and_marker();
+    let schema = reader.writer_schema().clone();
+    let mut writer = Writer::with_marker(&schema, output, codec, marker)?;
+    let mut records = 0;
+    while let Some((count, data)) = reader.read_block_data()? {
+        writer.append_block(count, &data)?;
+        records += count as u64;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schema::Schema;
+    use types::Value;
+
+    fn write(codec: Codec) -> Vec<u8> {
+        let schema = Schema::parse_str(r#"{"type": "array", "items": "string"}"#).unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), codec, [7u8; 16]).unwrap();
+        for i in 0..3 {
+            writer.append(Value::Array(vec![Value::String(i.to_string()); 3])).unwrap();
+            writer.append(Value::Array(vec![])).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn test_transcode() {
+        let encoded = write(Codec::Null);
+        for &codec in &[Codec::Null, #[cfg(feature = "deflate_codec")] Codec::Deflate] {
+            let mut transcoded = Vec::new();
+            assert_eq!(transcode(&encoded[..], &mut transcoded, codec).unwrap(), 6);
+            // The same blocks, with the same marker.
+            assert_eq!(transcoded, write(codec));
+
+            let mut back = Vec::new();
+            assert_eq!(transcode(&transcoded[..], &mut back, Codec::Null).unwrap(), 6);
+            assert_eq!(back, encoded);
+        }
+    }
+
+    #[test]
+    fn test_transcode_errors() {
+        let encoded = write(Codec::Null);
+        assert!(transcode(&encoded[..10], Vec::new(), Codec::Null).is_err());
+        assert!(transcode(&encoded[..encoded.len() - 3], Vec::new(), Codec::Null).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Logic applying user transformations to the values of record fields as they are read, e.g. to
+//! scrub personal data before it reaches the caller.
+use error::Error;
+use schema::Schema;
+use types::Value;
+
+// A transformation of the values of the field at `path`.
+struct FieldTransform {
+    path: Vec<String>,
+    transform: Box<dyn FnMut(&mut Value) + Send>,
+}
+
+// The field transformations registered on a `Reader`, in order.
+#[derive(Default)]
+pub(crate) struct Transforms(Vec<FieldTransform>);
+
+impl Transforms {
+    // Register `transform` for the values of the field at the dotted `path` of `schema`.
+    pub(crate) fn add(
+        &mut self,
+        schema: &Schema,
+        path: &str,
+        transform: Box<dyn FnMut(&mut Value) + Send>,
+    ) -> Result<(), Error> {
+        let path = path.split('.').map(str::to_owned).collect::<Vec<_>>();
+        if !has_field(schema, &path) {
+            return Err(Error::custom(format!("no field at path {}", path.join("."))))
+        }
+        self.0.push(FieldTransform { path, transform });
+        Ok(())
+    }
+
+    // Transform the fields of `value` in place.
+    pub(crate) fn apply(&mut self, value: &mut Value) {
+        for field in &mut self.0 {
+            apply(&field.path, &mut *field.transform, value);
+        }
+    }
+}
+
+// Whether `path` leads to a field from `schema`, going through unions, arrays and maps.
+fn has_field(schema: &Schema, path: &[String]) -> bool {
+    let (name, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    match *schema {
+        Schema::Record {
+            ref fields,
+            ref lookup,
+            ..
+        } => lookup
+            .get(name)
+            .is_some_and(|&i| has_field(&fields[i].schema, rest)),
+        Schema::Union(ref union) => union.variants().iter().any(|variant| has_field(variant, path)),
+        Schema::Array(ref items) | Schema::Map(ref items) => has_field(items, path),
+        _ => false,
+    }
+}
+
+fn apply(path: &[String], transform: &mut dyn FnMut(&mut Value), value: &mut Value) {
+    let (name, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return transform(value),
+    };
+    match *value {
+        Value::Record(ref mut fields) => {
+            for field in fields.iter_mut().filter(|field| field.0 == *name) {
+                apply(rest, transform, &mut field.1);
+            }
+        },
+        Value::Union(ref mut value) | Value::UnionBranch(_, ref mut value) => {
+            apply(path, transform, value)
+        },
+        Value::Array(ref mut items) => {
+            for item in items {
+                apply(path, transform, item);
+            }
+        },
+        Value::Map(ref mut items) => {
+            for item in items.values_mut() {
+                apply(path, transform, item);
+            }
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use reader::Reader;
+    use writer::Writer;
+    use Codec;
+
+    fn write() -> (Schema, Vec<u8>) {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "event", "fields": [
+                {"name": "user", "type": ["null", {"type": "record", "name": "user", "fields": [
+                    {"name": "email", "type": "string"},
+                    {"name": "age", "type": "int"}
+                ]}]},
+                {"name": "tags", "type": {"type": "array", "items": "string"}}
+            ]}"#,
+        ).unwrap();
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        for (i, email) in ["a@example.com", "b@example.com"].iter().enumerate() {
+            let user = Value::Record(vec![
+                ("email".to_owned(), Value::String((*email).to_owned())),
+                ("age".to_owned(), Value::Int(i as i32)),
+            ]);
+            writer
+                .append(Value::Record(vec![
+                    ("user".to_owned(), Value::Union(Box::new(user))),
+                    ("tags".to_owned(), Value::Array(vec![Value::String("x".to_owned())])),
+                ])).unwrap();
+            // A block per record.
+            writer.flush().unwrap();
+        }
+        writer
+            .append(Value::Record(vec![
+                ("user".to_owned(), Value::Union(Box::new(Value::Null))),
+                ("tags".to_owned(), Value::Array(vec![])),
+            ])).unwrap();
+        writer.flush().unwrap();
+        let encoded = writer.into_inner();
+        (schema, encoded)
+    }
+
+    fn scrub(reader: &mut Reader<&[u8]>) {
+        reader
+            .transform_field("user.email", |email| *email = Value::String("***".to_owned()))
+            .unwrap();
+        reader
+            .transform_field("tags", |tags| {
+                if let Value::Array(ref mut tags) = *tags {
+                    tags.clear();
+                }
+            }).unwrap();
+    }
+
+    fn emails(values: &[Value]) -> Vec<Option<Value>> {
+        values
+            .iter()
+            .map(|value| match *value {
+                Value::Record(ref fields) => match fields[0].1 {
+                    Value::Union(ref user) => match **user {
+                        Value::Record(ref fields) => Some(fields[0].1.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            }).collect()
+    }
+
+    #[test]
+    fn test_transform_field() {
+        let (_, encoded) = write();
+        let scrubbed = Some(Value::String("***".to_owned()));
+
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        scrub(&mut reader);
+        let values = reader.map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(emails(&values), vec![scrubbed.clone(), scrubbed.clone(), None]);
+        for value in &values {
+            if let Value::Record(ref fields) = *value {
+                assert_eq!(fields[1].1, Value::Array(vec![]));
+            }
+        }
+
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        scrub(&mut reader);
+        let mut value = Value::Null;
+        assert!(reader.read_into(&mut value).unwrap());
+        assert_eq!(emails(&[value]), vec![scrubbed.clone()]);
+
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        scrub(&mut reader);
+        assert!(reader.next().is_some());
+        let values = reader.into_parallel(2).map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(emails(&values), vec![scrubbed, None]);
+    }
+
+    #[test]
+    fn test_transform_field_order_and_paths() {
+        let (schema, encoded) = write();
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        let ages = Arc::new(Mutex::new(Vec::new()));
+        let seen = ages.clone();
+        reader
+            .transform_field("user.age", move |age| seen.lock().unwrap().push(age.clone()))
+            .unwrap();
+        reader.transform_field("user.age", |age| *age = Value::Int(-1)).unwrap();
+        assert_eq!(reader.count(), 3);
+        assert_eq!(*ages.lock().unwrap(), vec![Value::Int(0), Value::Int(1)]);
+
+        let mut reader = Reader::new(&encoded[..]).unwrap();
+        assert!(reader.transform_field("user.name", |_| {}).is_err());
+        assert!(reader.transform_field("email", |_| {}).is_err());
+        assert!(reader.transform_field("tags.x", |_| {}).is_err());
+        let mut transforms = Transforms::default();
+        assert!(transforms.add(&schema, "user", Box::new(|_| {})).is_ok());
+    }
+}
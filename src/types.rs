@@ -1,15 +1,23 @@
 //! Logic handling the intermediate representation of Avro values.
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::hash::BuildHasher;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::sync::Arc;
 
-use failure::Error;
+use std::io::Write;
+
+use encode::encode_to;
+use error::Error;
+use logical::to_underlying_ref;
 use serde_json::Value as JsonValue;
 
-use schema::{RecordField, Schema, SchemaKind, UnionSchema};
+use schema::{RecordField, RecordFieldOrder, Schema, SchemaKind, UnionSchema};
 
 /// Describes errors happened while performing schema resolution on Avro data.
-#[derive(Fail, Debug)]
-#[fail(display = "Decoding error: {}", _0)]
+#[derive(Error, Debug)]
+#[error("Decoding error: {0}")]
 pub struct SchemaResolutionError(String);
 
 impl SchemaResolutionError {
@@ -21,6 +29,12 @@ impl SchemaResolutionError {
     }
 }
 
+impl From<SchemaResolutionError> for Error {
+    fn from(error: SchemaResolutionError) -> Error {
+        Error::SchemaResolution { reason: error.0 }
+    }
+}
+
 /// Represents any valid Avro value
 /// More information about Avro values can be found in the
 /// [Avro Specification](https://avro.apache.org/docs/current/spec.html#schemas)
@@ -51,13 +65,26 @@ pub enum Value {
     /// of its corresponding schema.
     /// This allows schema-less encoding, as well as schema resolution while
     /// reading values.
-    Enum(i32, String),
+    ///
+    /// Symbols are shared between the values read by the same reader.
+    Enum(i32, Arc<str>),
     /// An `union` Avro value.
+    ///
+    /// Its branch is the one of the same kind as the value, see
+    /// [`UnionSchema::find_schema`](../schema/struct.UnionSchema.html#method.find_schema).
     Union(Box<Value>),
+    /// An `union` Avro value of the branch at the given index (counting from 0), for the unions
+    /// where the branch of the same kind is not the intended one, e.g. a `Long` of
+    /// `["int", "long"]` to encode as an `int`.
+    ///
+    /// Values are always decoded as `Union`s.
+    UnionBranch(usize, Box<Value>),
     /// An `array` Avro value.
     Array(Vec<Value>),
     /// A `map` Avro value.
-    Map(HashMap<String, Value>),
+    ///
+    /// Keys are shared between the values read by the same reader.
+    Map(HashMap<Arc<str>, Value>),
     /// A `record` Avro value.
     ///
     /// A Record is represented by a vector of (`<record name>`, `value`).
@@ -121,7 +148,11 @@ where
 {
     fn avro(self) -> Value {
         let v = match self {
-            Some(v) => T::avro(v),
+            // The branch is already explicit.
+            Some(v) => match T::avro(v) {
+                branch @ Value::UnionBranch(..) => return branch,
+                v => v,
+            },
             None => Value::Null,
         };
         Value::Union(Box::new(v))
@@ -135,7 +166,7 @@ where
     fn avro(self) -> Value {
         Value::Map(
             self.into_iter()
-                .map(|(key, value)| (key, value.avro()))
+                .map(|(key, value)| (key.into(), value.avro()))
                 .collect::<_>(),
         )
     }
@@ -148,7 +179,33 @@ where
     fn avro(self) -> Value {
         Value::Map(
             self.into_iter()
-                .map(|(key, value)| (key.to_owned(), value.avro()))
+                .map(|(key, value)| (key.into(), value.avro()))
+                .collect::<_>(),
+        )
+    }
+}
+
+impl<T> ToAvro for BTreeMap<String, T>
+where
+    T: ToAvro,
+{
+    fn avro(self) -> Value {
+        Value::Map(
+            self.into_iter()
+                .map(|(key, value)| (key.into(), value.avro()))
+                .collect::<_>(),
+        )
+    }
+}
+
+impl<T> ToAvro for BTreeMap<&str, T>
+where
+    T: ToAvro,
+{
+    fn avro(self) -> Value {
+        Value::Map(
+            self.into_iter()
+                .map(|(key, value)| (key.into(), value.avro()))
                 .collect::<_>(),
         )
     }
@@ -241,7 +298,7 @@ impl ToAvro for JsonValue {
             JsonValue::Object(items) => Value::Map(
                 items
                     .into_iter()
-                    .map(|(key, value)| (key, value.avro()))
+                    .map(|(key, value)| (key.into(), value.avro()))
                     .collect::<_>(),
             ),
         }
@@ -255,9 +312,14 @@ impl Value {
     /// for the full set of rules of schema validation.
     pub fn validate(&self, schema: &Schema) -> bool {
         match (self, schema) {
+            (value, Schema::Logical(logical)) => {
+                value.validate(logical.schema()) && logical.logical_type().validate(value).is_ok()
+            },
             (&Value::Null, &Schema::Null) => true,
             (&Value::Boolean(_), &Schema::Boolean) => true,
             (&Value::Int(_), &Schema::Int) => true,
+            // Longs within the range of ints are encoded the same way.
+            (&Value::Long(n), &Schema::Int) => n == i64::from(n as i32),
             (&Value::Long(_), &Schema::Long) => true,
             (&Value::Float(_), &Schema::Float) => true,
             (&Value::Double(_), &Schema::Double) => true,
@@ -267,12 +329,16 @@ impl Value {
             (&Value::String(ref s), &Schema::Enum { ref symbols, .. }) => symbols.contains(s),
             (&Value::Enum(i, ref s), &Schema::Enum { ref symbols, .. }) => symbols
                 .get(i as usize)
-                .map(|ref symbol| symbol == &s)
+                .map(|symbol| **symbol == **s)
                 .unwrap_or(false),
             // (&Value::Union(None), &Schema::Union(_)) => true,
             (&Value::Union(ref value), &Schema::Union(ref inner)) => {
                 inner.find_schema(value).is_some()
             },
+            (Value::UnionBranch(index, value), Schema::Union(inner)) => inner
+                .variants()
+                .get(*index)
+                .is_some_and(|variant| value.validate(variant)),
             (&Value::Array(ref items), &Schema::Array(ref inner)) => {
                 items.iter().all(|item| item.validate(inner))
             },
@@ -290,6 +356,149 @@ impl Value {
         }
     }
 
+    /// Encode the value against the given [Schema](../schema/enum.Schema.html) into `writer`,
+    /// also performing schema validation.
+    ///
+    /// Unlike [`to_avro_datum`](../fn.to_avro_datum.html), the encoded value is not
+    /// materialized in memory first: large bytes and strings are written to `writer` as is.
+    pub fn encode_to<W: Write>(&self, schema: &Schema, writer: &mut W) -> Result<(), Error> {
+        let value = to_underlying_ref(self, schema)?;
+        if !value.validate(schema) {
+            return Err(value.mismatch(schema))
+        }
+        encode_to(&value, schema, writer)?;
+        Ok(())
+    }
+
+    /// Render the value as JSON-like text, see the `Display` implementation.
+    pub fn to_json_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// Describe where the value does not match the given [Schema](../schema/enum.Schema.html),
+    /// as an `Error::SchemaMismatch`.
+    ///
+    /// Meant to be called once `validate` returned `false`: the error is built while walking the
+    /// value again, tracking the path of the first mismatching value.
+    pub(crate) fn mismatch(&self, schema: &Schema) -> Error {
+        self.mismatch_at("$", schema).unwrap_or_else(|| Error::SchemaMismatch {
+            path: "$".to_owned(),
+            expected: format!("{:?}", SchemaKind::from(schema)),
+            found: format!("{:?}", SchemaKind::from(self)),
+        })
+    }
+
+    fn mismatch_at(&self, path: &str, schema: &Schema) -> Option<Error> {
+        let mismatch = |expected: String, found: String| {
+            Some(Error::SchemaMismatch {
+                path: path.to_owned(),
+                expected,
+                found,
+            })
+        };
+        match (self, schema) {
+            (_, Schema::Logical(logical)) => match logical.logical_type().validate(self) {
+                _ if !self.validate(logical.schema()) => self.mismatch_at(path, logical.schema()),
+                Err(reason) => Some(Error::LogicalType {
+                    name: logical.name().to_owned(),
+                    reason: format!("{} at {}", reason, path),
+                }),
+                Ok(()) => None,
+            },
+            (Value::Long(n), Schema::Int) if !self.validate(schema) => {
+                mismatch("Int".to_owned(), format!("Long {} out of the int range", n))
+            },
+            (Value::Fixed(n, _), Schema::Fixed { size, .. }) if n != size => mismatch(
+                format!("Fixed of size {}", size),
+                format!("Fixed of size {}", n),
+            ),
+            (Value::String(s), Schema::Enum { symbols, .. })
+                if !self.validate(schema) =>
+            {
+                mismatch(format!("one of {:?}", symbols), format!("symbol {:?}", s))
+            },
+            (Value::Enum(i, s), Schema::Enum { symbols, .. })
+                if !self.validate(schema) =>
+            {
+                match symbols.iter().position(|symbol| **symbol == **s) {
+                    Some(position) => mismatch(
+                        format!("symbol {:?} at position {}", &**s, position),
+                        format!("symbol {:?} at position {}", &**s, i),
+                    ),
+                    None => mismatch(
+                        format!("one of {:?}", symbols),
+                        format!("symbol {:?} at position {}", &**s, i),
+                    ),
+                }
+            },
+            (Value::UnionBranch(index, value), Schema::Union(inner)) => {
+                match inner.variants().get(*index) {
+                    Some(variant) if !value.validate(variant) => {
+                        value.mismatch_at(&format!("{}[{}]", path, index), variant)
+                    },
+                    Some(_) => None,
+                    None => mismatch(
+                        format!("a branch index below {}", inner.variants().len()),
+                        format!("branch index {}", index),
+                    ),
+                }
+            },
+            (Value::Union(value), Schema::Union(inner))
+                if inner.find_schema(value).is_none() =>
+            {
+                mismatch(
+                    format!(
+                        "one of {:?}",
+                        inner
+                            .variants()
+                            .iter()
+                            .map(SchemaKind::from)
+                            .collect::<Vec<_>>()
+                    ),
+                    format!("{:?}", SchemaKind::from(&**value)),
+                )
+            },
+            (Value::Array(items), Schema::Array(inner)) => items
+                .iter()
+                .enumerate()
+                .find(|(_, item)| !item.validate(inner))
+                .and_then(|(i, item)| item.mismatch_at(&format!("{}[{}]", path, i), inner)),
+            (Value::Map(items), Schema::Map(inner)) => items
+                .iter()
+                .find(|(_, value)| !value.validate(inner))
+                .and_then(|(key, value)| {
+                    value.mismatch_at(&format!("{}[{:?}]", path, &**key), inner)
+                }),
+            (Value::Record(record_fields), Schema::Record { fields, .. }) => {
+                for (field, (name, value)) in fields.iter().zip(record_fields.iter()) {
+                    let field_path = format!("{}.{}", path, field.name);
+                    if field.name != *name {
+                        return Some(Error::SchemaMismatch {
+                            path: field_path,
+                            expected: format!("field {:?}", field.name),
+                            found: format!("field {:?}", name),
+                        })
+                    }
+                    if !value.validate(&field.schema) {
+                        return value.mismatch_at(&field_path, &field.schema)
+                    }
+                }
+                if fields.len() != record_fields.len() {
+                    return mismatch(
+                        format!("{} fields", fields.len()),
+                        format!("{} fields", record_fields.len()),
+                    )
+                }
+                None
+            },
+            _ if self.validate(schema) => None,
+            _ => mismatch(
+                format!("{:?}", SchemaKind::from(schema)),
+                format!("{:?}", SchemaKind::from(self)),
+            ),
+        }
+    }
+
     /// Attempt to perform schema resolution on the value, with the given
     /// [Schema](../schema/enum.Schema.html).
     ///
@@ -303,7 +512,7 @@ impl Value {
         {
             // Pull out the Union, and attempt to resolve against it.
             let v = match self {
-                Value::Union(b) => *b,
+                Value::Union(b) | Value::UnionBranch(_, b) => *b,
                 _ => unreachable!(),
             };
             self = v;
@@ -319,10 +528,110 @@ impl Value {
             Schema::String => self.resolve_string(),
             Schema::Fixed { size, .. } => self.resolve_fixed(size),
             Schema::Union(ref inner) => self.resolve_union(inner),
-            Schema::Enum { ref symbols, .. } => self.resolve_enum(symbols),
+            Schema::Enum {
+                ref symbols,
+                ref default,
+                ..
+            } => self.resolve_enum(symbols, default.as_deref()),
             Schema::Array(ref inner) => self.resolve_array(inner),
             Schema::Map(ref inner) => self.resolve_map(inner),
             Schema::Record { ref fields, .. } => self.resolve_record(fields),
+            Schema::Logical(ref logical) => self.resolve(logical.schema()),
+        }
+    }
+
+    /// Compute the hash code of the value, as Java's `GenericData.hashCode` computes it for the
+    /// equivalent generic datum and the given [Schema](../schema/enum.Schema.html).
+    ///
+    /// This allows partitioning data (e.g. Kafka topics or bucketed datasets) exactly the same way
+    /// JVM producers do. The hash is stable across releases and platforms.
+    ///
+    /// A few rules are worth knowing:
+    /// * record fields whose `order` is `ignore` do not contribute to the hash;
+    /// * enums hash to their ordinal, nulls to 0 and unions to the hash of their value;
+    /// * strings and map keys hash as `org.apache.avro.util.Utf8` does;
+    /// * maps hash as `java.util.Map` does, by summing `hash(key) ^ hash(value)` for all entries,
+    ///   values hashing as their `Object.hashCode` (e.g. enums as the `String` hash of their
+    ///   symbol, arrays as `java.util.List` does).
+    ///
+    /// **NOTE** The value is assumed to be valid with regards to the schema. To hash an encoded
+    /// datum, decode it first with [from_avro_datum](../fn.from_avro_datum.html).
+    pub fn hash_code(&self, schema: &Schema) -> i32 {
+        match (self, schema) {
+            (Value::Null, _) => 0,
+            (Value::Boolean(b), _) => if *b {
+                1231
+            } else {
+                1237
+            },
+            (Value::Int(i), _) => *i,
+            (Value::Long(i), _) => (i ^ ((*i as u64) >> 32) as i64) as i32,
+            (Value::Float(x), _) => java_float_bits(*x) as i32,
+            (Value::Double(x), _) => {
+                let bits = java_double_bits(*x);
+                (bits ^ (bits >> 32)) as i32
+            },
+            // `ByteBuffer.hashCode` walks the buffer backwards.
+            (Value::Bytes(bytes), _) => bytes
+                .iter()
+                .rev()
+                .fold(1i32, |h, &b| hash_code_add(h, i32::from(b as i8))),
+            (Value::Fixed(_, bytes), _) => bytes
+                .iter()
+                .fold(1i32, |h, &b| hash_code_add(h, i32::from(b as i8))),
+            (Value::String(s), Schema::Enum { symbols, .. }) => symbols
+                .iter()
+                .position(|symbol| symbol == s)
+                .map(|i| i as i32)
+                .unwrap_or(0),
+            (Value::String(s), _) => utf8_hash_code(s),
+            (Value::Enum(i, _), _) => *i,
+            (Value::Union(_), Schema::Union(inner))
+            | (Value::UnionBranch(..), Schema::Union(inner)) => inner
+                .find_branch(self)
+                .map(|(_, schema, value)| value.hash_code(schema))
+                .unwrap_or(0),
+            (Value::Union(value), _) | (Value::UnionBranch(_, value), _) => {
+                value.hash_code(schema)
+            },
+            (Value::Array(items), Schema::Array(inner)) => items
+                .iter()
+                .fold(1i32, |h, item| hash_code_add(h, item.hash_code(inner))),
+            (Value::Map(items), Schema::Map(inner)) => {
+                items.iter().fold(0i32, |h, (key, value)| {
+                    h.wrapping_add(utf8_hash_code(key) ^ value.object_hash_code(inner))
+                })
+            },
+            (Value::Record(record_fields), Schema::Record { fields, .. }) => fields
+                .iter()
+                .zip(record_fields.iter())
+                .filter(|(field, _)| field.order != RecordFieldOrder::Ignore)
+                .fold(1i32, |h, (field, (_, value))| {
+                    hash_code_add(h, value.hash_code(&field.schema))
+                }),
+            _ => 0,
+        }
+    }
+
+    // The `Object.hashCode` of the equivalent generic datum, which `java.util.Map` hashes its
+    // values with: it only differs from `GenericData.hashCode` for enums and arrays (records
+    // delegate to `GenericData`).
+    fn object_hash_code(&self, schema: &Schema) -> i32 {
+        match (self, schema) {
+            (Value::String(s), Schema::Enum { .. }) => java_hash_code(s),
+            (Value::Enum(_, symbol), _) => java_hash_code(symbol),
+            (Value::Union(_), Schema::Union(inner))
+            | (Value::UnionBranch(..), Schema::Union(inner)) => inner
+                .find_branch(self)
+                .map(|(_, schema, value)| value.object_hash_code(schema))
+                .unwrap_or(0),
+            (Value::Union(value), _) | (Value::UnionBranch(_, value), _) => {
+                value.object_hash_code(schema)
+            },
+            (Value::Array(items), Schema::Array(inner)) => items
+                .iter()
+                .fold(1i32, |h, item| hash_code_add(h, item.object_hash_code(inner))),
+            _ => self.hash_code(schema),
         }
     }
 
@@ -347,7 +656,11 @@ impl Value {
     fn resolve_int(self) -> Result<Self, Error> {
         match self {
             Value::Int(n) => Ok(Value::Int(n)),
-            Value::Long(n) => Ok(Value::Int(n as i32)),
+            Value::Long(n) if n == i64::from(n as i32) => Ok(Value::Int(n as i32)),
+            Value::Long(n) => Err(SchemaResolutionError::new(format!(
+                "Int expected, got Long {} out of the int range",
+                n
+            )).into()),
             other => {
                 Err(SchemaResolutionError::new(format!("Int expected, got {:?}", other)).into())
             },
@@ -401,7 +714,9 @@ impl Value {
     fn resolve_string(self) -> Result<Self, Error> {
         match self {
             Value::String(s) => Ok(Value::String(s)),
-            Value::Bytes(bytes) => Ok(Value::String(String::from_utf8(bytes)?)),
+            Value::Bytes(bytes) => String::from_utf8(bytes)
+                .map(Value::String)
+                .map_err(|e| SchemaResolutionError::new(e.to_string()).into()),
             other => {
                 Err(SchemaResolutionError::new(format!("String expected, got {:?}", other)).into())
             },
@@ -424,10 +739,13 @@ impl Value {
         }
     }
 
-    fn resolve_enum(self, symbols: &[String]) -> Result<Self, Error> {
-        let validate_symbol = |symbol: String, symbols: &[String]| {
-            if let Some(index) = symbols.iter().position(|ref item| item == &&symbol) {
+    fn resolve_enum(self, symbols: &[String], default: Option<&str>) -> Result<Self, Error> {
+        let validate_symbol = |symbol: Arc<str>, symbols: &[String]| {
+            if let Some(index) = symbols.iter().position(|item| **item == *symbol) {
                 Ok(Value::Enum(index as i32, symbol))
+            } else if let Some(default) = default {
+                let index = symbols.iter().position(|item| item == default).unwrap_or(0);
+                Ok(Value::Enum(index as i32, default.into()))
             } else {
                 Err(SchemaResolutionError::new(format!(
                     "Enum default {} is not among allowed symbols {:?}",
@@ -437,7 +755,7 @@ impl Value {
         };
 
         match self {
-            Value::Enum(i, s) => if i > 0 && i < symbols.len() as i32 {
+            Value::Enum(i, s) => if default.is_some() || i >= 0 && i < symbols.len() as i32 {
                 validate_symbol(s, symbols)
             } else {
                 Err(SchemaResolutionError::new(format!(
@@ -446,7 +764,7 @@ impl Value {
                     symbols.len() as i32
                 )).into())
             },
-            Value::String(s) => validate_symbol(s, symbols),
+            Value::String(s) => validate_symbol(s.into(), symbols),
             other => Err(SchemaResolutionError::new(format!(
                 "Enum({:?}) expected, got {:?}",
                 symbols, other
@@ -457,15 +775,29 @@ impl Value {
     fn resolve_union(self, schema: &UnionSchema) -> Result<Self, Error> {
         let v = match self {
             // Both are unions case.
-            Value::Union(v) => *v,
+            Value::Union(v) | Value::UnionBranch(_, v) => *v,
             // Reader is a union, but writer is not.
             v => v,
         };
         // Find the first match in the reader schema.
-        let (_, inner) = schema
-            .find_schema(&v)
-            .ok_or_else(|| SchemaResolutionError::new("Could not find matching type in union"))?;
-        v.resolve(inner)
+        if let Some((index, inner)) = schema.find_schema(&v) {
+            trace_event!(DEBUG, index, schema = ?inner, "chose union branch");
+            return v.resolve(inner)
+        }
+        // Otherwise, the first branch the value can be promoted to (e.g. a `Long` coming from a
+        // JSON number into an `int` branch).
+        schema
+            .variants()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, inner)| {
+                let value = v.clone().resolve(inner).ok()?;
+                trace_event!(DEBUG, index, schema = ?inner, promoted = true, "chose union branch");
+                Some(value)
+            }).next()
+            .ok_or_else(|| {
+                SchemaResolutionError::new("Could not find matching type in union").into()
+            })
     }
 
     fn resolve_array(self, schema: &Schema) -> Result<Self, Error> {
@@ -500,7 +832,10 @@ impl Value {
 
     fn resolve_record(self, fields: &[RecordField]) -> Result<Self, Error> {
         let mut items = match self {
-            Value::Map(items) => Ok(items),
+            Value::Map(items) => Ok(items
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect::<HashMap<_, _>>()),
             Value::Record(fields) => Ok(fields.into_iter().collect::<HashMap<_, _>>()),
             other => Err(Error::from(SchemaResolutionError::new(format!(
                 "Record({:?}) expected, got {:?}",
@@ -514,11 +849,19 @@ impl Value {
                 let value = match items.remove(&field.name) {
                     Some(value) => value,
                     None => match field.default {
-                        Some(ref value) => match field.schema {
-                            Schema::Enum { ref symbols, .. } => {
-                                value.clone().avro().resolve_enum(symbols)?
-                            },
-                            _ => value.clone().avro(),
+                        Some(ref value) => {
+                            trace_event!(
+                                DEBUG,
+                                field = %field.name,
+                                default = %value,
+                                "applied field default"
+                            );
+                            match field.schema {
+                                Schema::Enum { ref symbols, .. } => {
+                                    value.clone().avro().resolve_enum(symbols, None)?
+                                },
+                                _ => value.clone().avro(),
+                            }
                         },
                         _ => {
                             return Err(SchemaResolutionError::new(format!(
@@ -537,10 +880,285 @@ impl Value {
     }
 }
 
+#[inline]
+fn hash_code_add(hash_code: i32, item_hash_code: i32) -> i32 {
+    hash_code.wrapping_mul(31).wrapping_add(item_hash_code)
+}
+
+/// Render a value as JSON-like text meant for humans, e.g. `{"id": 42, "name": "x"}`.
+///
+/// Unions are rendered as their value, enums as their symbol, map entries sorted by key, bytes
+/// and fixed values as strings of code points 0-255 (as in the Avro JSON encoding), and
+/// non-finite floating-point numbers as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn float(x: f64, f: &mut fmt::Formatter) -> fmt::Result {
+            match x {
+                x if x.is_nan() => f.write_str("\"NaN\""),
+                x if x.is_infinite() && x > 0.0 => f.write_str("\"Infinity\""),
+                x if x.is_infinite() => f.write_str("\"-Infinity\""),
+                x => write!(f, "{:?}", x),
+            }
+        }
+        fn string(s: &str, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", JsonValue::String(s.to_owned()))
+        }
+
+        match *self {
+            Value::Null => f.write_str("null"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Long(i) => write!(f, "{}", i),
+            Value::Float(x) => float(f64::from(x), f),
+            Value::Double(x) => float(x, f),
+            Value::Bytes(ref bytes) | Value::Fixed(_, ref bytes) => {
+                string(&bytes.iter().map(|&b| char::from(b)).collect::<String>(), f)
+            },
+            Value::String(ref s) => string(s, f),
+            Value::Enum(_, ref symbol) => string(symbol, f),
+            Value::Union(ref value) | Value::UnionBranch(_, ref value) => write!(f, "{}", value),
+            Value::Array(ref items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            },
+            Value::Map(ref items) => {
+                let mut keys = items.keys().collect::<Vec<_>>();
+                keys.sort();
+                f.write_str("{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    string(key, f)?;
+                    write!(f, ": {}", items[key])?;
+                }
+                f.write_str("}")
+            },
+            Value::Record(ref fields) => {
+                f.write_str("{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    string(name, f)?;
+                    write!(f, ": {}", value)?;
+                }
+                f.write_str("}")
+            },
+        }
+    }
+}
+
+/// Hash a string as `org.apache.avro.util.Utf8` does, over its (signed) UTF-8 bytes.
+fn utf8_hash_code(s: &str) -> i32 {
+    s.bytes()
+        .fold(0i32, |h, b| h.wrapping_mul(31).wrapping_add(i32::from(b as i8)))
+}
+
+/// Hash a string as `java.lang.String` does, over its UTF-16 code units.
+fn java_hash_code(s: &str) -> i32 {
+    s.encode_utf16()
+        .fold(0i32, |h, unit| h.wrapping_mul(31).wrapping_add(i32::from(unit)))
+}
+
+/// Equivalent of Java's `Float.floatToIntBits`, which collapses every NaN to the canonical one.
+fn java_float_bits(x: f32) -> u32 {
+    if x.is_nan() {
+        0x7fc0_0000
+    } else {
+        x.to_bits()
+    }
+}
+
+/// Equivalent of Java's `Double.doubleToLongBits`, which collapses every NaN to the canonical one.
+fn java_double_bits(x: f64) -> u64 {
+    if x.is_nan() {
+        0x7ff8_0000_0000_0000
+    } else {
+        x.to_bits()
+    }
+}
+
+/// Options of [`generate`](fn.generate.html).
+#[derive(Clone, Debug)]
+pub struct GenerateConfig {
+    max_items: usize,
+    string_len: (usize, usize),
+    null_probability: f64,
+}
+
+impl Default for GenerateConfig {
+    fn default() -> GenerateConfig {
+        GenerateConfig {
+            max_items: 5,
+            string_len: (1, 16),
+            null_probability: 0.1,
+        }
+    }
+}
+
+impl GenerateConfig {
+    /// Create a `GenerateConfig` generating at most 5 items per array or map, strings and bytes
+    /// of 1 to 16 characters, and the `null` branch of unions 10% of the time.
+    pub fn new() -> GenerateConfig {
+        GenerateConfig::default()
+    }
+
+    /// Set the maximum number of items of arrays and maps.
+    pub fn max_items(mut self, max_items: usize) -> GenerateConfig {
+        self.max_items = max_items;
+        self
+    }
+
+    /// Set the range (inclusive) of lengths of strings, bytes and map keys.
+    pub fn string_len(mut self, min: usize, max: usize) -> GenerateConfig {
+        self.string_len = (min, max.max(min));
+        self
+    }
+
+    /// Set the probability (between 0 and 1) of generating `null` for unions with a `null` branch.
+    pub fn null_probability(mut self, null_probability: f64) -> GenerateConfig {
+        self.null_probability = null_probability;
+        self
+    }
+}
+
+/// A source of random numbers for [`generate`](fn.generate.html), e.g. wrapping the generator
+/// of any version of the `rand` crate.
+pub trait RandomSource {
+    /// The next random 64-bit integer, all of its bits being uniformly distributed.
+    fn next_u64(&mut self) -> u64;
+}
+
+/// A small seeded [`RandomSource`](trait.RandomSource.html) (SplitMix64), generating the same
+/// values for the same seed. It is not suitable for cryptography.
+#[derive(Clone, Debug)]
+pub struct SeededRandom(u64);
+
+impl SeededRandom {
+    /// Create a `SeededRandom` from `seed`.
+    pub fn new(seed: u64) -> SeededRandom {
+        SeededRandom(seed)
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+// A uniformly distributed float in [0, 1).
+fn random_f64<R: RandomSource + ?Sized>(rng: &mut R) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// A number in [low, high), with a negligible bias.
+fn random_below<R: RandomSource + ?Sized>(rng: &mut R, low: usize, high: usize) -> usize {
+    low + (rng.next_u64() % (high - low) as u64) as usize
+}
+
+fn random_alphanumeric<R: RandomSource + ?Sized>(rng: &mut R, len: usize) -> String {
+    (0..len)
+        .map(|_| char::from(ALPHANUMERIC[random_below(rng, 0, ALPHANUMERIC.len())]))
+        .collect()
+}
+
+/// Generate a random `Value` conforming to `schema`, e.g. for load testing, benchmarks or
+/// fixtures, drawing from `rng` (e.g. a [`SeededRandom`](struct.SeededRandom.html)).
+///
+/// Strings and map keys are alphanumeric, and floating-point numbers are taken between -1000
+/// and 1000. Values of logical types are generated in their underlying representation and
+/// converted, when valid.
+pub fn generate<R: RandomSource + ?Sized>(
+    schema: &Schema,
+    rng: &mut R,
+    config: &GenerateConfig,
+) -> Value {
+    let string_len = |rng: &mut R| random_below(rng, config.string_len.0, config.string_len.1 + 1);
+    match *schema {
+        Schema::Null => Value::Null,
+        Schema::Boolean => Value::Boolean(rng.next_u64() >> 63 == 1),
+        Schema::Int => Value::Int(rng.next_u64() as i32),
+        Schema::Long => Value::Long(rng.next_u64() as i64),
+        Schema::Float => Value::Float((random_f64(rng) * 2000.0 - 1000.0) as f32),
+        Schema::Double => Value::Double(random_f64(rng) * 2000.0 - 1000.0),
+        Schema::Bytes => {
+            let len = string_len(rng);
+            Value::Bytes((0..len).map(|_| rng.next_u64() as u8).collect())
+        },
+        Schema::String => {
+            let len = string_len(rng);
+            Value::String(random_alphanumeric(rng, len))
+        },
+        Schema::Fixed { size, .. } => {
+            Value::Fixed(size, (0..size).map(|_| rng.next_u64() as u8).collect())
+        },
+        Schema::Logical(ref logical) => {
+            let value = generate(logical.schema(), rng, config);
+            logical
+                .logical_type()
+                .to_logical(value.clone())
+                .unwrap_or(value)
+        },
+        Schema::Enum { ref symbols, .. } => {
+            let index = random_below(rng, 0, symbols.len());
+            Value::Enum(index as i32, symbols[index].as_str().into())
+        },
+        Schema::Union(ref union) => {
+            let variants = union.variants();
+            let null = variants.iter().position(|variant| *variant == Schema::Null);
+            let others = (0..variants.len())
+                .filter(|&i| Some(i) != null)
+                .collect::<Vec<_>>();
+            let index = match null {
+                Some(null) if others.is_empty() || random_f64(rng) < config.null_probability => {
+                    null
+                },
+                _ => others[random_below(rng, 0, others.len())],
+            };
+            Value::Union(Box::new(generate(&variants[index], rng, config)))
+        },
+        Schema::Array(ref items) => {
+            let len = random_below(rng, 0, config.max_items + 1);
+            Value::Array((0..len).map(|_| generate(items, rng, config)).collect())
+        },
+        Schema::Map(ref values) => {
+            let len = random_below(rng, 0, config.max_items + 1);
+            Value::Map(
+                (0..len)
+                    .map(|_| {
+                        let key_len = string_len(rng);
+                        let key = random_alphanumeric(rng, key_len);
+                        (key.into(), generate(values, rng, config))
+                    }).collect(),
+            )
+        },
+        Schema::Record { ref fields, .. } => Value::Record(
+            fields
+                .iter()
+                .map(|field| (field.name.clone(), generate(&field.schema, rng, config)))
+                .collect(),
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use schema::{Name, RecordField, RecordFieldOrder, UnionSchema};
+    use schema::{Name, RecordField, UnionSchema};
 
     #[test]
     fn validate() {
@@ -614,12 +1232,13 @@ mod tests {
                 "diamonds".to_string(),
                 "clubs".to_string(),
             ],
+            default: None,
         };
 
-        assert!(Value::Enum(0, "spades".to_string()).validate(&schema));
+        assert!(Value::Enum(0, "spades".into()).validate(&schema));
         assert!(Value::String("spades".to_string()).validate(&schema));
 
-        assert!(!Value::Enum(1, "spades".to_string()).validate(&schema));
+        assert!(!Value::Enum(1, "spades".into()).validate(&schema));
         assert!(!Value::String("lorem".to_string()).validate(&schema));
 
         let other_schema = Schema::Enum {
@@ -631,9 +1250,10 @@ mod tests {
                 "clubs".to_string(),
                 "spades".to_string(),
             ],
+            default: None,
         };
 
-        assert!(!Value::Enum(0, "spades".to_string()).validate(&other_schema));
+        assert!(!Value::Enum(0, "spades".into()).validate(&other_schema));
     }
 
     #[test]
@@ -705,4 +1325,422 @@ mod tests {
             ]).validate(&schema)
         );
     }
+
+    #[test]
+    fn int_range() {
+        use datum::to_avro_datum;
+
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "a", "type": "int"}]}"#,
+        ).unwrap();
+        let record = |n: i64| Value::Record(vec![("a".to_owned(), Value::Long(n))]);
+
+        // Longs within range are encoded as ints.
+        assert!(record(-3).validate(&schema));
+        assert_eq!(
+            to_avro_datum(&schema, record(-3)).unwrap(),
+            to_avro_datum(&schema, Value::Record(vec![("a".to_owned(), Value::Int(-3))])).unwrap()
+        );
+
+        let n = i64::from(i32::max_value()) + 1;
+        assert!(!record(n).validate(&schema));
+        match to_avro_datum(&schema, record(n)).unwrap_err() {
+            Error::SchemaMismatch { path, found, .. } => {
+                assert_eq!(path, "$.a");
+                assert_eq!(found, format!("Long {} out of the int range", n));
+            },
+            other => panic!("unexpected error {:?}", other),
+        }
+        #[cfg(feature = "serde_layer")]
+        assert!(!::ser::to_value(::std::u32::MAX).unwrap().validate(&Schema::Int));
+
+        assert_eq!(Value::Long(5).resolve(&Schema::Int).unwrap(), Value::Int(5));
+        assert!(Value::Long(n).resolve(&Schema::Int).is_err());
+    }
+
+    #[test]
+    fn mismatch_path() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": {"type": "array", "items": ["null", "string"]}}
+                ]
+            }
+        "#,
+        ).unwrap();
+
+        let mismatch = |value: Value| match value.mismatch(&schema) {
+            Error::SchemaMismatch {
+                path,
+                expected,
+                found,
+            } => (path, expected, found),
+            other => panic!("unexpected error {:?}", other),
+        };
+
+        assert_eq!(
+            mismatch(Value::Record(vec![
+                ("a".to_string(), Value::Long(42)),
+                (
+                    "b".to_string(),
+                    Value::Array(vec![
+                        Value::Union(Box::new(Value::Null)),
+                        Value::Union(Box::new(Value::Int(3))),
+                    ])
+                ),
+            ])),
+            (
+                "$.b[1]".to_owned(),
+                "one of [Null, String]".to_owned(),
+                "Int".to_owned()
+            )
+        );
+        assert_eq!(
+            mismatch(Value::Record(vec![("a".to_string(), Value::Int(42))])),
+            ("$.a".to_owned(), "Long".to_owned(), "Int".to_owned())
+        );
+        assert_eq!(
+            mismatch(Value::Record(vec![("a".to_string(), Value::Long(42))])),
+            ("$".to_owned(), "2 fields".to_owned(), "1 fields".to_owned())
+        );
+        assert_eq!(
+            mismatch(Value::Null),
+            ("$".to_owned(), "Record".to_owned(), "Null".to_owned())
+        );
+    }
+
+    #[test]
+    fn union_branch() {
+        let schema = Schema::parse_str(
+            r#"["string", {"type": "enum", "name": "suit", "symbols": ["Hearts", "Spades"]}]"#,
+        ).unwrap();
+        let hearts = |index| Value::UnionBranch(index, Box::new(Value::String("Hearts".into())));
+
+        // The kind of a string picks the `string` branch, unless told otherwise.
+        let by_kind = Value::Union(Box::new(Value::String("Hearts".into())));
+        assert_eq!(::to_avro_datum(&schema, by_kind).unwrap(), b"\x00\x0cHearts");
+        assert!(hearts(1).validate(&schema));
+        assert_eq!(::to_avro_datum(&schema, hearts(1)).unwrap(), [2, 0]);
+        assert_eq!(hearts(1).hash_code(&schema), 0);
+
+        let club = Value::UnionBranch(1, Box::new(Value::String("Clubs".into())));
+        assert!(!club.validate(&schema));
+        match club.mismatch(&schema) {
+            Error::SchemaMismatch { path, .. } => assert_eq!(path, "$[1]"),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(!hearts(2).validate(&schema));
+        match hearts(2).mismatch(&schema) {
+            Error::SchemaMismatch { expected, found, .. } => {
+                assert_eq!(expected, "a branch index below 2");
+                assert_eq!(found, "branch index 2");
+            },
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "serde_layer")]
+    #[test]
+    fn mismatch_enum() {
+        #[derive(Serialize)]
+        enum Suit {
+            Spades,
+            Hearts,
+        }
+
+        let schema = Schema::parse_str(
+            r#"{"type": "enum", "name": "suit", "symbols": ["Hearts", "Spades", "Clubs"]}"#,
+        ).unwrap();
+        let mismatch = |value: Value| match value.mismatch(&schema) {
+            Error::SchemaMismatch {
+                expected, found, ..
+            } => (expected, found),
+            other => panic!("unexpected error {:?}", other),
+        };
+
+        // Rust enums are serialized with the index of their variant.
+        let hearts = ::ser::to_value(Suit::Hearts).unwrap();
+        assert!(!hearts.validate(&schema));
+        assert_eq!(
+            mismatch(hearts),
+            (
+                r#"symbol "Hearts" at position 0"#.to_owned(),
+                r#"symbol "Hearts" at position 1"#.to_owned()
+            )
+        );
+        assert_eq!(
+            mismatch(Value::Enum(0, "Diamonds".into())),
+            (
+                r#"one of ["Hearts", "Spades", "Clubs"]"#.to_owned(),
+                r#"symbol "Diamonds" at position 0"#.to_owned()
+            )
+        );
+        assert!(!::ser::to_value(Suit::Spades).unwrap().validate(&schema));
+        assert!(Value::Enum(1, "Spades".into()).validate(&schema));
+    }
+
+    #[test]
+    fn hash_code_primitives() {
+        // Expected values follow Java's `GenericData.get().hashCode(datum, schema)`.
+        assert_eq!(Value::Null.hash_code(&Schema::Null), 0);
+        assert_eq!(Value::Boolean(true).hash_code(&Schema::Boolean), 1231);
+        assert_eq!(Value::Boolean(false).hash_code(&Schema::Boolean), 1237);
+        assert_eq!(Value::Int(-42).hash_code(&Schema::Int), -42);
+        assert_eq!(Value::Long(27).hash_code(&Schema::Long), 27);
+        assert_eq!(Value::Long(-1).hash_code(&Schema::Long), 0);
+        assert_eq!(Value::Long(1 << 32).hash_code(&Schema::Long), 1);
+        assert_eq!(Value::Float(1.5).hash_code(&Schema::Float), 1_069_547_520);
+        assert_eq!(Value::Double(1.5).hash_code(&Schema::Double), 1_073_217_536);
+        assert_eq!(
+            Value::String("foo".to_string()).hash_code(&Schema::String),
+            101_574
+        );
+        assert_eq!(
+            Value::String("été".to_string()).hash_code(&Schema::String),
+            -58_817_100
+        );
+        assert_eq!(Value::Bytes(vec![1, 2, 255]).hash_code(&Schema::Bytes), 28_893);
+    }
+
+    #[test]
+    fn hash_code_complex() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": "long"},
+                    {"name": "b", "type": "string"},
+                    {"name": "c", "type": ["null", "int"], "order": "ignore"},
+                    {"name": "d", "type": {"type": "array", "items": ["null", "int"]}},
+                    {
+                        "name": "e",
+                        "type": {"type": "enum", "name": "suit", "symbols": ["spades", "hearts"]}
+                    }
+                ]
+            }
+        "#,
+        ).unwrap();
+
+        let record = |c: Value| {
+            Value::Record(vec![
+                ("a".to_string(), Value::Long(27)),
+                ("b".to_string(), Value::String("foo".to_string())),
+                ("c".to_string(), c),
+                (
+                    "d".to_string(),
+                    Value::Array(vec![
+                        Value::Union(Box::new(Value::Null)),
+                        Value::Union(Box::new(Value::Int(3))),
+                    ]),
+                ),
+                ("e".to_string(), Value::Enum(1, "hearts".into())),
+            ])
+        };
+
+        // (((1 * 31 + 27) * 31 + 101574) * 31 + ((1 * 31 + 0) * 31 + 3)) * 31 + 1
+        let expected = 99_370_377;
+        assert_eq!(
+            record(Value::Union(Box::new(Value::Null))).hash_code(&schema),
+            expected
+        );
+        // Fields with an `ignore` order do not contribute to the hash.
+        assert_eq!(
+            record(Value::Union(Box::new(Value::Int(5)))).hash_code(&schema),
+            expected
+        );
+
+        let mut items = HashMap::new();
+        items.insert("foo".into(), Value::Int(1));
+        items.insert("bar".into(), Value::Int(2));
+        assert_eq!(
+            Value::Map(items).hash_code(&Schema::Map(Box::new(Schema::Int))),
+            (101_574 ^ 1) + (97_299 ^ 2)
+        );
+
+        // Map values hash as their `Object.hashCode`, enums as their symbol, computed by Java
+        // over a `java.util.HashMap` of `String`s and `List`s. Records hash as they do anywhere.
+        let suit = r#"{"type": "enum", "name": "suit", "symbols": ["spades", "hearts"]}"#;
+        let mut items = HashMap::new();
+        items.insert("a".into(), Value::Enum(1, "hearts".into()));
+        items.insert("b".into(), Value::String("spades".into()));
+        let map_schema = Schema::parse_str(&format!(r#"{{"type": "map", "values": {}}}"#, suit));
+        assert_eq!(Value::Map(items).hash_code(&map_schema.unwrap()), -2_117_448_680);
+
+        let mut items = HashMap::new();
+        let suits = vec![Value::Enum(1, "hearts".into()), Value::Enum(0, "spades".into())];
+        items.insert("a".into(), Value::Union(Box::new(Value::Array(suits))));
+        let map_schema = Schema::parse_str(&format!(
+            r#"{{"type": "map", "values": ["null", {{"type": "array", "items": {}}}]}}"#,
+            suit
+        ));
+        assert_eq!(Value::Map(items).hash_code(&map_schema.unwrap()), -100_451_325);
+
+        let mut items = HashMap::new();
+        items.insert("a".into(), record(Value::Union(Box::new(Value::Null))));
+        assert_eq!(
+            Value::Map(items).hash_code(&Schema::Map(Box::new(schema))),
+            97 ^ expected
+        );
+    }
+
+    #[test]
+    fn resolve_union() {
+        let schema = Schema::Union(UnionSchema::new(vec![Schema::Null, Schema::Double]).unwrap());
+
+        assert_eq!(Value::Null.resolve(&schema).unwrap(), Value::Null);
+        // There is no `int` branch, but an `int` can be promoted to a `double`.
+        assert_eq!(Value::Int(1).resolve(&schema).unwrap(), Value::Double(1.0));
+        assert!(Value::String("foo".into()).resolve(&schema).is_err());
+    }
+
+    #[test]
+    fn resolve_enum_default() {
+        let schema = Schema::parse_str(
+            r#"
+            {"type": "enum", "name": "Suit", "symbols": ["spades", "hearts"], "default": "hearts"}
+            "#,
+        ).unwrap();
+
+        assert_eq!(
+            Value::Enum(0, "spades".into()).resolve(&schema).unwrap(),
+            Value::Enum(0, "spades".into())
+        );
+        assert_eq!(
+            Value::Enum(3, "clubs".into()).resolve(&schema).unwrap(),
+            Value::Enum(1, "hearts".into())
+        );
+        assert_eq!(
+            Value::String("clubs".to_owned()).resolve(&schema).unwrap(),
+            Value::Enum(1, "hearts".into())
+        );
+
+        let schema = Schema::parse_str(
+            r#"{"type": "enum", "name": "Suit", "symbols": ["spades", "hearts"]}"#,
+        ).unwrap();
+        assert!(Value::Enum(3, "clubs".into()).resolve(&schema).is_err());
+    }
+
+    #[test]
+    fn generate_values() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": ["null", "long"]},
+                    {"name": "b", "type": {"type": "array", "items": "string"}},
+                    {"name": "c", "type": {"type": "map", "values": "bytes"}},
+                    {"name": "d", "type": {"type": "enum", "name": "e", "symbols": ["x", "y"]}},
+                    {"name": "e", "type": {"type": "fixed", "name": "f", "size": 4}},
+                    {"name": "f", "type": ["int", "float", "double", "boolean"]}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let mut rng = SeededRandom::new(42);
+
+        let config = GenerateConfig::new().max_items(3).string_len(2, 4);
+        for _ in 0..100 {
+            let value = generate(&schema, &mut rng, &config);
+            assert!(value.validate(&schema));
+            match value {
+                Value::Record(ref fields) => {
+                    match fields[1].1 {
+                        Value::Array(ref items) => {
+                            assert!(items.len() <= 3);
+                            for item in items {
+                                match *item {
+                                    Value::String(ref s) => assert!(s.len() >= 2 && s.len() <= 4),
+                                    ref other => panic!("unexpected value {:?}", other),
+                                }
+                            }
+                        },
+                        ref other => panic!("unexpected value {:?}", other),
+                    }
+                },
+                ref other => panic!("unexpected value {:?}", other),
+            }
+        }
+
+        // Always, or never `null`.
+        let schema = Schema::parse_str(r#"["null", "long"]"#).unwrap();
+        let config = GenerateConfig::new().null_probability(1.0);
+        let null = Value::Union(Box::new(Value::Null));
+        assert!((0..100).all(|_| generate(&schema, &mut rng, &config) == null));
+        let config = GenerateConfig::new().null_probability(0.0);
+        assert!((0..100).all(|_| generate(&schema, &mut rng, &config) != null));
+
+        // The same values for the same seed, from any source.
+        let config = GenerateConfig::new();
+        let values = |seed| {
+            let mut rng = SeededRandom::new(seed);
+            (0..10).map(|_| generate(&schema, &mut rng, &config)).collect::<Vec<_>>()
+        };
+        assert_eq!(values(7), values(7));
+        assert_ne!(values(7), values(8));
+        struct Zero;
+        impl RandomSource for Zero {
+            fn next_u64(&mut self) -> u64 {
+                0
+            }
+        }
+        assert_eq!(
+            generate(&Schema::String, &mut Zero, &config),
+            Value::String("A".to_owned())
+        );
+        assert_eq!(generate(&schema, &mut Zero, &config), null);
+    }
+
+    #[test]
+    fn seeded_random() {
+        // The reference outputs of SplitMix64 seeded with 1234567.
+        let mut rng = SeededRandom::new(1_234_567);
+        assert_eq!(rng.next_u64(), 6_457_827_717_110_365_317);
+        assert_eq!(rng.next_u64(), 3_203_168_211_198_807_973);
+    }
+
+    #[test]
+    fn encode_to_writer() {
+        let schema = Schema::Array(Box::new(Schema::String));
+        let value = Value::Array(vec![Value::String("foo".to_owned())]);
+        let mut buffer = Vec::new();
+        value.encode_to(&schema, &mut buffer).unwrap();
+        assert_eq!(buffer, vec![2, 6, b'f', b'o', b'o', 0]);
+
+        match Value::Int(1).encode_to(&schema, &mut buffer) {
+            Err(Error::SchemaMismatch { .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn display() {
+        let mut map = HashMap::new();
+        map.insert(Arc::from("b"), Value::Float(1.5));
+        map.insert(Arc::from("a"), Value::Double(::std::f64::NAN));
+        let value = Value::Record(vec![
+            ("id".to_owned(), Value::Long(42)),
+            ("name".to_owned(), Value::String("x\"y".to_owned())),
+            ("tags".to_owned(), Value::Array(vec![Value::Null, Value::Boolean(true)])),
+            ("map".to_owned(), Value::Map(map)),
+            ("union".to_owned(), Value::Union(Box::new(Value::Int(1)))),
+            ("enum".to_owned(), Value::Enum(0, "spades".into())),
+            ("bytes".to_owned(), Value::Bytes(vec![0, 0xff])),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"id": 42, "name": "x\"y", "tags": [null, true], "map": {"a": "NaN", "b": 1.5}, "#
+                .to_owned()
+                + r#""union": 1, "enum": "spades", "bytes": "\u0000ÿ"}"#
+        );
+        assert_eq!(value.to_json_string(), value.to_string());
+        assert_eq!(Value::Double(1.0).to_string(), "1.0");
+    }
 }
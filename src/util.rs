@@ -1,33 +1,33 @@
-use std::io::Read;
-use std::sync::{Once, ONCE_INIT};
-
-use failure::Error;
+#[cfg(feature = "std")]
+use std::io::{BufRead, BufReader, Cursor, Seek, SeekFrom};
+use std::io::{self, ErrorKind, Read};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use error::Error;
 use serde_json::{Map, Value};
 
+use varint::{
+    decode_variable as decode_variable_core, encode_variable, unzigzag, zigzag, VarintError,
+    MAX_VARINT_LEN,
+};
+
 /// Maximum number of bytes that can be allocated when decoding
 /// Avro-encoded values. This is a protection against ill-formed
 /// data, whose length field might be interpreted as enourmous.
 /// See max_allocation_bytes to change this limit.
-pub static mut MAX_ALLOCATION_BYTES: usize = 512 * 1024 * 1024;
-static MAX_ALLOCATION_BYTES_ONCE: Once = ONCE_INIT;
+static MAX_ALLOCATION_BYTES: AtomicUsize = AtomicUsize::new(512 * 1024 * 1024);
 
-/// Describes errors happened trying to allocate too many bytes
-#[derive(Fail, Debug)]
-#[fail(display = "Allocation error: {}", _0)]
-pub struct AllocationError(String);
-
-impl AllocationError {
-    pub fn new<S>(msg: S) -> AllocationError
-    where
-        S: Into<String>,
-    {
-        AllocationError(msg.into())
-    }
-}
+/// Maximum nesting depth of arrays, maps and records when decoding
+/// Avro-encoded values. This is a protection against exhausting the stack
+/// with deeply nested data.
+/// See max_decoding_depth to change this limit.
+static MAX_DECODING_DEPTH: AtomicUsize = AtomicUsize::new(128);
 
 /// Describes errors happened while decoding Avro data.
-#[derive(Fail, Debug)]
-#[fail(display = "Decoding error: {}", _0)]
+#[derive(Error, Debug)]
+#[error("Decoding error: {0}")]
 pub struct DecodeError(String);
 
 impl DecodeError {
@@ -39,6 +39,12 @@ impl DecodeError {
     }
 }
 
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Error {
+        Error::Decode { reason: error.0 }
+    }
+}
+
 pub trait MapHelper {
     fn string(&self, key: &str) -> Option<String>;
 
@@ -59,19 +65,137 @@ impl MapHelper for Map<String, Value> {
     }
 }
 
+/// Readers the decoder can pull variable-length integers from.
+///
+/// In-memory data (`&[u8]`, `Cursor` and `BufReader`) decodes varints in a tight loop over the
+/// buffered bytes, while any other `io::Read` goes through [`ByteReader`](struct.ByteReader.html)
+/// and reads them one byte at a time.
+pub trait VarintRead: Read {
+    fn read_variable(&mut self) -> Result<u64, Error>;
+
+    /// Advance past the next `n` bytes without keeping them around.
+    fn skip_bytes(&mut self, mut n: u64) -> Result<(), Error> {
+        let mut buf = [0u8; 8 * 1024];
+        while n > 0 {
+            let len = n.min(buf.len() as u64) as usize;
+            self.read_exact(&mut buf[..len]).map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => {
+                    io::Error::new(ErrorKind::UnexpectedEof, "failed to skip bytes")
+                },
+                _ => e,
+            })?;
+            n -= len as u64;
+        }
+        Ok(())
+    }
+}
+
+impl VarintRead for &[u8] {
+    fn read_variable(&mut self) -> Result<u64, Error> {
+        let (z, n) = decode_variable_slice(self)?;
+        *self = &self[n..];
+        Ok(z)
+    }
+
+    fn skip_bytes(&mut self, n: u64) -> Result<(), Error> {
+        if n > self.len() as u64 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "failed to skip bytes").into())
+        }
+        *self = &self[n as usize..];
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: AsRef<[u8]>> VarintRead for Cursor<T> {
+    fn read_variable(&mut self) -> Result<u64, Error> {
+        decode_variable_buf(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> VarintRead for BufReader<R> {
+    fn read_variable(&mut self) -> Result<u64, Error> {
+        decode_variable_buf(self)
+    }
+}
+
+/// Adapter for any `io::Read`, decoding varints one byte at a time.
+pub struct ByteReader<R>(pub R);
+
+impl<R: Read> Read for ByteReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.0.read_exact(buf)
+    }
+}
+
+impl<R: Read> VarintRead for ByteReader<R> {
+    fn read_variable(&mut self) -> Result<u64, Error> {
+        decode_variable(&mut self.0)
+    }
+}
+
+/// Adapter for any `io::Read`, counting the bytes read through it.
+#[derive(Debug, Clone)]
+pub struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> CountingReader<R> {
+        CountingReader { inner, count: 0 }
+    }
+
+    /// Number of bytes read so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Seek> CountingReader<R> {
+    /// Move to `position` in the input, counting it as the number of bytes read.
+    pub fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(position))?;
+        self.count = position;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "std")]
 pub fn read_long<R: Read>(reader: &mut R) -> Result<i64, Error> {
-    zag_i64(reader)
+    zag_i64(&mut ByteReader(reader))
 }
 
 pub fn zig_i32(n: i32, buffer: &mut Vec<u8>) {
-    encode_variable(i64::from((n << 1) ^ (n >> 31)), buffer)
+    zig_i64(i64::from(n), buffer)
 }
 
 pub fn zig_i64(n: i64, buffer: &mut Vec<u8>) {
-    encode_variable((n << 1) ^ (n >> 63), buffer)
+    let mut buf = [0u8; MAX_VARINT_LEN];
+    let len = encode_variable(zigzag(n), &mut buf);
+    buffer.extend_from_slice(&buf[..len]);
 }
 
-pub fn zag_i32<R: Read>(reader: &mut R) -> Result<i32, Error> {
+pub fn zag_i32<R: VarintRead>(reader: &mut R) -> Result<i32, Error> {
     let i = zag_i64(reader)?;
     if i < i64::from(i32::min_value()) || i > i64::from(i32::max_value()) {
         Err(DecodeError::new("int out of range").into())
@@ -80,25 +204,8 @@ pub fn zag_i32<R: Read>(reader: &mut R) -> Result<i32, Error> {
     }
 }
 
-pub fn zag_i64<R: Read>(reader: &mut R) -> Result<i64, Error> {
-    let z = decode_variable(reader)?;
-    Ok(if z & 0x1 == 0 {
-        (z >> 1) as i64
-    } else {
-        !(z >> 1) as i64
-    })
-}
-
-fn encode_variable(mut z: i64, buffer: &mut Vec<u8>) {
-    loop {
-        if z <= 0x7F {
-            buffer.push((z & 0x7F) as u8);
-            break
-        } else {
-            buffer.push((0x80 | (z & 0x7F)) as u8);
-            z >>= 7;
-        }
-    }
+pub fn zag_i64<R: VarintRead>(reader: &mut R) -> Result<i64, Error> {
+    reader.read_variable().map(unzigzag)
 }
 
 fn decode_variable<R: Read>(reader: &mut R) -> Result<u64, Error> {
@@ -123,32 +230,84 @@ fn decode_variable<R: Read>(reader: &mut R) -> Result<u64, Error> {
     Ok(i)
 }
 
-/// Set a new maximum number of bytes that can be allocated when decoding data.
-/// Once called, the limit cannot be changed.
+/// Decode a variable-length integer from the start of `buf`.
 ///
-/// **NOTE** This function must be called before decoding **any** data. The
-/// library leverages [`std::sync::Once`](https://doc.rust-lang.org/std/sync/struct.Once.html)
-/// to set the limit either when calling this method, or when decoding for
-/// the first time.
-pub fn max_allocation_bytes(num_bytes: usize) -> usize {
-    unsafe {
-        MAX_ALLOCATION_BYTES_ONCE.call_once(|| {
-            MAX_ALLOCATION_BYTES = num_bytes;
-        });
-        MAX_ALLOCATION_BYTES
+/// Return the decoded integer along with the number of bytes it spanned.
+fn decode_variable_slice(buf: &[u8]) -> Result<(u64, usize), Error> {
+    decode_variable_core(buf).map_err(|e| match e {
+        VarintError::Overflow => DecodeError::new("Overflow when decoding integer value").into(),
+        VarintError::UnexpectedEnd => {
+            io::Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer").into()
+        },
+    })
+}
+
+/// Decode a variable-length integer straight from the buffer of a `BufRead`, falling back to
+/// reading one byte at a time when the integer spans across the end of the buffer.
+#[cfg(feature = "std")]
+fn decode_variable_buf<R: BufRead>(reader: &mut R) -> Result<u64, Error> {
+    let decoded = decode_variable_slice(reader.fill_buf()?).ok();
+    match decoded {
+        Some((i, n)) => {
+            reader.consume(n);
+            Ok(i)
+        },
+        None => decode_variable(reader),
     }
 }
 
+/// Set a new maximum number of bytes that can be allocated when decoding data (512MB by
+/// default), returning it.
+///
+/// The limit can be raised or lowered at any time, and applies to all the decoding that follows,
+/// in every thread.
+pub fn max_allocation_bytes(num_bytes: usize) -> usize {
+    MAX_ALLOCATION_BYTES.store(num_bytes, Ordering::Relaxed);
+    num_bytes
+}
+
+/// The current maximum number of bytes that can be allocated when decoding data, see
+/// [`max_allocation_bytes`](fn.max_allocation_bytes.html).
+pub fn allocation_limit() -> usize {
+    MAX_ALLOCATION_BYTES.load(Ordering::Relaxed)
+}
+
 pub fn safe_len(len: usize) -> Result<usize, Error> {
-    let max_bytes = max_allocation_bytes(512 * 1024 * 1024);
+    let max_bytes = allocation_limit();
 
     if len <= max_bytes {
         Ok(len)
     } else {
-        Err(AllocationError::new(format!(
-            "Unable to allocate {} bytes (Maximum allowed: {})",
-            len, max_bytes
-        )).into())
+        Err(Error::Allocation {
+            requested: len,
+            limit: max_bytes,
+        })
+    }
+}
+
+/// Set a new maximum nesting depth of arrays, maps and records when decoding data (128 by
+/// default), returning it.
+///
+/// The limit can be raised or lowered at any time, and applies to all the decoding that follows,
+/// in every thread.
+pub fn max_decoding_depth(depth: usize) -> usize {
+    MAX_DECODING_DEPTH.store(depth, Ordering::Relaxed);
+    depth
+}
+
+/// The current maximum nesting depth of arrays, maps and records when decoding data, see
+/// [`max_decoding_depth`](fn.max_decoding_depth.html).
+pub fn decoding_depth_limit() -> usize {
+    MAX_DECODING_DEPTH.load(Ordering::Relaxed)
+}
+
+pub fn safe_depth(depth: usize) -> Result<usize, Error> {
+    let max_depth = decoding_depth_limit();
+
+    if depth <= max_depth {
+        Ok(depth)
+    } else {
+        Err(Error::Depth { limit: max_depth })
     }
 }
 
@@ -218,15 +377,105 @@ mod tests {
         assert_eq!(s, [129, 128, 128, 128, 16]);
     }
 
+    // Longs whose zig-zag form has its top bit set used to be encoded as a single byte.
+    #[test]
+    fn test_zig_i64_zigzag_top_bit_set() {
+        let mut s = Vec::new();
+        zig_i64(i64::max_value(), &mut s);
+        assert_eq!(s, [254, 255, 255, 255, 255, 255, 255, 255, 255, 1]);
+        assert_eq!(zag_i64(&mut &s[..]).unwrap(), i64::max_value());
+
+        s.clear();
+        zig_i64(i64::min_value(), &mut s);
+        assert_eq!(s, [255, 255, 255, 255, 255, 255, 255, 255, 255, 1]);
+        assert_eq!(zag_i64(&mut &s[..]).unwrap(), i64::min_value());
+    }
+
     #[test]
     fn test_overflow() {
         let causes_left_shift_overflow: &[u8] = &[0xe1, 0xe1, 0xe1, 0xe1, 0xe1];
         assert!(decode_variable(&mut &causes_left_shift_overflow[..]).is_err());
     }
 
+    #[test]
+    fn test_decode_variable_slice() {
+        let mut buf = Vec::new();
+        zig_i64(-2147483649i64, &mut buf);
+        buf.push(42);
+
+        let (z, n) = decode_variable_slice(&buf).unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(z, decode_variable(&mut &buf[..]).unwrap());
+
+        let mut slice = &buf[..];
+        assert_eq!(zag_i64(&mut slice).unwrap(), -2147483649i64);
+        assert_eq!(slice, &[42]);
+
+        let causes_left_shift_overflow: &[u8] = &[0xe1; 10];
+        assert!(decode_variable_slice(causes_left_shift_overflow).is_err());
+
+        let truncated: &[u8] = &[0xe1, 0xe1];
+        match decode_variable_slice(truncated).unwrap_err() {
+            Error::Io(err) => assert_eq!(err.kind(), ErrorKind::UnexpectedEof),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_variable_buf() {
+        let mut buf = Vec::new();
+        zig_i64(2147483648i64, &mut buf);
+        zig_i64(-1i64, &mut buf);
+
+        // A 2 bytes buffer splits the first integer, forcing the byte at a time fallback.
+        let mut reader = BufReader::with_capacity(2, &buf[..]);
+        assert_eq!(zag_i64(&mut reader).unwrap(), 2147483648i64);
+        assert_eq!(zag_i64(&mut reader).unwrap(), -1i64);
+        assert!(zag_i64(&mut reader).is_err());
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(zag_i64(&mut cursor).unwrap(), 2147483648i64);
+        assert_eq!(zag_i64(&mut cursor).unwrap(), -1i64);
+    }
+
+    #[test]
+    fn test_counting_reader() {
+        let mut reader = CountingReader::new(&[1u8, 2, 3, 4, 5][..]);
+        let mut buf = [0u8; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.count(), 2);
+        assert!(reader.read_exact(&mut [0u8; 4]).is_err());
+        assert_eq!(reader.count(), 5);
+    }
+
     #[test]
     fn test_safe_len() {
         assert_eq!(42usize, safe_len(42usize).unwrap());
         assert!(safe_len(1024 * 1024 * 1024).is_err());
+
+        // Raise the limit, then restore it.
+        let limit = allocation_limit();
+        assert!(safe_len(limit + 1).is_err());
+        assert_eq!(max_allocation_bytes(limit + 1), limit + 1);
+        assert_eq!(safe_len(limit + 1).unwrap(), limit + 1);
+        max_allocation_bytes(limit);
+        match safe_len(limit + 1).unwrap_err() {
+            Error::Allocation {
+                requested,
+                limit: current,
+            } => assert_eq!((requested, current), (limit + 1, limit)),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_safe_depth() {
+        assert_eq!(42usize, safe_depth(42usize).unwrap());
+        match safe_depth(1024).unwrap_err() {
+            Error::Depth { limit } => assert_eq!(limit, 128),
+            other => panic!("unexpected error {:?}", other),
+        }
+        // Changing the limit would race with the other tests decoding nested data.
+        assert_eq!(max_decoding_depth(decoding_depth_limit()), 128);
     }
 }
@@ -0,0 +1,89 @@
+//! Zig-zag and variable-length encoding of Avro `int` and `long` values over byte slices.
+
+/// Maximum number of bytes of a variable-length encoded `long`.
+pub const MAX_VARINT_LEN: usize = 10;
+
+/// Describes errors happened while decoding a variable-length integer from a slice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VarintError {
+    /// The integer spans more than `MAX_VARINT_LEN` bytes.
+    Overflow,
+    /// The slice ends before the last byte of the integer.
+    UnexpectedEnd,
+}
+
+/// Map a signed integer to an unsigned one, so that small negative values stay small.
+pub fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+/// Reverse [`zigzag`](fn.zigzag.html).
+pub fn unzigzag(z: u64) -> i64 {
+    if z & 0x1 == 0 {
+        (z >> 1) as i64
+    } else {
+        !(z >> 1) as i64
+    }
+}
+
+/// Write the variable-length encoding of `z` at the start of `buf`.
+///
+/// Return the number of bytes written.
+pub fn encode_variable(mut z: u64, buf: &mut [u8; MAX_VARINT_LEN]) -> usize {
+    let mut n = 0;
+    loop {
+        if z <= 0x7F {
+            buf[n] = z as u8;
+            return n + 1
+        }
+        buf[n] = (0x80 | (z & 0x7F)) as u8;
+        z >>= 7;
+        n += 1;
+    }
+}
+
+/// Decode a variable-length integer from the start of `buf`.
+///
+/// Return the decoded integer along with the number of bytes it spanned.
+pub fn decode_variable(buf: &[u8]) -> Result<(u64, usize), VarintError> {
+    let mut i = 0u64;
+    for (j, &byte) in buf.iter().take(MAX_VARINT_LEN).enumerate() {
+        i |= u64::from(byte & 0x7F) << (j * 7);
+        if (byte >> 7) == 0 {
+            return Ok((i, j + 1))
+        }
+    }
+
+    if buf.len() >= MAX_VARINT_LEN {
+        Err(VarintError::Overflow)
+    } else {
+        Err(VarintError::UnexpectedEnd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_round_trip() {
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        for &(n, len) in &[
+            (0i64, 1),
+            (-1, 1),
+            (63, 1),
+            (-64, 1),
+            (64, 2),
+            (i64::from(i32::max_value()), 5),
+            (i64::max_value(), 10),
+            (i64::min_value(), 10),
+        ] {
+            assert_eq!(encode_variable(zigzag(n), &mut buf), len);
+            assert_eq!(decode_variable(&buf[..len]), Ok((zigzag(n), len)));
+            assert_eq!(unzigzag(zigzag(n)), n);
+        }
+
+        assert_eq!(decode_variable(&[0x80]), Err(VarintError::UnexpectedEnd));
+        assert_eq!(decode_variable(&[0xFF; 11]), Err(VarintError::Overflow));
+    }
+}
@@ -1,75 +1,149 @@
 //! Logic handling writing in Avro format at user level.
-use std::collections::HashMap;
-use std::io::Write;
-
-use failure::Error;
+#[cfg(feature = "rand")]
+use std::fs::File;
+#[cfg(feature = "rand")]
+use std::io::BufWriter;
+use std::io::{self, ErrorKind, IoSlice, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "rand")]
+use std::path::Path;
+
+#[cfg(feature = "arrow")]
+use arrow_array::RecordBatch;
+use error::Error;
+#[cfg(feature = "rand")]
 use rand::random;
+#[cfg(feature = "serde_layer")]
 use serde::Serialize;
 use serde_json;
 
-use encode::{encode, encode_ref, encode_to_vec};
+#[cfg(feature = "arrow")]
+use arrow::record_batch_rows;
+use datum::{write_value_ref, DatumWriter};
+use decode::decode;
+use encode::encode;
 use schema::Schema;
+#[cfg(feature = "serde_layer")]
 use ser::Serializer;
+use stats::{FileStatistics, Tracker, STATISTICS_KEY};
 use types::{ToAvro, Value};
+use util::zig_i64;
 use Codec;
 
 const SYNC_SIZE: usize = 16;
 const SYNC_INTERVAL: usize = 1000 * SYNC_SIZE; // TODO: parametrize in Writer
 
-const AVRO_OBJECT_HEADER: &[u8] = &[b'O', b'b', b'j', 1u8];
-
-/// Describes errors happened while validating Avro data.
-#[derive(Fail, Debug)]
-#[fail(display = "Decoding error: {}", _0)]
-pub struct ValidationError(String);
+// Payloads of unknown length up to this size are staged in memory rather than in the scratch
+// storage.
+const STAGING_THRESHOLD: usize = 1024 * 1024;
 
-impl ValidationError {
-    pub fn new<S>(msg: S) -> ValidationError
-    where
-        S: Into<String>,
-    {
-        ValidationError(msg.into())
-    }
-}
+const AVRO_OBJECT_HEADER: &[u8] = &[b'O', b'b', b'j', 1u8];
 
 /// Main interface for writing Avro formatted values.
+///
+/// A `Writer` is `Send` when `W` is, so it can be moved to a worker thread. As it borrows its
+/// schema, only to threads which do not outlive the schema, e.g. scoped ones
+/// (`std::thread::scope`), unless the schema is `'static`.
 pub struct Writer<'a, W> {
     schema: &'a Schema,
+    #[cfg(feature = "serde_layer")]
     serializer: Serializer,
     writer: W,
     buffer: Vec<u8>,
+    block_header: Vec<u8>,
+    compressed: Vec<u8>,
     num_values: usize,
     codec: Codec,
-    marker: Vec<u8>,
-    has_header: bool,
+    marker: [u8; SYNC_SIZE],
+    sized_blocks: bool,
+    sorted_maps: bool,
+    // Statistics of the values appended, and position of the header they are written in.
+    statistics: Option<(Tracker, u64)>,
 }
 
 impl<'a, W: Write> Writer<'a, W> {
     /// Creates a `Writer` given a `Schema` and something implementing the `io::Write` trait to write
     /// to.
     /// No compression `Codec` will be used.
-    pub fn new(schema: &'a Schema, writer: W) -> Writer<'a, W> {
+    ///
+    /// The header of the file is written right away, so that a schema which cannot be serialized
+    /// (or a failing `writer`) is reported here rather than by the first append.
+    ///
+    /// Only available with the `rand` feature to generate the sync marker.
+    #[cfg(feature = "rand")]
+    pub fn new(schema: &'a Schema, writer: W) -> Result<Writer<'a, W>, Error> {
         Self::with_codec(schema, writer, Codec::Null)
     }
 
     /// Creates a `Writer` with a specific `Codec` given a `Schema` and something implementing the
     /// `io::Write` trait to write to.
-    pub fn with_codec(schema: &'a Schema, writer: W, codec: Codec) -> Writer<'a, W> {
-        let mut marker = Vec::with_capacity(16);
-        for _ in 0..16 {
-            marker.push(random::<u8>());
-        }
+    ///
+    /// Only available with the `rand` feature to generate the sync marker.
+    #[cfg(feature = "rand")]
+    pub fn with_codec(
+        schema: &'a Schema,
+        writer: W,
+        codec: Codec,
+    ) -> Result<Writer<'a, W>, Error> {
+        Self::with_marker(schema, writer, codec, random())
+    }
+
+    /// Creates a `Writer` with a specific `Codec` and sync marker given a `Schema` and something
+    /// implementing the `io::Write` trait to write to.
+    ///
+    /// The marker separates the blocks of the file and should be random, e.g. obtained from the
+    /// platform where the `rand` feature is not available (like `wasm32-unknown-unknown`). Given a
+    /// fixed marker, writing the same values yields byte-identical files.
+    pub fn with_marker(
+        schema: &'a Schema,
+        writer: W,
+        codec: Codec,
+        marker: [u8; SYNC_SIZE],
+    ) -> Result<Writer<'a, W>, Error> {
+        Self::create(schema, writer, codec, marker, None)
+    }
 
-        Writer {
+    fn create(
+        schema: &'a Schema,
+        writer: W,
+        codec: Codec,
+        marker: [u8; SYNC_SIZE],
+        statistics: Option<(Tracker, u64)>,
+    ) -> Result<Writer<'a, W>, Error> {
+        let mut writer = Writer {
             schema,
+            #[cfg(feature = "serde_layer")]
             serializer: Serializer::default(),
             writer,
             buffer: Vec::with_capacity(SYNC_INTERVAL),
+            // The object count and the block size are at most 10 bytes each as zig-zag longs.
+            block_header: Vec::with_capacity(20),
+            compressed: Vec::new(),
             num_values: 0,
             codec,
             marker,
-            has_header: false,
-        }
+            sized_blocks: false,
+            sorted_maps: false,
+            statistics,
+        };
+        let header = writer.header()?;
+        writer.writer.write_all(&header)?;
+        Ok(writer)
+    }
+
+    /// Write the blocks of arrays and maps with their size in bytes, as allowed by the
+    /// specification, so that readers not interested in their items can skip them in one step.
+    ///
+    /// This costs a few bytes per non-empty array or map, and is disabled by default.
+    pub fn set_sized_blocks(&mut self, sized: bool) {
+        self.sized_blocks = sized;
+    }
+
+    /// Write the entries of maps in the order of their keys, so that equal values always yield
+    /// the same bytes (e.g. to deduplicate or hash files), whatever the order they were built in.
+    ///
+    /// This costs sorting the entries of every map, and is disabled by default.
+    pub fn set_sorted_maps(&mut self, sorted: bool) {
+        self.sorted_maps = sorted;
     }
 
     /// Get a reference to the `Schema` associated to a `Writer`.
@@ -77,78 +151,170 @@ impl<'a, W: Write> Writer<'a, W> {
         self.schema
     }
 
+    /// Get the statistics of the values appended so far, if tracked, see
+    /// [`with_statistics`](#method.with_statistics).
+    pub fn statistics(&self) -> Option<&FileStatistics> {
+        self.statistics.as_ref().map(|(tracker, _)| tracker.statistics())
+    }
+
     /// Append a compatible value (implementing the `ToAvro` trait) to a `Writer`, also performing
     /// schema validation.
     ///
+    /// Values other than `Value`s are converted to a new `Value` first: to append without
+    /// allocating once the buffers of the `Writer` are sized, see
+    /// [`append_value_ref`](#method.append_value_ref) and [`append_with`](#method.append_with).
+    ///
     /// Return the number of bytes written (it might be 0, see below).
     ///
     /// **NOTE** This function is not guaranteed to perform any actual write, since it relies on
     /// internal buffering for performance reasons. If you want to be sure the value has been
     /// written, then call [`flush`](struct.Writer.html#method.flush).
     pub fn append<T: ToAvro>(&mut self, value: T) -> Result<usize, Error> {
-        let n = if !self.has_header {
-            let header = self.header()?;
-            let n = self.append_bytes(header.as_ref())?;
-            self.has_header = true;
-            n
-        } else {
-            0
-        };
-
         let avro = value.avro();
-        write_value_ref(self.schema, &avro, &mut self.buffer)?;
+        write_value_ref(
+            self.schema,
+            &avro,
+            self.sized_blocks,
+            self.sorted_maps,
+            &mut self.buffer,
+        )?;
+        if let Some((ref mut tracker, _)) = self.statistics {
+            tracker.update(&avro);
+        }
 
         self.num_values += 1;
 
         if self.buffer.len() >= SYNC_INTERVAL {
-            return self.flush().map(|b| b + n)
+            return self.flush()
         }
 
-        Ok(n)
+        Ok(0)
     }
 
     /// Append a compatible value to a `Writer`, also performing schema validation.
     ///
+    /// Once the buffers of the `Writer` are sized, this does not allocate unless the value has
+    /// logical types, maps are sorted or statistics are tracked.
+    ///
     /// Return the number of bytes written (it might be 0, see below).
     ///
     /// **NOTE** This function is not guaranteed to perform any actual write, since it relies on
     /// internal buffering for performance reasons. If you want to be sure the value has been
     /// written, then call [`flush`](struct.Writer.html#method.flush).
     pub fn append_value_ref(&mut self, value: &Value) -> Result<usize, Error> {
-        let n = if !self.has_header {
-            let header = self.header()?;
-            let n = self.append_bytes(header.as_ref())?;
-            self.has_header = true;
-            n
-        } else {
-            0
-        };
+        write_value_ref(
+            self.schema,
+            value,
+            self.sized_blocks,
+            self.sorted_maps,
+            &mut self.buffer,
+        )?;
+        if let Some((ref mut tracker, _)) = self.statistics {
+            tracker.update(value);
+        }
 
-        write_value_ref(self.schema, value, &mut self.buffer)?;
+        self.num_values += 1;
 
+        if self.buffer.len() >= SYNC_INTERVAL {
+            return self.flush()
+        }
+
+        Ok(0)
+    }
+
+    /// Append a datum encoded by `datum_writer` to a `Writer`, e.g. a user type encoded without
+    /// going through a `Value`, see [`DatumWriter`](trait.DatumWriter.html).
+    ///
+    /// Return the number of bytes written (it might be 0, see below).
+    ///
+    /// **NOTE** This function is not guaranteed to perform any actual write, since it relies on
+    /// internal buffering for performance reasons. If you want to be sure the value has been
+    /// written, then call [`flush`](struct.Writer.html#method.flush).
+    pub fn append_with<T, D>(&mut self, datum_writer: &mut D, datum: &T) -> Result<usize, Error>
+    where
+        T: ?Sized,
+        D: DatumWriter<T>,
+    {
+        let start = self.buffer.len();
+        let mut result = datum_writer.write(self.schema, datum, &mut self.buffer);
+        if let (true, Some((ref mut tracker, _))) = (result.is_ok(), &mut self.statistics) {
+            // The statistics are taken from the datum as encoded.
+            result = decode(self.schema, &mut &self.buffer[start..])
+                .map(|value| tracker.update(&value));
+        }
+        if let Err(e) = result {
+            self.buffer.truncate(start);
+            return Err(e)
+        }
+
+        self.num_values += 1;
+
+        if self.buffer.len() >= SYNC_INTERVAL {
+            return self.flush()
+        }
+
+        Ok(0)
+    }
+
+    // Append a datum already encoded with the schema of the `Writer`.
+    pub(crate) fn append_encoded(&mut self, datum: &[u8]) -> Result<usize, Error> {
+        self.buffer.extend_from_slice(datum);
         self.num_values += 1;
 
         if self.buffer.len() >= SYNC_INTERVAL {
-            return self.flush().map(|b| b + n)
+            return self.flush()
         }
 
-        Ok(n)
+        Ok(0)
+    }
+
+    // Write `count` datums already encoded with the schema of the `Writer` as a data block of their
+    // own, after the values appended so far.
+    pub(crate) fn append_block(&mut self, count: usize, data: &[u8]) -> Result<usize, Error> {
+        let mut num_bytes = self.flush()?;
+        if count > 0 {
+            self.buffer.extend_from_slice(data);
+            self.num_values = count;
+            num_bytes += self.flush()?;
+        }
+        Ok(num_bytes)
     }
 
     /// Append anything implementing the `Serialize` trait to a `Writer` for
     /// [`serde`](https://docs.serde.rs/serde/index.html) compatibility, also performing schema
     /// validation.
     ///
+    /// The value goes through a new `Value`, see [`append_with`](#method.append_with) to encode
+    /// it directly.
+    ///
     /// Return the number of bytes written.
     ///
     /// **NOTE** This function is not guaranteed to perform any actual write, since it relies on
     /// internal buffering for performance reasons. If you want to be sure the value has been
     /// written, then call [`flush`](struct.Writer.html#method.flush).
+    #[cfg(feature = "serde_layer")]
     pub fn append_ser<S: Serialize>(&mut self, value: S) -> Result<usize, Error> {
         let avro_value = value.serialize(&mut self.serializer)?;
         self.append(avro_value)
     }
 
+    /// Append the rows of an Arrow record batch to a `Writer`, record fields being looked up by
+    /// name among the columns, see [`from_record_batch`](fn.from_record_batch.html).
+    ///
+    /// Return the number of bytes written.
+    ///
+    /// **NOTE** This function is not guaranteed to perform any actual write, since it relies on
+    /// internal buffering for performance reasons. If you want to be sure the values have been
+    /// written, then call [`flush`](struct.Writer.html#method.flush).
+    #[cfg(feature = "arrow")]
+    pub fn append_record_batch(&mut self, batch: &RecordBatch) -> Result<usize, Error> {
+        let mut num_bytes = 0;
+        for value in record_batch_rows(self.schema, batch) {
+            num_bytes += self.append_value_ref(&value?)?;
+        }
+        Ok(num_bytes)
+    }
+
     /// Extend a `Writer` with an `Iterator` of compatible values (implementing the `ToAvro`
     /// trait), also performing schema validation.
     ///
@@ -191,6 +357,7 @@ impl<'a, W: Write> Writer<'a, W> {
     ///
     /// **NOTE** This function forces the written data to be flushed (an implicit
     /// call to [`flush`](struct.Writer.html#method.flush) is performed).
+    #[cfg(feature = "serde_layer")]
     pub fn extend_ser<I, T: Serialize>(&mut self, values: I) -> Result<usize, Error>
     where
         I: IntoIterator<Item = T>,
@@ -244,15 +411,30 @@ impl<'a, W: Write> Writer<'a, W> {
             return Ok(0)
         }
 
-        self.codec.compress(&mut self.buffer)?;
+        trace_span!("write_block", count = self.num_values, size = self.buffer.len());
 
-        let num_values = self.num_values;
-        let stream_len = self.buffer.len();
+        // Scratch buffers are cleared rather than dropped, so that steady-state appends and
+        // flushes do not allocate.
+        let block = match self.codec {
+            Codec::Null => &self.buffer,
+            codec => {
+                codec.compress_into(&self.buffer, &mut self.compressed)?;
+                &self.compressed
+            },
+        };
 
-        let num_bytes = self.append_raw(&num_values.avro(), &Schema::Long)?
-            + self.append_raw(&stream_len.avro(), &Schema::Long)?
-            + self.writer.write(self.buffer.as_ref())?
-            + self.append_marker()?;
+        self.block_header.clear();
+        zig_i64(self.num_values as i64, &mut self.block_header);
+        zig_i64(block.len() as i64, &mut self.block_header);
+
+        let num_bytes = write_all_vectored(
+            &mut self.writer,
+            &mut [
+                IoSlice::new(&self.block_header),
+                IoSlice::new(block),
+                IoSlice::new(&self.marker),
+            ],
+        )?;
 
         self.buffer.clear();
         self.num_values = 0;
@@ -268,85 +450,205 @@ impl<'a, W: Write> Writer<'a, W> {
         self.writer
     }
 
-    /// Generate and append synchronization marker to the payload.
-    fn append_marker(&mut self) -> Result<usize, Error> {
-        // using .writer.write directly to avoid mutable borrow of self
-        // with ref borrowing of self.marker
-        Ok(self.writer.write(&self.marker)?)
-    }
-
-    /// Append a raw Avro Value to the payload avoiding to encode it again.
-    fn append_raw(&mut self, value: &Value, schema: &Schema) -> Result<usize, Error> {
-        self.append_bytes(encode_to_vec(&value, schema).as_ref())
-    }
-
-    /// Append pure bytes to the payload.
-    fn append_bytes(&mut self, bytes: &[u8]) -> Result<usize, Error> {
-        Ok(self.writer.write(bytes)?)
-    }
-
     /// Create an Avro header based on schema, codec and sync marker.
     fn header(&self) -> Result<Vec<u8>, Error> {
         let schema_bytes = serde_json::to_string(self.schema)?.into_bytes();
 
-        let mut metadata = HashMap::with_capacity(2);
-        metadata.insert("avro.schema", Value::Bytes(schema_bytes));
-        metadata.insert("avro.codec", self.codec.avro());
+        // The metadata map is written in sorted key order (and the schema is always serialized the
+        // same way), so that identical inputs yield byte-identical files given a fixed marker.
+        let mut metadata = vec![
+            ("avro.codec", self.codec.avro()),
+            ("avro.schema", Value::Bytes(schema_bytes)),
+        ];
+        if let Some((ref tracker, _)) = self.statistics {
+            metadata.push((STATISTICS_KEY, Value::Bytes(tracker.to_json()?)));
+        }
 
         let mut header = Vec::new();
         header.extend_from_slice(AVRO_OBJECT_HEADER);
-        encode(
-            &metadata.avro(),
-            &Schema::Map(Box::new(Schema::Bytes)),
-            &mut header,
-        );
+        zig_i64(metadata.len() as i64, &mut header);
+        for (key, value) in &metadata {
+            encode(&Value::String((*key).to_owned()), &Schema::String, &mut header);
+            encode(value, &Schema::Bytes, &mut header);
+        }
+        zig_i64(0, &mut header);
         header.extend_from_slice(&self.marker);
 
         Ok(header)
     }
 }
 
-/// Encode a compatible value (implementing the `ToAvro` trait) into Avro format, also performing
-/// schema validation.
+impl<'a, W: Write + Seek> Writer<'a, W> {
+    /// Creates a `Writer` with a specific `Codec` which tracks statistics of the records
+    /// appended: their count, and the smallest and largest values of their fields of primitive
+    /// types (or unions of `null` and a primitive type), with the number of `null`s.
+    ///
+    /// The statistics are stored in the [`STATISTICS_KEY`](constant.STATISTICS_KEY.html)
+    /// metadata of the header on [`close`](#method.close), over space reserved when the header
+    /// is written (about a kilobyte per field), so that query planners can read them back with
+    /// [`Reader::statistics`](struct.Reader.html#method.statistics) and skip files without
+    /// scanning them. Bounds of strings and bytes longer than 64 bytes are not stored.
+    ///
+    /// Only available with the `rand` feature to generate the sync marker.
+    #[cfg(feature = "rand")]
+    pub fn with_statistics(
+        schema: &'a Schema,
+        mut writer: W,
+        codec: Codec,
+    ) -> Result<Writer<'a, W>, Error> {
+        let position = writer.stream_position()?;
+        Self::create(
+            schema,
+            writer,
+            codec,
+            random(),
+            Some((Tracker::new(schema), position)),
+        )
+    }
+
+    /// Flush the content appended to a `Writer`, then write its statistics (if tracked) into the
+    /// header, returning what the `Writer` is writing to, positioned at the end of the data.
+    pub fn close(mut self) -> Result<W, Error> {
+        self.flush()?;
+        if let Some((_, header_position)) = self.statistics {
+            let header = self.header()?;
+            let end = self.writer.stream_position()?;
+            self.writer.seek(SeekFrom::Start(header_position))?;
+            self.writer.write_all(&header)?;
+            self.writer.seek(SeekFrom::Start(end))?;
+        }
+        Ok(self.writer)
+    }
+}
+
+/// Write every slice in `bufs` to `writer`, issuing as few `write_vectored` calls as the writer
+/// allows.
 ///
-/// This is an internal function which gets the bytes buffer where to write as parameter instead of
-/// creating a new one like `to_avro_datum`.
-fn write_avro_datum<T: ToAvro>(
-    schema: &Schema,
-    value: T,
-    buffer: &mut Vec<u8>,
+/// Writers which do not support vectored writes fall back to the default `write_vectored`
+/// implementation, which writes the first non-empty slice only: the loop then simply keeps going
+/// until everything has been written.
+///
+/// Return the number of bytes written.
+fn write_all_vectored<W: Write>(writer: &mut W, mut bufs: &mut [IoSlice]) -> Result<usize, Error> {
+    let total = bufs.iter().map(|buf| buf.len()).sum();
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => {
+                return Err(
+                    ::std::io::Error::new(ErrorKind::WriteZero, "failed to write whole block")
+                        .into(),
+                )
+            },
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(total)
+}
+
+/// Write `len` bytes read from `reader` to `writer` as an Avro `bytes` (or `string`) value,
+/// copying them as they are read so that large payloads are never held in memory whole.
+///
+/// This writes raw datums, e.g. single datums sent over a socket: as records are encoded as the
+/// concatenation of their fields, such a payload can be written in between the encodings of the
+/// fields around it (e.g. from `write_avro_datum`). The blocks of a `Writer` are held in memory
+/// anyway, as their size precedes them.
+pub fn write_bytes_from<R: Read, W: Write>(
+    reader: R,
+    len: u64,
+    writer: &mut W,
 ) -> Result<(), Error> {
-    let avro = value.avro();
-    if !avro.validate(schema) {
-        return Err(ValidationError::new("value does not match schema").into())
+    if len > i64::MAX as u64 {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "payload too large").into())
+    }
+    let mut header = Vec::new();
+    zig_i64(len as i64, &mut header);
+    writer.write_all(&header)?;
+    if io::copy(&mut reader.take(len), writer)? < len {
+        let error = io::Error::new(ErrorKind::UnexpectedEof, "payload shorter than its length");
+        return Err(error.into())
     }
-    encode(&avro, schema, buffer);
     Ok(())
 }
 
-fn write_value_ref(schema: &Schema, value: &Value, buffer: &mut Vec<u8>) -> Result<(), Error> {
-    if !value.validate(schema) {
-        return Err(ValidationError::new("value does not match schema").into())
+/// Same as [`write_bytes_from`](fn.write_bytes_from.html), for payloads of unknown length read
+/// until the end of `reader`, returning their length.
+///
+/// Payloads larger than a megabyte are staged in `scratch` from its current position until their
+/// length is known, e.g. in an anonymous temporary file (as created by the `tempfile` crate) or a
+/// `Cursor<Vec<u8>>` to reuse. The staged bytes are left there for the caller to discard.
+pub fn write_bytes_staged<R: Read, S: Read + Write + Seek, W: Write>(
+    mut reader: R,
+    mut scratch: S,
+    writer: &mut W,
+) -> Result<u64, Error> {
+    let mut staged = Vec::new();
+    (&mut reader)
+        .take(STAGING_THRESHOLD as u64 + 1)
+        .read_to_end(&mut staged)?;
+    if staged.len() <= STAGING_THRESHOLD {
+        write_bytes_from(&staged[..], staged.len() as u64, writer)?;
+        return Ok(staged.len() as u64)
     }
-    encode_ref(value, schema, buffer);
-    Ok(())
+
+    let start = scratch.stream_position()?;
+    scratch.write_all(&staged)?;
+    let len = staged.len() as u64 + io::copy(&mut reader, &mut scratch)?;
+    scratch.seek(SeekFrom::Start(start))?;
+    write_bytes_from(&mut scratch, len, writer)?;
+    Ok(len)
 }
 
-/// Encode a compatible value (implementing the `ToAvro` trait) into Avro format, also
-/// performing schema validation.
+/// Write compatible values (implementing the `ToAvro` trait) to an in-memory Avro file, header
+/// and blocks compressed with `codec` included, also performing schema validation.
 ///
-/// **NOTE** This function has a quite small niche of usage and does NOT generate headers and sync
-/// markers; use [`Writer`](struct.Writer.html) to be fully Avro-compatible if you don't know what
-/// you are doing, instead.
-pub fn to_avro_datum<T: ToAvro>(schema: &Schema, value: T) -> Result<Vec<u8>, Error> {
-    let mut buffer = Vec::new();
-    write_avro_datum(schema, value, &mut buffer)?;
-    Ok(buffer)
+/// Only available with the `rand` feature to generate the sync marker.
+#[cfg(feature = "rand")]
+pub fn write_to_vec<I, T: ToAvro>(
+    schema: &Schema,
+    codec: Codec,
+    values: I,
+) -> Result<Vec<u8>, Error>
+where
+    I: IntoIterator<Item = T>,
+{
+    let mut writer = Writer::with_codec(schema, Vec::new(), codec)?;
+    writer.extend(values)?;
+    writer.flush()?;
+    Ok(writer.into_inner())
+}
+
+/// Write compatible values (implementing the `ToAvro` trait) to an Avro file at `path`, created
+/// or truncated, also performing schema validation.
+///
+/// Only available with the `rand` feature to generate the sync marker.
+#[cfg(feature = "rand")]
+pub fn write_file<P: AsRef<Path>, I, T: ToAvro>(
+    path: P,
+    schema: &Schema,
+    codec: Codec,
+    values: I,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = T>,
+{
+    let file = BufWriter::new(File::create(path)?);
+    let mut writer = Writer::with_codec(schema, file, codec)?;
+    writer.extend(values)?;
+    writer.flush()?;
+    writer.into_inner().flush()?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::collections::{BTreeMap, HashMap};
+    use datum::{from_avro_datum, to_avro_datum, to_avro_datum_sorted, write_avro_datum};
+    use reader::Reader;
     use types::Record;
     use util::zig_i64;
 
@@ -364,6 +666,36 @@ mod tests {
             ["null", "long"]
         "#;
 
+    // Counts the allocations of each thread, for tests to check that writing does not allocate.
+    struct CountingAllocator;
+
+    thread_local!(static ALLOCATIONS: Cell<usize> = const { Cell::new(0) });
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            unsafe { System.realloc(ptr, layout, size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations<F: FnOnce()>(f: F) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
     #[test]
     fn test_to_avro_datum() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
@@ -391,10 +723,20 @@ mod tests {
         assert_eq!(to_avro_datum(&schema, union).unwrap(), expected);
     }
 
+    // Number of bytes of the header, written when creating a `Writer`.
+    #[cfg(feature = "rand")]
+    fn header_len(schema: &Schema, codec: Codec) -> usize {
+        Writer::with_codec(schema, Vec::new(), codec)
+            .unwrap()
+            .into_inner()
+            .len()
+    }
+
+    #[cfg(feature = "rand")]
     #[test]
     fn test_writer_append() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
-        let mut writer = Writer::new(&schema, Vec::new());
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 
         let mut record = Record::new(&schema).unwrap();
         record.put("a", 27i64);
@@ -405,7 +747,7 @@ mod tests {
         let n3 = writer.flush().unwrap();
         let result = writer.into_inner();
 
-        assert_eq!(n1 + n2 + n3, result.len());
+        assert_eq!(n1 + n2 + n3, result.len() - header_len(&schema, Codec::Null));
 
         let mut header = Vec::new();
         header.extend(vec![b'O', b'b', b'j', b'\x01']);
@@ -442,10 +784,11 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "rand")]
     #[test]
     fn test_writer_extend() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
-        let mut writer = Writer::new(&schema, Vec::new());
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 
         let mut record = Record::new(&schema).unwrap();
         record.put("a", 27i64);
@@ -457,7 +800,7 @@ mod tests {
         let n2 = writer.flush().unwrap();
         let result = writer.into_inner();
 
-        assert_eq!(n1 + n2, result.len());
+        assert_eq!(n1 + n2, result.len() - header_len(&schema, Codec::Null));
 
         let mut header = Vec::new();
         header.extend(vec![b'O', b'b', b'j', b'\x01']);
@@ -494,16 +837,18 @@ mod tests {
         );
     }
 
+    #[cfg(all(feature = "rand", feature = "serde_layer"))]
     #[derive(Debug, Clone, Deserialize, Serialize)]
     struct TestSerdeSerialize {
         a: i64,
         b: String,
     }
 
+    #[cfg(all(feature = "rand", feature = "serde_layer"))]
     #[test]
     fn test_writer_append_ser() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
-        let mut writer = Writer::new(&schema, Vec::new());
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 
         let record = TestSerdeSerialize {
             a: 27,
@@ -514,7 +859,7 @@ mod tests {
         let n2 = writer.flush().unwrap();
         let result = writer.into_inner();
 
-        assert_eq!(n1 + n2, result.len());
+        assert_eq!(n1 + n2, result.len() - header_len(&schema, Codec::Null));
 
         let mut header = Vec::new();
         header.extend(vec![b'O', b'b', b'j', b'\x01']);
@@ -549,10 +894,11 @@ mod tests {
         );
     }
 
+    #[cfg(all(feature = "rand", feature = "serde_layer"))]
     #[test]
     fn test_writer_extend_ser() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
-        let mut writer = Writer::new(&schema, Vec::new());
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
 
         let record = TestSerdeSerialize {
             a: 27,
@@ -565,7 +911,7 @@ mod tests {
         let n2 = writer.flush().unwrap();
         let result = writer.into_inner();
 
-        assert_eq!(n1 + n2, result.len());
+        assert_eq!(n1 + n2, result.len() - header_len(&schema, Codec::Null));
 
         let mut header = Vec::new();
         header.extend(vec![b'O', b'b', b'j', b'\x01']);
@@ -602,10 +948,105 @@ mod tests {
         );
     }
 
+    // Accepts at most 3 bytes per call and keeps track of how many calls were issued.
+    struct ChunkedWriter {
+        inner: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for ChunkedWriter {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            self.calls += 1;
+            let n = buf.len().min(3);
+            self.inner.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_all_vectored() {
+        let mut writer = Vec::new();
+        let n = write_all_vectored(
+            &mut writer,
+            &mut [
+                IoSlice::new(b"ab"),
+                IoSlice::new(b""),
+                IoSlice::new(b"cdefg"),
+            ],
+        ).unwrap();
+        assert_eq!(n, 7);
+        assert_eq!(writer, b"abcdefg");
+
+        let mut writer = ChunkedWriter {
+            inner: Vec::new(),
+            calls: 0,
+        };
+        let n = write_all_vectored(
+            &mut writer,
+            &mut [IoSlice::new(b"ab"), IoSlice::new(b"cdefg")],
+        ).unwrap();
+        assert_eq!(n, 7);
+        assert_eq!(writer.inner, b"abcdefg");
+        assert_eq!(writer.calls, 3);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_writer_partial_writes() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::new(
+            &schema,
+            ChunkedWriter {
+                inner: Vec::new(),
+                calls: 0,
+            },
+        ).unwrap();
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+
+        let n1 = writer.append(record.clone()).unwrap();
+        let n2 = writer.append(record.clone()).unwrap();
+        let n3 = writer.flush().unwrap();
+        let result = writer.into_inner().inner;
+
+        assert_eq!(n1 + n2 + n3, result.len() - header_len(&schema, Codec::Null));
+
+        let mut data = Vec::new();
+        zig_i64(2, &mut data);
+        zig_i64(10, &mut data);
+        for _ in 0..2 {
+            zig_i64(27, &mut data);
+            zig_i64(3, &mut data);
+            data.extend(vec![b'f', b'o', b'o'].into_iter());
+        }
+
+        // ends with the block header, data and sync marker
+        assert_eq!(
+            result
+                .iter()
+                .cloned()
+                .rev()
+                .skip(16)
+                .take(data.len())
+                .collect::<Vec<u8>>()
+                .into_iter()
+                .rev()
+                .collect::<Vec<u8>>(),
+            data
+        );
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
     #[test]
     fn test_writer_with_codec() {
         let schema = Schema::parse_str(SCHEMA).unwrap();
-        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate);
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
 
         let mut record = Record::new(&schema).unwrap();
         record.put("a", 27i64);
@@ -616,7 +1057,7 @@ mod tests {
         let n3 = writer.flush().unwrap();
         let result = writer.into_inner();
 
-        assert_eq!(n1 + n2 + n3, result.len());
+        assert_eq!(n1 + n2 + n3, result.len() - header_len(&schema, Codec::Deflate));
 
         let mut header = Vec::new();
         header.extend(vec![b'O', b'b', b'j', b'\x01']);
@@ -653,4 +1094,386 @@ mod tests {
             data
         );
     }
+
+    #[cfg(feature = "deflate_codec")]
+    #[test]
+    fn test_writer_deterministic() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let write = || {
+            let mut writer =
+                Writer::with_marker(&schema, Vec::new(), Codec::Deflate, [7u8; 16]).unwrap();
+            let mut record = Record::new(&schema).unwrap();
+            record.put("a", 27i64);
+            record.put("b", "foo");
+            writer.append(record).unwrap();
+            writer.flush().unwrap();
+            writer.into_inner()
+        };
+
+        let result = write();
+        for _ in 0..10 {
+            assert_eq!(write(), result);
+        }
+        // Two metadata entries, in sorted key order.
+        assert_eq!(&result[4..16], b"\x04\x14avro.codec");
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_writer_eager_header() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+
+        // Without any value, the header alone makes a valid file.
+        let result = Writer::new(&schema, Vec::new()).unwrap().into_inner();
+        assert_eq!(result.len(), header_len(&schema, Codec::Null));
+        assert_eq!(Reader::new(&result[..]).unwrap().count(), 0);
+
+        // A failing writer is reported by the constructor.
+        assert!(Writer::new(&schema, &mut [0u8; 4][..]).is_err());
+    }
+
+    #[test]
+    fn test_writer_with_marker() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let marker = [7u8; 16];
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, marker).unwrap();
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        writer.append(record.clone()).unwrap();
+        writer.flush().unwrap();
+        let result = writer.into_inner();
+
+        assert_eq!(&result[result.len() - 16..], &marker);
+        let reader = Reader::new(&result[..]).unwrap();
+        assert_eq!(
+            reader.map(|value| value.unwrap()).collect::<Vec<_>>(),
+            vec![record.avro()]
+        );
+    }
+
+    #[test]
+    fn test_writer_sized_blocks() {
+        let schema = Schema::parse_str(
+            r#"
+            {
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "a", "type": {"type": "array", "items": "string"}},
+                    {"name": "b", "type": "long"}
+                ]
+            }
+        "#,
+        ).unwrap();
+        let value = Value::Record(vec![
+            (
+                "a".to_owned(),
+                Value::Array(vec![Value::String("foo".to_owned()); 3]),
+            ),
+            ("b".to_owned(), Value::Long(27)),
+        ]);
+        let marker = [7u8; 16];
+        let mut writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, marker).unwrap();
+        writer.set_sized_blocks(true);
+        writer.append_value_ref(&value).unwrap();
+        writer.flush().unwrap();
+        let result = writer.into_inner();
+
+        // 1 record of 16 bytes: the array block of 3 items and 12 bytes, its end, and the long.
+        let mut block = Vec::new();
+        zig_i64(1, &mut block);
+        zig_i64(16, &mut block);
+        zig_i64(-3, &mut block);
+        zig_i64(12, &mut block);
+        for _ in 0..3 {
+            block.extend_from_slice(&[6, b'f', b'o', b'o']);
+        }
+        block.extend_from_slice(&[0, 54]);
+        block.extend_from_slice(&marker);
+        assert!(result.ends_with(&block));
+        let reader = Reader::new(&result[..]).unwrap();
+        assert_eq!(reader.map(|value| value.unwrap()).collect::<Vec<_>>(), vec![value]);
+
+        // The array is skipped over when not read.
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [{"name": "b", "type": "long"}]}"#,
+        ).unwrap();
+        let reader = Reader::with_schema(&reader_schema, &result[..]).unwrap();
+        assert_eq!(
+            reader.map(|value| value.unwrap()).collect::<Vec<_>>(),
+            vec![Value::Record(vec![("b".to_owned(), Value::Long(27))])]
+        );
+    }
+
+    #[test]
+    fn test_writer_sorted_maps() {
+        let schema = Schema::parse_str(r#"{"type": "map", "values": "long"}"#).unwrap();
+        let keys = (0..64).map(|i| format!("k{:02}", i)).collect::<Vec<_>>();
+        // Maps with the same entries, inserted in opposite orders.
+        let map = |keys: &mut dyn Iterator<Item = &String>| {
+            keys.map(|key| (key.clone(), 1i64)).collect::<HashMap<_, _>>()
+        };
+        let (a, b) = (map(&mut keys.iter()), map(&mut keys.iter().rev()));
+
+        let encoded = to_avro_datum_sorted(&schema, a.clone()).unwrap();
+        assert_eq!(encoded, to_avro_datum_sorted(&schema, b.clone()).unwrap());
+        let sorted = keys.iter().cloned().map(|key| (key, 1i64)).collect::<BTreeMap<_, _>>();
+        let mut expected = Vec::new();
+        zig_i64(64, &mut expected);
+        for key in &keys {
+            zig_i64(3, &mut expected);
+            expected.extend_from_slice(key.as_bytes());
+            zig_i64(1, &mut expected);
+        }
+        expected.push(0);
+        assert_eq!(encoded, expected);
+        assert_eq!(to_avro_datum_sorted(&schema, sorted).unwrap(), encoded);
+
+        for &sized in &[false, true] {
+            let write = |map: &HashMap<String, i64>| {
+                let mut writer =
+                    Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+                writer.set_sized_blocks(sized);
+                writer.set_sorted_maps(true);
+                writer.append(map.clone()).unwrap();
+                writer.flush().unwrap();
+                writer.into_inner()
+            };
+            let written = write(&a);
+            assert_eq!(written, write(&b));
+            let reader = Reader::new(&written[..]).unwrap();
+            let values = reader.map(|value| value.unwrap()).collect::<Vec<_>>();
+            assert_eq!(values, vec![a.clone().avro()]);
+        }
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand"))]
+    #[test]
+    fn test_writer_reuses_buffers() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let mut writer = Writer::with_codec(&schema, Vec::new(), Codec::Deflate).unwrap();
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("a", 27i64);
+        record.put("b", "foo");
+        let value = record.avro();
+
+        writer.append_value_ref(&value).unwrap();
+        writer.flush().unwrap();
+        let buffers = (
+            writer.buffer.as_ptr(),
+            writer.block_header.as_ptr(),
+            writer.compressed.as_ptr(),
+        );
+
+        for _ in 0..10 {
+            writer.append_value_ref(&value).unwrap();
+            writer.flush().unwrap();
+            assert_eq!(
+                (
+                    writer.buffer.as_ptr(),
+                    writer.block_header.as_ptr(),
+                    writer.compressed.as_ptr(),
+                ),
+                buffers
+            );
+        }
+
+        let result = writer.into_inner();
+        let reader = Reader::new(&result[..]).unwrap();
+        assert_eq!(reader.map(|value| value.unwrap()).collect::<Vec<_>>(), vec![value; 11]);
+    }
+
+    #[test]
+    fn test_steady_state_appends_do_not_allocate() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [
+                {"name": "a", "type": ["null", "long"]},
+                {"name": "b", "type": {
+                    "type": "map", "values": {"type": "array", "items": "string"}
+                }}
+            ]}"#,
+        ).unwrap();
+        let mut items = HashMap::new();
+        items.insert("x".into(), Value::Array(vec![Value::String("foo".to_owned()); 3]));
+        items.insert("y".into(), Value::Array(vec![]));
+        let value = Value::Record(vec![
+            ("a".to_owned(), Value::Union(Box::new(Value::Long(27)))),
+            ("b".to_owned(), Value::Map(items)),
+        ]);
+        let encoded = to_avro_datum(&schema, value.clone()).unwrap();
+
+        struct Copied;
+        impl DatumWriter<[u8]> for Copied {
+            fn write(&mut self, _: &Schema, datum: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+                out.extend_from_slice(datum);
+                Ok(())
+            }
+        }
+
+        for &sized in &[false, true] {
+            let output = Vec::with_capacity(1 << 16);
+            let mut writer = Writer::with_marker(&schema, output, Codec::Null, [7u8; 16]).unwrap();
+            writer.set_sized_blocks(sized);
+            // The first block sizes the buffers.
+            writer.append_value_ref(&value).unwrap();
+            writer.append_with(&mut Copied, &encoded[..]).unwrap();
+            writer.flush().unwrap();
+            let count = allocations(|| {
+                for _ in 0..10 {
+                    writer.append_value_ref(&value).unwrap();
+                    writer.append_with(&mut Copied, &encoded[..]).unwrap();
+                    writer.flush().unwrap();
+                }
+            });
+            assert_eq!(count, 0);
+
+            let result = writer.into_inner();
+            let reader = Reader::new(&result[..]).unwrap();
+            assert_eq!(reader.map(Result::unwrap).collect::<Vec<_>>(), vec![value.clone(); 22]);
+        }
+    }
+
+    #[cfg(all(feature = "deflate_codec", feature = "rand", feature = "serde_layer"))]
+    #[test]
+    fn test_one_shot_helpers() {
+        use std::env::temp_dir;
+        use std::fs::remove_file;
+        use {read_all_values, read_file};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Test {
+            a: i64,
+            b: String,
+        }
+
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let records = [(1i64, "foo"), (2, "bar")]
+            .iter()
+            .map(|&(a, b)| {
+                let mut record = Record::new(&schema).unwrap();
+                record.put("a", a);
+                record.put("b", b);
+                record.avro()
+            }).collect::<Vec<_>>();
+
+        let input = write_to_vec(&schema, Codec::Deflate, records.clone()).unwrap();
+        let values = Reader::new(&input[..])
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, records);
+
+        let path = temp_dir().join(format!("avro-rs-one-shot-{}.avro", std::process::id()));
+        write_file(&path, &schema, Codec::Null, records.clone()).unwrap();
+        let values = read_all_values(&path);
+        let tests = read_file::<Test, _>(&path);
+        remove_file(&path).unwrap();
+        assert_eq!(values.unwrap(), records);
+        assert_eq!(
+            tests.unwrap(),
+            vec![
+                Test {
+                    a: 1,
+                    b: "foo".to_owned(),
+                },
+                Test {
+                    a: 2,
+                    b: "bar".to_owned(),
+                },
+            ]
+        );
+
+        assert!(read_all_values(&path).is_err());
+    }
+
+    #[test]
+    fn test_write_bytes_from() {
+        let payload = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let expected = to_avro_datum(&Schema::Bytes, Value::Bytes(payload.clone())).unwrap();
+
+        let mut encoded = Vec::new();
+        write_bytes_from(&payload[..], payload.len() as u64, &mut encoded).unwrap();
+        assert_eq!(encoded, expected);
+
+        // A reader shorter than the given length.
+        let mut encoded = Vec::new();
+        assert!(write_bytes_from(&payload[..10], 11, &mut encoded).is_err());
+
+        // A payload in between the fields of a record.
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "test", "fields": [
+                {"name": "a", "type": "long"},
+                {"name": "b", "type": "bytes"},
+                {"name": "c", "type": "string"}
+            ]}"#,
+        ).unwrap();
+        let mut encoded = to_avro_datum(&Schema::Long, 27i64).unwrap();
+        write_bytes_from(&payload[..], payload.len() as u64, &mut encoded).unwrap();
+        write_avro_datum(&Schema::String, "foo", &mut encoded).unwrap();
+        assert_eq!(
+            from_avro_datum(&schema, &mut &encoded[..], None).unwrap(),
+            Value::Record(vec![
+                ("a".to_owned(), Value::Long(27)),
+                ("b".to_owned(), Value::Bytes(payload)),
+                ("c".to_owned(), Value::String("foo".to_owned())),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_write_bytes_staged() {
+        for &len in &[0, 1000, STAGING_THRESHOLD, STAGING_THRESHOLD + 1000] {
+            let payload = (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let mut encoded = Vec::new();
+            // Scratch storage holding earlier bytes, from past its start.
+            let mut scratch = io::Cursor::new(vec![9u8; 10]);
+            scratch.set_position(4);
+            assert_eq!(
+                write_bytes_staged(&payload[..], &mut scratch, &mut encoded).unwrap(),
+                len as u64
+            );
+            assert_eq!(
+                encoded,
+                to_avro_datum(&Schema::Bytes, Value::Bytes(payload.clone())).unwrap()
+            );
+            let scratch = scratch.into_inner();
+            assert_eq!(scratch[..4], [9u8; 4]);
+            if len > STAGING_THRESHOLD {
+                assert_eq!(scratch[4..], payload[..]);
+            } else {
+                assert_eq!(scratch.len(), 10);
+            }
+        }
+
+        // Errors of the scratch storage are returned.
+        let payload = vec![0u8; STAGING_THRESHOLD + 1];
+        let mut small = [0u8; 16];
+        let scratch = io::Cursor::new(&mut small[..]);
+        assert!(write_bytes_staged(&payload[..], scratch, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_writer_is_send() {
+        let schema = Schema::parse_str(SCHEMA).unwrap();
+        let writer = Writer::with_marker(&schema, Vec::new(), Codec::Null, [7u8; 16]).unwrap();
+        // A `Writer` can be moved to a worker thread, and back.
+        let encoded = ::std::thread::scope(|scope| {
+            scope
+                .spawn(move || {
+                    let mut writer = writer;
+                    let mut record = Record::new(writer.schema()).unwrap();
+                    record.put("a", 27i64);
+                    record.put("b", "foo");
+                    writer.append(record).unwrap();
+                    writer.flush().unwrap();
+                    writer
+                }).join()
+                .unwrap()
+                .into_inner()
+        });
+        assert_eq!(Reader::new(&encoded[..]).unwrap().count(), 1);
+    }
 }